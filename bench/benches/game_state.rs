@@ -0,0 +1,51 @@
+//! How long the work done on every move takes: building each player's view, sending it to the table, and playing out a legislative session.
+//!
+//! ```sh
+//! cd bench
+//! cargo bench
+//! ```
+
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use secrethitler_bench::{nominated_game, started_game};
+use secrethitler_core::{game_state::GameStatePlayerView, machine::Action, odds::DeckOdds};
+
+fn player_view(c: &mut Criterion) {
+    let mut group = c.benchmark_group("player_view");
+    for players in [5, 10] {
+        let (game, ids) = started_game(players);
+        group.bench_function(format!("{} players", players), |b| {
+            b.iter(|| serde_json::to_string(&GameStatePlayerView { player: ids[0], state: &game }).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn broadcast_game_state(c: &mut Criterion) {
+    let mut group = c.benchmark_group("broadcast_game_state");
+    for players in [5, 10] {
+        let (game, _) = started_game(players);
+        group.bench_function(format!("{} players", players), |b| b.iter(|| game.broadcast_game_state()));
+    }
+    group.finish();
+}
+
+fn legislative_session(c: &mut Criterion) {
+    let mut group = c.benchmark_group("deck");
+    // every vote passes, so the session draws three policies, discards one, and enacts one
+    group.bench_function("legislative session", |b| {
+        b.iter_batched(|| nominated_game(5), |(mut game, ids)| {
+            for id in &ids {
+                game.apply(*id, Action::Vote { approve: true }).unwrap();
+            }
+            for player in [game.president().unwrap(), game.chancellor().unwrap()] {
+                game.apply(player, Action::PickCard { index: 0 }).unwrap();
+            }
+            game
+        }, BatchSize::SmallInput)
+    });
+    group.bench_function("odds", |b| b.iter(|| DeckOdds::new(criterion::black_box(2), criterion::black_box(3))));
+    group.finish();
+}
+
+criterion_group!(benches, player_view, broadcast_game_state, legislative_session);
+criterion_main!(benches);
@@ -0,0 +1,28 @@
+//! Games set up for the benchmarks in `benches/`.
+
+use std::sync::Arc;
+
+use secrethitler_core::{game_state::GameState, machine::Action, protocol::{NullSink, PlayerConnection}};
+use uuid::Uuid;
+
+/// A game that has just started with `players` seats, whose messages go nowhere.
+pub fn started_game(players: usize) -> (GameState, Vec<Uuid>) {
+    let mut game = GameState::new();
+    let ids: Vec<Uuid> = (0..players).map(|_| Uuid::new_v4()).collect();
+    for (i, id) in ids.iter().enumerate() {
+        let mut conn = PlayerConnection::new(Arc::new(NullSink));
+        conn.name = Some(format!("player {}", i + 1));
+        game.add_player(*id, conn);
+    }
+    game.apply(ids[0], Action::Start).unwrap();
+    (game, ids)
+}
+
+/// A game waiting on the table to vote for the first government.
+pub fn nominated_game(players: usize) -> (GameState, Vec<Uuid>) {
+    let (mut game, ids) = started_game(players);
+    let president = game.president().unwrap();
+    let chancellor = *game.living_players().iter().find(|p| **p != president).unwrap();
+    game.apply(president, Action::Nominate { chancellor }).unwrap();
+    (game, ids)
+}
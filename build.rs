@@ -0,0 +1,16 @@
+use std::process::Command;
+
+/// Record which commit the server was built from, so deployments can tell clients when their cached assets are stale.
+fn main() {
+    // heroku builds without the git directory but passes the commit along
+    let hash = std::env::var("SOURCE_VERSION").ok()
+        .or_else(|| {
+            let output = Command::new("git").args(["rev-parse", "HEAD"]).output().ok()?;
+            output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".into());
+    println!("cargo:rustc-env=BUILD_HASH={}", hash);
+    println!("cargo:rerun-if-env-changed=SOURCE_VERSION");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
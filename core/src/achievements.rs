@@ -0,0 +1,44 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{events::GameEvent, game_state::{CardColor, GameState, PlayerType, PresidentialPower}};
+
+/// Wins in a row needed for [`Achievement::WinStreak`].
+pub const WIN_STREAK: u32 = 5;
+
+/// Milestones players earn over their games.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+pub enum Achievement {
+    /// Won a game as Hitler.
+    HitlerWin,
+    /// Was still alive at the end of a game in which someone was executed.
+    SurvivedExecution,
+    /// Won `WIN_STREAK` games in a row.
+    WinStreak,
+}
+
+impl GameState {
+    /// The achievements each seated player earned in this game alone, once it has ended.
+    /// Streaks span several games, so they are left to whoever keeps track of the players.
+    pub fn game_achievements(&self) -> BTreeMap<Uuid, Vec<Achievement>> {
+        let winner = match self.winner() {
+            Some(winner) => winner,
+            None => return BTreeMap::new()
+        };
+        let executed = self.timeline().iter().any(|entry| matches!(entry.event, GameEvent::PowerUsed { power: PresidentialPower::Execution, .. }));
+        self.conn.keys().filter_map(|player| {
+            let role = self.role(player)?;
+            let mut earned = vec![];
+            if matches!(role, PlayerType::Hitler) && winner == CardColor::Facist {
+                earned.push(Achievement::HitlerWin);
+            }
+            if executed && self.is_alive(player) {
+                earned.push(Achievement::SurvivedExecution);
+            }
+            Some((*player, earned))
+        }).collect()
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{claims::Conflict, events::GameEvent, game_state::{CardColor, PresidentialPower, TimelineEntry}, rules};
+
+/// How often a facist in government passes a liberal policy for cover when they could have passed a facist one.
+const FACIST_COVER: f64 = 0.25;
+
+/// How often a member of the facist team votes for a government with one of their own in it.
+/// Liberals cannot tell governments apart, so they are taken to vote for any government half of the time.
+const FACIST_TEAM_VOTE: f64 = 0.75;
+
+/// What could be inferred about every player once an event had happened.
+#[derive(Serialize)]
+pub struct AnalysisStep {
+    pub at: u64,
+    pub description: String,
+    /// The chance that each player is a facist or Hitler.
+    pub facist: BTreeMap<Uuid, f64>,
+    pub hitler: BTreeMap<Uuid, f64>,
+}
+
+/// A Bayesian reading of a game's public events, showing what a liberal who watched closely could have worked out as the game went on.
+/// Every possible deal of the roles is weighed by how well a simple model of play explains the votes, policies, and executions,
+/// so the numbers show how suspicious each public record was rather than certainties.
+/// A liberal reading the analysis would also rule themselves out.
+#[derive(Serialize)]
+pub struct Analysis {
+    /// Every seat, in turn order.
+    pub players: Vec<Uuid>,
+    pub steps: Vec<AnalysisStep>,
+    /// How often each player's claims conflicted with each other player's.
+    /// A player's entry for themselves counts their claims that could not fit the board whatever their partner said.
+    pub conflicts: BTreeMap<Uuid, BTreeMap<Uuid, usize>>,
+}
+
+/// One way the roles could have been dealt, by seat.
+struct Deal {
+    facist: Vec<bool>,
+    hitler: usize,
+    weight: f64,
+}
+
+impl Deal {
+    fn has_facist(&self, seats: &[Option<usize>]) -> bool {
+        seats.iter().flatten().any(|seat| self.facist[*seat])
+    }
+}
+
+/// Every deal with this many players on the facist team, including Hitler, all equally likely.
+fn deals(players: usize, team: usize) -> Vec<Deal> {
+    let mut deals = vec![];
+    for mask in 0u32..1 << players {
+        if mask.count_ones() as usize != team {
+            continue
+        }
+        let facist: Vec<bool> = (0..players).map(|seat| mask & (1 << seat) != 0).collect();
+        for hitler in (0..players).filter(|seat| facist[*seat]) {
+            deals.push(Deal { facist: facist.clone(), hitler, weight: 1.0 });
+        }
+    }
+    deals
+}
+
+fn choose(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0
+    }
+    (0..k).fold(1.0, |total, i| total * (n - i) as f64 / (i + 1) as f64)
+}
+
+impl Analysis {
+    /// Analyse the last game started in a timeline.
+    pub fn new(timeline: &[TimelineEntry]) -> Option<Analysis> {
+        let start = timeline.iter().rposition(|entry| matches!(entry.event, GameEvent::GameStarted { .. }))?;
+        let (players, facists, hitler_chancellor_policies) = match &timeline[start].event {
+            GameEvent::GameStarted { players, facists, hitler_chancellor_policies } => (players.clone(), *facists, *hitler_chancellor_policies),
+            _ => return None
+        };
+        let seat = |player: &Uuid| players.iter().position(|p| p == player);
+        let mut deals = deals(players.len(), facists + 1);
+        let (mut liberal_policies, mut facist_policies) = (0, 0);
+        let mut government = None;
+        let mut steps = vec![];
+        let mut conflicts: BTreeMap<Uuid, BTreeMap<Uuid, usize>> = BTreeMap::new();
+
+        for (i, entry) in timeline.iter().enumerate().skip(start) {
+            // whatever happens last may have ended the game, which says nothing about who Hitler was not,
+            // and so may the vote just before Hitler is revealed
+            let continued = timeline.get(i + 1).is_some_and(|next| !matches!(next.event, GameEvent::HitlerElected { .. }));
+            match &entry.event {
+                GameEvent::VoteHeld { president, chancellor, votes, elected } => {
+                    let members = [seat(president), seat(chancellor)];
+                    for (voter, vote) in votes {
+                        if let Some(voter) = seat(voter) {
+                            for deal in deals.iter_mut() {
+                                let approve = if deal.facist[voter] && deal.has_facist(&members) { FACIST_TEAM_VOTE } else { 0.5 };
+                                deal.weight *= if *vote { approve } else { 1.0 - approve };
+                            }
+                        }
+                    }
+                    if *elected && continued && facist_policies >= hitler_chancellor_policies {
+                        deals.retain(|deal| Some(deal.hitler) != members[1]);
+                    }
+                    government = Some(members).filter(|_| *elected);
+                },
+                GameEvent::PolicyEnacted { policy, chaos, .. } => {
+                    if let (Some(members), false) = (government, chaos) {
+                        // the chance of drawing three cards of one color from what has not been enacted yet
+                        let liberals = rules::LIBERAL_CARDS - liberal_policies as usize;
+                        let facists = rules::FACIST_CARDS - facist_policies as usize;
+                        let draws = choose(liberals + facists, 3);
+                        let (all_facist, all_liberal) = (choose(facists, 3) / draws, choose(liberals, 3) / draws);
+                        for deal in deals.iter_mut() {
+                            let facist_chance = if deal.has_facist(&members) { all_facist + (1.0 - all_facist - all_liberal) * (1.0 - FACIST_COVER) } else { all_facist };
+                            deal.weight *= if *policy == CardColor::Facist { facist_chance } else { 1.0 - facist_chance };
+                        }
+                    }
+                    match policy {
+                        CardColor::Liberal => liberal_policies += 1,
+                        CardColor::Facist => facist_policies += 1
+                    }
+                    government = None;
+                },
+                GameEvent::ClaimConflict { president, chancellor, conflict } => {
+                    let pairs = match conflict {
+                        Conflict::Disagreement => vec![(*president, *chancellor), (*chancellor, *president)],
+                        Conflict::ContradictsPolicy { player } | Conflict::ImpossibleDraw { player } => vec![(*player, *player)],
+                    };
+                    for (player, other) in pairs {
+                        *conflicts.entry(player).or_default().entry(other).or_default() += 1;
+                    }
+                },
+                GameEvent::HitlerElected { hitler, .. } => {
+                    deals.retain(|deal| Some(deal.hitler) == seat(hitler));
+                },
+                GameEvent::PowerUsed { power: PresidentialPower::Execution, target: Some(target), .. } if continued => {
+                    deals.retain(|deal| Some(deal.hitler) != seat(target));
+                },
+                _ => {}
+            }
+
+            let total: f64 = deals.iter().map(|deal| deal.weight).sum();
+            let chance = |matches: &dyn Fn(&Deal) -> bool| {
+                let weight: f64 = deals.iter().filter(|deal| matches(deal)).map(|deal| deal.weight).sum();
+                if total > 0.0 { (weight / total * 1000.0).round() / 1000.0 } else { 0.0 }
+            };
+            steps.push(AnalysisStep {
+                at: entry.at,
+                description: entry.event.to_string(),
+                facist: players.iter().enumerate().map(|(i, player)| (*player, chance(&|deal| deal.facist[i]))).collect(),
+                hitler: players.iter().enumerate().map(|(i, player)| (*player, chance(&|deal| deal.hitler == i))).collect(),
+            });
+        }
+        Some(Analysis { players, steps, conflicts })
+    }
+}
@@ -0,0 +1,126 @@
+use rand::{Rng, seq::SliceRandom};
+use uuid::Uuid;
+
+use crate::{game_state::{CardColor, GameOptions, GameState, PlayerType, PresidentialPower, TurnPhase}, machine::Action, messages::Message, protocol::PlayerConnection, tutorial::Tutorial};
+
+/// Upper bound on bot moves handled at once, in case the bots ever get stuck in a loop.
+const MAX_BOT_ACTIONS: usize = 200;
+
+const BOT_NAMES: &[&str] = &["Otto", "Greta", "Franz", "Liesl", "Bruno", "Ilse", "Konrad", "Hedwig", "Emil"];
+
+fn is_facist_team(role: Option<PlayerType>) -> bool {
+    matches!(role, Some(PlayerType::Facist) | Some(PlayerType::Hitler))
+}
+
+impl GameState {
+    /// Create a practice game for a single player, filled with bots, and start it.
+    pub fn new_practice(player: Uuid, conn: PlayerConnection, num_bots: usize) -> GameState {
+        let mut state = GameState::with_options(GameOptions::default());
+        state.add_player(player, conn);
+        let mut names = BOT_NAMES.to_vec();
+        names.shuffle(&mut state.bot_rng);
+        for name in names.into_iter().take(num_bots) {
+            state.add_player(Uuid::new_v4(), PlayerConnection::bot(format!("{} (Bot)", name)));
+        }
+        state.apply(player, Action::Start).expect("practice games have a valid number of players");
+        state.tutorial = Some(Tutorial::new(player));
+        state.advance_tutorial();
+        state.run_bots();
+        state.advance_tutorial();
+        state
+    }
+
+    /// Let the bots take their turns until the game is waiting on a human player.
+    pub fn run_bots(&mut self) {
+        let mut bots: Vec<Uuid> = self.conn.iter().filter(|(_, c)| c.is_bot).map(|(id, _)| *id).collect();
+        // a fixed order keeps seeded games reproducible
+        bots.sort();
+        if bots.is_empty() {
+            return
+        }
+        for _ in 0..MAX_BOT_ACTIONS {
+            if !bots.iter().any(|bot| self.bot_act(*bot)) {
+                break
+            }
+        }
+    }
+
+    /// Make the moves the game is waiting on from players who have lost their connection, the way a bot would, so a game they walked out on can go on.
+    /// Returns true if any move was made.
+    pub fn stand_in_for_absent(&mut self) -> bool {
+        let absent: Vec<Uuid> = self.awaiting().into_iter().filter(|player| self.conn.get(player).is_some_and(|conn| !conn.connected && !conn.is_bot)).collect();
+        let mut acted = false;
+        for player in absent {
+            acted |= self.bot_act(player);
+        }
+        if acted {
+            self.announce(Message::StoodIn);
+        }
+        acted
+    }
+
+    /// Take a single action for the bot if it has one pending, returning true if it acted.
+    fn bot_act(&mut self, bot: Uuid) -> bool {
+        if !self.is_alive(&bot) {
+            return false
+        }
+        let is_president = self.president() == Some(bot);
+        let is_chancellor = self.chancellor() == Some(bot);
+        let facist = is_facist_team(self.role(&bot));
+
+        match self.turn_phase() {
+            TurnPhase::Electing if self.unacknowledged().contains(&bot) => {
+                self.acknowledge_role(bot).is_ok()
+            },
+            TurnPhase::Electing if is_president => {
+                let mut candidates = self.living_players().to_vec();
+                candidates.shuffle(&mut self.bot_rng);
+                // fascists prefer to nominate their teammates
+                if facist {
+                    candidates.sort_by_key(|c| !is_facist_team(self.role(c)));
+                }
+                candidates.into_iter().any(|c| self.apply(bot, Action::Nominate { chancellor: c }).is_ok())
+            },
+            // bots have nothing to say, so they agree to vote straight away
+            TurnPhase::Discussion if self.awaiting().contains(&bot) => {
+                self.apply(bot, Action::CallVote).is_ok()
+            },
+            TurnPhase::Voting if !self.has_voted(&bot) => {
+                let vote = match self.chancellor() {
+                    Some(chancellor) if facist && is_facist_team(self.role(&chancellor)) => true,
+                    _ => self.bot_rng.gen_bool(0.7)
+                };
+                self.apply(bot, Action::Vote { approve: vote }).is_ok()
+            },
+            TurnPhase::PresidentSelect if is_president => {
+                let hand = self.hand(bot).unwrap_or_default();
+                let unwanted = if facist { CardColor::Liberal } else { CardColor::Facist };
+                let index = hand.iter().position(|card| *card == unwanted).unwrap_or(0);
+                self.apply(bot, Action::PickCard { index }).is_ok()
+            },
+            // a chancellor asks for a veto when they hold nothing their team wants, so liberals agree and facists refuse
+            TurnPhase::ChancellorSelect if is_president && self.awaiting().contains(&bot) => {
+                self.apply(bot, Action::RespondVeto { accept: !facist }).is_ok()
+            },
+            TurnPhase::ChancellorSelect if is_chancellor => {
+                let hand = self.hand(bot).unwrap_or_default();
+                let wanted = if facist { CardColor::Facist } else { CardColor::Liberal };
+                let index = hand.iter().position(|card| *card == wanted).unwrap_or(0);
+                self.apply(bot, Action::PickCard { index }).is_ok()
+            },
+            TurnPhase::PresidentialPower { power: PresidentialPower::PolicyPeek } if is_president => {
+                self.apply(bot, Action::UsePower { target: None }).is_ok()
+            },
+            TurnPhase::PresidentialPower { power: _ } if is_president => {
+                let mut targets: Vec<Uuid> = self.living_players().iter().filter(|p| **p != bot).copied().collect();
+                targets.shuffle(&mut self.bot_rng);
+                // fascists avoid using powers on their teammates
+                if facist {
+                    targets.sort_by_key(|t| is_facist_team(self.role(t)));
+                }
+                targets.into_iter().any(|t| self.apply(bot, Action::UsePower { target: Some(t) }).is_ok())
+            },
+            _ => false
+        }
+    }
+}
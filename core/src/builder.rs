@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use rand::{SeedableRng, rngs::StdRng};
+use uuid::Uuid;
+
+use crate::{game_state::{GameOptions, GameState}, protocol::{NullSink, PlayerConnection}};
+
+/// Sets up a game for code that embeds the engine, such as bots, chat bridges, and simulations.
+/// Players seated by id get a connection that drops every message, since such callers read the game through its methods and the events each action returns.
+#[derive(Default)]
+pub struct GameBuilder {
+    options: GameOptions,
+    rng: Option<StdRng>,
+    seats: Vec<(Uuid, PlayerConnection)>,
+}
+
+impl GameState {
+    pub fn builder() -> GameBuilder {
+        GameBuilder::default()
+    }
+}
+
+impl GameBuilder {
+    pub fn options(mut self, options: GameOptions) -> GameBuilder {
+        self.options = options;
+        self
+    }
+
+    /// The random source that deals roles, shuffles the deck, and moves the bots.
+    pub fn rng(mut self, rng: StdRng) -> GameBuilder {
+        self.rng = Some(rng);
+        self
+    }
+
+    /// Decide the game by a seed, so it plays out the same way every time.
+    pub fn seed(self, seed: u64) -> GameBuilder {
+        self.rng(StdRng::seed_from_u64(seed))
+    }
+
+    /// Seat players who are not sent any messages. The first player seated is the host.
+    pub fn players(mut self, players: impl IntoIterator<Item = Uuid>) -> GameBuilder {
+        self.seats.extend(players.into_iter().map(|player| (player, PlayerConnection::new(Arc::new(NullSink)))));
+        self
+    }
+
+    /// Seat a player with their own connection, such as a bot or a player whose messages are delivered somewhere.
+    pub fn player(mut self, player: Uuid, conn: PlayerConnection) -> GameBuilder {
+        self.seats.push((player, conn));
+        self
+    }
+
+    /// Create the game in its lobby. Players beyond the number of seats are put on the waitlist, as when joining.
+    pub fn build(self) -> GameState {
+        let mut state = GameState::with_rng(self.options, self.rng.unwrap_or_else(StdRng::from_entropy));
+        for (player, conn) in self.seats {
+            state.add_player(player, conn);
+        }
+        state
+    }
+}
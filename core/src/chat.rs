@@ -0,0 +1,116 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A piece of a chat message, so clients can show links without rendering anything the sender wrote as markup.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum ChatSegment {
+    Text { text: String },
+    /// A link to a website, where `text` is what the sender wrote and `url` always starts with `http://` or `https://`.
+    Link { text: String, url: String },
+}
+
+/// Which links in chat messages are made clickable. Links to other sites are left as text.
+#[derive(Clone, Debug, Default)]
+pub struct LinkPolicy {
+    allowed_hosts: Option<Vec<String>>,
+}
+
+impl LinkPolicy {
+    /// Links to any website.
+    pub fn any() -> LinkPolicy {
+        LinkPolicy { allowed_hosts: None }
+    }
+
+    /// Only links to these hosts and their subdomains.
+    pub fn only(hosts: Vec<String>) -> LinkPolicy {
+        LinkPolicy { allowed_hosts: Some(hosts.into_iter().map(|host| host.trim_start_matches('.').to_ascii_lowercase()).collect()) }
+    }
+
+    fn allows(&self, host: &str) -> bool {
+        match &self.allowed_hosts {
+            Some(hosts) => hosts.iter().any(|allowed| host == allowed || host.ends_with(&format!(".{}", allowed))),
+            None => true
+        }
+    }
+}
+
+/// Remove HTML tags from a message, along with everything inside script and style elements.
+/// A `<` that does not start a tag, as in `<3`, is kept.
+pub fn strip_html(message: &str) -> String {
+    let mut stripped = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(start) = rest.find('<') {
+        stripped.push_str(&rest[..start]);
+        let tag = &rest[start + 1..];
+        let end = tag.find('>').filter(|_| tag.starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!'));
+        match end {
+            Some(end) => {
+                let name = tag.chars().take_while(char::is_ascii_alphanumeric).collect::<String>().to_ascii_lowercase();
+                rest = &tag[end + 1..];
+                if name == "script" || name == "style" {
+                    // lowercasing ascii keeps byte offsets the same
+                    rest = match rest.to_ascii_lowercase().find(&format!("</{}", name)) {
+                        Some(closing) => rest[closing..].find('>').map_or("", |end| &rest[closing + end + 1..]),
+                        None => ""
+                    };
+                }
+            },
+            None => {
+                stripped.push('<');
+                rest = tag;
+            }
+        }
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// The url of a word that is a link, and the host it points to.
+fn link(word: &str) -> Option<(String, String)> {
+    let lower = word.to_ascii_lowercase();
+    let (url, host_start) = if lower.starts_with("http://") || lower.starts_with("https://") {
+        (word.to_string(), lower.find("//").unwrap() + 2)
+    }
+    else if lower.starts_with("www.") {
+        (format!("https://{}", word), "https://".len())
+    }
+    else {
+        return None
+    };
+    let lower_url = url.to_ascii_lowercase();
+    let host = lower_url[host_start..].split(['/', '?', '#', ':']).next().unwrap_or_default();
+    let valid = host.contains('.') && !host.starts_with('.') && !host.ends_with('.') && host.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+    if !valid {
+        return None
+    }
+    Some((url, host.to_string()))
+}
+
+/// Split a message into text and the links the policy allows.
+pub fn segments(message: &str, policy: &LinkPolicy) -> Vec<ChatSegment> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut rest = message;
+    while !rest.is_empty() {
+        let word_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let space_end = rest[word_end..].find(|c: char| !c.is_whitespace()).map_or(rest.len(), |end| word_end + end);
+        // punctuation after a link is not part of it
+        let word = rest[..word_end].trim_end_matches(['.', ',', '!', '?', ';', ':', ')', ']', '\'', '"']);
+        match link(word).filter(|(_, host)| policy.allows(host)) {
+            Some((url, _)) => {
+                if !text.is_empty() {
+                    segments.push(ChatSegment::Text { text: std::mem::take(&mut text) });
+                }
+                segments.push(ChatSegment::Link { text: word.to_string(), url });
+                text.push_str(&rest[word.len()..space_end]);
+            },
+            None => text.push_str(&rest[..space_end])
+        }
+        rest = &rest[space_end..];
+    }
+    if !text.is_empty() {
+        segments.push(ChatSegment::Text { text });
+    }
+    segments
+}
@@ -0,0 +1,123 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{error::GameError, events::GameEvent, game_state::{CardColor, GameState}, history::Command, messages::Message, rules};
+
+/// Ways a government's claims cannot all be true.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum Conflict {
+    /// The chancellor claims policies that the president could not have passed from the hand they claim.
+    Disagreement,
+    /// A claimed hand has no policy of the color the government enacted.
+    ContradictsPolicy { player: Uuid },
+    /// A claimed hand holds more policies of a color than had not yet been enacted.
+    ImpossibleDraw { player: Uuid },
+}
+
+/// A government that enacted a policy, and what its members say they were dealt.
+/// Claims are only what the players declare, so they may well be lies.
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Government {
+    pub president: Uuid,
+    pub chancellor: Uuid,
+    pub enacted: CardColor,
+    /// The three policies the president says they drew.
+    pub president_claim: Option<Vec<CardColor>>,
+    /// The two policies the chancellor says they were passed.
+    pub chancellor_claim: Option<Vec<CardColor>>,
+    /// Policies of each color that had not been enacted when the president drew, so no hand could hold more of them.
+    pub liberals_left: u8,
+    pub facists_left: u8,
+    /// Ways the claims made so far cannot all be true.
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Government {
+    pub(crate) fn new(president: Uuid, chancellor: Uuid, enacted: CardColor, liberal_policies: u8, facist_policies: u8) -> Government {
+        Government {
+            president,
+            chancellor,
+            enacted,
+            president_claim: None,
+            chancellor_claim: None,
+            liberals_left: (rules::LIBERAL_CARDS as u8).saturating_sub(liberal_policies),
+            facists_left: (rules::FACIST_CARDS as u8).saturating_sub(facist_policies),
+            conflicts: vec![],
+        }
+    }
+
+    /// The conflicts a new claim by a player brings, given the claims already made.
+    fn conflicts_with(&self, player: Uuid, cards: &[CardColor]) -> Vec<Conflict> {
+        let count = |cards: &[CardColor], color: CardColor| cards.iter().filter(|card| **card == color).count() as u8;
+        let mut conflicts = vec![];
+        if !cards.contains(&self.enacted) {
+            conflicts.push(Conflict::ContradictsPolicy { player });
+        }
+        if count(cards, CardColor::Liberal) > self.liberals_left || count(cards, CardColor::Facist) > self.facists_left {
+            conflicts.push(Conflict::ImpossibleDraw { player });
+        }
+        let (president, chancellor) = match (&self.president_claim, &self.chancellor_claim) {
+            (Some(president), None) if player == self.chancellor => (president.as_slice(), cards),
+            (None, Some(chancellor)) if player == self.president => (cards, chancellor.as_slice()),
+            _ => return conflicts
+        };
+        // the president passes on all but one of the policies they drew
+        if [CardColor::Liberal, CardColor::Facist].iter().any(|color| count(chancellor, *color) > count(president, *color)) {
+            conflicts.push(Conflict::Disagreement);
+        }
+        conflicts
+    }
+}
+
+impl GameState {
+    /// Every government that has enacted a policy this game, oldest first.
+    pub fn governments(&self) -> &[Government] {
+        &self.governments
+    }
+
+    /// Declare the policies the player was dealt as president or chancellor of the most recent government.
+    /// Each member claims once, and only until the next government enacts a policy.
+    pub fn claim(&mut self, player: Uuid, cards: Vec<CardColor>) -> Result<(), GameError> {
+        let government = match self.governments.last_mut() {
+            Some(government) => government,
+            None => return Err(GameError::WrongPhase)
+        };
+        let as_president = government.president == player;
+        let (claimed, size) = if as_president {
+            (government.president_claim.is_some(), 3)
+        }
+        else if government.chancellor == player {
+            (government.chancellor_claim.is_some(), 2)
+        }
+        else {
+            return Err(GameError::NotInGovernment { action: "claim the policies of the last government" });
+        };
+        if claimed {
+            return Err(GameError::AlreadyClaimed);
+        }
+        if cards.len() != size {
+            return Err(GameError::InvalidClaim);
+        }
+        let conflicts = government.conflicts_with(player, &cards);
+        let (president, chancellor) = (government.president, government.chancellor);
+        if as_president {
+            government.president_claim = Some(cards.clone());
+        }
+        else {
+            government.chancellor_claim = Some(cards.clone());
+        }
+        government.conflicts.extend(conflicts.iter().copied());
+
+        if let Some(name) = self.player_name(&player) {
+            self.announce(Message::PoliciesClaimed { as_president, name: &name, cards: &cards });
+        }
+        self.send_event(GameEvent::PoliciesClaimed { player, as_president, cards: cards.clone() });
+        for conflict in conflicts {
+            self.send_event(GameEvent::ClaimConflict { president, chancellor, conflict });
+        }
+        self.record(Command::Claim { player, cards });
+        Ok(())
+    }
+}
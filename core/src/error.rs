@@ -0,0 +1,109 @@
+use std::fmt;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Reasons a player's action can be rejected by the game.
+/// The code and any context are sent to the client along with the message.
+#[derive(Debug, Clone, PartialEq, Error, Serialize, JsonSchema)]
+#[serde(tag = "code")]
+pub enum GameError {
+    #[error("You cannot perform this action at this time!")]
+    WrongPhase,
+    #[error("The game has already started!")]
+    AlreadyStarted,
+    #[error("Only the host may {action}!")]
+    NotHost { action: &'static str },
+    #[error("There are too many or too few players to start a game!")]
+    InvalidPlayerCount { players: usize },
+    #[error("Only the president may {action}.")]
+    NotPresident { action: &'static str },
+    #[error("Only the chancellor may {action}.")]
+    NotChancellor { action: &'static str },
+    #[error("Only the president and the chancellor may {action}.")]
+    NotInGovernment { action: &'static str },
+    #[error("You cannot choose yourself. You must choose another player.")]
+    SelfTarget,
+    #[error("You cannot choose the last elected president or chancellor.")]
+    TermLimited { player: Uuid },
+    #[error("You must select a player!")]
+    MissingTarget,
+    #[error("That player does not exist!")]
+    PlayerNotFound { player: Uuid },
+    #[error("That player is dead!")]
+    PlayerDead { player: Uuid },
+    #[error("You are dead and therefore cannot vote!")]
+    VoterDead,
+    #[error("You are not a player in this game!")]
+    NotAPlayer,
+    #[error("That policy is not a valid option.")]
+    InvalidPolicy,
+    #[error("You cannot veto policies until 5 facist policies have been passed.")]
+    VetoLocked,
+    #[error("You have already asked for a veto this turn.")]
+    VetoAlreadyRequested,
+    #[error("The chancellor has not asked for a veto.")]
+    NoVetoRequested,
+    #[error("The president has yet to answer your request for a veto.")]
+    VetoPending,
+    #[error("Everyone has to be ready before the game can start!")]
+    NotReady { players: Vec<Uuid> },
+    #[error("Everyone has to see their role before the first election!")]
+    RolesNotAcknowledged { players: Vec<Uuid> },
+    #[error("The game is already counting down to its start.")]
+    CountdownRunning,
+    #[error("There is no countdown to cancel.")]
+    NoCountdown,
+    #[error("Another vote is already under way.")]
+    LobbyVoteInProgress,
+    #[error("There is no vote to take part in.")]
+    NoLobbyVote,
+    #[error("Actions cannot be undone in ranked games.")]
+    UndoDisabled,
+    #[error("There is nothing to undo.")]
+    NothingToUndo,
+    #[error("You have already claimed the policies you were dealt.")]
+    AlreadyClaimed,
+    #[error("A president claims three policies and a chancellor claims two.")]
+    InvalidClaim,
+    /// A game sent to be imported does not describe a game that can be played on.
+    #[error("The game cannot be imported: {reason}")]
+    InvalidExport { reason: &'static str },
+    /// Someone other than the player whose turn it is tried to take the device in a pass and play game.
+    #[error("Pass the device to the player whose turn it is.")]
+    NotHoldingDevice,
+    /// The server requires actions to carry the player secret or a resume token, and this one did not, or carried the wrong one.
+    #[error("Your action could not be verified. Please rejoin the game.")]
+    Unauthenticated,
+    /// The server failed while handling the action. The game carries on, but the action may or may not have been applied.
+    #[error("Something went wrong on the server. Please try again.")]
+    Internal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    /// An ordinary mistake, such as picking an ineligible chancellor.
+    Info,
+    /// Something the game interface should never allow, which may point to a client bug or a tampered client.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+impl GameError {
+    pub fn severity(&self) -> Severity {
+        match self {
+            GameError::WrongPhase | GameError::NotAPlayer | GameError::PlayerNotFound { .. } | GameError::NotInGovernment { .. } | GameError::Unauthenticated | GameError::Internal => Severity::Warning,
+            _ => Severity::Info,
+        }
+    }
+}
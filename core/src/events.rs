@@ -0,0 +1,138 @@
+use std::{collections::BTreeMap, fmt};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{claims::Conflict, game_state::{CardColor, PresidentialPower}, rules};
+
+/// Things that happen during a game which clients may want to animate.
+/// These are sent alongside the game state, which only shows the end result, and kept in the game's timeline.
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum GameEvent {
+    /// Roles were dealt and the game began.
+    GameStarted {
+        /// Every seat, in turn order.
+        players: Vec<Uuid>,
+        /// Number of facists, not counting Hitler.
+        facists: usize,
+        /// Facist policies needed on the board for Hitler's election to win, which the host may have changed.
+        #[serde(default = "default_hitler_chancellor_policies")]
+        hitler_chancellor_policies: u8,
+    },
+    /// Every living player has voted on a government.
+    VoteHeld {
+        president: Uuid,
+        chancellor: Uuid,
+        /// How each player voted, which is left empty in games with anonymous votes.
+        votes: BTreeMap<Uuid, bool>,
+        elected: bool,
+    },
+    /// A policy was placed on the board.
+    PolicyEnacted {
+        policy: CardColor,
+        /// The policy came from the top of the deck after three failed elections instead of from the chancellor.
+        chaos: bool,
+        /// Where the card was in the draw pile when it was drawn, counting down from the top at 0.
+        deck_position: usize,
+    },
+    /// A government failed to form or vetoed its policies.
+    ElectionTrackerAdvanced {
+        value: u8,
+        /// One more failed government will enact the top policy of the deck.
+        chaos_imminent: bool,
+    },
+    /// The president did not nominate a chancellor in time and lost their turn.
+    NominationExpired {
+        president: Uuid,
+    },
+    /// The president used the power granted by a facist policy.
+    PowerUsed {
+        president: Uuid,
+        power: PresidentialPower,
+        target: Option<Uuid>,
+    },
+    /// A member of the last government declared the policies they were dealt.
+    PoliciesClaimed {
+        player: Uuid,
+        /// Whether they claim as the president, who draws three policies, or the chancellor, who is passed two.
+        as_president: bool,
+        cards: Vec<CardColor>,
+    },
+    /// A government's claims turned out not to fit with each other or with the board.
+    ClaimConflict {
+        president: Uuid,
+        chancellor: Uuid,
+        conflict: Conflict,
+    },
+    /// The chancellor asked the president to veto the policies they were passed.
+    VetoRequested {
+        chancellor: Uuid,
+    },
+    /// The president answered a request for a veto. A declined veto leaves the chancellor to enact a policy.
+    VetoAnswered {
+        president: Uuid,
+        accepted: bool,
+    },
+    /// The discard pile was shuffled back into the draw pile.
+    DeckReshuffled {
+        /// Size of the draw pile after the reshuffle.
+        cards_in_deck: usize,
+    },
+    /// Hitler was elected chancellor once enough facist policies were on the board, winning the game for the facists.
+    /// This follows the vote that elected them, so clients can reveal Hitler instead of showing an ordinary policy win.
+    HitlerElected {
+        president: Uuid,
+        hitler: Uuid,
+        /// How each player voted, which is left empty in games with anonymous votes.
+        votes: BTreeMap<Uuid, bool>,
+        ayes: usize,
+        nays: usize,
+        /// Facist policies on the board at the time of the vote.
+        facist_policies: u8,
+        /// Facist policies needed on the board for Hitler's election to win.
+        required: u8,
+    },
+}
+
+fn default_hitler_chancellor_policies() -> u8 {
+    rules::HITLER_CHANCELLOR_POLICIES
+}
+
+impl fmt::Display for GameEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameEvent::GameStarted { players, facists, .. } => write!(f, "game started with {} players and {} facists besides Hitler", players.len(), facists),
+            GameEvent::VoteHeld { president, chancellor, votes, elected } => {
+                if votes.is_empty() {
+                    return write!(f, "government of president {} and chancellor {} was {} by secret ballot", president, chancellor, if *elected { "elected" } else { "rejected" })
+                }
+                let ayes = votes.values().filter(|vote| **vote).count();
+                write!(f, "government of president {} and chancellor {} was {} {} to {}", president, chancellor, if *elected { "elected" } else { "rejected" }, ayes, votes.len() - ayes)
+            },
+            GameEvent::PolicyEnacted { policy, chaos: true, deck_position } => write!(f, "chaos enacted a {} policy from position {} in the deck", policy, deck_position),
+            GameEvent::PolicyEnacted { policy, chaos: false, deck_position } => write!(f, "government enacted a {} policy from position {} in the deck", policy, deck_position),
+            GameEvent::ElectionTrackerAdvanced { value, chaos_imminent } => write!(f, "election tracker advanced to {}{}", value, if *chaos_imminent { ", chaos is imminent" } else { "" }),
+            GameEvent::NominationExpired { president } => write!(f, "president {} ran out of time to nominate a chancellor", president),
+            GameEvent::PowerUsed { president, power, target: Some(target) } => write!(f, "president {} used {:?} on {}", president, power, target),
+            GameEvent::PowerUsed { president, power, target: None } => write!(f, "president {} used {:?}", president, power),
+            GameEvent::PoliciesClaimed { player, as_president, cards } => {
+                let cards = cards.iter().map(|card| card.to_string()).collect::<Vec<_>>().join(", ");
+                write!(f, "{} {} claimed {}", if *as_president { "president" } else { "chancellor" }, player, cards)
+            },
+            GameEvent::ClaimConflict { president, chancellor, conflict } => {
+                write!(f, "claims of president {} and chancellor {} conflict: ", president, chancellor)?;
+                match conflict {
+                    Conflict::Disagreement => write!(f, "the chancellor could not have been passed what they claim"),
+                    Conflict::ContradictsPolicy { player } => write!(f, "{} claims no policy of the enacted color", player),
+                    Conflict::ImpossibleDraw { player } => write!(f, "{} claims more policies of a color than were left", player),
+                }
+            },
+            GameEvent::VetoRequested { chancellor } => write!(f, "chancellor {} asked for a veto", chancellor),
+            GameEvent::VetoAnswered { president, accepted } => write!(f, "president {} {} the veto", president, if *accepted { "accepted" } else { "declined" }),
+            GameEvent::DeckReshuffled { cards_in_deck } => write!(f, "deck reshuffled with {} cards", cards_in_deck),
+            GameEvent::HitlerElected { hitler, ayes, nays, facist_policies, .. } => write!(f, "Hitler ({}) was elected chancellor {} to {} with {} facist policies enacted", hitler, ayes, nays, facist_policies),
+        }
+    }
+}
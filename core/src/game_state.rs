@@ -0,0 +1,2308 @@
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize, ser::SerializeMap};
+use uuid::Uuid;
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+use std::{collections::{BTreeMap, BTreeSet, HashMap, LinkedList, VecDeque}, fmt, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use crate::{chat::{self, ChatSegment, LinkPolicy}, claims::Government, error::GameError, events::GameEvent, history::{Command, History}, lobby_vote::{LobbyVote, Motion, VoteOutcome}, messages::{Language, Message}, nicknames, odds::DeckOdds, protocol::{ConnectionState, PlayerConnection, ServerProtocol, Sink, Topic, send_to_all, send_to_matching}, rules::{self, Rules}, seating::Seating, theme::{Theme, ThemeNames}, tutorial::Tutorial};
+
+#[derive(Clone, Copy, Serialize, Deserialize, JsonSchema)]
+pub enum PlayerType {
+    Liberal,
+    Facist,
+    Hitler
+}
+
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+struct PlayerState {
+    role: PlayerType,
+    vote: Option<bool>,
+    dead: bool,
+    /// Whether the player has said they are ready to start, in the lobby.
+    ready: bool
+}
+
+#[derive(Serialize)]
+struct PartialPlayerState {
+    name: String,
+    avatar: Option<String>,
+    color: Option<String>,
+    role: Option<PlayerType>,
+    vote: Option<bool>,
+    dead: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ready: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acknowledged: Option<bool>
+}
+
+#[derive(Serialize)]
+struct WaitlistEntry {
+    id: Uuid,
+    name: String
+}
+
+/// Where a game is in a round. The phases a game can move between are listed in [`TurnPhase::can_become`].
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum TurnPhase {
+    Lobby,
+    Ended { winner: CardColor },
+    
+    Electing,
+    /// Time for the table to talk over a nomination before the vote opens.
+    Discussion,
+    Voting,
+    PresidentSelect,
+    ChancellorSelect,
+
+    PresidentialPower { power: PresidentialPower },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum PresidentialPower {
+    InvestigateLoyalty,
+    CallSpecialElection,
+    PolicyPeek,
+    Execution,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+pub enum CardColor {
+    Facist,
+    Liberal
+}
+
+impl std::fmt::Display for CardColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CardColor::Facist => write!(f, "facist"),
+            CardColor::Liberal => write!(f, "liberal")
+        }
+    }
+}
+
+impl CardColor {
+    /// An identifier for the faction that stays the same whatever the host calls it.
+    pub fn id(self) -> &'static str {
+        match self {
+            CardColor::Facist => "facist",
+            CardColor::Liberal => "liberal"
+        }
+    }
+
+    pub fn pattern(self) -> CardPattern {
+        match self {
+            CardColor::Facist => CardPattern::Striped,
+            CardColor::Liberal => CardPattern::Plain
+        }
+    }
+
+    pub fn shape(self) -> CardShape {
+        match self {
+            CardColor::Facist => CardShape::Triangle,
+            CardColor::Liberal => CardShape::Circle
+        }
+    }
+}
+
+/// A fill for each kind of policy, so they can be told apart without color.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum CardPattern {
+    Plain,
+    Striped
+}
+
+/// A symbol for each kind of policy, so they can be told apart without color.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum CardShape {
+    Circle,
+    Triangle
+}
+
+/// How a frontend should draw one kind of policy, listed in the game state for boards that do not rely on color.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CardFace {
+    pub color: CardColor,
+    pub id: String,
+    /// The faction's name, which the host may have changed.
+    pub name: String,
+    pub pattern: CardPattern,
+    pub shape: CardShape,
+}
+
+/// Settings chosen by the host when the game is created.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct GameOptions {
+    /// Number of seconds each turn phase lasts, or none if timers are disabled.
+    pub turn_timer: Option<u64>,
+    /// Number of seats in the lobby, between 5 and 10. Extra players are placed on a waitlist.
+    pub max_players: Option<usize>,
+    /// Whether a president who runs out of time to nominate a chancellor counts as a failed government.
+    #[serde(default)]
+    pub nomination_timeout_fails: bool,
+    /// Played over days rather than in one sitting. The game is kept while nobody is connected, and players are notified when it is their turn.
+    /// Turn timers of several hours are expected for these games.
+    #[serde(default)]
+    pub asynchronous: bool,
+    /// Number of seconds that spectators see the game behind the players, so that a streamed game cannot be used to cheat.
+    #[serde(default)]
+    pub spectator_delay: Option<u64>,
+    /// When the lobby opens for joining, in milliseconds since the epoch. Until then only the host is seated.
+    #[serde(default)]
+    pub scheduled_at: Option<u64>,
+    /// Only let the host start once every seated player has said they are ready.
+    #[serde(default)]
+    pub require_ready: bool,
+    /// Hold the first election until every player has said they have seen their role, so nobody still loading misses it.
+    #[serde(default)]
+    pub require_role_ack: bool,
+    /// A competitive game, in which the host cannot undo actions.
+    #[serde(default)]
+    pub ranked: bool,
+    /// Number of seconds the table has to discuss a nomination before voting, or none to vote straight away.
+    /// The president and chancellor can agree to call the vote early.
+    #[serde(default)]
+    pub discussion_timer: Option<u64>,
+    /// Keep each player's vote to themselves, so only whether a government was elected and by how much is ever shown.
+    #[serde(default)]
+    pub anonymous_votes: bool,
+    /// The language the game writes its chat messages in.
+    #[serde(default)]
+    pub language: Language,
+    /// What the game calls its factions, roles, and boards.
+    #[serde(default)]
+    pub theme: Theme,
+    /// Show every player the chances for the next policies drawn, for groups who would rather not work them out.
+    #[serde(default)]
+    pub deck_odds: bool,
+    /// Number of facist policies that must be enacted before electing Hitler as chancellor wins, or none for the usual three.
+    /// This is kept between 1 and one fewer than the facist policies needed to win.
+    #[serde(default)]
+    pub hitler_chancellor_policies: Option<u8>,
+}
+
+/// How long before a scheduled game opens that its players are reminded.
+pub const SCHEDULE_REMINDER: Duration = Duration::from_secs(15 * 60);
+
+/// Longest countdown the host can start before the game begins, in seconds.
+pub const MAX_COUNTDOWN: u64 = 60;
+
+/// How long the first election waits for players to say they have seen their role before it starts without them.
+pub const ROLE_ACK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Steps in the countdown of a scheduled lobby.
+#[derive(Debug, PartialEq)]
+pub enum ScheduleEvent {
+    /// The lobby opens soon.
+    Reminder,
+    /// The lobby is now open for joining.
+    Opened,
+}
+
+/// Game options saved under a name, so a host can set up the same kind of game again.
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GamePreset {
+    pub name: String,
+    pub options: GameOptions,
+}
+
+/// The public score of a game, for lightweight displays that do not need the full state.
+#[derive(Serialize, JsonSchema)]
+pub struct Scoreboard<'a> {
+    pub liberal_policies: u8,
+    pub facist_policies: u8,
+    pub election_tracker: u8,
+    pub turn_phase: &'a TurnPhase,
+}
+
+/// Public information about a game that is safe to share outside of it.
+#[derive(Serialize)]
+pub struct GameSummary {
+    pub host: Option<String>,
+    pub players: Vec<String>,
+    pub liberal_policies: u8,
+    pub facist_policies: u8,
+    pub winner: Option<CardColor>,
+}
+
+/// An event in the game's timeline and when it happened, in milliseconds since the epoch.
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TimelineEntry {
+    pub at: u64,
+    pub event: GameEvent,
+}
+
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ChatLine {
+    pub id: Option<Uuid>,
+    pub message: String,
+    /// The message split into text and links, for clients to show without treating any of it as markup.
+    #[serde(default)]
+    pub segments: Vec<ChatSegment>,
+    /// The token of an image uploaded for the game, fetched from `/upload/{token}`.
+    #[serde(default)]
+    pub attachment: Option<Uuid>,
+}
+
+impl ChatLine {
+    /// A line written by a player, with any HTML removed and the links the policy allows marked.
+    pub fn new(player: Uuid, message: &str, links: &LinkPolicy) -> ChatLine {
+        let message = chat::strip_html(message);
+        ChatLine { id: Some(player), segments: chat::segments(&message, links), message, attachment: None }
+    }
+
+    /// A line from the game itself, which never holds links.
+    pub fn system(message: String) -> ChatLine {
+        ChatLine { id: None, segments: vec![ChatSegment::Text { text: message.clone() }], message, attachment: None }
+    }
+}
+
+/// The policies drawn for an elected government, held from the vote until one is enacted or the rest are vetoed.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct LegislativeSession {
+    /// The policies still in hand, in the order they lay in the deck, so the last was on top.
+    pub drawn: Vec<CardColor>,
+    /// The policies thrown away so far, which go on the discard pile once the session ends.
+    pub discarded: Vec<CardColor>,
+    pub stage: LegislativeStage,
+}
+
+/// Whose turn it is to pick a policy in a legislative session.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum LegislativeStage {
+    /// The president holds three policies and discards one.
+    President,
+    /// The chancellor holds the other two and enacts one.
+    Chancellor {
+        /// Where the president's discard was among the three drawn.
+        discarded_slot: usize,
+    },
+}
+
+impl LegislativeSession {
+    /// Where a card in hand was in the deck when it was drawn, counting down from the top at 0.
+    fn deck_position(&self, index: usize) -> usize {
+        let slot = match self.stage {
+            LegislativeStage::Chancellor { discarded_slot } if index >= discarded_slot => index + 1,
+            _ => index
+        };
+        2 - slot
+    }
+}
+
+/// A seat's connection details, kept in an export so players can take their seats again.
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+struct SeatExport {
+    name: Option<String>,
+    secret: Option<Uuid>,
+    avatar: Option<String>,
+    color: Option<String>,
+    topics: Vec<Topic>,
+    is_bot: bool,
+    fingerprint: Option<String>,
+    #[serde(default)]
+    language: Option<Language>,
+}
+
+/// Everything needed to carry a game over to another server, or across a restart.
+/// Connections cannot be saved, so every player rejoins the imported game with their player id and secret.
+/// Lobby votes, countdowns, and retried requests are left out, and the deck is shuffled with a fresh random source from then on.
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GameExport {
+    options: GameOptions,
+    seats: HashMap<Uuid, SeatExport>,
+    chat_log: LinkedList<ChatLine>,
+    players: HashMap<Uuid, PlayerState>,
+    seating: Seating,
+    num_facists: usize,
+    liberal_policies: u8,
+    facist_policies: u8,
+    election_tracker: u8,
+    cards: Vec<CardColor>,
+    discarded: Vec<CardColor>,
+    legislative_session: Option<LegislativeSession>,
+    turn_phase: TurnPhase,
+    phase_started_at: u64,
+    turn_counter: usize,
+    turn_order: Vec<Uuid>,
+    rotation: usize,
+    last_president: Option<Uuid>,
+    last_chancellor: Option<Uuid>,
+    president: Option<Uuid>,
+    chancellor: Option<Uuid>,
+    host: Option<Uuid>,
+    veto_requested: bool,
+    veto_declined: bool,
+    president_called_vote: bool,
+    chancellor_called_vote: bool,
+    investigated: HashMap<Uuid, Vec<Uuid>>,
+    governments: Vec<Government>,
+    timeline: Vec<TimelineEntry>,
+    opens_at: Option<u64>,
+    schedule_reminded: bool,
+    banned: Vec<(Option<Uuid>, Option<String>)>,
+    #[serde(default)]
+    community: Option<String>,
+    #[serde(default)]
+    unacknowledged: BTreeSet<Uuid>,
+}
+
+/// Everything about a game that helps work out why it is stuck, for admins.
+#[derive(Serialize, JsonSchema)]
+pub struct GameDebug {
+    /// The whole game as it would be exported, or none for practice games.
+    pub game: Option<GameExport>,
+    /// The players the game is waiting on.
+    pub awaiting: Vec<Uuid>,
+    /// When each pending timer runs out, in milliseconds since the epoch.
+    pub phase_deadline: Option<u64>,
+    pub lobby_vote_expires_at: Option<u64>,
+    pub countdown_ends_at: Option<u64>,
+    pub opens_at: Option<u64>,
+    /// When the game was left without anyone connected, which starts the countdown to it being cleaned up.
+    pub idle_since: Option<u64>,
+    pub connections: BTreeMap<Uuid, ConnectionDebug>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ConnectionDebug {
+    pub name: Option<String>,
+    pub is_bot: bool,
+    pub connected: bool,
+    pub connected_since: u64,
+    /// Whether a transport is attached, so messages are delivered rather than held for when the player returns.
+    pub attached: bool,
+}
+
+impl fmt::Debug for GameExport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GameExport").field("players", &self.players.len()).field("timeline", &self.timeline.len()).finish_non_exhaustive()
+    }
+}
+
+impl GameExport {
+    /// Check that an export from elsewhere describes a game that can be played on, since a game in progress assumes its seats, deck, and government agree with each other.
+    pub fn validate(&self) -> Result<(), GameError> {
+        if !self.players.keys().all(|id| self.seats.contains_key(id)) {
+            return Err(GameError::InvalidExport { reason: "Every player in the game needs a seat." })
+        }
+        if matches!(self.turn_phase, TurnPhase::Lobby | TurnPhase::Ended { .. }) {
+            return Ok(())
+        }
+        if !(rules::MIN_PLAYERS..=rules::MAX_PLAYERS).contains(&self.players.len()) {
+            return Err(GameError::InvalidExport { reason: "A game in progress needs between 5 and 10 players." })
+        }
+        let session = self.legislative_session.iter().flat_map(|session| session.drawn.iter().chain(session.discarded.iter()));
+        let cards: Vec<&CardColor> = self.cards.iter().chain(self.discarded.iter()).chain(session).collect();
+        let unplayed_liberals = cards.iter().filter(|card| matches!(card, CardColor::Liberal)).count();
+        let liberals = unplayed_liberals + self.liberal_policies as usize;
+        let facists = cards.len() - unplayed_liberals + self.facist_policies as usize;
+        if liberals != rules::LIBERAL_CARDS || facists != rules::FACIST_CARDS {
+            return Err(GameError::InvalidExport { reason: "The deck, discard pile, and board do not add up to a full set of policies." })
+        }
+        let living = |player: &Option<Uuid>| player.is_some_and(|id| self.players.get(&id).is_some_and(|plr| !plr.dead));
+        if !living(&self.president) || (self.chancellor.is_some() && !living(&self.chancellor)) {
+            return Err(GameError::InvalidExport { reason: "The president and chancellor have to be living players." })
+        }
+        if self.turn_order.is_empty() || self.rotation >= self.turn_order.len() || !self.turn_order.iter().all(|id| living(&Some(*id))) {
+            return Err(GameError::InvalidExport { reason: "The turn order has to be made of living players." })
+        }
+        let needs_chancellor = matches!(self.turn_phase, TurnPhase::Discussion | TurnPhase::Voting | TurnPhase::PresidentSelect | TurnPhase::ChancellorSelect);
+        if needs_chancellor && self.chancellor.is_none() {
+            return Err(GameError::InvalidExport { reason: "The game is waiting on a government without a chancellor." })
+        }
+        let held = self.legislative_session.as_ref().map(|session| session.drawn.len());
+        let hand_ok = match self.turn_phase {
+            TurnPhase::PresidentSelect => held == Some(3),
+            TurnPhase::ChancellorSelect => held == Some(2),
+            _ => held.is_none()
+        };
+        if !hand_ok {
+            return Err(GameError::InvalidExport { reason: "The policies in hand do not match the phase of the game." })
+        }
+        // a government elected from here draws three policies straight from the deck
+        if self.legislative_session.is_none() && self.cards.len() < 3 {
+            return Err(GameError::InvalidExport { reason: "The deck has fewer than three policies left." })
+        }
+        Ok(())
+    }
+}
+
+pub struct GameState {
+    pub conn: ConnectionState,
+    pub chat_log: LinkedList<ChatLine>,
+    pub timeout: Option<SystemTime>,
+    pub options: GameOptions,
+    /// The community the game belongs to, on a server shared by several, or none for the server's own.
+    pub community: Option<String>,
+    pub tutorial: Option<Tutorial>,
+    /// Source of the game's randomness, so that a seeded game always plays out the same way.
+    pub(crate) rng: StdRng,
+    /// Bots decide their moves with their own random source, so a game can be replayed from its history without them.
+    pub(crate) bot_rng: StdRng,
+    pub(crate) history: History,
+    pub(crate) governments: Vec<Government>,
+
+    players: HashMap<Uuid, PlayerState>,
+    seating: Seating,
+    num_facists: usize,
+    liberal_policies: u8,
+    facist_policies: u8,
+    election_tracker: u8,
+    cards: Vec<CardColor>,
+    discarded: Vec<CardColor>,
+    /// The hand of the elected government, drawn from the deck when the vote passes.
+    legislative_session: Option<LegislativeSession>,
+
+    turn_phase: TurnPhase,
+    phase_started_at: SystemTime,
+    turn_counter: usize,
+    /// The living players, in the order the presidency passes between them.
+    turn_order: Vec<Uuid>,
+    /// Where the presidency is in the turn order, which special elections leave alone.
+    rotation: usize,
+    last_president: Option<Uuid>,
+    last_chancellor: Option<Uuid>,
+    president: Option<Uuid>,
+    chancellor: Option<Uuid>,
+    host: Option<Uuid>,
+
+    /// Whether the chancellor has asked to veto their policies, and whether the president said no.
+    veto_requested: bool,
+    veto_declined: bool,
+    /// Whether the president and chancellor have agreed to end the discussion and vote early.
+    president_called_vote: bool,
+    chancellor_called_vote: bool,
+    investigated: HashMap<Uuid, Vec<Uuid>>,
+    /// The results of the most recent actions each player sent with a request id, so retries are not applied twice.
+    processed_requests: HashMap<Uuid, VecDeque<ProcessedRequest>>,
+    timeline: Vec<TimelineEntry>,
+    /// When a scheduled lobby opens for joining, until it does.
+    opens_at: Option<SystemTime>,
+    schedule_reminded: bool,
+    lobby_vote: Option<LobbyVote>,
+    /// When the host's countdown to the start of the game runs out, while one is running. Nobody new can join until it ends.
+    countdown: Option<SystemTime>,
+    /// Players who have yet to say they have seen their role, while the first election waits on them.
+    unacknowledged: BTreeSet<Uuid>,
+    /// The secrets and devices of players removed by a vote, who may not rejoin.
+    banned: Vec<(Option<Uuid>, Option<String>)>,
+}
+
+/// A request id and the result of the action it was sent with.
+type ProcessedRequest = (String, Result<(), GameError>);
+
+/// How many request ids are remembered for each player.
+const MAX_PROCESSED_REQUESTS: usize = 16;
+
+fn shuffle_deck(rng: &mut impl Rng) -> Vec<CardColor> {
+    let mut cards = vec![];
+    for _ in 0..rules::LIBERAL_CARDS {
+        cards.push(CardColor::Liberal);
+    }
+    for _ in 0..rules::FACIST_CARDS {
+        cards.push(CardColor::Facist);
+    }
+    cards.shuffle(rng);
+    cards
+}
+
+/// The current time. Browsers give `SystemTime::now` no clock to read, so the wasm build asks JavaScript instead.
+pub fn now() -> SystemTime {
+    #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+    return UNIX_EPOCH + Duration::from_millis(js_sys::Date::now() as u64);
+    #[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+    SystemTime::now()
+}
+
+pub fn epoch_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+
+pub struct GameStatePlayerView<'a> {
+    pub player: Uuid,
+    pub state: &'a GameState
+}
+
+impl GameStatePlayerView<'_> {
+    /// Write the fields of the view that every version shares.
+    fn serialize_entries<M: SerializeMap>(&self, map: &mut M) -> Result<(), M::Error> {
+        let role = self.state.players.get(&self.player).map(|p| p.role);
+        let investigated = vec![];
+        let investigated = self.state.investigated.get(&self.player).unwrap_or(&investigated);
+        map.serialize_entry("liberal_policies", &self.state.liberal_policies)?;
+        map.serialize_entry("facist_policies", &self.state.facist_policies)?;
+        map.serialize_entry("election_tracker", &self.state.election_tracker)?;
+        map.serialize_entry("liberal_cards", &self.state.cards.iter().filter(|c| matches!(**c, CardColor::Liberal)).count())?;
+        map.serialize_entry("facist_cards", &self.state.cards.iter().filter(|c| matches!(**c, CardColor::Facist)).count())?;
+        let theme = self.state.theme_for(&self.player);
+        map.serialize_entry("card_faces", &theme.card_faces())?;
+        map.serialize_entry("theme", &theme)?;
+        map.serialize_entry("host", &self.state.host)?;
+        map.serialize_entry("president", &self.state.president)?;
+        map.serialize_entry("last_president", &self.state.last_president)?;
+        map.serialize_entry("chancellor", &self.state.chancellor)?;
+        map.serialize_entry("last_chancellor", &self.state.last_chancellor)?;
+        map.serialize_entry("turn_phase", &self.state.turn_phase)?;
+        map.serialize_entry("turn_order", &self.state.turn_order)?;
+        if let Some(deadline) = self.state.phase_deadline() {
+            map.serialize_entry("phase_started_at", &epoch_millis(self.state.phase_started_at))?;
+            map.serialize_entry("phase_deadline", &epoch_millis(deadline))?;
+        }
+        map.serialize_entry("cards_in_deck", &self.state.cards.len())?;
+        map.serialize_entry("cards_in_discard", &self.state.discarded.len())?;
+        map.serialize_entry("num_facists", &self.state.num_facists)?;
+        map.serialize_entry("players", &self.state.players.iter().map(|(k, v)| {
+            let conn = self.state.conn.get(k);
+            (k, PartialPlayerState {
+                name: conn.and_then(|conn| conn.name.clone()).unwrap_or_default(),
+                avatar: conn.and_then(|conn| conn.avatar.clone()),
+                color: conn.and_then(|conn| conn.color.clone()),
+                role: if matches!(self.state.turn_phase, TurnPhase::Ended { winner: _ }) || self.player == *k || self.state.knows_roles(role) { Some(v.role) } else if investigated.contains(k) { Some(match v.role { PlayerType::Liberal => PlayerType::Liberal, _ => PlayerType::Facist }) } else { None },
+                vote: if (matches!(self.state.turn_phase, TurnPhase::Voting) || self.state.options.anonymous_votes) && self.player != *k { None } else { v.vote },
+                dead: v.dead,
+                ready: Some(v.ready).filter(|_| matches!(self.state.turn_phase, TurnPhase::Lobby)),
+                acknowledged: Some(!self.state.unacknowledged.contains(k)).filter(|_| !self.state.unacknowledged.is_empty())
+            })
+        }).collect::<HashMap<&Uuid, PartialPlayerState>>())?;
+        map.serialize_entry("governments", &self.state.governments)?;
+        map.serialize_entry("waitlist", &self.state.seating.waitlist().iter().map(|id| WaitlistEntry {
+            id: *id,
+            name: self.state.player_name(id).unwrap_or_default()
+        }).collect::<Vec<WaitlistEntry>>())?;
+        if let Some(opens_at) = self.state.opens_at {
+            map.serialize_entry("opens_at", &epoch_millis(opens_at))?;
+        }
+        if let Some(vote) = &self.state.lobby_vote {
+            map.serialize_entry("lobby_vote", vote)?;
+        }
+        if let Some(countdown) = self.state.countdown {
+            map.serialize_entry("countdown_ends_at", &epoch_millis(countdown))?;
+        }
+        if matches!(self.state.turn_phase, TurnPhase::Lobby) && self.state.host == Some(self.player) {
+            let shared = self.state.shared_devices();
+            if !shared.is_empty() {
+                map.serialize_entry("shared_devices", &shared)?;
+            }
+        }
+        if let Some(idx) = self.state.seating.waitlist().iter().position(|id| *id == self.player) {
+            map.serialize_entry("waitlist_position", &(idx + 1))?;
+        }
+        if matches!(self.state.turn_phase, TurnPhase::Discussion) {
+            let called: Vec<Uuid> = [(self.state.president, self.state.president_called_vote), (self.state.chancellor, self.state.chancellor_called_vote)].iter().filter(|(_, called)| *called).filter_map(|(player, _)| *player).collect();
+            map.serialize_entry("vote_called", &called)?;
+        }
+        if matches!(self.state.turn_phase, TurnPhase::ChancellorSelect) {
+            map.serialize_entry("veto_requested", &self.state.veto_requested)?;
+            map.serialize_entry("veto_declined", &self.state.veto_declined)?;
+        }
+        if matches!(self.state.turn_phase, TurnPhase::Voting) {
+            map.serialize_entry("votes", &self.state.players.values().filter(|s| s.vote.is_some()).count())?;
+        }
+        if self.state.options.deck_odds && self.state.is_in_game() {
+            map.serialize_entry("deck_odds", &DeckOdds::new(self.state.liberal_policies, self.state.facist_policies))?;
+        }
+        if let Some(cards) = self.state.hand(self.player) {
+            map.serialize_entry("cards", &cards)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for GameStatePlayerView<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer {
+            let mut map = serializer.serialize_map(None)?;
+            self.serialize_entries(&mut map)?;
+            map.end()
+        }
+}
+
+/// The newest version of the player view that the server can send.
+pub const LATEST_VIEW_VERSION: u32 = 2;
+
+/// The version of the player view to send a client that asked for `requested`, which is the newest the server has that is no newer.
+pub fn negotiate_view_version(requested: u32) -> u32 {
+    requested.clamp(1, LATEST_VIEW_VERSION)
+}
+
+/// The second version of the player view, which also carries the game's options and its timeline of events so clients do not have to ask for them.
+pub struct GameStatePlayerViewV2<'a> {
+    pub player: Uuid,
+    pub state: &'a GameState
+}
+
+impl Serialize for GameStatePlayerViewV2<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer {
+            let mut map = serializer.serialize_map(None)?;
+            GameStatePlayerView { player: self.player, state: self.state }.serialize_entries(&mut map)?;
+            map.serialize_entry("options", &self.state.options)?;
+            map.serialize_entry("events", &self.state.timeline)?;
+            map.end()
+        }
+}
+
+/// The player view in the version a connection asked for. Clients get the first version unless they ask for another with `SetViewVersion`.
+#[derive(Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum VersionedPlayerView<'a> {
+    V1(GameStatePlayerView<'a>),
+    V2(GameStatePlayerViewV2<'a>),
+}
+
+impl<'a> VersionedPlayerView<'a> {
+    pub fn new(version: u32, player: Uuid, state: &'a GameState) -> VersionedPlayerView<'a> {
+        match version {
+            1 => VersionedPlayerView::V1(GameStatePlayerView { player, state }),
+            _ => VersionedPlayerView::V2(GameStatePlayerViewV2 { player, state })
+        }
+    }
+}
+
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameState {
+    pub fn broadcast_game_state(&self) {
+        self.players.keys().chain(self.seating.waitlist().iter()).for_each(|k| {
+            self.send_game_state(*k);
+        });
+    }
+
+    pub fn send_game_state(&self, player: Uuid) {
+        if let Some(conn) = self.conn.get(&player) {
+            if conn.is_subscribed(Topic::GameState) {
+                conn.send(&ServerProtocol::GameState { state: VersionedPlayerView::new(conn.view_version, player, self) });
+            }
+            if conn.is_subscribed(Topic::Scoreboard) {
+                conn.send(&ServerProtocol::Scoreboard { state: self.scoreboard() });
+            }
+        }
+    }
+
+    pub fn scoreboard(&self) -> Scoreboard<'_> {
+        Scoreboard {
+            liberal_policies: self.liberal_policies,
+            facist_policies: self.facist_policies,
+            election_tracker: self.election_tracker,
+            turn_phase: &self.turn_phase,
+        }
+    }
+
+    /// The rules for the number of players at the table.
+    pub fn rules(&self) -> Rules {
+        let mut rules = Rules::new(self.players.len());
+        if let Some(policies) = self.options.hitler_chancellor_policies {
+            rules.hitler_chancellor_policies = policies.clamp(1, rules.facist_policies_to_win - 1);
+        }
+        rules
+    }
+
+    /// Whether the player holds a seat in the game or is on the waitlist.
+    pub fn has_player(&self, player: &Uuid) -> bool {
+        self.players.contains_key(player) || self.seating.is_waiting(player)
+    }
+
+    /// How many presidential terms have started, not counting special elections.
+    pub fn rounds(&self) -> usize {
+        if matches!(self.turn_phase, TurnPhase::Lobby) { 0 } else { self.turn_counter + 1 }
+    }
+
+    pub fn is_in_game(&self) -> bool {
+        !matches!(self.turn_phase, TurnPhase::Lobby | TurnPhase::Ended { winner: _ })
+    }
+
+    pub fn winner(&self) -> Option<CardColor> {
+        match self.turn_phase {
+            TurnPhase::Ended { winner } => Some(winner),
+            _ => None
+        }
+    }
+
+    pub(crate) fn player_name(&self, player: &Uuid) -> Option<String> {
+        self.conn.get(player).and_then(|c| c.name.clone())
+    }
+
+    pub fn is_practice(&self) -> bool {
+        self.tutorial.is_some()
+    }
+
+    /// Send the player in a practice game an explanation of the current turn phase, if they have not seen one yet.
+    pub fn advance_tutorial(&mut self) {
+        if let Some(mut tutorial) = self.tutorial.take() {
+            if let Some(step) = tutorial.next_step(self) {
+                if let Some(conn) = self.conn.get(&tutorial.player) {
+                    conn.send(&ServerProtocol::TutorialStep { topic: step.topic, title: step.title, text: step.text });
+                }
+            }
+            self.tutorial = Some(tutorial);
+        }
+    }
+
+    pub fn turn_phase(&self) -> &TurnPhase {
+        &self.turn_phase
+    }
+
+    pub fn host(&self) -> Option<Uuid> {
+        self.host
+    }
+
+    pub fn president(&self) -> Option<Uuid> {
+        self.president
+    }
+
+    pub fn chancellor(&self) -> Option<Uuid> {
+        self.chancellor
+    }
+
+    pub fn role(&self, player: &Uuid) -> Option<PlayerType> {
+        self.players.get(player).map(|p| p.role)
+    }
+
+    pub fn is_alive(&self, player: &Uuid) -> bool {
+        self.players.get(player).is_some_and(|p| !p.dead)
+    }
+
+    /// Groups of seated players who joined from the same device, which could be an accidental double join or collusion.
+    pub fn shared_devices(&self) -> Vec<Vec<Uuid>> {
+        let mut devices: HashMap<&str, Vec<Uuid>> = HashMap::new();
+        for player in self.players.keys() {
+            if let Some(fingerprint) = self.conn.get(player).and_then(|conn| conn.fingerprint.as_deref()) {
+                devices.entry(fingerprint).or_default().push(*player);
+            }
+        }
+        let mut shared: Vec<Vec<Uuid>> = devices.into_values().filter(|players| players.len() > 1).collect();
+        shared.iter_mut().for_each(|players| players.sort());
+        shared.sort();
+        shared
+    }
+
+    pub fn has_voted(&self, player: &Uuid) -> bool {
+        self.players.get(player).is_some_and(|p| p.vote.is_some())
+    }
+
+    /// Players the game is waiting on to move on, such as the president while they nominate a chancellor.
+    pub fn awaiting(&self) -> Vec<Uuid> {
+        match self.turn_phase {
+            TurnPhase::Discussion => [(self.president, self.president_called_vote), (self.chancellor, self.chancellor_called_vote)].iter().filter(|(_, called)| !called).filter_map(|(player, _)| *player).collect(),
+            TurnPhase::Voting => self.turn_order.iter().filter(|p| !self.has_voted(p)).copied().collect(),
+            TurnPhase::ChancellorSelect if self.veto_pending() => self.president.into_iter().collect(),
+            TurnPhase::ChancellorSelect => self.chancellor.into_iter().collect(),
+            TurnPhase::Electing if !self.unacknowledged.is_empty() => self.unacknowledged(),
+            TurnPhase::Electing | TurnPhase::PresidentSelect | TurnPhase::PresidentialPower { .. } => self.president.into_iter().collect(),
+            TurnPhase::Lobby | TurnPhase::Ended { .. } => vec![]
+        }
+    }
+
+    /// Players who are still alive, in turn order.
+    pub fn living_players(&self) -> &[Uuid] {
+        &self.turn_order
+    }
+
+    /// The policy cards the player is currently allowed to see, if any.
+    /// The president sees the three policies drawn for the government while discarding and the top three cards of the deck while peeking, and the chancellor sees the two they are passed.
+    pub fn hand(&self, player: Uuid) -> Option<Vec<CardColor>> {
+        match (&self.turn_phase, &self.legislative_session) {
+            (TurnPhase::PresidentialPower { power: PresidentialPower::PolicyPeek }, _) if Some(player) == self.president => Some(self.cards[self.cards.len().saturating_sub(3)..].into()),
+            (TurnPhase::PresidentSelect, Some(session)) if Some(player) == self.president => Some(session.drawn.clone()),
+            (TurnPhase::ChancellorSelect, Some(session)) if Some(player) == self.chancellor => Some(session.drawn.clone()),
+            _ => None
+        }
+    }
+
+    pub fn legislative_session(&self) -> Option<&LegislativeSession> {
+        self.legislative_session.as_ref()
+    }
+
+    pub fn summary(&self) -> GameSummary {
+        let mut players: Vec<String> = self.players.keys().filter_map(|k| self.player_name(k)).collect();
+        players.sort();
+        GameSummary {
+            host: self.host.and_then(|h| self.player_name(&h)),
+            players,
+            liberal_policies: self.liberal_policies,
+            facist_policies: self.facist_policies,
+            winner: self.winner(),
+        }
+    }
+
+    pub fn new() -> GameState {
+        GameState::with_options(GameOptions::default())
+    }
+
+    pub fn with_options(options: GameOptions) -> GameState {
+        GameState::with_rng(options, StdRng::from_entropy())
+    }
+
+    /// Create a game whose roles, deck, and bot moves are decided by the seed, for reproducible simulations and tests.
+    pub fn with_seed(options: GameOptions, seed: u64) -> GameState {
+        GameState::with_rng(options, StdRng::seed_from_u64(seed))
+    }
+
+    /// Save the game so it can be imported elsewhere. Practice games cannot be exported, since the tutorial is not saved.
+    pub fn export(&self) -> Option<GameExport> {
+        if self.is_practice() {
+            return None
+        }
+        Some(GameExport {
+            options: self.options.clone(),
+            seats: self.conn.iter().map(|(id, conn)| (*id, SeatExport {
+                name: conn.name.clone(),
+                secret: conn.secret,
+                avatar: conn.avatar.clone(),
+                color: conn.color.clone(),
+                topics: conn.topics.clone(),
+                is_bot: conn.is_bot,
+                fingerprint: conn.fingerprint.clone(),
+                language: conn.language,
+            })).collect(),
+            chat_log: self.chat_log.iter().cloned().collect(),
+            players: self.players.iter().map(|(id, state)| (*id, PlayerState { role: state.role, vote: state.vote, dead: state.dead, ready: state.ready })).collect(),
+            seating: self.seating.clone(),
+            num_facists: self.num_facists,
+            liberal_policies: self.liberal_policies,
+            facist_policies: self.facist_policies,
+            election_tracker: self.election_tracker,
+            cards: self.cards.clone(),
+            discarded: self.discarded.clone(),
+            legislative_session: self.legislative_session.clone(),
+            turn_phase: self.turn_phase.clone(),
+            phase_started_at: epoch_millis(self.phase_started_at),
+            turn_counter: self.turn_counter,
+            rotation: self.rotation,
+            turn_order: self.turn_order.clone(),
+            last_president: self.last_president,
+            last_chancellor: self.last_chancellor,
+            president: self.president,
+            chancellor: self.chancellor,
+            host: self.host,
+            veto_requested: self.veto_requested,
+            veto_declined: self.veto_declined,
+            president_called_vote: self.president_called_vote,
+            chancellor_called_vote: self.chancellor_called_vote,
+            investigated: self.investigated.clone(),
+            governments: self.governments.clone(),
+            timeline: self.timeline.clone(),
+            opens_at: self.opens_at.map(epoch_millis),
+            schedule_reminded: self.schedule_reminded,
+            banned: self.banned.clone(),
+            community: self.community.clone(),
+            unacknowledged: self.unacknowledged.clone(),
+        })
+    }
+
+    /// Whether the game has sat in the same phase for at least `limit`, waiting only on players who have lost their connection.
+    /// Asynchronous games are expected to wait on players who are away, so they are never stuck.
+    pub fn is_stuck(&self, now: SystemTime, limit: Duration) -> bool {
+        if !self.is_in_game() || self.options.asynchronous {
+            return false
+        }
+        let awaiting = self.awaiting();
+        !awaiting.is_empty()
+            && awaiting.iter().all(|player| self.conn.get(player).is_none_or(|conn| !conn.connected && !conn.is_bot))
+            && now.duration_since(self.phase_started_at).is_ok_and(|stalled| stalled >= limit)
+    }
+
+    /// A dump of the game for debugging, with the deck and discard pile put in order if the order should stay hidden.
+    pub fn debug(&self, redact_deck: bool) -> GameDebug {
+        let mut game = self.export();
+        if let (true, Some(game)) = (redact_deck, &mut game) {
+            game.cards.sort_by_key(|card| card.id());
+            game.discarded.sort_by_key(|card| card.id());
+        }
+        GameDebug {
+            game,
+            awaiting: self.awaiting(),
+            phase_deadline: self.phase_deadline().map(epoch_millis),
+            lobby_vote_expires_at: self.lobby_vote.as_ref().map(|vote| epoch_millis(vote.expires_at)),
+            countdown_ends_at: self.countdown.map(epoch_millis),
+            opens_at: self.opens_at.map(epoch_millis),
+            idle_since: self.timeout.map(epoch_millis),
+            connections: self.conn.iter().map(|(id, conn)| (*id, ConnectionDebug {
+                name: conn.name.clone(),
+                is_bot: conn.is_bot,
+                connected: conn.connected,
+                connected_since: epoch_millis(conn.connected_since),
+                attached: conn.tx.current().is_some(),
+            })).collect(),
+        }
+    }
+
+    /// Recreate an exported game. Every seat starts out disconnected, holding its messages until the player rejoins.
+    pub fn import(export: GameExport) -> GameState {
+        let millis = |millis: u64| UNIX_EPOCH + Duration::from_millis(millis);
+        let conn = export.seats.into_iter().map(|(id, seat)| {
+            let mut conn = if seat.is_bot { PlayerConnection::bot(seat.name.clone().unwrap_or_default()) } else { PlayerConnection::detached() };
+            conn.name = seat.name;
+            conn.secret = seat.secret;
+            conn.avatar = seat.avatar;
+            conn.color = seat.color;
+            conn.topics = seat.topics;
+            conn.fingerprint = seat.fingerprint;
+            conn.language = seat.language;
+            (id, conn)
+        }).collect();
+        let mut state = GameState {
+            conn,
+            chat_log: export.chat_log,
+            players: export.players,
+            seating: export.seating,
+            num_facists: export.num_facists,
+            liberal_policies: export.liberal_policies,
+            facist_policies: export.facist_policies,
+            election_tracker: export.election_tracker,
+            cards: export.cards,
+            discarded: export.discarded,
+            legislative_session: export.legislative_session,
+            turn_phase: export.turn_phase,
+            phase_started_at: millis(export.phase_started_at),
+            turn_counter: export.turn_counter,
+            rotation: export.rotation,
+            turn_order: export.turn_order,
+            last_president: export.last_president,
+            last_chancellor: export.last_chancellor,
+            president: export.president,
+            chancellor: export.chancellor,
+            host: export.host,
+            veto_requested: export.veto_requested,
+            veto_declined: export.veto_declined,
+            president_called_vote: export.president_called_vote,
+            chancellor_called_vote: export.chancellor_called_vote,
+            investigated: export.investigated,
+            governments: export.governments,
+            timeline: export.timeline,
+            opens_at: export.opens_at.map(millis),
+            schedule_reminded: export.schedule_reminded,
+            banned: export.banned,
+            community: export.community,
+            unacknowledged: export.unacknowledged,
+            ..GameState::with_options(export.options)
+        };
+        state.delay_spectators();
+        if state.is_in_game() {
+            state.take_snapshot();
+        }
+        state
+    }
+
+    pub(crate) fn with_rng(options: GameOptions, mut rng: StdRng) -> GameState {
+        let cards = shuffle_deck(&mut rng);
+        let bot_rng = StdRng::seed_from_u64(rng.gen());
+        let opens_at = options.scheduled_at.map(|millis| UNIX_EPOCH + Duration::from_millis(millis)).filter(|at| *at > now());
+        GameState {
+            conn: ConnectionState::default(),
+            chat_log: LinkedList::default(),
+
+            timeout: None,
+            community: None,
+            options,
+            tutorial: None,
+            cards,
+            rng,
+            bot_rng,
+            history: History::default(),
+            governments: vec![],
+            players: HashMap::new(),
+            seating: Seating::default(),
+            num_facists: 0,
+            liberal_policies: 0,
+            facist_policies: 0,
+            election_tracker: 0,
+            host: None,
+            president: None,
+            chancellor: None,
+            last_president: None,
+            last_chancellor: None,
+
+            turn_order: vec![],
+            discarded: vec![],
+            legislative_session: None,
+            turn_counter: 0,
+            rotation: 0,
+            turn_phase: TurnPhase::Lobby,
+            phase_started_at: now(),
+
+            veto_requested: false,
+            veto_declined: false,
+            president_called_vote: false,
+            chancellor_called_vote: false,
+            investigated: HashMap::new(),
+            processed_requests: HashMap::new(),
+            timeline: vec![],
+            opens_at,
+            schedule_reminded: false,
+            lobby_vote: None,
+            countdown: None,
+            unacknowledged: BTreeSet::new(),
+            banned: vec![],
+        }
+    }
+
+    /// Move the game into a new turn phase and restart the phase timer.
+    fn set_turn_phase(&mut self, phase: TurnPhase) {
+        debug_assert!(self.turn_phase.can_become(&phase), "cannot move from {:?} to {:?}", self.turn_phase, phase);
+        self.turn_phase = phase;
+        self.phase_started_at = now();
+    }
+
+    pub fn phase_started_at(&self) -> SystemTime {
+        self.phase_started_at
+    }
+
+    /// The time at which the current turn phase expires, if timers are enabled and the game is in progress.
+    /// Discussions have their own timer.
+    pub fn phase_deadline(&self) -> Option<SystemTime> {
+        let timer = if matches!(self.turn_phase, TurnPhase::Discussion) { self.options.discussion_timer } else { self.options.turn_timer };
+        match timer {
+            // nobody is on the clock while players are still seeing their roles
+            Some(secs) if self.is_in_game() && self.unacknowledged.is_empty() => Some(self.phase_started_at + Duration::from_secs(secs)),
+            _ => None
+        }
+    }
+
+    /// When a scheduled lobby opens for joining, if it has not yet.
+    pub fn opens_at(&self) -> Option<SystemTime> {
+        self.opens_at
+    }
+
+    /// Remind players shortly before a scheduled lobby opens, and open it once the time comes.
+    pub fn advance_schedule(&mut self, now: SystemTime) -> Option<ScheduleEvent> {
+        let opens_at = self.opens_at?;
+        if now >= opens_at {
+            self.opens_at = None;
+            self.announce(Message::GameOpened);
+            return Some(ScheduleEvent::Opened)
+        }
+        if !self.schedule_reminded && now + SCHEDULE_REMINDER >= opens_at {
+            self.schedule_reminded = true;
+            return Some(ScheduleEvent::Reminder)
+        }
+        None
+    }
+
+    /// Add a player during the lobby phase or reconnect an existing player to a game.
+    /// Returns true if the player was successfully added.
+    pub fn add_player(&mut self, player_id: Uuid, mut player_connection: PlayerConnection) -> bool {
+        if !matches!(self.turn_phase, TurnPhase::Lobby) && !self.conn.contains_key(&player_id) {
+            return false
+        }
+        if self.is_banned(&player_connection) {
+            return false
+        }
+        // only the host sits in a scheduled lobby until it opens, and nobody new joins once the countdown to the start is running
+        if (self.opens_at.is_some() || self.countdown.is_some()) && !self.conn.is_empty() && !self.conn.contains_key(&player_id) {
+            return false
+        }
+        if let Some(existing) = self.conn.get(&player_id) {
+            // the seat keeps its relay, so the new connection gets anything the player missed and the old one stops receiving
+            if let Some(sink) = player_connection.tx.current() {
+                existing.tx.attach(sink);
+            }
+            player_connection.tx = existing.tx.clone();
+        }
+        let name = player_connection.name.clone().unwrap_or_default();
+        let is_new = self.conn.insert(player_id, player_connection).is_none();
+        if self.seating.is_waiting(&player_id) {
+            return true
+        }
+        if !self.players.contains_key(&player_id) && !self.seating.join(player_id, self.players.len(), self.max_players()) {
+            self.announce(Message::JoinedWaitlist { name: &name });
+            return true
+        }
+        if let std::collections::hash_map::Entry::Vacant(entry) = self.players.entry(player_id) {
+            entry.insert(PlayerState { role: PlayerType::Liberal, vote: None, dead: false, ready: false });
+            if is_new {
+                self.announce(Message::Joined { name: &name });
+            }
+            else {
+                self.announce(Message::Reconnected { name: &name });
+            }
+        }
+        if self.host.is_none() {
+            self.host = Some(player_id);
+        }
+        true
+    }
+
+    /// The number of seats available in this game.
+    pub fn max_players(&self) -> usize {
+        self.options.max_players.unwrap_or(rules::MAX_PLAYERS).clamp(rules::MIN_PLAYERS, rules::MAX_PLAYERS)
+    }
+
+    /// Seat players from the front of the waitlist while there are open seats in the lobby.
+    fn seat_waitlist(&mut self) {
+        if !matches!(self.turn_phase, TurnPhase::Lobby) {
+            return
+        }
+        while let Some(player) = self.seating.next_seated(self.players.len(), self.max_players()) {
+            self.players.insert(player, PlayerState { role: PlayerType::Liberal, vote: None, dead: false, ready: false });
+            if self.host.is_none() {
+                self.host = Some(player);
+            }
+            if let Some(name) = self.player_name(&player) {
+                self.announce(Message::SeatedFromWaitlist { name: &name });
+            }
+        }
+    }
+
+    pub fn get_player_secret(&self, player_id: &Uuid) -> Option<Uuid> {
+        match self.conn.get(player_id) {
+            Some(conn) => conn.secret,
+            None => None
+        }
+    }
+
+    /// Replace the player's secret with a new random one, invalidating the old secret.
+    pub fn rotate_secret(&mut self, player: Uuid) -> Option<Uuid> {
+        let conn = self.conn.get_mut(&player)?;
+        let secret = Uuid::new_v4();
+        conn.secret = Some(secret);
+        Some(secret)
+    }
+
+    /// The result of an action the player already sent with this request id, if it is still remembered.
+    pub fn processed_result(&self, player: &Uuid, request_id: &str) -> Option<Result<(), GameError>> {
+        self.processed_requests.get(player)?.iter().find(|(id, _)| id == request_id).map(|(_, result)| result.clone())
+    }
+
+    /// Remember the result of an action so a retry with the same request id gets the same answer.
+    pub fn record_result(&mut self, player: Uuid, request_id: String, result: Result<(), GameError>) {
+        let processed = self.processed_requests.entry(player).or_default();
+        if processed.len() >= MAX_PROCESSED_REQUESTS {
+            processed.pop_front();
+        }
+        processed.push_back((request_id, result));
+    }
+
+    /// Allow the host to reset the secret of a player whose credentials may have been shared.
+    /// Returns the new secret, which should only be sent to the player's current connection.
+    pub fn revoke_secret(&mut self, player: Uuid, target: Uuid) -> Result<Uuid, GameError> {
+        if self.host != Some(player) {
+            return Err(GameError::NotHost { action: "revoke a player's credentials" });
+        }
+        if !self.players.contains_key(&target) {
+            return Err(GameError::PlayerNotFound { player: target });
+        }
+        let secret = self.rotate_secret(target).ok_or(GameError::PlayerNotFound { player: target })?;
+        if let Some(name) = self.player_name(&target) {
+            self.announce(Message::CredentialsReset { name: &name });
+        }
+        Ok(secret)
+    }
+
+    pub fn has_connected_players(&self) -> bool {
+        if matches!(self.turn_phase, TurnPhase::Lobby) {
+            return !self.players.is_empty();
+        }
+        self.conn.iter().any(|(_, c)| c.connected)
+    }
+
+    /// Tell everyone in the game something, in the game's language.
+    pub(crate) fn announce(&mut self, message: Message) {
+        let text = message.text(self.options.language, &self.options.theme);
+        self.add_chat(ChatLine::system(text));
+    }
+
+    /// The language to write to a player in, which is the game's unless they chose their own.
+    pub fn language_for(&self, player: &Uuid) -> Language {
+        self.conn.get(player).and_then(|conn| conn.language).unwrap_or(self.options.language)
+    }
+
+    /// A message written out for one player, in their language and the game's theme.
+    pub fn message_for(&self, player: &Uuid, message: Message) -> String {
+        message.text(self.language_for(player), &self.options.theme)
+    }
+
+    /// The theme's names in the language of the player given.
+    pub fn theme_for(&self, player: &Uuid) -> ThemeNames {
+        self.options.theme.names(self.language_for(player))
+    }
+
+    /// A nickname in the game's theme that nobody in the game already goes by.
+    pub fn suggest_nickname(&self, rng: &mut impl Rng) -> String {
+        nicknames::suggest(rng, self.options.theme.preset, |nickname| self.conn.values().any(|conn| conn.name.as_deref().is_some_and(|name| name.trim().eq_ignore_ascii_case(nickname))))
+    }
+
+    /// Send a chat message to all participants in this game, except those who have muted the sender.
+    /// Only keep the last 250 messages.
+    pub fn add_chat(&mut self, line: ChatLine) {
+        let sender = line.id;
+        send_to_matching(&self.conn, Topic::Chat, &ServerProtocol::ReceiveChat { id: line.id, message: line.message.clone(), segments: &line.segments, attachment: line.attachment }, |conn| !sender.is_some_and(|sender| conn.muted.contains(&sender)));
+        self.chat_log.push_back(line);
+        while self.chat_log.len() > 250 {
+            self.chat_log.pop_front();
+        }
+    }
+
+    /// Disconnect a player during the lobby phase and return true.
+    /// If the game has started, mark the connection as disconnected instead and return false.
+    pub fn remove_player(&mut self, player: Uuid) -> bool {
+        if matches!(self.turn_phase, TurnPhase::Lobby) {
+            self.players.remove(&player);
+            self.seating.leave(&player);
+            if self.host == Some(player) {
+                self.migrate_host();
+            }
+            let player_connection = self.conn.get(&player);
+            let name = player_connection.and_then(|plr| plr.name.clone());
+            if let Some(name) = name {
+                self.announce(Message::Disconnected { name: &name });
+            }
+            self.seat_waitlist();
+            return true
+        }
+        else if let Some(conn) = self.conn.get_mut(&player) {
+            conn.connected = false;
+            if self.host == Some(player) {
+                self.migrate_host();
+            }
+        }
+        false
+    }
+
+    /// Move a player who already has a seat to another transport, such as a different device, keeping their name and profile.
+    pub fn reconnect(&mut self, player: Uuid, sink: Sink) -> bool {
+        let existing = match self.conn.get(&player) {
+            Some(existing) if !existing.is_bot => existing,
+            _ => return false
+        };
+        let mut conn = PlayerConnection::new(sink);
+        conn.name = existing.name.clone();
+        conn.secret = existing.secret;
+        conn.avatar = existing.avatar.clone();
+        conn.color = existing.color.clone();
+        conn.topics = existing.topics.clone();
+        conn.fingerprint = existing.fingerprint.clone();
+        self.add_player(player, conn)
+    }
+
+    /// Remove a player during the lobby phase and return true.
+    pub fn delete_player(&mut self, player: Uuid) -> bool {
+        if matches!(self.turn_phase, TurnPhase::Lobby) {
+            self.players.remove(&player);
+            self.seating.leave(&player);
+            if self.host == Some(player) {
+                self.migrate_host();
+            }
+            if let Some(plr) = self.conn.remove(&player) {
+                self.announce(Message::LeftLobby { name: &plr.name.unwrap_or_default() });
+            }
+            self.seat_waitlist();
+            return true
+        }
+        else if self.seating.is_waiting(&player) {
+            // spectators do not hold a seat, so they can leave freely
+            self.seating.leave(&player);
+            if let Some(plr) = self.conn.remove(&player) {
+                self.announce(Message::StoppedSpectating { name: &plr.name.unwrap_or_default() });
+            }
+            return true
+        }
+        else {
+            let name = match self.conn.get(&player) {
+                Some(plr) => plr.name.clone(),
+                None => None,
+            };
+            if let Some(name) = name {
+                self.announce(Message::LeftGame { name: &name });
+            }
+            if self.host == Some(player) {
+                self.migrate_host();
+            }
+            // a player who walked out is not going to say they have seen their role
+            self.stop_waiting_on_role(player);
+        }
+        false
+    }
+
+    /// Hand the game to the seated player who has been connected the longest, once the host has left or lost their connection.
+    /// Bots and players who are away are passed over, so a host who still holds a seat keeps it while nobody else is connected.
+    fn migrate_host(&mut self) {
+        let longest_connected = self.players.keys()
+            .filter(|id| Some(**id) != self.host)
+            .filter_map(|id| self.conn.get(id).filter(|conn| conn.connected && !conn.is_bot).map(|conn| (conn.connected_since, *id)))
+            .min()
+            .map(|(_, id)| id);
+        let host = match longest_connected {
+            Some(host) => host,
+            None if self.host.is_some_and(|host| self.players.contains_key(&host)) => return,
+            None => match self.players.keys().next() {
+                Some(host) => *host,
+                None => {
+                    self.host = None;
+                    return
+                }
+            }
+        };
+        self.host = Some(host);
+        if let Some(name) = self.player_name(&host) {
+            self.announce(Message::HostChanged { name: &name });
+        }
+        send_to_all(&self.conn, Topic::Events, &ServerProtocol::HostChanged { host });
+    }
+   
+    pub fn start(&mut self, player: Uuid) -> Result<(), GameError> {
+        if !matches!(self.turn_phase, TurnPhase::Lobby) {
+            return Err(GameError::AlreadyStarted);
+        }
+
+        if self.host != Some(player) {
+            return Err(GameError::NotHost { action: "start the game" });
+        }
+
+        if self.players.len() < rules::MIN_PLAYERS || self.players.len() > rules::MAX_PLAYERS {
+            return Err(GameError::InvalidPlayerCount { players: self.players.len() });
+        }
+
+        if self.options.require_ready {
+            let mut waiting = self.not_ready();
+            if !waiting.is_empty() {
+                waiting.sort();
+                return Err(GameError::NotReady { players: waiting });
+            }
+        }
+
+        // a vote called in the lobby no longer applies once the game is under way
+        self.lobby_vote = None;
+        self.countdown = None;
+
+        let mut turn_order = vec![];
+
+        // assign roles to all players
+        self.num_facists = rules::num_facists(self.players.len());
+        let mut roles = Vec::new();
+        for _ in 0..self.players.len() - self.num_facists - 1 {
+            roles.push(PlayerType::Liberal);
+        }
+        for _ in 0..self.num_facists {
+            roles.push(PlayerType::Facist);
+        }
+        roles.push(PlayerType::Hitler);
+        roles.shuffle(&mut self.rng);
+
+        // sort the seats first so the shuffles alone decide the outcome, rather than the map's iteration order
+        let mut seats: Vec<(&Uuid, &mut PlayerState)> = self.players.iter_mut().collect();
+        seats.sort_by_key(|(uuid, _)| **uuid);
+        for ((uuid, value), role) in seats.into_iter().zip(roles) {
+            turn_order.push(*uuid);
+            value.role = role;
+        }
+        
+        // create turn order
+        turn_order.shuffle(&mut self.rng);
+        self.president = Some(turn_order[0]);
+        self.turn_order = turn_order;
+        self.rotation = 0;
+
+        self.set_turn_phase(TurnPhase::Electing);
+        self.delay_spectators();
+        self.send_event(GameEvent::GameStarted { players: self.turn_order.clone(), facists: self.num_facists, hitler_chancellor_policies: self.rules().hitler_chancellor_policies });
+        self.send_reveal();
+        if self.options.require_role_ack {
+            self.unacknowledged = self.players.keys().filter(|id| !self.conn.get(id).is_some_and(|conn| conn.is_bot)).copied().collect();
+        }
+        self.take_snapshot();
+        Ok(())
+    }
+
+    /// Whether a player with this role knows every other player's role from the start of the game.
+    fn knows_roles(&self, role: Option<PlayerType>) -> bool {
+        match role {
+            Some(PlayerType::Facist) => true,
+            Some(PlayerType::Hitler) => self.rules().hitler_knows_facists,
+            _ => false
+        }
+    }
+
+    /// Tell everyone the turn order and how the reveal goes, and tell each player their role and the roles they know, so clients can lead the table through the reveal.
+    fn send_reveal(&self) {
+        send_to_all(&self.conn, Topic::Events, &ServerProtocol::TurnOrderAssigned { turn_order: &self.turn_order, president: self.president, reveal: self.rules().reveal() });
+        for (id, state) in &self.players {
+            let known = match self.knows_roles(Some(state.role)) {
+                true => self.players.iter().filter(|(other, _)| *other != id).map(|(other, state)| (*other, state.role)).collect(),
+                false => BTreeMap::new()
+            };
+            if let Some(conn) = self.conn.get(id) {
+                conn.send(&ServerProtocol::RoleAssigned { role: state.role, known });
+            }
+        }
+    }
+
+    /// Say that the player has seen their role. Once everyone has, the first election begins and the president's turn timer starts.
+    pub fn acknowledge_role(&mut self, player: Uuid) -> Result<(), GameError> {
+        if !self.players.contains_key(&player) {
+            return Err(GameError::NotAPlayer);
+        }
+        if !self.unacknowledged.contains(&player) {
+            return Err(GameError::WrongPhase);
+        }
+        self.stop_waiting_on_role(player);
+        Ok(())
+    }
+
+    /// Stop holding the first election for a player, starting it if nobody else is left to acknowledge their role.
+    fn stop_waiting_on_role(&mut self, player: Uuid) {
+        if self.unacknowledged.remove(&player) && self.unacknowledged.is_empty() {
+            self.phase_started_at = now();
+        }
+    }
+
+    /// When the first election starts whether or not everyone has acknowledged their role, while it is being held.
+    pub fn role_ack_deadline(&self) -> Option<SystemTime> {
+        Some(self.phase_started_at + ROLE_ACK_TIMEOUT).filter(|_| !self.unacknowledged.is_empty())
+    }
+
+    /// Start the first election once players have had long enough to see their roles.
+    /// Returns true if it started, so the new state should be sent out.
+    pub fn expire_role_ack(&mut self, now: SystemTime) -> bool {
+        if self.role_ack_deadline().is_none_or(|deadline| deadline > now) {
+            return false
+        }
+        self.unacknowledged.clear();
+        self.phase_started_at = now;
+        true
+    }
+
+    /// Players the first election is waiting on to say they have seen their role.
+    pub fn unacknowledged(&self) -> Vec<Uuid> {
+        self.unacknowledged.iter().copied().collect()
+    }
+
+    /// Put the spectators of a game in progress on the spectator delay, and everyone else back in real time.
+    fn delay_spectators(&self) {
+        let delay = self.options.spectator_delay.filter(|_| self.is_in_game()).map(Duration::from_secs);
+        for id in self.seating.waitlist() {
+            if let Some(conn) = self.conn.get(id) {
+                conn.tx.set_delay(delay);
+            }
+        }
+        for id in self.players.keys() {
+            if let Some(conn) = self.conn.get(id) {
+                conn.tx.set_delay(None);
+            }
+        }
+    }
+
+    /// Send spectators the messages that have waited out the spectator delay.
+    pub fn flush_spectators(&self, now: SystemTime) {
+        if self.options.spectator_delay.is_some() {
+            self.conn.values().for_each(|conn| conn.tx.flush_delayed(now));
+        }
+    }
+
+    /// Mark a seated player in the lobby as ready to start or not.
+    pub fn set_ready(&mut self, player: Uuid, ready: bool) -> Result<(), GameError> {
+        if !matches!(self.turn_phase, TurnPhase::Lobby) {
+            return Err(GameError::AlreadyStarted);
+        }
+        let state = self.players.get_mut(&player).ok_or(GameError::NotAPlayer)?;
+        state.ready = ready;
+        Ok(())
+    }
+
+    /// Seated players who have not said they are ready. The host and bots are always taken to be ready.
+    pub fn not_ready(&self) -> Vec<Uuid> {
+        self.players.iter()
+            .filter(|(id, state)| !state.ready && self.host != Some(**id) && !self.conn.get(id).is_some_and(|conn| conn.is_bot))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Count down to the start of the game as the host, locking the lobby to new players in the meantime.
+    /// Players can still say whether they are ready, and the game starts when the countdown runs out if it could be started then.
+    pub fn start_countdown(&mut self, player: Uuid, seconds: u64, now: SystemTime) -> Result<(), GameError> {
+        if !matches!(self.turn_phase, TurnPhase::Lobby) {
+            return Err(GameError::AlreadyStarted);
+        }
+        if self.host != Some(player) {
+            return Err(GameError::NotHost { action: "start the countdown" });
+        }
+        if self.countdown.is_some() {
+            return Err(GameError::CountdownRunning);
+        }
+        if self.players.len() < rules::MIN_PLAYERS || self.players.len() > rules::MAX_PLAYERS {
+            return Err(GameError::InvalidPlayerCount { players: self.players.len() });
+        }
+        let seconds = seconds.clamp(1, MAX_COUNTDOWN);
+        self.countdown = Some(now + Duration::from_secs(seconds));
+        self.announce(Message::CountdownStarted { seconds });
+        send_to_all(&self.conn, Topic::Events, &ServerProtocol::Countdown { seconds });
+        Ok(())
+    }
+
+    /// Stop the countdown to the start of the game as the host, opening the lobby again.
+    pub fn cancel_countdown(&mut self, player: Uuid) -> Result<(), GameError> {
+        if !matches!(self.turn_phase, TurnPhase::Lobby) {
+            return Err(GameError::AlreadyStarted);
+        }
+        if self.host != Some(player) {
+            return Err(GameError::NotHost { action: "cancel the countdown" });
+        }
+        if self.countdown.take().is_none() {
+            return Err(GameError::NoCountdown);
+        }
+        self.announce(Message::CountdownCancelled);
+        send_to_all(&self.conn, Topic::Events, &ServerProtocol::CountdownCancelled);
+        Ok(())
+    }
+
+    /// When the countdown to the start of the game runs out, if one is running.
+    pub fn countdown(&self) -> Option<SystemTime> {
+        self.countdown
+    }
+
+    /// Start the game once the countdown runs out. If it cannot start, such as when a player is not ready, the countdown is called off and the host is told why.
+    /// Returns true if the countdown ended, so the new state should be sent out.
+    pub fn expire_countdown(&mut self, now: SystemTime) -> bool {
+        if self.countdown.is_none_or(|countdown| countdown > now) {
+            return false
+        }
+        self.countdown = None;
+        let host = match self.host {
+            Some(host) => host,
+            None => return true
+        };
+        if let Err(error) = self.start(host) {
+            self.announce(Message::CountdownCancelled);
+            send_to_all(&self.conn, Topic::Events, &ServerProtocol::CountdownCancelled);
+            if let Some(conn) = self.conn.get(&host) {
+                conn.send(&ServerProtocol::Error { message: error.to_string(), error: &error, request_id: None });
+            }
+        }
+        true
+    }
+
+    /// Call a vote in the lobby. Only one vote can run at a time.
+    pub fn call_lobby_vote(&mut self, player: Uuid, motion: Motion, now: SystemTime) -> Result<(), GameError> {
+        if !matches!(self.turn_phase, TurnPhase::Lobby) {
+            return Err(GameError::AlreadyStarted);
+        }
+        if !self.players.contains_key(&player) {
+            return Err(GameError::NotAPlayer);
+        }
+        if self.lobby_vote.as_ref().is_some_and(|vote| vote.outcome(now) == VoteOutcome::Pending) {
+            return Err(GameError::LobbyVoteInProgress);
+        }
+        let target = match motion {
+            Motion::Kick { player: target } if target == player => return Err(GameError::SelfTarget),
+            Motion::Kick { player: target } if !self.conn.contains_key(&target) => return Err(GameError::PlayerNotFound { player: target }),
+            Motion::Kick { player: target } => target,
+            // only the host can ask to undo, and only once the game is under way
+            Motion::Undo { .. } => return Err(GameError::WrongPhase),
+        };
+        let voters = self.players.keys()
+            .filter(|id| Some(**id) != motion.subject() && !self.conn.get(id).is_some_and(|conn| conn.is_bot))
+            .copied()
+            .collect();
+        let (caller, target_name) = (self.player_name(&player).unwrap_or_default(), self.player_name(&target).unwrap_or_default());
+        self.announce(Message::KickCalled { caller: &caller, target: &target_name });
+        self.lobby_vote = Some(LobbyVote::new(motion, player, voters, now));
+        self.resolve_lobby_vote(now);
+        Ok(())
+    }
+
+    /// Vote on the open lobby vote, or on the host's request to undo an action once the game is under way.
+    pub fn cast_lobby_vote(&mut self, player: Uuid, approve: bool, now: SystemTime) -> Result<(), GameError> {
+        let vote = self.lobby_vote.as_mut().filter(|vote| vote.outcome(now) == VoteOutcome::Pending).ok_or(GameError::NoLobbyVote)?;
+        if !vote.cast(player, approve) {
+            return Err(GameError::NotAPlayer);
+        }
+        self.resolve_lobby_vote(now);
+        Ok(())
+    }
+
+    /// End the lobby vote once time runs out. Returns true if it ended, so the new state should be sent out.
+    pub fn expire_lobby_vote(&mut self, now: SystemTime) -> bool {
+        if self.lobby_vote.as_ref().is_none_or(|vote| vote.expires_at > now) {
+            return false
+        }
+        self.resolve_lobby_vote(now);
+        true
+    }
+
+    /// Carry out the lobby vote if it has passed, and clear it once it is decided.
+    fn resolve_lobby_vote(&mut self, now: SystemTime) {
+        let outcome = match &self.lobby_vote {
+            Some(vote) => vote.outcome(now),
+            None => return
+        };
+        if outcome == VoteOutcome::Pending {
+            return
+        }
+        let vote = self.lobby_vote.take().unwrap();
+        match (vote.motion, outcome) {
+            (Motion::Kick { player }, VoteOutcome::Passed) => {
+                let name = self.player_name(&player).unwrap_or_default();
+                self.expel(player, Message::RemovedByVote);
+                self.announce(Message::Kicked { name: &name });
+                self.delete_player(player);
+            },
+            (Motion::Kick { player }, _) => {
+                let name = self.player_name(&player).unwrap_or_default();
+                self.announce(Message::KickFailed { name: &name });
+            },
+            (Motion::Undo { commands }, VoteOutcome::Passed) => {
+                if self.history.commands().len() != commands || self.undo().is_err() {
+                    self.announce(Message::UndoOutdated);
+                }
+            },
+            (Motion::Undo { .. }, _) => {
+                self.announce(Message::UndoRefused);
+            },
+        }
+    }
+
+    /// Tell a player they were removed and keep them from joining again.
+    fn expel(&mut self, player: Uuid, notice: Message) {
+        if let Some(conn) = self.conn.get(&player) {
+            conn.send(&ServerProtocol::Alert { message: self.message_for(&player, notice) });
+            self.banned.push((conn.secret, conn.fingerprint.clone()));
+        }
+    }
+
+    /// Remove a player on a moderator's say, without a vote. Seated players can only be removed before the game starts, while spectators can be removed at any time.
+    pub fn kick(&mut self, player: Uuid) -> Result<(), GameError> {
+        if !self.conn.contains_key(&player) {
+            return Err(GameError::PlayerNotFound { player });
+        }
+        if !matches!(self.turn_phase, TurnPhase::Lobby) && !self.seating.is_waiting(&player) {
+            return Err(GameError::AlreadyStarted);
+        }
+        let name = self.player_name(&player).unwrap_or_default();
+        self.expel(player, Message::RemovedByModerator);
+        self.announce(Message::KickedByModerator { name: &name });
+        self.delete_player(player);
+        Ok(())
+    }
+
+    /// Ask to undo the last action of a game in progress, for friendly games where someone misclicked.
+    /// The players the action involved have to agree first, unless the host is the only one.
+    pub fn request_undo(&mut self, player: Uuid, now: SystemTime) -> Result<(), GameError> {
+        if self.options.ranked {
+            return Err(GameError::UndoDisabled);
+        }
+        if !self.is_in_game() {
+            return Err(GameError::WrongPhase);
+        }
+        if self.host != Some(player) {
+            return Err(GameError::NotHost { action: "undo an action" });
+        }
+        if self.lobby_vote.as_ref().is_some_and(|vote| vote.outcome(now) == VoteOutcome::Pending) {
+            return Err(GameError::LobbyVoteInProgress);
+        }
+        let involved = self.history.last_command().map(Command::players).ok_or(GameError::NothingToUndo)?;
+        let voters: Vec<Uuid> = involved.into_iter()
+            .filter(|id| *id != player && self.players.contains_key(id) && !self.conn.get(id).is_some_and(|conn| conn.is_bot))
+            .collect();
+        if voters.is_empty() {
+            return self.undo()
+        }
+        let names: Vec<String> = voters.iter().map(|id| self.player_name(id).unwrap_or_default()).collect();
+        self.announce(Message::UndoRequested { names: &names });
+        self.lobby_vote = Some(LobbyVote::new(Motion::Undo { commands: self.history.commands().len() }, player, voters, now));
+        Ok(())
+    }
+
+    /// Rewind the game to before the last command in its history, keeping everyone's connections and the chat.
+    /// Changes that are not logged as commands, such as who is host, who is watching, and who was voted out, are kept as well.
+    fn undo(&mut self) -> Result<(), GameError> {
+        let mut rebuilt = self.rebuild_before_last().ok_or(GameError::NothingToUndo)?;
+        rebuilt.conn = std::mem::take(&mut self.conn);
+        rebuilt.chat_log = std::mem::take(&mut self.chat_log);
+        rebuilt.host = self.host;
+        rebuilt.seating = std::mem::take(&mut self.seating);
+        rebuilt.banned = std::mem::take(&mut self.banned);
+        rebuilt.unacknowledged = std::mem::take(&mut self.unacknowledged);
+        rebuilt.processed_requests = std::mem::take(&mut self.processed_requests);
+        rebuilt.timeout = self.timeout;
+        rebuilt.bot_rng = self.bot_rng.clone();
+        *self = rebuilt;
+        self.delay_spectators();
+        self.announce(Message::Undone);
+        Ok(())
+    }
+
+    /// Whether a connection belongs to a player who was removed from this game by a vote.
+    pub fn is_banned(&self, conn: &PlayerConnection) -> bool {
+        self.banned.iter().any(|(secret, fingerprint)| {
+            (secret.is_some() && *secret == conn.secret) || (fingerprint.is_some() && *fingerprint == conn.fingerprint)
+        })
+    }
+
+    /// Return a finished game to the lobby with the same players so the host can start another round.
+    /// Everyone still connected, including dead players and spectators, is reseated in the order they joined.
+    pub fn rematch(&mut self, player: Uuid) -> Result<(), GameError> {
+        if !matches!(self.turn_phase, TurnPhase::Ended { winner: _ }) {
+            return Err(GameError::WrongPhase);
+        }
+        if self.host != Some(player) {
+            return Err(GameError::NotHost { action: "start a rematch" });
+        }
+
+        let departed: Vec<Uuid> = self.conn.iter().filter(|(_, c)| !c.connected && !c.is_bot).map(|(id, _)| *id).collect();
+        for id in departed.iter() {
+            self.seating.leave(id);
+            self.conn.remove(id);
+        }
+
+        let previous = std::mem::take(self);
+        let options = previous.options.clone();
+        *self = GameState {
+            conn: previous.conn,
+            chat_log: previous.chat_log,
+            tutorial: previous.tutorial,
+            seating: previous.seating,
+            processed_requests: previous.processed_requests,
+            banned: previous.banned,
+            community: previous.community,
+            rng: previous.rng,
+            bot_rng: previous.bot_rng,
+            ..GameState::with_options(options)
+        };
+        for id in self.seating.rollover(self.max_players()) {
+            self.players.insert(id, PlayerState { role: PlayerType::Liberal, vote: None, dead: false, ready: false });
+        }
+        self.host = previous.host.filter(|h| self.players.contains_key(h)).or_else(|| self.players.keys().next().copied());
+        self.announce(Message::RematchStarted);
+        for id in self.seating.waitlist().to_vec() {
+            if let Some(name) = self.player_name(&id) {
+                self.announce(Message::WaitlistedForRound { name: &name });
+            }
+        }
+        self.delay_spectators();
+        Ok(())
+    }
+
+    pub fn choose_chancellor(&mut self, player: Uuid, target_player: Uuid) -> Result<(), GameError> {
+        if !matches!(self.turn_phase, TurnPhase::Electing) {
+            return Err(GameError::WrongPhase);
+        }
+
+        if Some(player) != self.president {
+            return Err(GameError::NotPresident { action: "choose the chancellor" });
+        }
+
+        let waiting = self.unacknowledged();
+        if !waiting.is_empty() {
+            return Err(GameError::RolesNotAcknowledged { players: waiting });
+        }
+
+        if player == target_player {
+            return Err(GameError::SelfTarget);
+        }
+
+        let rules = self.rules();
+        if (rules.eligibility.last_chancellor_ineligible && Some(target_player) == self.last_chancellor) || (rules.last_president_ineligible(self.turn_order.len()) && Some(target_player) == self.last_president) {
+            return Err(GameError::TermLimited { player: target_player });
+        }
+
+        match self.players.get(&target_player) {
+            Some(plr) => {
+                if plr.dead {
+                    return Err(GameError::PlayerDead { player: target_player })
+                }
+            },
+            None => return Err(GameError::PlayerNotFound { player: target_player })
+        }
+
+        self.chancellor = Some(target_player);
+        self.players.values_mut().for_each(|val| val.vote = None);
+        if self.options.discussion_timer.is_some() {
+            self.president_called_vote = false;
+            self.chancellor_called_vote = false;
+            self.set_turn_phase(TurnPhase::Discussion);
+        }
+        else {
+            self.set_turn_phase(TurnPhase::Voting);
+        }
+        Ok(())
+    }
+
+    /// Agree as president or chancellor to end the discussion, which opens the vote once both have agreed.
+    pub fn call_vote(&mut self, player: Uuid) -> Result<(), GameError> {
+        if !matches!(self.turn_phase, TurnPhase::Discussion) {
+            return Err(GameError::WrongPhase);
+        }
+
+        if Some(player) == self.president {
+            self.president_called_vote = true;
+        }
+        else if Some(player) == self.chancellor {
+            self.chancellor_called_vote = true;
+        }
+        else {
+            return Err(GameError::NotInGovernment { action: "call the vote early" });
+        }
+
+        if self.president_called_vote && self.chancellor_called_vote {
+            self.announce(Message::VoteCalled);
+            self.set_turn_phase(TurnPhase::Voting);
+        }
+        else if let Some(name) = self.player_name(&player) {
+            self.announce(Message::WantsToCallVote { name: &name });
+        }
+        Ok(())
+    }
+
+    /// Open the vote once the discussion timer has run out.
+    /// Returns true if the discussion ended, so the new state should be sent out.
+    pub fn expire_discussion(&mut self, now: SystemTime) -> bool {
+        if !matches!(self.turn_phase, TurnPhase::Discussion) || self.phase_deadline().is_none_or(|deadline| deadline > now) {
+            return false
+        }
+        self.record(Command::DiscussionExpired);
+        self.end_discussion();
+        true
+    }
+
+    pub(crate) fn end_discussion(&mut self) {
+        self.set_turn_phase(TurnPhase::Voting);
+    }
+
+    pub fn vote_chancellor(&mut self, player: Uuid, vote: bool) -> Result<(), GameError> {
+        if !matches!(self.turn_phase, TurnPhase::Voting) {
+            return Err(GameError::WrongPhase)
+        }
+
+        if let Some(data) = self.players.get_mut(&player) {
+            if data.dead {
+                return Err(GameError::VoterDead);
+            }
+            data.vote = Some(vote);
+        }
+        else {
+            return Err(GameError::NotAPlayer);
+        }
+
+        if self.players.values().all(|plr| plr.dead || plr.vote.is_some()) {
+            // executed players have no say, so only the votes of the living are counted
+            let votes: BTreeMap<Uuid, bool> = self.players.iter().filter(|(_, plr)| !plr.dead).filter_map(|(id, plr)| plr.vote.map(|vote| (*id, vote))).collect();
+            let ayes = votes.values().filter(|vote| **vote).count();
+            let nays = votes.len() - ayes;
+            let passed = ayes > nays;
+            let votes = Some(votes).filter(|_| !self.options.anonymous_votes);
+            let record = votes.clone().unwrap_or_default();
+            self.send_event(GameEvent::VoteHeld { president: self.president.unwrap(), chancellor: self.chancellor.unwrap(), votes: record.clone(), elected: passed });
+            send_to_all(&self.conn, Topic::Events, &ServerProtocol::ElectionResult { votes, ayes, nays, passed });
+            if passed {
+                // hitler wins if elected chancellor once enough facist policies are enacted, three by default
+                if matches!(self.players.get(&self.chancellor.unwrap()).unwrap().role, PlayerType::Hitler) && self.facist_policies >= self.rules().hitler_chancellor_policies {
+                    self.send_event(GameEvent::HitlerElected { president: self.president.unwrap(), hitler: self.chancellor.unwrap(), votes: record, ayes, nays, facist_policies: self.facist_policies, required: self.rules().hitler_chancellor_policies });
+                    self.set_turn_phase(TurnPhase::Ended { winner: CardColor::Facist });
+                    return Ok(())
+                }
+                else {
+                    // do card selection
+                    let drawn = self.cards.split_off(self.cards.len() - 3);
+                    self.legislative_session = Some(LegislativeSession { drawn, discarded: vec![], stage: LegislativeStage::President });
+                    self.set_turn_phase(TurnPhase::PresidentSelect);
+                    self.election_tracker = 0;
+                }
+            }
+            else {
+                // do veto continue
+                self.chancellor = None;
+                if self.advance_election_tracker() {
+                    let card = self.cards.pop().unwrap();
+                    self.enact_policy(card, true, 0);
+                }
+                else {
+                    self.next_president();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Enact the chosen policy, reshuffle the deck if necessary, and handle moving on to the next president's turn.
+    /// Does not handle discarding the selected policy cards from the deck.
+    /// The deck position is where the card was in the draw pile before it was drawn, with 0 as the top.
+    fn enact_policy(&mut self, card: CardColor, chaos: bool, deck_position: usize) {
+        let mut pick_president = false;
+
+        if let (Some(president), Some(chancellor)) = (self.president.and_then(|p| self.conn.get(&p)).and_then(|p| p.name.clone()), self.chancellor.and_then(|p| self.conn.get(&p).and_then(|p| p.name.clone()))) {
+            self.announce(Message::PolicyEnacted { president: &president, chancellor: &chancellor, policy: card });
+        }
+        else {
+            self.announce(Message::ChaosPolicy { policy: card });
+        }
+        self.send_event(GameEvent::PolicyEnacted { policy: card, chaos, deck_position });
+        if let (false, Some(president), Some(chancellor)) = (chaos, self.president, self.chancellor) {
+            self.governments.push(Government::new(president, chancellor, card, self.liberal_policies, self.facist_policies));
+        }
+
+        if self.cards.len() < 3 {
+            self.reshuffle_deck();
+        }
+        match card {
+            CardColor::Facist => {
+                self.facist_policies += 1;
+                if self.facist_policies >= self.rules().facist_policies_to_win {
+                    self.set_turn_phase(TurnPhase::Ended { winner: CardColor::Facist });
+                }
+                else if let Some(power) = rules::presidential_power(self.players.len(), self.facist_policies) {
+                    self.set_turn_phase(TurnPhase::PresidentialPower { power });
+                }
+                else {
+                    pick_president = true;
+                }
+            }
+            CardColor::Liberal => {
+                self.liberal_policies += 1;
+                if self.liberal_policies >= self.rules().liberal_policies_to_win {
+                    self.set_turn_phase(TurnPhase::Ended { winner: CardColor::Liberal });
+                }
+                else {
+                    pick_president = true;
+                }
+            }
+        }
+
+        if pick_president {
+            self.next_president();
+        }
+    }
+
+    /// Pass the presidency on if the president has not nominated a chancellor before the turn timer ran out.
+    /// Returns true if the nomination expired, so the new state should be sent out.
+    pub fn expire_nomination(&mut self, now: SystemTime) -> bool {
+        if !matches!(self.turn_phase, TurnPhase::Electing) || self.phase_deadline().is_none_or(|deadline| deadline > now) {
+            return false
+        }
+        self.record(Command::NominationExpired);
+        self.pass_nomination();
+        true
+    }
+
+    /// Move on from a president who did not nominate anyone in time.
+    pub(crate) fn pass_nomination(&mut self) {
+        if let Some(president) = self.president {
+            self.send_event(GameEvent::NominationExpired { president });
+        }
+
+        if self.options.nomination_timeout_fails && self.advance_election_tracker() {
+            let card = self.cards.pop().unwrap();
+            self.enact_policy(card, true, 0);
+        }
+        else {
+            // no government was formed, so term limits still apply to the last one
+            let (last_president, last_chancellor) = (self.last_president, self.last_chancellor);
+            self.next_president();
+            self.last_president = last_president;
+            self.last_chancellor = last_chancellor;
+        }
+    }
+
+    /// Move onto the next president, keeping track of the last president and chancellor.
+    fn next_president(&mut self) {
+        self.last_president = self.president;
+        self.last_chancellor = self.chancellor;
+
+        self.chancellor = None;
+        self.turn_counter += 1;
+        self.rotation = (self.rotation + 1) % self.turn_order.len();
+        self.set_turn_phase(TurnPhase::Electing);
+        self.president = Some(self.turn_order[self.rotation]);
+    }
+
+    /// Take a dead player out of the turn order, keeping the presidency moving on to whoever sat after them.
+    fn remove_from_rotation(&mut self, player: Uuid) {
+        if let Some(idx) = self.turn_order.iter().position(|p| *p == player) {
+            self.turn_order.remove(idx);
+            if idx < self.rotation {
+                self.rotation -= 1;
+            }
+            else if idx == self.rotation {
+                // the player whose turn it was died during a special election, so the turn passes on from the seat before theirs
+                self.rotation = (idx + self.turn_order.len() - 1) % self.turn_order.len();
+            }
+        }
+    }
+
+    /// Whether the chancellor has asked for a veto that the president has yet to answer.
+    pub fn veto_pending(&self) -> bool {
+        self.veto_requested && !self.veto_declined
+    }
+
+    /// Whether the chancellor has already asked for a veto of the policies they hold, whatever the answer.
+    pub fn veto_requested(&self) -> bool {
+        self.veto_requested
+    }
+
+    /// Ask the president to veto the policies the chancellor was passed, once per government.
+    pub fn request_veto(&mut self, player: Uuid) -> Result<(), GameError> {
+        if !matches!(self.turn_phase, TurnPhase::ChancellorSelect) {
+            return Err(GameError::WrongPhase);
+        }
+        if self.facist_policies < self.rules().veto_policies {
+            return Err(GameError::VetoLocked);
+        }
+        if Some(player) != self.chancellor {
+            return Err(GameError::NotChancellor { action: "ask for a veto" });
+        }
+        if self.veto_requested {
+            return Err(GameError::VetoAlreadyRequested);
+        }
+
+        self.veto_requested = true;
+        if let Some(conn) = self.president.and_then(|president| self.conn.get(&president)) {
+            conn.send(&ServerProtocol::VetoRequested { chancellor: player });
+        }
+        self.send_event(GameEvent::VetoRequested { chancellor: player });
+        Ok(())
+    }
+
+    /// Answer the chancellor's request for a veto as president.
+    /// An accepted veto discards both policies and counts as a failed government, and a declined one leaves the chancellor to enact a policy.
+    pub fn respond_veto(&mut self, player: Uuid, accept: bool) -> Result<(), GameError> {
+        if !matches!(self.turn_phase, TurnPhase::ChancellorSelect) {
+            return Err(GameError::WrongPhase);
+        }
+        if Some(player) != self.president {
+            return Err(GameError::NotPresident { action: "answer a request for a veto" });
+        }
+        if !self.veto_pending() {
+            return Err(GameError::NoVetoRequested);
+        }
+
+        self.send_event(GameEvent::VetoAnswered { president: player, accepted: accept });
+        if !accept {
+            self.veto_declined = true;
+            return Ok(())
+        }
+        // both policies passed to the chancellor are discarded
+        if let Some(mut session) = self.legislative_session.take() {
+            self.discarded.append(&mut session.discarded);
+            self.discarded.append(&mut session.drawn);
+        }
+        if self.advance_election_tracker() {
+            // the vetoed government has failed, so the policy from the top of the deck is not theirs
+            self.chancellor = None;
+            // draw the next card and enact it
+            let card = self.cards.pop().unwrap_or_else(|| {
+                self.reshuffle_deck();
+                self.cards.pop().unwrap()
+            });
+            self.enact_policy(card, true, 0);
+        }
+        else {
+            if self.cards.len() < 3 {
+                self.reshuffle_deck();
+            }
+            self.next_president();
+        }
+
+        Ok(())
+    }
+
+    /// Count a failed government, warning players when the next failure will throw the government into chaos.
+    /// Returns true if the tracker has run out, in which case it is reset and the caller should enact the top policy.
+    fn advance_election_tracker(&mut self) -> bool {
+        self.election_tracker += 1;
+        let limit = self.rules().election_tracker_limit;
+        let chaos_imminent = self.election_tracker + 1 == limit;
+        self.log_event(GameEvent::ElectionTrackerAdvanced { value: self.election_tracker, chaos_imminent });
+        send_to_all(&self.conn, Topic::Events, &ServerProtocol::ElectionTrackerAdvanced { value: self.election_tracker, chaos_imminent });
+        if self.election_tracker >= limit {
+            self.election_tracker = 0;
+            return true
+        }
+        false
+    }
+
+    /// Move the discard pile into the draw pile and shuffle the draw pile.
+    fn reshuffle_deck(&mut self) {
+        self.cards.append(&mut self.discarded);
+        self.cards.shuffle(&mut self.rng);
+        self.send_event(GameEvent::DeckReshuffled { cards_in_deck: self.cards.len() });
+    }
+
+    /// Record an event in the game's timeline.
+    fn log_event(&mut self, event: GameEvent) {
+        self.timeline.push(TimelineEntry { at: epoch_millis(now()), event });
+    }
+
+    /// Record an event in the timeline and send it to everyone watching for events.
+    pub(crate) fn send_event(&mut self, event: GameEvent) {
+        send_to_all(&self.conn, Topic::Events, &ServerProtocol::GameEvent { event: event.clone() });
+        self.log_event(event);
+    }
+
+    pub fn timeline(&self) -> &[TimelineEntry] {
+        &self.timeline
+    }
+
+    /// Discard a policy as president or enact one as chancellor, picked by its place in the player's hand.
+    pub fn pick_card(&mut self, player: Uuid, index: usize) -> Result<(), GameError> {
+        let held = self.legislative_session.as_ref().map_or(0, |session| session.drawn.len());
+        match self.turn_phase {
+            TurnPhase::PresidentSelect => {
+                if Some(player) != self.president {
+                    return Err(GameError::NotPresident { action: "select policies at this time" });
+                }
+                if let Some(session) = self.legislative_session.as_mut().filter(|_| index < held) {
+                    session.discarded.push(session.drawn.remove(index));
+                    session.stage = LegislativeStage::Chancellor { discarded_slot: index };
+                    self.veto_requested = false;
+                    self.veto_declined = false;
+                    self.set_turn_phase(TurnPhase::ChancellorSelect);
+                    Ok(())
+                }
+                else {
+                    Err(GameError::InvalidPolicy)
+                }
+            },
+            TurnPhase::ChancellorSelect => {
+                if Some(player) != self.chancellor {
+                    return Err(GameError::NotChancellor { action: "select policies at this time" });
+                }
+                if self.veto_pending() {
+                    return Err(GameError::VetoPending);
+                }
+                if let Some(mut session) = self.legislative_session.take_if(|_| index < held) {
+                    let deck_position = session.deck_position(index);
+                    let card = session.drawn.remove(index);
+                    self.discarded.append(&mut session.discarded);
+                    self.discarded.append(&mut session.drawn);
+                    self.enact_policy(card, false, deck_position);
+                    Ok(())
+                }
+                else {
+                    Err(GameError::InvalidPolicy)
+                }
+            },
+            _ => {
+                Err(GameError::WrongPhase)
+            }
+        }
+    }
+
+    pub fn execute_presidential_power(&mut self, player: Uuid, target: Option<Uuid>) -> Result<(), GameError> {
+        if Some(player) != self.president {
+            return Err(GameError::NotPresident { action: "execute presidential powers" })
+        }
+
+        if let TurnPhase::PresidentialPower { power } = self.turn_phase {
+            match power {
+                PresidentialPower::InvestigateLoyalty => {
+                    if let Some(target) = target {
+                        match self.players.get(&target) {
+                            Some(_) => {
+                                if target == player {
+                                    return Err(GameError::SelfTarget)
+                                }
+
+                                let mut lst = vec![];
+                                if let Some(old) = self.investigated.get(&player) {
+                                    lst.extend(old);
+                                }
+                                lst.push(target);
+                                self.investigated.insert(player, lst);
+                                    
+                                if let (Some(president), Some(target)) = (self.conn.get(&self.president.unwrap()).and_then(|c| c.name.clone()), self.conn.get(&target).and_then(|c| c.name.clone())) {
+                                    self.announce(Message::Investigated { president: &president, target: &target });
+                                }
+
+                                self.next_president();
+                            },
+                            None => return Err(GameError::PlayerNotFound { player: target })
+                        }
+                    }
+                    else {
+                        return Err(GameError::MissingTarget);
+                    }
+                },
+                PresidentialPower::CallSpecialElection => {
+                    // president can choose any other player
+                    if target == Some(player) {
+                        return Err(GameError::SelfTarget)
+                    }
+                    if let Some(target) = target {
+                        match self.players.get(&target) {
+                            Some(plr) => {
+                                if plr.dead {
+                                    return Err(GameError::PlayerDead { player: target })
+                                }
+                            },
+                            None => return Err(GameError::PlayerNotFound { player: target })
+                        }
+
+                        if let (Some(president), Some(target)) = (self.conn.get(&self.president.unwrap()).and_then(|c| c.name.clone()), self.conn.get(&target).and_then(|c| c.name.clone())) {
+                            self.announce(Message::SpecialElection { president: &president, target: &target });
+                        }
+
+                        self.last_president = self.president;
+                        self.last_chancellor = self.chancellor;
+                        self.chancellor = None;
+                        self.president = Some(target);
+                        self.set_turn_phase(TurnPhase::Electing);
+                    }
+                    else {
+                        return Err(GameError::MissingTarget);
+                    }
+                },
+                PresidentialPower::Execution => {
+                    if target == Some(player) {
+                        return Err(GameError::SelfTarget)
+                    }
+
+                    if let Some(target) = target {
+                        match self.players.get_mut(&target) {
+                            Some(plr) => {
+                                if plr.dead {
+                                    return Err(GameError::PlayerDead { player: target })
+                                }
+                                else {
+                                    plr.dead = true;
+                                    let hitler = matches!(plr.role, PlayerType::Hitler);
+                                    // the presidency moves on below, so remember who gave the order for the announcement
+                                    let executioner = self.president;
+                                    self.remove_from_rotation(target);
+                                    if hitler {
+                                        self.set_turn_phase(TurnPhase::Ended { winner: CardColor::Liberal });
+                                    }
+                                    else {
+                                        self.next_president();
+                                    }
+                                    // the dead cannot be nominated, so term limits no longer need to remember them
+                                    if self.last_president == Some(target) {
+                                        self.last_president = None;
+                                    }
+                                    if self.last_chancellor == Some(target) {
+                                        self.last_chancellor = None;
+                                    }
+                                    if let (Some(president), Some(target)) = (executioner.and_then(|id| self.conn.get(&id)).and_then(|c| c.name.clone()), self.conn.get(&target).and_then(|c| c.name.clone())) {
+                                        self.announce(Message::Executed { president: &president, target: &target });
+                                    }
+                                }
+                            },
+                            None => return Err(GameError::PlayerNotFound { player: target })
+                        }
+                    }
+                    else {
+                        return Err(GameError::MissingTarget)
+                    }
+                },
+                PresidentialPower::PolicyPeek => {
+                    self.next_president();
+                },
+            }
+            self.send_event(GameEvent::PowerUsed { president: player, power, target: target.filter(|_| power != PresidentialPower::PolicyPeek) });
+        }
+        else {
+            return Err(GameError::WrongPhase)
+        }
+
+        Ok(())
+    }
+}
\ No newline at end of file
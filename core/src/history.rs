@@ -0,0 +1,128 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{game_state::{CardColor, GameExport, GameState}, machine::Action};
+
+/// Commands logged after a snapshot before a new snapshot is taken, so rebuilding a game never replays more than this.
+const SNAPSHOT_INTERVAL: usize = 50;
+
+/// Something that moved a game along once it started.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Command {
+    Act { player: Uuid, action: Action },
+    /// The president ran out of time to nominate a chancellor.
+    NominationExpired,
+    /// The discussion of a nomination ran out of time and the vote opened.
+    DiscussionExpired,
+    /// A member of the last government claimed the policies they were dealt.
+    Claim { player: Uuid, cards: Vec<CardColor> },
+}
+
+impl Command {
+    /// The players a command involved: whoever gave it, and whoever it was aimed at.
+    pub fn players(&self) -> Vec<Uuid> {
+        match self {
+            Command::Act { player, action: Action::Nominate { chancellor } } => vec![*player, *chancellor],
+            Command::Act { player, action: Action::UsePower { target: Some(target) } } => vec![*player, *target],
+            Command::Act { player, .. } | Command::Claim { player, .. } => vec![*player],
+            Command::NominationExpired | Command::DiscussionExpired => vec![],
+        }
+    }
+}
+
+/// A game as it was at some point, and the seed its random source was reset to at that point.
+#[derive(Clone)]
+struct Snapshot {
+    game: GameExport,
+    seed: u64,
+}
+
+/// The log of a game in progress: a snapshot, and every command applied since.
+/// Replaying the commands on top of the snapshot gives back the game as it is now.
+/// Games have no history until they start, and practice games have none at all, since they cannot be exported.
+#[derive(Clone, Default)]
+pub struct History {
+    snapshot: Option<Box<Snapshot>>,
+    commands: Vec<Command>,
+    /// The snapshot before the last one and the commands logged on top of it, so the command that set off a new snapshot can still be undone.
+    previous: Option<(Box<Snapshot>, Vec<Command>)>,
+    /// Set while commands are being replayed, so they are not logged a second time.
+    replaying: bool,
+}
+
+impl History {
+    /// The commands applied since the last snapshot.
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    /// The last command applied, even if a snapshot was taken straight after it.
+    pub fn last_command(&self) -> Option<&Command> {
+        self.commands.last().or_else(|| self.previous.as_ref().and_then(|(_, commands)| commands.last()))
+    }
+}
+
+impl GameState {
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Start the log over from the game as it is now.
+    /// The random source is reseeded with a seed kept in the snapshot, so replaying from here shuffles the deck the same way.
+    pub(crate) fn take_snapshot(&mut self) {
+        let seed = self.rng.gen();
+        self.rng = StdRng::seed_from_u64(seed);
+        self.history.previous = self.history.snapshot.take().map(|snapshot| (snapshot, std::mem::take(&mut self.history.commands)));
+        self.history.commands.clear();
+        self.history.snapshot = self.export().map(|game| Box::new(Snapshot { game, seed }));
+    }
+
+    /// Log a command that has just been applied, taking a new snapshot once enough have built up.
+    pub(crate) fn record(&mut self, command: Command) {
+        if self.history.replaying || self.history.snapshot.is_none() {
+            return
+        }
+        self.history.commands.push(command);
+        if self.history.commands.len() >= SNAPSHOT_INTERVAL {
+            self.take_snapshot();
+        }
+    }
+
+    /// Rebuild the game from its last snapshot and the commands logged since, or none if it has no history.
+    /// Connections are not part of the log, so every seat in the rebuilt game is disconnected, as in an imported game.
+    pub fn rebuild(&self) -> Option<GameState> {
+        GameState::replay(self.history.snapshot.as_ref()?, &self.history.commands, self.history.commands.len())
+    }
+
+    /// Rebuild the game as it was before the last logged command, with that command dropped from its history.
+    /// If a snapshot was taken straight after that command, the game is rebuilt from the snapshot before it.
+    pub(crate) fn rebuild_before_last(&self) -> Option<GameState> {
+        match self.history.commands.len().checked_sub(1) {
+            Some(count) => GameState::replay(self.history.snapshot.as_ref()?, &self.history.commands, count),
+            None => {
+                let (snapshot, commands) = self.history.previous.as_ref()?;
+                GameState::replay(snapshot, commands, commands.len().checked_sub(1)?)
+            }
+        }
+    }
+
+    /// Replay the first `count` commands on top of a snapshot.
+    fn replay(snapshot: &Snapshot, commands: &[Command], count: usize) -> Option<GameState> {
+        let mut state = GameState::import(snapshot.game.clone());
+        state.rng = StdRng::seed_from_u64(snapshot.seed);
+        state.history.replaying = true;
+        for command in &commands[..count] {
+            match command {
+                // every logged action succeeded the first time, so one that fails now means the log does not match the game
+                Command::Act { player, action } => { state.apply(*player, *action).ok()?; },
+                Command::NominationExpired => state.pass_nomination(),
+                Command::DiscussionExpired => state.end_discussion(),
+                Command::Claim { player, cards } => { state.claim(*player, cards.clone()).ok()?; },
+            }
+        }
+        state.history = History { snapshot: Some(Box::new(snapshot.clone())), commands: commands[..count].to_vec(), previous: None, replaying: false };
+        Some(state)
+    }
+}
@@ -0,0 +1,76 @@
+//! The Secret Hitler game engine.
+//!
+//! A [`GameState`](game_state::GameState) holds one game and checks every action against the rules.
+//! Players' moves are applied with [`GameState::apply`](game_state::GameState::apply), which returns the
+//! [`GameEvent`](events::GameEvent)s each move caused.
+//! Once a game starts, each move is also logged in its [`History`](history::History), from which
+//! [`GameState::rebuild`](game_state::GameState::rebuild) can recreate the game.
+//! Players are seated with a [`PlayerConnection`](protocol::PlayerConnection), which delivers the
+//! game's messages to a [`MessageSink`](protocol::MessageSink) so the engine can be driven by any
+//! transport, such as the websocket server, a chat bot, or a native client.
+//! The JSON messages they exchange are described by the schemas in [`schema`].
+//! With the `wasm` feature the engine also builds for `wasm32-unknown-unknown`, where `wasm` exposes it to JavaScript.
+//!
+//! ```
+//! use std::sync::{Arc, mpsc};
+//! use secrethitler_core::{game_state::{GameState, TurnPhase}, protocol::PlayerConnection};
+//! use uuid::Uuid;
+//!
+//! let (tx, rx) = mpsc::channel();
+//! let mut game = GameState::new();
+//! let players: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+//! for player in &players {
+//!     game.add_player(*player, PlayerConnection::new(Arc::new(tx.clone())));
+//! }
+//! game.start(players[0]).unwrap();
+//! assert!(matches!(game.turn_phase(), TurnPhase::Electing));
+//!
+//! // every seat is sent its own view of the game as JSON
+//! rx.try_iter().for_each(drop);
+//! game.broadcast_game_state();
+//! assert_eq!(rx.try_iter().count(), 5);
+//! ```
+//!
+//! Code that embeds the engine without delivering its messages, such as a bot or a simulation, can set a game up
+//! with [`GameState::builder`](game_state::GameState::builder) and move for each player with
+//! [`GameState::act_as`](game_state::GameState::act_as).
+//!
+//! ```
+//! use secrethitler_core::{events::GameEvent, game_state::{GameOptions, GameState}};
+//! use uuid::Uuid;
+//!
+//! let players: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+//! let mut game = GameState::builder().options(GameOptions::default()).players(players.clone()).seed(7).build();
+//! let events = game.act_as(players[0]).start().unwrap();
+//! assert!(matches!(events.as_slice(), [GameEvent::GameStarted { .. }]));
+//!
+//! let president = game.president().unwrap();
+//! let chancellor = *players.iter().find(|player| **player != president).unwrap();
+//! game.act_as(president).nominate(chancellor).unwrap();
+//! ```
+
+pub mod achievements;
+pub mod analysis;
+pub mod bots;
+pub mod builder;
+pub mod chat;
+pub mod claims;
+pub mod error;
+pub mod events;
+pub mod game_state;
+pub mod history;
+pub mod lobby_vote;
+pub mod machine;
+pub mod messages;
+pub mod nicknames;
+pub mod odds;
+pub mod pass_and_play;
+pub mod protocol;
+pub mod rules;
+pub mod schema;
+pub mod seating;
+pub mod simulation;
+pub mod theme;
+pub mod tutorial;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,101 @@
+use std::{collections::BTreeMap, time::{Duration, SystemTime}};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, ser::SerializeMap};
+use uuid::Uuid;
+
+use crate::game_state::epoch_millis;
+
+/// How long players have to vote before a lobby vote lapses.
+pub const LOBBY_VOTE_DURATION: Duration = Duration::from_secs(60);
+
+/// What a lobby vote decides.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum Motion {
+    /// Remove a player from the lobby, even the host. They cannot rejoin the game with the same secret or device.
+    Kick { player: Uuid },
+    /// Undo the last action of a game in progress, at the host's request. Only the players the action involved vote on it.
+    /// The action is named by how many commands the game's history held when the vote was called.
+    Undo { commands: usize },
+}
+
+impl Motion {
+    /// The player the motion is about, who does not get a say in it.
+    pub fn subject(&self) -> Option<Uuid> {
+        match self {
+            Motion::Kick { player } => Some(*player),
+            Motion::Undo { .. } => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VoteOutcome {
+    Pending,
+    Passed,
+    Failed,
+}
+
+/// A vote among the players in a lobby, which passes once a majority of everyone allowed to vote is in favor.
+/// It fails as soon as a majority can no longer be reached, or when time runs out.
+pub struct LobbyVote {
+    pub motion: Motion,
+    pub called_by: Uuid,
+    pub expires_at: SystemTime,
+    /// Everyone allowed to vote, fixed when the vote is called.
+    voters: Vec<Uuid>,
+    votes: BTreeMap<Uuid, bool>,
+}
+
+impl LobbyVote {
+    /// Call a vote, counting the caller in favor.
+    pub fn new(motion: Motion, called_by: Uuid, voters: Vec<Uuid>, now: SystemTime) -> LobbyVote {
+        let mut vote = LobbyVote { motion, called_by, expires_at: now + LOBBY_VOTE_DURATION, voters, votes: BTreeMap::new() };
+        vote.cast(called_by, true);
+        vote
+    }
+
+    /// Record a vote, replacing any earlier one. Returns false if the player is not allowed to vote.
+    pub fn cast(&mut self, voter: Uuid, approve: bool) -> bool {
+        if !self.voters.contains(&voter) {
+            return false
+        }
+        self.votes.insert(voter, approve);
+        true
+    }
+
+    /// Votes in favor needed for the motion to pass.
+    pub fn needed(&self) -> usize {
+        self.voters.len() / 2 + 1
+    }
+
+    pub fn outcome(&self, now: SystemTime) -> VoteOutcome {
+        let approvals = self.votes.values().filter(|approve| **approve).count();
+        let rejections = self.votes.len() - approvals;
+        if approvals >= self.needed() {
+            VoteOutcome::Passed
+        }
+        else if rejections > self.voters.len() - self.needed() || now >= self.expires_at {
+            VoteOutcome::Failed
+        }
+        else {
+            VoteOutcome::Pending
+        }
+    }
+}
+
+impl Serialize for LobbyVote {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer {
+            let mut map = serializer.serialize_map(None)?;
+            map.serialize_entry("motion", &self.motion)?;
+            map.serialize_entry("called_by", &self.called_by)?;
+            map.serialize_entry("expires_at", &epoch_millis(self.expires_at))?;
+            map.serialize_entry("voters", &self.voters)?;
+            map.serialize_entry("votes", &self.votes)?;
+            map.serialize_entry("needed", &self.needed())?;
+            map.end()
+    }
+}
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{error::GameError, events::GameEvent, game_state::{GameState, TurnPhase}, history::Command};
+
+/// Something a player does to move the game along. Every action goes through [`GameState::apply`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Action {
+    Start,
+    Nominate { chancellor: Uuid },
+    /// Agree to end the discussion of a nomination and vote early.
+    CallVote,
+    Vote { approve: bool },
+    /// Discard a policy as president, or enact one as chancellor, by its place in the player's hand.
+    PickCard { index: usize },
+    /// Ask the president to veto the policies the chancellor was passed.
+    RequestVeto,
+    /// Accept or decline the chancellor's request for a veto, as president.
+    RespondVeto { accept: bool },
+    UsePower { target: Option<Uuid> },
+    Rematch,
+}
+
+impl Action {
+    /// Whether the action can be taken at all in a turn phase, whoever takes it.
+    pub fn allowed_in(&self, phase: &TurnPhase) -> bool {
+        matches!((self, phase),
+            (Action::Start, TurnPhase::Lobby) |
+            (Action::Nominate { .. }, TurnPhase::Electing) |
+            (Action::CallVote, TurnPhase::Discussion) |
+            (Action::Vote { .. }, TurnPhase::Voting) |
+            (Action::PickCard { .. }, TurnPhase::PresidentSelect | TurnPhase::ChancellorSelect) |
+            (Action::RequestVeto | Action::RespondVeto { .. }, TurnPhase::ChancellorSelect) |
+            (Action::UsePower { .. }, TurnPhase::PresidentialPower { .. }) |
+            (Action::Rematch, TurnPhase::Ended { .. })
+        )
+    }
+}
+
+impl TurnPhase {
+    /// Whether the game may move straight from this phase to another. Every change of phase is checked against this table.
+    /// A failed election or veto can throw the government into chaos, so any phase that ends a government can lead to a power or the end of the game.
+    pub fn can_become(&self, next: &TurnPhase) -> bool {
+        match self {
+            TurnPhase::Lobby => matches!(next, TurnPhase::Electing),
+            // an expired nomination passes the presidency on
+            TurnPhase::Electing => matches!(next, TurnPhase::Discussion | TurnPhase::Voting | TurnPhase::Electing | TurnPhase::PresidentialPower { .. } | TurnPhase::Ended { .. }),
+            TurnPhase::Discussion => matches!(next, TurnPhase::Voting),
+            TurnPhase::Voting => matches!(next, TurnPhase::PresidentSelect | TurnPhase::Electing | TurnPhase::PresidentialPower { .. } | TurnPhase::Ended { .. }),
+            TurnPhase::PresidentSelect => matches!(next, TurnPhase::ChancellorSelect),
+            TurnPhase::ChancellorSelect => matches!(next, TurnPhase::Electing | TurnPhase::PresidentialPower { .. } | TurnPhase::Ended { .. }),
+            TurnPhase::PresidentialPower { .. } => matches!(next, TurnPhase::Electing | TurnPhase::Ended { .. }),
+            TurnPhase::Ended { .. } => matches!(next, TurnPhase::Lobby),
+        }
+    }
+}
+
+impl GameState {
+    /// Apply a player's action, returning the events it caused in the order they happened.
+    /// Actions that are not allowed in the current phase are refused before anything else is checked.
+    pub fn apply(&mut self, player: Uuid, action: Action) -> Result<Vec<GameEvent>, GameError> {
+        if !action.allowed_in(self.turn_phase()) {
+            return Err(if action == Action::Start { GameError::AlreadyStarted } else { GameError::WrongPhase })
+        }
+        let before = self.timeline().len();
+        match action {
+            Action::Start => self.start(player),
+            Action::Nominate { chancellor } => self.choose_chancellor(player, chancellor),
+            Action::CallVote => self.call_vote(player),
+            Action::Vote { approve } => self.vote_chancellor(player, approve),
+            Action::PickCard { index } => self.pick_card(player, index),
+            Action::RequestVeto => self.request_veto(player),
+            Action::RespondVeto { accept } => self.respond_veto(player, accept),
+            Action::UsePower { target } => self.execute_presidential_power(player, target),
+            Action::Rematch => self.rematch(player),
+        }?;
+        // starting a game and starting a rematch begin a new history instead
+        if !matches!(action, Action::Start | Action::Rematch) {
+            self.record(Command::Act { player, action });
+        }
+        // a rematch starts a new timeline
+        Ok(self.timeline().get(before..).unwrap_or_default().iter().map(|entry| entry.event.clone()).collect())
+    }
+}
+
+/// One player's moves, each returning the events it caused, for code that embeds the engine.
+/// These are the same as passing an [`Action`] to [`GameState::apply`].
+pub struct Actor<'a> {
+    game: &'a mut GameState,
+    player: Uuid,
+}
+
+impl GameState {
+    pub fn act_as(&mut self, player: Uuid) -> Actor<'_> {
+        Actor { game: self, player }
+    }
+}
+
+impl Actor<'_> {
+    pub fn start(&mut self) -> Result<Vec<GameEvent>, GameError> {
+        self.game.apply(self.player, Action::Start)
+    }
+
+    pub fn nominate(&mut self, chancellor: Uuid) -> Result<Vec<GameEvent>, GameError> {
+        self.game.apply(self.player, Action::Nominate { chancellor })
+    }
+
+    pub fn call_vote(&mut self) -> Result<Vec<GameEvent>, GameError> {
+        self.game.apply(self.player, Action::CallVote)
+    }
+
+    pub fn vote(&mut self, approve: bool) -> Result<Vec<GameEvent>, GameError> {
+        self.game.apply(self.player, Action::Vote { approve })
+    }
+
+    /// Discard or enact the policy at this place in the player's hand.
+    pub fn pick_card(&mut self, index: usize) -> Result<Vec<GameEvent>, GameError> {
+        self.game.apply(self.player, Action::PickCard { index })
+    }
+
+    pub fn request_veto(&mut self) -> Result<Vec<GameEvent>, GameError> {
+        self.game.apply(self.player, Action::RequestVeto)
+    }
+
+    pub fn respond_veto(&mut self, accept: bool) -> Result<Vec<GameEvent>, GameError> {
+        self.game.apply(self.player, Action::RespondVeto { accept })
+    }
+
+    pub fn use_power(&mut self, target: Option<Uuid>) -> Result<Vec<GameEvent>, GameError> {
+        self.game.apply(self.player, Action::UsePower { target })
+    }
+
+    pub fn rematch(&mut self) -> Result<Vec<GameEvent>, GameError> {
+        self.game.apply(self.player, Action::Rematch)
+    }
+}
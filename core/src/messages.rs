@@ -0,0 +1,158 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{game_state::CardColor, theme::{Theme, ThemeNames, capitalize}};
+
+/// Languages that the game's own messages can be written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum Language {
+    #[default]
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "es")]
+    Spanish,
+}
+
+/// Something the game tells its players, written out in a language and the game's theme with [`Message::text`].
+/// Chat lines use the game's language, and messages sent to one player use their own if they have set one.
+pub enum Message<'a> {
+    GameOpened,
+    JoinedWaitlist { name: &'a str },
+    Joined { name: &'a str },
+    Reconnected { name: &'a str },
+    SeatedFromWaitlist { name: &'a str },
+    CredentialsReset { name: &'a str },
+    Disconnected { name: &'a str },
+    LeftLobby { name: &'a str },
+    StoppedSpectating { name: &'a str },
+    LeftGame { name: &'a str },
+    HostChanged { name: &'a str },
+    KickCalled { caller: &'a str, target: &'a str },
+    CountdownStarted { seconds: u64 },
+    CountdownCancelled,
+    Kicked { name: &'a str },
+    KickFailed { name: &'a str },
+    /// Sent to the player who was voted out.
+    RemovedByVote,
+    KickedByModerator { name: &'a str },
+    /// Sent to the player a moderator removed.
+    RemovedByModerator,
+    UndoRequested { names: &'a [String] },
+    UndoOutdated,
+    UndoRefused,
+    Undone,
+    RematchStarted,
+    /// Moves were made for players who left a game that was waiting on them.
+    StoodIn,
+    WaitlistedForRound { name: &'a str },
+    VoteCalled,
+    WantsToCallVote { name: &'a str },
+    PolicyEnacted { president: &'a str, chancellor: &'a str, policy: CardColor },
+    ChaosPolicy { policy: CardColor },
+    Investigated { president: &'a str, target: &'a str },
+    SpecialElection { president: &'a str, target: &'a str },
+    Executed { president: &'a str, target: &'a str },
+    PoliciesClaimed { as_president: bool, name: &'a str, cards: &'a [CardColor] },
+    EmailEnabled,
+    EmailDisabled,
+}
+
+impl Message<'_> {
+    pub fn text(&self, language: Language, theme: &Theme) -> String {
+        let names = theme.names(language);
+        match language {
+            Language::English => self.english(&names),
+            Language::Spanish => self.spanish(&names),
+        }
+    }
+
+    fn english(&self, names: &ThemeNames) -> String {
+        let (president_title, chancellor_title) = (capitalize(&names.president), &names.chancellor);
+        match self {
+            Message::GameOpened => "The game is now open for joining.".into(),
+            Message::JoinedWaitlist { name } => format!("{} has joined the waitlist", name),
+            Message::Joined { name } => format!("{} has joined the game", name),
+            Message::Reconnected { name } => format!("{} has reconnected", name),
+            Message::SeatedFromWaitlist { name } => format!("{} has been seated from the waitlist", name),
+            Message::CredentialsReset { name } => format!("The host has reset the credentials for {}.", name),
+            Message::Disconnected { name } => format!("{} has disconnected", name),
+            Message::LeftLobby { name } => format!("{} has left the lobby", name),
+            Message::StoppedSpectating { name } => format!("{} has stopped spectating", name),
+            Message::LeftGame { name } => format!("{} has left the game", name),
+            Message::HostChanged { name } => format!("{} is now the host", name),
+            Message::KickCalled { caller, target } => format!("{} called a vote to remove {} from the game", caller, target),
+            Message::CountdownStarted { seconds } => format!("The game starts in {} seconds. The lobby is closed to new players", seconds),
+            Message::CountdownCancelled => "The countdown has been called off and the lobby is open again".into(),
+            Message::Kicked { name } => format!("The vote passed and {} has been removed from the game", name),
+            Message::KickFailed { name } => format!("The vote to remove {} failed", name),
+            Message::RemovedByVote => "You have been removed from the game by a vote.".into(),
+            Message::KickedByModerator { name } => format!("{} has been removed from the game by a moderator", name),
+            Message::RemovedByModerator => "You have been removed from the game by a moderator.".into(),
+            Message::UndoRequested { names } => format!("The host asked to undo the last action, which {} must agree to", names.join(" and ")),
+            Message::UndoOutdated => "The game moved on before everyone agreed, so nothing was undone.".into(),
+            Message::UndoRefused => "The last action will not be undone.".into(),
+            Message::Undone => "The last action has been undone.".into(),
+            Message::RematchStarted => "The host has started a rematch.".into(),
+            Message::StoodIn => "The game was stuck waiting on players who have left, so their moves were made for them.".into(),
+            Message::WaitlistedForRound { name } => format!("{} is on the waitlist for this round", name),
+            Message::VoteCalled => format!("The {} and {} have called the vote early.", names.president, chancellor_title),
+            Message::WantsToCallVote { name } => format!("{} wants to call the vote early.", name),
+            Message::PolicyEnacted { president, chancellor, policy: card } => format!("{} {} and {} {} have enacted a {} {}.", president_title, president, chancellor_title, chancellor, names.faction(*card), names.policy),
+            Message::ChaosPolicy { policy: card } => format!("The government has been thrown into chaos! A random {} {} has been enacted.", names.faction(*card), names.policy),
+            Message::Investigated { president, target } => format!("{} {} has investigated {}.", president_title, president, target),
+            Message::SpecialElection { president, target } => format!("{} {} has nominated {} as {} in a special election.", president_title, president, target, names.president),
+            Message::Executed { president, target } => format!("{} {} has killed {}.", president_title, president, target),
+            Message::PoliciesClaimed { as_president, name, cards } => {
+                let cards = cards.iter().map(|card| names.faction(*card)).collect::<Vec<_>>().join(", ");
+                format!("{} {} claims to have been dealt {}.", if *as_president { president_title } else { capitalize(chancellor_title) }, name, cards)
+            },
+            Message::EmailEnabled => "You will get email about this game.".into(),
+            Message::EmailDisabled => "You will no longer get email about this game.".into(),
+        }
+    }
+
+    fn spanish(&self, names: &ThemeNames) -> String {
+        let (president_title, chancellor_title) = (&names.president, &names.chancellor);
+        match self {
+            Message::GameOpened => "La partida ya está abierta para unirse.".into(),
+            Message::JoinedWaitlist { name } => format!("{} se ha unido a la lista de espera", name),
+            Message::Joined { name } => format!("{} se ha unido a la partida", name),
+            Message::Reconnected { name } => format!("{} se ha reconectado", name),
+            Message::SeatedFromWaitlist { name } => format!("{} ha pasado de la lista de espera a la mesa", name),
+            Message::CredentialsReset { name } => format!("El anfitrión ha restablecido las credenciales de {}.", name),
+            Message::Disconnected { name } => format!("{} se ha desconectado", name),
+            Message::LeftLobby { name } => format!("{} ha salido de la sala", name),
+            Message::StoppedSpectating { name } => format!("{} ha dejado de observar", name),
+            Message::LeftGame { name } => format!("{} ha abandonado la partida", name),
+            Message::HostChanged { name } => format!("{} es ahora el anfitrión", name),
+            Message::KickCalled { caller, target } => format!("{} ha pedido una votación para expulsar a {} de la partida", caller, target),
+            Message::CountdownStarted { seconds } => format!("La partida empieza en {} segundos. La sala está cerrada a nuevos jugadores", seconds),
+            Message::CountdownCancelled => "Se ha cancelado la cuenta atrás y la sala vuelve a estar abierta".into(),
+            Message::Kicked { name } => format!("La votación ha salido adelante y {} ha sido expulsado de la partida", name),
+            Message::KickFailed { name } => format!("La votación para expulsar a {} no ha salido adelante", name),
+            Message::RemovedByVote => "Has sido expulsado de la partida por votación.".into(),
+            Message::KickedByModerator { name } => format!("{} ha sido expulsado de la partida por un moderador", name),
+            Message::RemovedByModerator => "Has sido expulsado de la partida por un moderador.".into(),
+            Message::UndoRequested { names } => format!("El anfitrión ha pedido deshacer la última acción, lo que deben aceptar {}", names.join(" y ")),
+            Message::UndoOutdated => "La partida avanzó antes de que todos estuvieran de acuerdo, así que no se ha deshecho nada.".into(),
+            Message::UndoRefused => "La última acción no se deshará.".into(),
+            Message::Undone => "Se ha deshecho la última acción.".into(),
+            Message::RematchStarted => "El anfitrión ha empezado una revancha.".into(),
+            Message::StoodIn => "La partida estaba esperando a jugadores que se han ido, así que se han hecho sus jugadas por ellos.".into(),
+            Message::WaitlistedForRound { name } => format!("{} está en la lista de espera para esta ronda", name),
+            Message::VoteCalled => format!("El {} y el {} han adelantado la votación.", president_title, chancellor_title),
+            Message::WantsToCallVote { name } => format!("{} quiere adelantar la votación.", name),
+            Message::PolicyEnacted { president, chancellor, policy: card } => format!("El {} {} y el {} {} han aprobado una {} {}.", president_title, president, chancellor_title, chancellor, names.policy, names.faction(*card)),
+            Message::ChaosPolicy { policy: card } => format!("¡El gobierno ha caído en el caos! Se ha aprobado una {} {} al azar.", names.policy, names.faction(*card)),
+            Message::Investigated { president, target } => format!("El {} {} ha investigado a {}.", president_title, president, target),
+            Message::SpecialElection { president, target } => format!("El {} {} ha nombrado {} a {} en una elección especial.", president_title, president, president_title, target),
+            Message::Executed { president, target } => format!("El {} {} ha ejecutado a {}.", president_title, president, target),
+            Message::PoliciesClaimed { as_president, name, cards } => {
+                let cards = cards.iter().map(|card| names.faction(*card)).collect::<Vec<_>>().join(", ");
+                format!("El {} {} dice haber recibido {}.", if *as_president { president_title } else { chancellor_title }, name, cards)
+            },
+            Message::EmailEnabled => "Recibirás correos sobre esta partida.".into(),
+            Message::EmailDisabled => "Ya no recibirás correos sobre esta partida.".into(),
+        }
+    }
+}
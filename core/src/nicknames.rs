@@ -0,0 +1,33 @@
+use rand::{Rng, seq::SliceRandom};
+
+use crate::theme::ThemePreset;
+
+/// How many random nicknames are tried before a number is added to make one unique.
+const ATTEMPTS: usize = 20;
+
+const ADJECTIVES: &[&str] = &[
+    "Quiet", "Bold", "Honest", "Sly", "Curious", "Steady", "Clever", "Patient", "Restless", "Cautious",
+    "Cheerful", "Silent", "Shrewd", "Humble", "Brave", "Lucky", "Nervous", "Calm", "Swift", "Wary",
+];
+
+/// Names for the people at the table in each theme.
+fn nouns(preset: ThemePreset) -> &'static [&'static str] {
+    match preset {
+        ThemePreset::Classic => &["Delegate", "Senator", "Envoy", "Minister", "Diplomat", "Clerk", "Journalist", "Magistrate", "Attache", "Consul"],
+        ThemePreset::Starship => &["Navigator", "Engineer", "Pilot", "Medic", "Cadet", "Quartermaster", "Astronomer", "Mechanic", "Signaller", "Ensign"],
+    }
+}
+
+/// A random nickname in the theme that is not yet taken. The words are picked by hand, so every suggestion is fit for any table.
+pub fn suggest(rng: &mut impl Rng, preset: ThemePreset, taken: impl Fn(&str) -> bool) -> String {
+    let nouns = nouns(preset);
+    let mut pick = || format!("{} {}", ADJECTIVES.choose(rng).unwrap(), nouns.choose(rng).unwrap());
+    for _ in 0..ATTEMPTS {
+        let nickname = pick();
+        if !taken(&nickname) {
+            return nickname
+        }
+    }
+    let nickname = pick();
+    (2..).map(|n| format!("{} {}", nickname, n)).find(|numbered| !taken(numbered)).unwrap()
+}
@@ -0,0 +1,47 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::rules;
+
+/// Policies drawn for the president each round.
+const DRAW: usize = 3;
+
+/// The chances for the next policies drawn, worked out only from what every player can see: how many of each policy have been enacted.
+/// Until they are enacted, policies in the deck and the discard pile are equally likely to be anywhere, so claims and policy peeks are not taken into account.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct DeckOdds {
+    /// Policies of each kind that have not been enacted, and so are in the deck or the discard pile.
+    pub liberals_left: usize,
+    pub facists_left: usize,
+    /// The chance that the next three policies drawn include at least one liberal.
+    pub at_least_one_liberal: f64,
+    /// The chance that the next three policies drawn are all liberal.
+    pub all_liberal: f64,
+    /// How many liberals the next three policies drawn hold on average.
+    pub expected_liberals: f64,
+}
+
+impl DeckOdds {
+    pub fn new(liberal_policies: u8, facist_policies: u8) -> DeckOdds {
+        let liberals_left = rules::LIBERAL_CARDS.saturating_sub(liberal_policies as usize);
+        let facists_left = rules::FACIST_CARDS.saturating_sub(facist_policies as usize);
+        let total = liberals_left + facists_left;
+        let draws = choose(total, DRAW);
+        let chance = |ways: f64| if draws > 0.0 { ways / draws } else { 0.0 };
+        DeckOdds {
+            liberals_left,
+            facists_left,
+            at_least_one_liberal: 1.0 - chance(choose(facists_left, DRAW)),
+            all_liberal: chance(choose(liberals_left, DRAW)),
+            expected_liberals: if total > 0 { (DRAW * liberals_left) as f64 / total as f64 } else { 0.0 },
+        }
+    }
+}
+
+/// The number of ways to pick k things out of n.
+fn choose(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0
+    }
+    (0..k).fold(1.0, |ways, i| ways * (n - i) as f64 / (i + 1) as f64)
+}
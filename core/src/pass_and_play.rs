@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::{error::GameError, events::GameEvent, game_state::{GameOptions, GameState, GameStatePlayerView, TurnPhase}, machine::Action, protocol::{NullSink, PlayerConnection}};
+
+/// A game played on a single device that is passed around the table.
+/// Whoever holds the device sees the game as their seat does, and everyone else sees the public view, so secrets are only
+/// ever shown to one player at a time. The device is handed to each player to see their role before the first nomination,
+/// and after that to whoever the game is waiting on.
+pub struct PassAndPlay {
+    state: GameState,
+    holder: Option<Uuid>,
+    /// Players who have not been shown their role since the game started, in turn order.
+    unseen_roles: Vec<Uuid>,
+}
+
+impl PassAndPlay {
+    /// Seat the players in the order given, with the first one as host, and start the game.
+    pub fn new(options: GameOptions, names: Vec<String>) -> Result<PassAndPlay, GameError> {
+        let mut state = GameState::with_options(options);
+        if names.len() > state.max_players() {
+            return Err(GameError::InvalidPlayerCount { players: names.len() });
+        }
+        for name in names {
+            let mut conn = PlayerConnection::new(Arc::new(NullSink));
+            conn.name = Some(name);
+            state.add_player(Uuid::new_v4(), conn);
+        }
+        let mut game = PassAndPlay { state, holder: None, unseen_roles: vec![] };
+        let host = game.state.host().ok_or(GameError::InvalidPlayerCount { players: 0 })?;
+        game.holder = Some(host);
+        game.apply(Action::Start)?;
+        game.holder = None;
+        Ok(game)
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    /// The player holding the device, if it is not lying face down between turns.
+    pub fn holder(&self) -> Option<Uuid> {
+        self.holder
+    }
+
+    /// Who the device should be passed to next, once the current holder has put it down.
+    pub fn next(&self) -> Option<Uuid> {
+        if self.holder.is_some() {
+            return None
+        }
+        if let Some(player) = self.unseen_roles.first() {
+            return Some(*player)
+        }
+        match self.state.turn_phase() {
+            TurnPhase::Lobby | TurnPhase::Ended { .. } => self.state.host(),
+            _ => self.state.awaiting().first().copied()
+        }
+    }
+
+    /// Give the device to a player, who then sees everything their seat can.
+    pub fn hand_to(&mut self, player: Uuid) -> Result<(), GameError> {
+        if self.next() != Some(player) {
+            return Err(GameError::NotHoldingDevice);
+        }
+        self.holder = Some(player);
+        Ok(())
+    }
+
+    /// Put the device down, hiding the holder's view until it is handed to the next player.
+    pub fn hide(&mut self) {
+        if let Some(holder) = self.holder.take() {
+            self.unseen_roles.retain(|player| *player != holder);
+        }
+    }
+
+    /// Take an action as the player holding the device.
+    /// The device stays with them so they can see what it led to, such as the result of an investigation, until they put it down.
+    pub fn apply(&mut self, action: Action) -> Result<Vec<GameEvent>, GameError> {
+        let holder = self.holder.ok_or(GameError::NotHoldingDevice)?;
+        let events = self.state.apply(holder, action)?;
+        if action == Action::Start {
+            self.unseen_roles = self.state.living_players().to_vec();
+        }
+        Ok(events)
+    }
+
+    /// The game as the holder sees it, or as a spectator would while nobody holds the device.
+    pub fn view(&self) -> GameStatePlayerView<'_> {
+        GameStatePlayerView { player: self.holder.unwrap_or_else(Uuid::nil), state: &self.state }
+    }
+}
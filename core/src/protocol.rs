@@ -0,0 +1,609 @@
+use std::{collections::{BTreeMap, HashMap, HashSet, LinkedList, VecDeque}, sync::{Arc, mpsc}, time::{Duration, SystemTime}};
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+use crate::{achievements::Achievement, chat::ChatSegment, error::GameError, events::GameEvent, lobby_vote::Motion, game_state::{CardColor, ChatLine, GameDebug, GameExport, GameOptions, GamePreset, PlayerType, Scoreboard, TimelineEntry, VersionedPlayerView, self}, messages::Language, rules::{RevealStep, Rules}, tutorial::TutorialTopic};
+
+pub type ConnectionState = HashMap<Uuid, PlayerConnection>;
+
+/// Where the messages for a connection are delivered, such as a websocket.
+/// The engine only hands over serialized messages, so it does not depend on any particular transport.
+pub trait MessageSink: Send + Sync {
+    /// Deliver a serialized message, failing if the other end has gone away.
+    fn send(&self, message: String) -> Result<(), String>;
+}
+
+pub type Sink = Arc<dyn MessageSink>;
+
+impl MessageSink for mpsc::Sender<String> {
+    fn send(&self, message: String) -> Result<(), String> {
+        mpsc::Sender::send(self, message).map_err(|e| e.to_string())
+    }
+}
+
+/// Any function can take a seat's messages, such as one that posts them to a chat.
+impl<F: Fn(String) -> Result<(), String> + Send + Sync> MessageSink for F {
+    fn send(&self, message: String) -> Result<(), String> {
+        self(message)
+    }
+}
+
+/// Drops every message, for seats that nobody is listening to or that are told about the game some other way.
+pub struct NullSink;
+
+impl MessageSink for NullSink {
+    fn send(&self, _message: String) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Most messages held for a player while they have nowhere to receive them. Older messages are dropped first.
+const MAX_MISSED_MESSAGES: usize = 256;
+
+/// Delivers a seat's messages to whichever transport the player is using at the moment.
+/// The transport can be swapped mid-game, and messages that could not be delivered in between are replayed to the next one.
+/// Messages can also be held back for a while, so that spectators of a streamed game see it on a delay.
+pub struct Relay {
+    state: Mutex<RelayState>,
+}
+
+struct RelayState {
+    sink: Option<Sink>,
+    missed: VecDeque<String>,
+    delay: Option<Duration>,
+    /// Messages waiting out the delay, with the time each one is due.
+    delayed: VecDeque<(SystemTime, String)>,
+}
+
+impl RelayState {
+    fn deliver(&mut self, message: String) -> Result<(), String> {
+        let result = match &self.sink {
+            Some(sink) => sink.send(message.clone()),
+            None => Err(String::new())
+        };
+        if let Err(e) = result {
+            if self.missed.len() >= MAX_MISSED_MESSAGES {
+                self.missed.pop_front();
+            }
+            self.missed.push_back(message);
+            // nothing is wrong when the player is between transports, so only report failures of an attached sink
+            if self.sink.is_some() {
+                return Err(e)
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Relay {
+    pub fn new(sink: Sink) -> Relay {
+        Relay { state: Mutex::new(RelayState { sink: Some(sink), missed: VecDeque::new(), delay: None, delayed: VecDeque::new() }) }
+    }
+
+    /// A relay with no transport yet, which holds messages until one is attached.
+    pub fn detached() -> Relay {
+        Relay { state: Mutex::new(RelayState { sink: None, missed: VecDeque::new(), delay: None, delayed: VecDeque::new() }) }
+    }
+
+    /// Deliver messages to another transport from now on, starting with any the player missed.
+    pub fn attach(&self, sink: Sink) {
+        let mut state = self.state.lock();
+        while let Some(message) = state.missed.pop_front() {
+            if let Err(message) = sink.send(message.clone()).map_err(|_| message) {
+                state.missed.push_front(message);
+                break
+            }
+        }
+        state.sink = Some(sink);
+    }
+
+    /// Stop delivering to the current transport, holding messages until another is attached.
+    pub fn detach(&self) {
+        self.state.lock().sink = None;
+    }
+
+    /// Whether messages are currently delivered to this sink.
+    pub fn is_attached(&self, sink: &Sink) -> bool {
+        self.state.lock().sink.as_ref().is_some_and(|current| Arc::ptr_eq(current, sink))
+    }
+
+    pub fn current(&self) -> Option<Sink> {
+        self.state.lock().sink.clone()
+    }
+
+    /// Hold every message back for this long, or deliver them right away with no delay, including any still being held.
+    pub fn set_delay(&self, delay: Option<Duration>) {
+        let mut state = self.state.lock();
+        state.delay = delay;
+        if delay.is_none() {
+            while let Some((_, message)) = state.delayed.pop_front() {
+                let _ = state.deliver(message);
+            }
+        }
+    }
+
+    /// Deliver the held back messages that have waited out the delay.
+    pub fn flush_delayed(&self, now: SystemTime) {
+        let mut state = self.state.lock();
+        while state.delayed.front().is_some_and(|(due, _)| *due <= now) {
+            let (_, message) = state.delayed.pop_front().unwrap();
+            if let Err(e) = state.deliver(message) {
+                eprintln!("error sending delayed message: {}", e);
+            }
+        }
+    }
+}
+
+impl MessageSink for Relay {
+    fn send(&self, message: String) -> Result<(), String> {
+        let mut state = self.state.lock();
+        if let Some(delay) = state.delay {
+            state.delayed.push_back((game_state::now() + delay, message));
+            return Ok(())
+        }
+        state.deliver(message)
+    }
+}
+
+/// Groups of server messages that a connection can choose to receive.
+/// Alerts and identifiers are always delivered.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum Topic {
+    /// Chat messages from players and the game.
+    Chat,
+    /// The full game state from the perspective of the player.
+    GameState,
+    /// Policy counts, the election tracker, and the turn phase only.
+    Scoreboard,
+    /// Hints about what just happened, such as which card was drawn, so clients can animate it.
+    Events,
+}
+
+/// The topics a connection receives until it subscribes to something else.
+pub const DEFAULT_TOPICS: &[Topic] = &[Topic::Chat, Topic::GameState, Topic::Events];
+
+/// Messages that change the game accept an optional `request_id`.
+/// Sending the same id again returns the original result instead of applying the action twice.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum ClientProtocol {
+    /// Host a new game. Naming a preset saved with the player secret uses its options instead of `options`.
+    /// Servers that ask hosts to prove they are not bots need `captcha`, which is either a solved proof of work as `challenge:nonce` or an hCaptcha token.
+    HostGame { nickname: String, #[serde(default)] options: GameOptions, avatar: Option<String>, color: Option<String>, player_secret: Option<Uuid>, #[serde(default)] preset: Option<String>, #[serde(default)] captcha: Option<String> },
+    /// Ask what has to be done before hosting a game.
+    GetHostChallenge,
+    HostPractice { nickname: String },
+    /// Ask for a random nickname, in the theme of the game given and unlike anyone's already in it.
+    SuggestNickname { #[serde(default)] game_id: Option<Uuid> },
+    /// Join a game. A blank nickname is replaced with a suggested one.
+    JoinGame { id: Uuid, nickname: String, player_id: Option<Uuid>, player_secret: Option<Uuid>, resume_token: Option<String>, avatar: Option<String>, color: Option<String> },
+    /// A chat message, with an image from `POST /upload` attached if a token is given.
+    SendChat { message: String, #[serde(default)] attachment: Option<Uuid> },
+    StartGame { request_id: Option<String> },
+    /// Call a vote among the players in the lobby, such as to remove a disruptive player or host.
+    CallLobbyVote { motion: Motion, request_id: Option<String> },
+    CastLobbyVote { approve: bool, request_id: Option<String> },
+    /// Ask to undo the last action of a game in progress, as the host. The players it involved confirm with `CastLobbyVote`.
+    UndoLastAction { request_id: Option<String> },
+    /// Count down to the start of the game as the host, for up to 60 seconds. Nobody new can join while it runs, and the game starts when it reaches zero.
+    StartCountdown { seconds: u64, request_id: Option<String> },
+    CancelCountdown { request_id: Option<String> },
+    /// Say whether the player is ready for the game to start, while in the lobby.
+    SetReady { ready: bool, request_id: Option<String> },
+    /// Say that the player has seen their role, in games that hold the first election until everyone has.
+    AckRole { request_id: Option<String> },
+    Rematch { request_id: Option<String> },
+    ChooseChancellor { player: Uuid, request_id: Option<String> },
+    /// Agree as president or chancellor to end the discussion of a nomination and open the vote.
+    CallVote { request_id: Option<String> },
+    VoteChancellor { vote: bool, request_id: Option<String> },
+    /// Discard a policy as president, or enact one as chancellor, by its place in the `cards` of the player's view.
+    PickCard { index: usize, request_id: Option<String> },
+    /// Ask the president to veto the policies the chancellor was passed, as chancellor.
+    RequestVeto { request_id: Option<String> },
+    /// Accept or decline the chancellor's request for a veto, as president.
+    RespondVeto { accept: bool, request_id: Option<String> },
+    /// Declare the policies the player was dealt as president or chancellor of the government that just enacted a policy.
+    Claim { cards: Vec<CardColor>, request_id: Option<String> },
+    PresidentialPower { player: Option<Uuid>, request_id: Option<String> },
+    GetChatLog,
+    /// Ask for the rules that apply to the current game.
+    GetRules,
+    /// Ask for the timeline of events in the current game.
+    GetTimeline,
+    Leave,
+    RotateSecret,
+    RevokeSecret { player: Uuid, request_id: Option<String> },
+    /// Send an action along with the player secret or a resume token for the seat the connection holds, which servers can require so only the seat's owner can act for it.
+    Authenticated { #[serde(default)] player_secret: Option<Uuid>, #[serde(default)] resume_token: Option<String>, message: Box<ClientProtocol> },
+    Subscribe { topics: Vec<Topic> },
+    /// Look up the game that the player with this secret is currently in.
+    WhereAmI { player_secret: Uuid },
+    /// Choose the language of the messages sent to the player alone, or go back to the game's language with none.
+    SetLanguage { language: Option<Language> },
+    /// Ask for a version of the player view sent in `GameState` messages. The server answers with `ViewVersion`, naming the version it will send.
+    SetViewVersion { version: u32 },
+    /// Get email about the current game, such as turn reminders and the result, or stop getting it with no address.
+    SetEmail { email: Option<String> },
+    /// Invite someone to the current game by email.
+    InviteByEmail { email: String },
+    /// Report another player in the current game to the server admins.
+    ReportPlayer { player: Uuid, reason: String },
+    /// Say how a game that has just ended went: how fun it was from 1 to 5, whether the rules were unclear, and any players to report.
+    SubmitFeedback { fun: Option<u8>, #[serde(default)] rules_unclear: bool, #[serde(default)] comment: Option<String>, #[serde(default)] reports: Vec<FeedbackReport> },
+    /// Save game options under a name for the player with this secret, replacing any preset with the same name.
+    SavePreset { player_secret: Uuid, name: String, options: GameOptions },
+    DeletePreset { player_secret: Uuid, name: String },
+    ListPresets { player_secret: Uuid },
+    /// Ask to be friends with another player, or accept their request. Listing friends gives a player their own friend id to share.
+    AddFriend { player_secret: Uuid, friend_id: Uuid },
+    /// End a friendship or turn down a friend request.
+    RemoveFriend { player_secret: Uuid, friend_id: Uuid },
+    /// List friends, requests, and waiting invitations. Invitations that arrive later are sent to this connection.
+    ListFriends { player_secret: Uuid },
+    /// Invite a friend to a game that the sender is in.
+    InviteFriend { friend_id: Uuid, game_id: Uuid },
+    RespondToInvite { player_secret: Uuid, invite_id: Uuid, accept: bool },
+    /// Stop seeing a player's chat and stop being seated with them if they block back, given either their friend id or their player id in the sender's game.
+    BlockPlayer { player_secret: Uuid, #[serde(default)] friend_id: Option<Uuid>, #[serde(default)] player: Option<Uuid> },
+    UnblockPlayer { player_secret: Uuid, friend_id: Uuid },
+    ListBlocked { player_secret: Uuid },
+    /// Save a game so an admin can import it on another server, or on this one after a restart.
+    ExportGame { admin_token: String, game_id: Uuid },
+    /// Dump everything about a game for debugging it, such as its timers and who is connected, optionally hiding the order of the deck.
+    DebugGame { admin_token: String, game_id: Uuid, #[serde(default)] redact_deck: bool },
+    /// Recreate an exported game under its old id. Players rejoin it with their player id and secret.
+    ImportGame { admin_token: String, game_id: Uuid, game: Box<GameExport> },
+    /// Set the message of the day, which is sent to everyone in a lobby and to every new connection, or clear it with no message.
+    /// Naming a community sets its own message, which only its players see alongside the server's.
+    SetMotd { admin_token: String, message: Option<String>, #[serde(default, skip_serializing_if = "Option::is_none")] community: Option<String> },
+    /// Send a message to everyone in one game, such as a notice about maintenance.
+    Announce { admin_token: String, game_id: Uuid, message: String },
+    /// Keep a player, address range, or device out of the server until `expires_at`, in milliseconds since the epoch, or for good.
+    /// Naming a community only keeps them out of its games.
+    Ban { admin_token: String, target: BanTarget, reason: String, expires_at: Option<u64>, #[serde(default, skip_serializing_if = "Option::is_none")] community: Option<String> },
+    Unban { admin_token: String, ban_id: Uuid },
+    /// Note what a banned player said when they appealed, or clear the note.
+    SetBanAppeal { admin_token: String, ban_id: Uuid, note: Option<String> },
+    ListBans { admin_token: String },
+    /// Help run a community as one of its owners or moderators, known by the player secret.
+    Moderate { player_secret: Uuid, community: String, action: ModeratorAction },
+}
+
+impl ClientProtocol {
+    /// Whether the message acts for the player's seat in a game, as opposed to joining one, asking about it, or carrying its own credentials.
+    pub fn is_action(&self) -> bool {
+        matches!(self, ClientProtocol::SendChat { .. } | ClientProtocol::StartGame { .. } | ClientProtocol::StartCountdown { .. } | ClientProtocol::CancelCountdown { .. } | ClientProtocol::CallLobbyVote { .. } | ClientProtocol::CastLobbyVote { .. }
+            | ClientProtocol::UndoLastAction { .. } | ClientProtocol::SetReady { .. } | ClientProtocol::AckRole { .. } | ClientProtocol::Rematch { .. } | ClientProtocol::ChooseChancellor { .. }
+            | ClientProtocol::CallVote { .. } | ClientProtocol::VoteChancellor { .. } | ClientProtocol::PickCard { .. } | ClientProtocol::RequestVeto { .. }
+            | ClientProtocol::RespondVeto { .. } | ClientProtocol::Claim { .. } | ClientProtocol::PresidentialPower { .. } | ClientProtocol::Leave
+            | ClientProtocol::RotateSecret | ClientProtocol::RevokeSecret { .. } | ClientProtocol::SetEmail { .. } | ClientProtocol::InviteByEmail { .. } | ClientProtocol::ReportPlayer { .. }
+            | ClientProtocol::SubmitFeedback { .. })
+    }
+
+    /// The name of the message type, for logging a message without its contents, which can carry secrets.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ClientProtocol::HostGame { .. } => "HostGame",
+            ClientProtocol::GetHostChallenge => "GetHostChallenge",
+            ClientProtocol::HostPractice { .. } => "HostPractice",
+            ClientProtocol::SuggestNickname { .. } => "SuggestNickname",
+            ClientProtocol::JoinGame { .. } => "JoinGame",
+            ClientProtocol::SendChat { .. } => "SendChat",
+            ClientProtocol::StartGame { .. } => "StartGame",
+            ClientProtocol::CallLobbyVote { .. } => "CallLobbyVote",
+            ClientProtocol::CastLobbyVote { .. } => "CastLobbyVote",
+            ClientProtocol::UndoLastAction { .. } => "UndoLastAction",
+            ClientProtocol::StartCountdown { .. } => "StartCountdown",
+            ClientProtocol::CancelCountdown { .. } => "CancelCountdown",
+            ClientProtocol::SetReady { .. } => "SetReady",
+            ClientProtocol::AckRole { .. } => "AckRole",
+            ClientProtocol::Rematch { .. } => "Rematch",
+            ClientProtocol::ChooseChancellor { .. } => "ChooseChancellor",
+            ClientProtocol::CallVote { .. } => "CallVote",
+            ClientProtocol::VoteChancellor { .. } => "VoteChancellor",
+            ClientProtocol::PickCard { .. } => "PickCard",
+            ClientProtocol::RequestVeto { .. } => "RequestVeto",
+            ClientProtocol::RespondVeto { .. } => "RespondVeto",
+            ClientProtocol::Claim { .. } => "Claim",
+            ClientProtocol::PresidentialPower { .. } => "PresidentialPower",
+            ClientProtocol::GetChatLog => "GetChatLog",
+            ClientProtocol::GetRules => "GetRules",
+            ClientProtocol::GetTimeline => "GetTimeline",
+            ClientProtocol::Leave => "Leave",
+            ClientProtocol::RotateSecret => "RotateSecret",
+            ClientProtocol::RevokeSecret { .. } => "RevokeSecret",
+            ClientProtocol::Authenticated { .. } => "Authenticated",
+            ClientProtocol::Subscribe { .. } => "Subscribe",
+            ClientProtocol::WhereAmI { .. } => "WhereAmI",
+            ClientProtocol::SetLanguage { .. } => "SetLanguage",
+            ClientProtocol::SetViewVersion { .. } => "SetViewVersion",
+            ClientProtocol::SetEmail { .. } => "SetEmail",
+            ClientProtocol::InviteByEmail { .. } => "InviteByEmail",
+            ClientProtocol::ReportPlayer { .. } => "ReportPlayer",
+            ClientProtocol::SubmitFeedback { .. } => "SubmitFeedback",
+            ClientProtocol::SavePreset { .. } => "SavePreset",
+            ClientProtocol::DeletePreset { .. } => "DeletePreset",
+            ClientProtocol::ListPresets { .. } => "ListPresets",
+            ClientProtocol::AddFriend { .. } => "AddFriend",
+            ClientProtocol::RemoveFriend { .. } => "RemoveFriend",
+            ClientProtocol::ListFriends { .. } => "ListFriends",
+            ClientProtocol::InviteFriend { .. } => "InviteFriend",
+            ClientProtocol::RespondToInvite { .. } => "RespondToInvite",
+            ClientProtocol::BlockPlayer { .. } => "BlockPlayer",
+            ClientProtocol::UnblockPlayer { .. } => "UnblockPlayer",
+            ClientProtocol::ListBlocked { .. } => "ListBlocked",
+            ClientProtocol::ExportGame { .. } => "ExportGame",
+            ClientProtocol::DebugGame { .. } => "DebugGame",
+            ClientProtocol::ImportGame { .. } => "ImportGame",
+            ClientProtocol::SetMotd { .. } => "SetMotd",
+            ClientProtocol::Announce { .. } => "Announce",
+            ClientProtocol::Ban { .. } => "Ban",
+            ClientProtocol::Unban { .. } => "Unban",
+            ClientProtocol::SetBanAppeal { .. } => "SetBanAppeal",
+            ClientProtocol::ListBans { .. } => "ListBans",
+            ClientProtocol::Moderate { .. } => "Moderate"
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum ServerProtocol<'a> {
+    SetIdentifiers { player_id: Uuid, game_id: Uuid, secret: Uuid },
+    ResumeToken { token: String, expires_at: u64 },
+    CurrentGame { game_id: Option<Uuid>, player_id: Option<Uuid> },
+    Alert { message: String },
+    /// A game action was rejected. The error's code and context are flattened into the message.
+    Error { #[serde(flatten)] error: &'a GameError, message: String, #[serde(skip_serializing_if = "Option::is_none")] request_id: Option<&'a str> },
+    /// A game action sent with a request id was applied.
+    Ack { request_id: &'a str },
+    /// The server is at capacity and the request was refused. Clients should wait `retry_after` seconds, or `retry_after_ms`, which is spread out between clients.
+    ServerBusy { retry_after: u64, retry_after_ms: u64 },
+    /// The player has rejoined their seat too many times in a row, and should wait `retry_after_ms` before trying again.
+    ReconnectThrottled { retry_after_ms: u64 },
+    /// The connection is sending messages too quickly, and the last one was dropped. Nothing more will be accepted for `retry_after_ms`.
+    MessagesThrottled { retry_after_ms: u64 },
+    /// The version of the player view the connection will be sent from now on, and the newest the server has.
+    ViewVersion { version: u32, latest: u32 },
+    ReceiveChat { id: Option<Uuid>, message: String, segments: &'a [ChatSegment], attachment: Option<Uuid> },
+    GameState { state: VersionedPlayerView<'a> },
+    Scoreboard { state: Scoreboard<'a> },
+    ChatLog { log: &'a LinkedList<ChatLine> },
+    Rules { rules: Rules },
+    TutorialStep { topic: TutorialTopic, title: &'a str, text: String },
+    GameEvent { event: GameEvent },
+    /// A government failed. Sent separately from other events so clients can warn players when chaos is close.
+    ElectionTrackerAdvanced { value: u8, chaos_imminent: bool },
+    /// The host left or lost their connection, and another player has taken over as host.
+    HostChanged { host: Uuid },
+    /// The game started, with the order the presidency passes in and the steps of the reveal, so clients can lead the table through it.
+    TurnOrderAssigned { turn_order: &'a [Uuid], president: Option<Uuid>, reveal: Vec<RevealStep> },
+    /// The player's own role, sent to them alone as the game starts, along with the roles of the players they know from the start.
+    RoleAssigned { role: PlayerType, known: BTreeMap<Uuid, PlayerType> },
+    /// The host started a countdown, and the game starts in this many seconds unless it is cancelled. Nobody new can join in the meantime.
+    Countdown { seconds: u64 },
+    /// The countdown to the start of the game was cancelled, or could not start the game.
+    CountdownCancelled,
+    /// Sent to the president when the chancellor asks for a veto, which they answer with `RespondVeto`.
+    VetoRequested { chancellor: Uuid },
+    /// Every living player has voted on a government. Each player's vote is left out in games with anonymous votes.
+    ElectionResult { votes: Option<BTreeMap<Uuid, bool>>, ayes: usize, nays: usize, passed: bool },
+    Timeline { events: &'a [TimelineEntry] },
+    /// The presets saved with the player's secret.
+    Presets { presets: &'a [GamePreset] },
+    /// The player's own friend id, and their friends, incoming friend requests, and waiting invitations.
+    Friends { friend_id: Uuid, friends: &'a [Uuid], requests: &'a [Uuid], invites: &'a [FriendInvite] },
+    /// The friend ids of the players the player has blocked.
+    Blocked { blocked: &'a [Uuid] },
+    /// A game saved for an admin with `ExportGame`.
+    GameExport { game_id: Uuid, game: &'a GameExport },
+    /// A game's state dumped for an admin with `DebugGame`.
+    GameDebug { game_id: Uuid, debug: &'a GameDebug },
+    /// A friend invited the player to a game.
+    InviteReceived { invite: &'a FriendInvite },
+    /// The message of the day, sent when the connection opens and to lobbies when an admin changes it.
+    Motd { message: &'a str },
+    /// A message from the admins to the players of this game.
+    Announcement { message: &'a str },
+    /// What has to be done before hosting a game.
+    HostChallenge { challenge: HostChallenge },
+    /// A nickname the player could use, sent when asked for and when the one they gave was turned down.
+    NicknameSuggestion { nickname: String },
+    /// The bans that have not expired, for an admin or a community's moderators.
+    Bans { bans: &'a [Ban] },
+    /// The friend ids of a community's owners and moderators.
+    Moderators { community: &'a str, owners: &'a [Uuid], moderators: &'a [Uuid] },
+    /// The player cannot join or host games while the ban lasts.
+    Banned { reason: &'a str, expires_at: Option<u64> },
+    /// Sent to each player when a game ends, with the achievements they earned for the first time and their wins so far this season.
+    Achievements { earned: &'a [Achievement], season: u64, season_wins: u32 },
+}
+
+/// What a server asks of hosts before creating a game for them, so bots cannot fill it with lobbies.
+#[derive(Clone, Serialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum HostChallenge {
+    None,
+    /// Find a nonce for which the SHA-256 hash of `challenge:nonce` starts with `difficulty` zero bits, and host with `challenge:nonce`.
+    ProofOfWork { challenge: String, difficulty: u32 },
+    /// Solve an hCaptcha with this site key, and host with the token it gives.
+    HCaptcha { site_key: String },
+}
+
+/// What a community's moderators can do within it. Only its owners can choose who its moderators are.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum ModeratorAction {
+    /// Remove a player from one of the community's games, keeping them from joining it again. Seated players can only be removed before the game starts.
+    Kick { game_id: Uuid, player: Uuid },
+    /// Keep a player, address range, or device out of the community's games until `expires_at`, or for good.
+    Ban { target: BanTarget, reason: String, expires_at: Option<u64> },
+    Unban { ban_id: Uuid },
+    ListBans,
+    /// Set the community's message of the day, or clear it with no message.
+    SetMotd { message: Option<String> },
+    /// Send a message to everyone in one of the community's games.
+    Announce { game_id: Uuid, message: String },
+    AddModerator { friend_id: Uuid },
+    RemoveModerator { friend_id: Uuid },
+    ListModerators,
+}
+
+/// Who a ban applies to.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum BanTarget {
+    /// The player whose secret has this friend id.
+    Account { friend_id: Uuid },
+    /// Addresses in a range such as `203.0.113.0/24`, or a single address.
+    Address { range: String },
+    /// Connections from the same address and browser, as recorded in exported games.
+    Fingerprint { fingerprint: String },
+}
+
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Ban {
+    pub id: Uuid,
+    pub target: BanTarget,
+    pub reason: String,
+    /// What the player said when they appealed, noted by an admin.
+    pub appeal_note: Option<String>,
+    /// When the ban was made, in milliseconds since the epoch.
+    pub created_at: u64,
+    /// When the ban ends, in milliseconds since the epoch, or none if it lasts until it is lifted.
+    pub expires_at: Option<u64>,
+    /// The community the ban keeps the player out of, or none for the whole server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub community: Option<String>,
+}
+
+/// A player reported along with feedback on a game.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct FeedbackReport {
+    pub player: Uuid,
+    pub reason: String,
+}
+
+/// An invitation from a friend to join their game.
+#[derive(Clone, Serialize, JsonSchema)]
+pub struct FriendInvite {
+    pub id: Uuid,
+    /// The friend id of the player who sent it.
+    pub from: Uuid,
+    /// The sender's name in the game.
+    pub name: String,
+    pub game_id: Uuid,
+}
+
+pub struct PlayerConnection {
+    pub name: Option<String>,
+    pub secret: Option<Uuid>,
+    pub avatar: Option<String>,
+    pub color: Option<String>,
+    pub topics: Vec<Topic>,
+    /// Bots are driven by the server and have no socket to send messages to.
+    pub is_bot: bool,
+    /// An opaque id for the device the player joined from, used to warn the host about the same device taking two seats.
+    pub fingerprint: Option<String>,
+    /// The language the player asked for their own messages in, instead of the game's.
+    pub language: Option<Language>,
+    /// The version of the player view the player's client asked for.
+    pub view_version: u32,
+    /// Players in the game whose chat is not delivered to this player, because this player blocked them.
+    pub muted: HashSet<Uuid>,
+    pub tx: Arc<Relay>,
+    pub connected: bool,
+    /// When this connection to the seat was opened, so the player who has been around longest can take over as host.
+    pub connected_since: SystemTime,
+}
+
+/// Avatars are either a short emoji or an https link to an image.
+fn validate_avatar(avatar: &str) -> Result<(), &'static str> {
+    if avatar.starts_with("https://") {
+        let path = avatar.split(['?', '#']).next().unwrap_or_default().to_lowercase();
+        if avatar.len() > 300 || avatar.chars().any(|c| c.is_whitespace() || c.is_control() || "\"'<>\\".contains(c)) {
+            return Err("Your avatar link is not a valid url.");
+        }
+        if ![".png", ".jpg", ".jpeg", ".gif", ".webp"].iter().any(|ext| path.ends_with(ext)) {
+            return Err("Your avatar link must point to a png, jpg, gif, or webp image.");
+        }
+        return Ok(())
+    }
+    let length = avatar.chars().count();
+    if length == 0 || length > 8 || avatar.chars().any(|c| c.is_ascii() || c.is_whitespace() || c.is_control()) {
+        return Err("Your avatar must be a single emoji or an https image link.");
+    }
+    Ok(())
+}
+
+/// Colors must be given as a hex code such as `#1e90ff`.
+fn validate_color(color: &str) -> Result<(), &'static str> {
+    let digits = color.strip_prefix('#').unwrap_or_default();
+    if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("Your color must be a hex color code.");
+    }
+    Ok(())
+}
+
+impl PlayerConnection {
+    pub fn new(ptx: Sink) -> PlayerConnection {
+        PlayerConnection { tx: Arc::new(Relay::new(ptx)), connected: true, connected_since: game_state::now(), name: None, secret: None, avatar: None, color: None, topics: DEFAULT_TOPICS.to_vec(), is_bot: false, fingerprint: None, language: None, view_version: 1, muted: HashSet::new() }
+    }
+
+    /// Create a seat for a player who has no transport yet, such as one who will pick up an asynchronous game later.
+    /// Their messages are held until a transport is attached to `tx`, and they count as away until then.
+    pub fn detached() -> PlayerConnection {
+        PlayerConnection { tx: Arc::new(Relay::detached()), connected: false, ..PlayerConnection::new(Arc::new(NullSink)) }
+    }
+
+    /// Create a seat for a server controlled player.
+    pub fn bot(name: String) -> PlayerConnection {
+        PlayerConnection { tx: Arc::new(Relay::new(Arc::new(NullSink))), connected: false, connected_since: game_state::now(), name: Some(name), secret: None, avatar: Some("\u{1F916}".into()), color: None, topics: vec![], is_bot: true, fingerprint: None, language: None, view_version: 1, muted: HashSet::new() }
+    }
+
+    pub fn is_subscribed(&self, topic: Topic) -> bool {
+        self.topics.contains(&topic)
+    }
+
+    /// Validate and set the cosmetic profile shown next to this player's name.
+    pub fn set_profile(&mut self, avatar: Option<String>, color: Option<String>) -> Result<(), &'static str> {
+        if let Some(avatar) = &avatar {
+            validate_avatar(avatar)?;
+        }
+        if let Some(color) = &color {
+            validate_color(color)?;
+        }
+        self.avatar = avatar;
+        self.color = color.map(|c| c.to_lowercase());
+        Ok(())
+    }
+
+    pub fn send(&self, message: &ServerProtocol) {
+        if self.is_bot {
+            return
+        }
+        if let Err(e) = self.tx.send(serde_json::to_string(message).unwrap()) {
+            eprintln!("error sending message: {}", e);
+        }
+    }
+}
+
+/// Send a message to every connection subscribed to the topic.
+pub fn send_to_all(conn: &ConnectionState, topic: Topic, message: &ServerProtocol) {
+    send_to_matching(conn, topic, message, |_| true)
+}
+
+/// Send a message to the connections subscribed to the topic that `keep` accepts.
+pub fn send_to_matching(conn: &ConnectionState, topic: Topic, message: &ServerProtocol, keep: impl Fn(&PlayerConnection) -> bool) {
+    let serialized_msg = serde_json::to_string(message).unwrap();
+
+    conn.values().filter(|conn| conn.is_subscribed(topic) && keep(conn)).for_each(|conn| {
+        if let Err(e) = conn.tx.send(serialized_msg.clone()) {
+            eprintln!("error sending all message: {}", e);
+        }
+    });
+}
\ No newline at end of file
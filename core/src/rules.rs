@@ -0,0 +1,120 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::game_state::PresidentialPower;
+
+pub const MIN_PLAYERS: usize = 5;
+pub const MAX_PLAYERS: usize = 10;
+
+/// The policy cards in a full deck.
+pub const LIBERAL_CARDS: usize = 6;
+pub const FACIST_CARDS: usize = 11;
+
+/// Fascist policies that must be enacted before electing Hitler as chancellor wins, unless the host picks otherwise.
+pub const HITLER_CHANCELLOR_POLICIES: u8 = 3;
+
+/// Rules that depend on the size of the table.
+/// The game logic reads these, and the same values are sent to clients that ask for the rules.
+#[derive(Serialize, JsonSchema)]
+pub struct Rules {
+    pub players: usize,
+    pub liberals: usize,
+    /// Number of fascists, not counting Hitler.
+    pub facists: usize,
+    /// The power granted by each fascist policy slot, in the order they are filled.
+    pub power_track: Vec<Option<PresidentialPower>>,
+    pub liberal_policies_to_win: u8,
+    pub facist_policies_to_win: u8,
+    /// Fascist policies that must be enacted before electing Hitler as chancellor wins the game.
+    pub hitler_chancellor_policies: u8,
+    /// Failed governments in a row before the top policy is enacted.
+    pub election_tracker_limit: u8,
+    /// Fascist policies that must be enacted before the government may veto.
+    pub veto_policies: u8,
+    /// Whether Hitler is told who the other fascists are.
+    pub hitler_knows_facists: bool,
+    pub eligibility: Eligibility,
+}
+
+/// Who may not be nominated as chancellor.
+#[derive(Serialize, JsonSchema)]
+pub struct Eligibility {
+    pub last_chancellor_ineligible: bool,
+    /// The last president may be nominated again once only this many players are left alive.
+    pub last_president_eligible_at: usize,
+}
+
+/// A step of the reveal at the start of the game, when the fascists find out who each other are while everyone else has their eyes closed.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, JsonSchema)]
+pub enum RevealStep {
+    EveryoneCloseEyes,
+    FacistsOpenEyes,
+    /// Hitler opens their eyes with the fascists, in games where Hitler knows who they are.
+    HitlerOpensEyes,
+    /// Hitler keeps their eyes closed and raises a thumb so the fascists can see who they are.
+    HitlerRaisesThumb,
+    /// The fascists, and Hitler if their eyes are open, close their eyes.
+    FacistsCloseEyes,
+    HitlerLowersThumb,
+    EveryoneOpenEyes,
+}
+
+impl Rules {
+    pub fn new(players: usize) -> Rules {
+        let facists = num_facists(players);
+        Rules {
+            players,
+            liberals: players.saturating_sub(facists + 1),
+            facists,
+            power_track: (1..=5).map(|policies| presidential_power(players, policies)).collect(),
+            liberal_policies_to_win: 5,
+            facist_policies_to_win: 6,
+            hitler_chancellor_policies: HITLER_CHANCELLOR_POLICIES,
+            election_tracker_limit: 3,
+            veto_policies: 5,
+            hitler_knows_facists: players <= 6,
+            eligibility: Eligibility { last_chancellor_ineligible: true, last_president_eligible_at: 5 },
+        }
+    }
+
+    /// The steps of the reveal at the start of the game, in the order they are read out.
+    pub fn reveal(&self) -> Vec<RevealStep> {
+        if self.hitler_knows_facists {
+            vec![RevealStep::EveryoneCloseEyes, RevealStep::FacistsOpenEyes, RevealStep::HitlerOpensEyes, RevealStep::FacistsCloseEyes, RevealStep::EveryoneOpenEyes]
+        }
+        else {
+            vec![RevealStep::EveryoneCloseEyes, RevealStep::FacistsOpenEyes, RevealStep::HitlerRaisesThumb, RevealStep::FacistsCloseEyes, RevealStep::HitlerLowersThumb, RevealStep::EveryoneOpenEyes]
+        }
+    }
+
+    /// Whether the last president is barred from being nominated chancellor with this many players alive.
+    pub fn last_president_ineligible(&self, living_players: usize) -> bool {
+        living_players > self.eligibility.last_president_eligible_at
+    }
+}
+
+/// Number of fascists, not counting Hitler.
+pub fn num_facists(players: usize) -> usize {
+    match players {
+        5 | 6 => 1,
+        7 | 8 => 2,
+        9 | 10 => 3,
+        d if d % 2 == 0 => (d - 1) / 2 - 1,
+        _ => players / 2 - 1
+    }
+}
+
+/// The power granted to the president when the given fascist policy is enacted.
+pub fn presidential_power(players: usize, facist_policies: u8) -> Option<PresidentialPower> {
+    match (players, facist_policies) {
+        // examine top three
+        (5..=6, 3) => Some(PresidentialPower::PolicyPeek),
+        // investigate identity
+        (9..=10, 1..=2) | (7..=8, 2) => Some(PresidentialPower::InvestigateLoyalty),
+        // president picks next candidate
+        (7..=10, 3) => Some(PresidentialPower::CallSpecialElection),
+        // kill a player
+        (_, 4..=5) => Some(PresidentialPower::Execution),
+        _ => None
+    }
+}
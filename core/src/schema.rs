@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, HashMap};
+
+use schemars::{JsonSchema, gen::SchemaGenerator, schema::{RootSchema, Schema}, schema_for};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{claims::Government, game_state::{CardColor, CardFace, GameOptions, GameStatePlayerView, GameStatePlayerViewV2, PlayerType, TimelineEntry, TurnPhase}, lobby_vote::Motion, odds::DeckOdds, protocol::{ClientProtocol, ServerProtocol}, theme::ThemeNames};
+
+/// The game as one player sees it, as sent in `GameState` messages.
+/// The view is built by hand so that each player only sees what they are allowed to, so this spells out its shape.
+/// Unknown fields are refused, so a view that gains a field this does not list fails to deserialize.
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PlayerView {
+    pub liberal_policies: u8,
+    pub facist_policies: u8,
+    pub election_tracker: u8,
+    pub liberal_cards: usize,
+    pub facist_cards: usize,
+    /// How to draw each kind of policy without relying on color, and what the host calls its faction.
+    pub card_faces: Vec<CardFace>,
+    /// What the game calls its factions, roles, and boards, in the player's language.
+    pub theme: ThemeNames,
+    pub host: Option<Uuid>,
+    pub president: Option<Uuid>,
+    pub last_president: Option<Uuid>,
+    pub chancellor: Option<Uuid>,
+    pub last_chancellor: Option<Uuid>,
+    pub turn_phase: TurnPhase,
+    pub turn_order: Vec<Uuid>,
+    /// When the current phase started, in milliseconds since the epoch, for phases with a time limit.
+    pub phase_started_at: Option<u64>,
+    /// When the current phase times out, in milliseconds since the epoch.
+    pub phase_deadline: Option<u64>,
+    pub cards_in_deck: usize,
+    pub cards_in_discard: usize,
+    pub num_facists: usize,
+    pub players: HashMap<Uuid, SeatView>,
+    /// Every government that has enacted a policy this game, with its members' claims.
+    pub governments: Vec<Government>,
+    pub waitlist: Vec<WaitlistView>,
+    /// When a scheduled game opens, in milliseconds since the epoch.
+    pub opens_at: Option<u64>,
+    pub lobby_vote: Option<LobbyVoteView>,
+    /// When the host's countdown to the start of the game runs out, in milliseconds since the epoch.
+    pub countdown_ends_at: Option<u64>,
+    /// Groups of seats taken from the same device, shown to the host in the lobby.
+    pub shared_devices: Option<Vec<Vec<Uuid>>>,
+    /// The player's place on the waitlist, counting from 1.
+    pub waitlist_position: Option<usize>,
+    /// Who in the government has agreed to call the vote early, during a discussion.
+    pub vote_called: Option<Vec<Uuid>>,
+    /// Whether the chancellor has asked for a veto, and whether the president declined it, while the chancellor chooses a policy.
+    pub veto_requested: Option<bool>,
+    pub veto_declined: Option<bool>,
+    /// How many players have voted, while voting.
+    pub votes: Option<usize>,
+    /// The chances for the next policies drawn, in games with the `deck_odds` option.
+    pub deck_odds: Option<DeckOdds>,
+    /// The policies in the player's hand, while they are choosing one.
+    pub cards: Option<Vec<CardColor>>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SeatView {
+    pub name: String,
+    pub avatar: Option<String>,
+    pub color: Option<String>,
+    /// The player's role, if the viewer knows it.
+    pub role: Option<PlayerType>,
+    pub vote: Option<bool>,
+    pub dead: bool,
+    /// Whether the player is ready to start, in the lobby.
+    pub ready: Option<bool>,
+    /// Whether the player has seen their role, while the first election waits on everyone to.
+    pub acknowledged: Option<bool>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WaitlistView {
+    pub id: Uuid,
+    pub name: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LobbyVoteView {
+    pub motion: Motion,
+    pub called_by: Uuid,
+    pub expires_at: u64,
+    pub voters: Vec<Uuid>,
+    pub votes: BTreeMap<Uuid, bool>,
+    /// Votes in favor needed for the motion to pass.
+    pub needed: usize,
+}
+
+impl JsonSchema for GameStatePlayerView<'_> {
+    fn schema_name() -> String {
+        PlayerView::schema_name()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        PlayerView::json_schema(gen)
+    }
+}
+
+/// The second version of the player view, sent to clients that ask for it with `SetViewVersion`.
+/// It has every field of the first version as well as these.
+#[derive(Deserialize, JsonSchema)]
+pub struct PlayerViewV2 {
+    /// The options the game is played with.
+    pub options: GameOptions,
+    /// Everything that has happened in the game so far, as returned by `GetTimeline`.
+    pub events: Vec<TimelineEntry>,
+}
+
+impl JsonSchema for GameStatePlayerViewV2<'_> {
+    fn schema_name() -> String {
+        PlayerViewV2::schema_name()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let mut schema = PlayerViewV2::json_schema(gen).into_object();
+        schema.subschemas().all_of = Some(vec![gen.subschema_for::<GameStatePlayerView>()]);
+        schema.into()
+    }
+}
+
+/// The schema of every message a client can send.
+pub fn client_schema() -> RootSchema {
+    schema_for!(ClientProtocol)
+}
+
+/// The schema of every message the server sends.
+pub fn server_schema() -> RootSchema {
+    schema_for!(ServerProtocol<'static>)
+}
+
+pub fn player_view_schema() -> RootSchema {
+    schema_for!(PlayerView)
+}
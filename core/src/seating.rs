@@ -0,0 +1,56 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Keeps track of who waits for a seat, and the order everyone joined in.
+/// Both the lobby waitlist and rematches fill seats from here, so seating is always first come first served.
+#[derive(Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Seating {
+    join_order: Vec<Uuid>,
+    waitlist: Vec<Uuid>,
+}
+
+impl Seating {
+    /// Record a player joining. Returns true if they take a free seat, or false if they were put on the waitlist.
+    pub fn join(&mut self, player: Uuid, seats_taken: usize, max_players: usize) -> bool {
+        if !self.join_order.contains(&player) {
+            self.join_order.push(player);
+        }
+        if seats_taken < max_players {
+            return true
+        }
+        if !self.waitlist.contains(&player) {
+            self.waitlist.push(player);
+        }
+        false
+    }
+
+    /// Forget a player that has left, whether they were seated or waiting.
+    pub fn leave(&mut self, player: &Uuid) {
+        self.join_order.retain(|p| p != player);
+        self.waitlist.retain(|p| p != player);
+    }
+
+    pub fn is_waiting(&self, player: &Uuid) -> bool {
+        self.waitlist.contains(player)
+    }
+
+    pub fn waitlist(&self) -> &[Uuid] {
+        &self.waitlist
+    }
+
+    /// Take the next player off the waitlist if there is a free seat.
+    pub fn next_seated(&mut self, seats_taken: usize, max_players: usize) -> Option<Uuid> {
+        if seats_taken >= max_players || self.waitlist.is_empty() {
+            return None
+        }
+        Some(self.waitlist.remove(0))
+    }
+
+    /// Seat everyone again for a new round, returning the seated players.
+    /// The first players to have joined get a seat and everyone else waits, regardless of where they sat before.
+    pub fn rollover(&mut self, max_players: usize) -> Vec<Uuid> {
+        self.waitlist = self.join_order.iter().skip(max_players).copied().collect();
+        self.join_order.iter().take(max_players).copied().collect()
+    }
+}
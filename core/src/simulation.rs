@@ -0,0 +1,101 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{events::GameEvent, game_state::{CardColor, GameOptions, GameState}, protocol::PlayerConnection};
+
+/// Rounds of bot moves to allow before giving up on a game.
+const MAX_ROUNDS: usize = 500;
+
+/// Totals from a batch of games played only by bots at one table size, for checking the balance of the rules.
+#[derive(Default, Serialize)]
+pub struct SimulationReport {
+    pub players: usize,
+    pub games: usize,
+    pub liberal_wins: usize,
+    pub facist_wins: usize,
+    /// Games the bots could not finish, which points at a bug in the game or the bots.
+    pub unfinished: usize,
+    pub liberal_policies: usize,
+    pub facist_policies: usize,
+    /// Policies enacted from the top of the deck after failed elections.
+    pub chaos_policies: usize,
+    /// Presidential terms played across all games.
+    pub rounds: usize,
+}
+
+impl SimulationReport {
+    /// Record the outcome of a finished or abandoned game.
+    pub fn add(&mut self, game: &GameState) {
+        let summary = game.summary();
+        self.games += 1;
+        match summary.winner {
+            Some(CardColor::Liberal) => self.liberal_wins += 1,
+            Some(CardColor::Facist) => self.facist_wins += 1,
+            None => self.unfinished += 1
+        }
+        self.liberal_policies += summary.liberal_policies as usize;
+        self.facist_policies += summary.facist_policies as usize;
+        self.chaos_policies += game.timeline().iter().filter(|entry| matches!(entry.event, GameEvent::PolicyEnacted { chaos: true, .. })).count();
+        self.rounds += game.rounds();
+    }
+
+    /// The share of finished games won by the liberals.
+    pub fn liberal_win_rate(&self) -> f64 {
+        let finished = self.liberal_wins + self.facist_wins;
+        if finished == 0 {
+            return 0.0
+        }
+        self.liberal_wins as f64 / finished as f64
+    }
+
+    /// The average number of presidential terms in a game.
+    pub fn average_rounds(&self) -> f64 {
+        if self.games == 0 {
+            return 0.0
+        }
+        self.rounds as f64 / self.games as f64
+    }
+}
+
+/// Runs batches of bot games without any connections. With a seed, every batch plays out the same way.
+pub struct Simulation {
+    rng: StdRng,
+}
+
+impl Simulation {
+    pub fn new(seed: Option<u64>) -> Simulation {
+        Simulation { rng: seed.map(StdRng::seed_from_u64).unwrap_or_else(StdRng::from_entropy) }
+    }
+
+    /// Play one game with a table of bots, returning it once it has ended or the bots stop making progress.
+    pub fn play(&mut self, players: usize) -> GameState {
+        let mut builder = GameState::builder().options(GameOptions { max_players: Some(players), ..GameOptions::default() }).seed(self.rng.gen());
+        let ids: Vec<Uuid> = (0..players).map(|_| Uuid::from_u128(self.rng.gen())).collect();
+        for (i, id) in ids.iter().enumerate() {
+            builder = builder.player(*id, PlayerConnection::bot(format!("bot {}", i + 1)));
+        }
+        let mut state = builder.build();
+        if state.act_as(ids[0]).start().is_err() {
+            return state
+        }
+        for _ in 0..MAX_ROUNDS {
+            if state.winner().is_some() {
+                break
+            }
+            state.run_bots();
+        }
+        state
+    }
+
+    /// Play a number of games at one table size, calling back with each game once it is over.
+    pub fn run(&mut self, players: usize, games: usize, mut on_game: impl FnMut(usize, &GameState)) -> SimulationReport {
+        let mut report = SimulationReport { players, ..SimulationReport::default() };
+        for i in 0..games {
+            let game = self.play(players);
+            report.add(&game);
+            on_game(i, &game);
+        }
+        report
+    }
+}
@@ -0,0 +1,131 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{game_state::{CardColor, CardFace}, messages::Language};
+
+/// Longest name a host can give anything in a theme. Longer names are cut short.
+pub const MAX_THEME_NAME_LENGTH: usize = 20;
+
+/// Themes built into the server, which a host can pick instead of naming everything themselves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ThemePreset {
+    #[default]
+    Classic,
+    /// A starship's crew keeping it running while saboteurs work against them.
+    Starship,
+}
+
+/// What the game calls its factions, roles, and boards, so the same rules can be played as a different game.
+/// Any name given here replaces the one from the preset.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Theme {
+    #[serde(default)]
+    pub preset: ThemePreset,
+    #[serde(default)]
+    pub liberal: Option<String>,
+    #[serde(default)]
+    pub facist: Option<String>,
+    #[serde(default)]
+    pub hitler: Option<String>,
+    #[serde(default)]
+    pub president: Option<String>,
+    #[serde(default)]
+    pub chancellor: Option<String>,
+    #[serde(default)]
+    pub policy: Option<String>,
+    #[serde(default)]
+    pub liberal_board: Option<String>,
+    #[serde(default)]
+    pub facist_board: Option<String>,
+    #[serde(default)]
+    pub election_tracker: Option<String>,
+}
+
+/// Every name in a theme, with the preset's filling in any the host left out.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ThemeNames {
+    pub liberal: String,
+    pub facist: String,
+    pub hitler: String,
+    pub president: String,
+    pub chancellor: String,
+    pub policy: String,
+    pub liberal_board: String,
+    pub facist_board: String,
+    pub election_tracker: String,
+}
+
+impl Theme {
+    /// The theme's names, with the preset's written in the language given.
+    pub fn names(&self, language: Language) -> ThemeNames {
+        let preset = self.preset.names(language);
+        let pick = |custom: &Option<String>, default: String| {
+            match custom.as_deref().map(str::trim).filter(|name| !name.is_empty()) {
+                Some(name) => name.chars().take(MAX_THEME_NAME_LENGTH).collect(),
+                None => default
+            }
+        };
+        ThemeNames {
+            liberal: pick(&self.liberal, preset.liberal),
+            facist: pick(&self.facist, preset.facist),
+            hitler: pick(&self.hitler, preset.hitler),
+            president: pick(&self.president, preset.president),
+            chancellor: pick(&self.chancellor, preset.chancellor),
+            policy: pick(&self.policy, preset.policy),
+            liberal_board: pick(&self.liberal_board, preset.liberal_board),
+            facist_board: pick(&self.facist_board, preset.facist_board),
+            election_tracker: pick(&self.election_tracker, preset.election_tracker),
+        }
+    }
+}
+
+impl ThemePreset {
+    fn names(self, language: Language) -> ThemeNames {
+        let [liberal, facist, hitler, president, chancellor, policy, liberal_board, facist_board, election_tracker] = match (self, language) {
+            (ThemePreset::Classic, Language::English) => ["liberal", "facist", "Hitler", "president", "chancellor", "policy", "liberal board", "facist board", "election tracker"],
+            (ThemePreset::Classic, Language::Spanish) => ["liberal", "fascista", "Hitler", "presidente", "canciller", "ley", "tablero liberal", "tablero fascista", "contador de elecciones"],
+            (ThemePreset::Starship, Language::English) => ["crew", "saboteur", "Mastermind", "captain", "first officer", "order", "repairs", "sabotage", "mutiny tracker"],
+            (ThemePreset::Starship, Language::Spanish) => ["tripulante", "saboteador", "Cerebro", "capitán", "primer oficial", "orden", "reparaciones", "sabotajes", "contador de motines"],
+        };
+        ThemeNames {
+            liberal: liberal.into(),
+            facist: facist.into(),
+            hitler: hitler.into(),
+            president: president.into(),
+            chancellor: chancellor.into(),
+            policy: policy.into(),
+            liberal_board: liberal_board.into(),
+            facist_board: facist_board.into(),
+            election_tracker: election_tracker.into(),
+        }
+    }
+}
+
+impl ThemeNames {
+    pub fn faction(&self, color: CardColor) -> &str {
+        match color {
+            CardColor::Facist => &self.facist,
+            CardColor::Liberal => &self.liberal
+        }
+    }
+
+    /// How to draw each kind of policy.
+    pub fn card_faces(&self) -> Vec<CardFace> {
+        [CardColor::Liberal, CardColor::Facist].iter().map(|&color| CardFace {
+            color,
+            id: color.id().into(),
+            name: self.faction(color).into(),
+            pattern: color.pattern(),
+            shape: color.shape(),
+        }).collect()
+    }
+}
+
+/// The name with its first letter in upper case, for the start of a sentence.
+pub(crate) fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new()
+    }
+}
@@ -0,0 +1,116 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::game_state::{GameState, PlayerType, PresidentialPower, TurnPhase};
+
+#[derive(Clone, Copy, PartialEq, Serialize, JsonSchema)]
+pub enum TutorialTopic {
+    Roles,
+    Nomination,
+    Voting,
+    Legislation,
+    PresidentLegislation,
+    ChancellorLegislation,
+    PresidentialPower,
+    GameOver,
+}
+
+/// A single explanation shown to the player in a practice game.
+pub struct TutorialStep {
+    pub topic: TutorialTopic,
+    pub title: &'static str,
+    pub text: String,
+}
+
+/// Tracks which parts of the game have been explained to the player in a practice game.
+/// Each topic is only explained the first time it comes up.
+pub struct Tutorial {
+    pub player: Uuid,
+    shown: Vec<TutorialTopic>,
+}
+
+impl Tutorial {
+    pub fn new(player: Uuid) -> Tutorial {
+        Tutorial { player, shown: vec![] }
+    }
+
+    /// The next explanation to show for the current state of the game, if there is a new one.
+    pub fn next_step(&mut self, state: &GameState) -> Option<TutorialStep> {
+        let step = if !self.shown.contains(&TutorialTopic::Roles) {
+            roles_step(state.role(&self.player))
+        }
+        else {
+            phase_step(state, self.player)?
+        };
+        if self.shown.contains(&step.topic) {
+            return None;
+        }
+        self.shown.push(step.topic);
+        Some(step)
+    }
+}
+
+fn roles_step(role: Option<PlayerType>) -> TutorialStep {
+    let text = match role {
+        Some(PlayerType::Liberal) => "You are a Liberal. Liberals win by enacting five Liberal policies or by executing Hitler. You do not know who anyone else is, so watch how the other players vote and which policies their governments enact.",
+        Some(PlayerType::Facist) => "You are a Facist. Facists win by enacting six Facist policies or by electing Hitler as chancellor once three Facist policies are in play. Your teammates and Hitler are revealed in the player list.",
+        _ => "You are Hitler. You play for the Facists, who win by enacting six Facist policies or by electing you as chancellor once three Facist policies are in play. Act like a Liberal so the table trusts you.",
+    };
+    TutorialStep { topic: TutorialTopic::Roles, title: "Your secret role", text: text.into() }
+}
+
+fn phase_step(state: &GameState, player: Uuid) -> Option<TutorialStep> {
+    let is_president = state.president() == Some(player);
+    let is_chancellor = state.chancellor() == Some(player);
+    let step = match state.turn_phase() {
+        TurnPhase::Electing => TutorialStep {
+            topic: TutorialTopic::Nomination,
+            title: "Nominating a chancellor",
+            text: if is_president {
+                "You are the presidential candidate. Pick another player to nominate as chancellor. The previous president and chancellor are not eligible.".into()
+            }
+            else {
+                "Each round, the presidential candidate nominates a chancellor. The presidency rotates around the table in turn order.".into()
+            }
+        },
+        TurnPhase::Voting => TutorialStep {
+            topic: TutorialTopic::Voting,
+            title: "Voting on the government",
+            text: "Everyone votes Ja (yes) or Nein (no) on the proposed government. If the vote fails three times in a row, the top policy of the deck is enacted automatically.".into()
+        },
+        TurnPhase::PresidentSelect if is_president => TutorialStep {
+            topic: TutorialTopic::PresidentLegislation,
+            title: "Discarding a policy",
+            text: "As president you draw three policies. Choose one to discard and the other two are passed to the chancellor.".into()
+        },
+        TurnPhase::ChancellorSelect if is_chancellor => TutorialStep {
+            topic: TutorialTopic::ChancellorLegislation,
+            title: "Enacting a policy",
+            text: "As chancellor you receive two policies from the president. Choose one to enact and the other is discarded.".into()
+        },
+        TurnPhase::PresidentSelect | TurnPhase::ChancellorSelect => TutorialStep {
+            topic: TutorialTopic::Legislation,
+            title: "The legislative session",
+            text: "The government was elected. The president draws three policies and discards one, then the chancellor enacts one of the remaining two. Nobody else sees the cards, so they may lie about what they drew.".into()
+        },
+        TurnPhase::PresidentialPower { power } => TutorialStep {
+            topic: TutorialTopic::PresidentialPower,
+            title: "Presidential powers",
+            text: format!("Some Facist policies grant the president a power. {}", match power {
+                PresidentialPower::InvestigateLoyalty => "This time the president investigates the party membership of another player.",
+                PresidentialPower::CallSpecialElection => "This time the president chooses the next presidential candidate.",
+                PresidentialPower::PolicyPeek => "This time the president looks at the top three policies of the deck.",
+                PresidentialPower::Execution => "This time the president must execute a player. If Hitler is executed, the Liberals win.",
+            })
+        },
+        TurnPhase::Ended { winner } => TutorialStep {
+            topic: TutorialTopic::GameOver,
+            title: "Game over",
+            text: format!("The {}s have won. All roles are now revealed, so look back at who you trusted. You are ready to play a real game!", winner)
+        },
+        // practice games vote straight after a nomination, without a discussion
+        TurnPhase::Lobby | TurnPhase::Discussion => return None,
+    };
+    Some(step)
+}
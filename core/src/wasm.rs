@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+use wasm_bindgen::prelude::*;
+
+use crate::{game_state::{GameOptions, GameState, GameStatePlayerView, TurnPhase}, machine::Action, pass_and_play::PassAndPlay, protocol::{NullSink, PlayerConnection}};
+
+/// Whether an action can be taken at all in a turn phase, both given as JSON, so a client can check a move against its view before sending it.
+#[wasm_bindgen(js_name = actionAllowed)]
+pub fn action_allowed(action: &str, phase: &str) -> Result<bool, String> {
+    let action: Action = serde_json::from_str(action).map_err(|e| e.to_string())?;
+    let phase: TurnPhase = serde_json::from_str(phase).map_err(|e| e.to_string())?;
+    Ok(action.allowed_in(&phase))
+}
+
+/// A whole game held in the browser, played by the same rules as the server.
+/// Everything goes in and out as the JSON the server speaks, so a client can share its types between the two.
+#[wasm_bindgen]
+pub struct Engine {
+    state: GameState,
+}
+
+#[wasm_bindgen]
+impl Engine {
+    /// Open a lobby with the game options given as JSON.
+    #[wasm_bindgen(constructor)]
+    pub fn new(options: &str) -> Result<Engine, String> {
+        let options: GameOptions = serde_json::from_str(options).map_err(|e| e.to_string())?;
+        Ok(Engine { state: GameState::with_options(options) })
+    }
+
+    /// Seat a player, returning their id.
+    #[wasm_bindgen(js_name = addPlayer)]
+    pub fn add_player(&mut self, name: String) -> Result<String, String> {
+        let player = Uuid::new_v4();
+        let mut conn = PlayerConnection::new(Arc::new(NullSink));
+        conn.name = Some(name);
+        if !self.state.add_player(player, conn) {
+            return Err("This game has already started!".into())
+        }
+        Ok(player.to_string())
+    }
+
+    /// Apply a player's action given as JSON, returning the events it caused as JSON.
+    pub fn apply(&mut self, player: &str, action: &str) -> Result<String, String> {
+        let player = parse_id(player)?;
+        let action: Action = serde_json::from_str(action).map_err(|e| e.to_string())?;
+        let events = self.state.apply(player, action).map_err(|e| e.to_string())?;
+        Ok(serde_json::to_string(&events).unwrap())
+    }
+
+    /// The game as one player sees it, in the same shape as the server's `GameState` message.
+    pub fn view(&self, player: &str) -> Result<String, String> {
+        let player = parse_id(player)?;
+        Ok(serde_json::to_string(&GameStatePlayerView { player, state: &self.state }).unwrap())
+    }
+}
+
+/// A pass and play game on this device, see [`PassAndPlay`].
+#[wasm_bindgen(js_name = PassAndPlay)]
+pub struct PassAndPlayEngine {
+    game: PassAndPlay,
+}
+
+#[wasm_bindgen(js_class = PassAndPlay)]
+impl PassAndPlayEngine {
+    /// Start a game with the game options given as JSON and the players' names in seating order.
+    #[wasm_bindgen(constructor)]
+    pub fn new(options: &str, names: Vec<String>) -> Result<PassAndPlayEngine, String> {
+        let options: GameOptions = serde_json::from_str(options).map_err(|e| e.to_string())?;
+        Ok(PassAndPlayEngine { game: PassAndPlay::new(options, names).map_err(|e| e.to_string())? })
+    }
+
+    /// The id of the player to pass the device to, once it has been put down.
+    pub fn next(&self) -> Option<String> {
+        self.game.next().map(|player| player.to_string())
+    }
+
+    #[wasm_bindgen(js_name = handTo)]
+    pub fn hand_to(&mut self, player: &str) -> Result<(), String> {
+        self.game.hand_to(parse_id(player)?).map_err(|e| e.to_string())
+    }
+
+    pub fn hide(&mut self) {
+        self.game.hide();
+    }
+
+    /// Apply an action given as JSON for the player holding the device, returning the events it caused as JSON.
+    pub fn apply(&mut self, action: &str) -> Result<String, String> {
+        let action: Action = serde_json::from_str(action).map_err(|e| e.to_string())?;
+        let events = self.game.apply(action).map_err(|e| e.to_string())?;
+        Ok(serde_json::to_string(&events).unwrap())
+    }
+
+    /// The game as the holder sees it, or the public view while the device is down.
+    pub fn view(&self) -> String {
+        serde_json::to_string(&self.game.view()).unwrap()
+    }
+}
+
+fn parse_id(id: &str) -> Result<Uuid, String> {
+    Uuid::parse_str(id).map_err(|_| "That player does not exist!".to_string())
+}
@@ -0,0 +1,1638 @@
+use core::panic;
+use std::{collections::HashMap, sync::{Arc, mpsc}, time::{Duration, SystemTime}};
+use serde::Deserialize;
+
+#[cfg(test)]
+use secrethitler_core::game_state::GameState;
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+use schemars::schema::RootSchema;
+use secrethitler_core::{analysis::Analysis, chat::{self, ChatSegment, LinkPolicy}, claims::Conflict, error::GameError, events::GameEvent, lobby_vote::{LOBBY_VOTE_DURATION, Motion}, machine::Action, messages::{Language, Message}, nicknames, odds::DeckOdds, pass_and_play::PassAndPlay, game_state::{CardColor, GameExport, GameOptions, GameStatePlayerView, LATEST_VIEW_VERSION, LegislativeStage, ROLE_ACK_TIMEOUT, PlayerType, PresidentialPower, ScheduleEvent, TimelineEntry, TurnPhase, VersionedPlayerView, epoch_millis, negotiate_view_version}, protocol::{ClientProtocol, PlayerConnection}, rules::Rules, schema::{self, PlayerView, PlayerViewV2}, simulation::Simulation, theme::{Theme, ThemePreset}};
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+struct ClientState {
+    turn_phase: TurnPhase,
+    turn_order: Vec<Uuid>,
+    players: HashMap<Uuid, serde_json::Value>,
+    waitlist_position: Option<usize>,
+    phase_started_at: Option<u64>,
+    phase_deadline: Option<u64>,
+    election_tracker: u8
+}
+
+fn get_state_snapshot(state: &GameState, player: &Uuid) -> ClientState {
+    let serialized = serde_json::to_string(&GameStatePlayerView { state, player: *player }).unwrap();
+    serde_json::from_str(&serialized).unwrap()
+}
+
+#[test]
+fn test_game_lobby_init() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+
+    // start game with 5 players
+    let mut state = GameState::new();
+    let mut ids = vec![];
+    for _ in 0..5 {
+        ids.push(Uuid::new_v4());
+    }
+    ids.iter().for_each(|id| {
+        state.add_player(*id, PlayerConnection::new(ptx.clone()));
+    });
+
+    // should be in lobby
+    assert!(matches!(get_state_snapshot(&state, &ids[0]).turn_phase, TurnPhase::Lobby));
+
+    // start game
+    assert!(state.start(ids[1]).is_err());
+    assert!(state.start(Uuid::new_v4()).is_err());
+
+    assert!(matches!(state.start(ids[0]), Ok(())));
+
+    assert!(matches!(get_state_snapshot(&state, &ids[0]).turn_phase, TurnPhase::Electing));
+
+    // choose chancellor
+    let turn_order = get_state_snapshot(&state, &ids[3]).turn_order;
+    if let Err(e) = state.choose_chancellor(turn_order[0], turn_order[1]) {
+        panic!("failed to choose chancellor: {}", e);
+    }
+    assert!(matches!(get_state_snapshot(&state, &ids[0]).turn_phase, TurnPhase::Voting));
+
+    // vote yes
+    ids.iter().for_each(|id| {
+        if let Err(e) = state.vote_chancellor(*id, true) {
+            panic!("failed to vote for chancellor: {}", e);
+        }
+    });
+
+    // choose card
+    assert!(matches!(get_state_snapshot(&state, &ids[0]).turn_phase, TurnPhase::PresidentSelect));
+}
+
+#[test]
+fn test_phase_deadline() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+
+    let mut state = GameState::with_options(GameOptions { turn_timer: Some(30), ..GameOptions::default() });
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    ids.iter().for_each(|id| {
+        state.add_player(*id, PlayerConnection::new(ptx.clone()));
+    });
+
+    // no deadline while in the lobby
+    let snapshot = get_state_snapshot(&state, &ids[0]);
+    assert!(snapshot.phase_deadline.is_none());
+    assert!(snapshot.phase_started_at.is_none());
+
+    assert!(state.start(ids[0]).is_ok());
+    let snapshot = get_state_snapshot(&state, &ids[0]);
+    assert_eq!(snapshot.phase_deadline.unwrap() - snapshot.phase_started_at.unwrap(), 30 * 1000);
+}
+
+#[test]
+fn test_revoke_secret() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+
+    let mut state = GameState::new();
+    let ids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+    ids.iter().for_each(|id| {
+        let mut conn = PlayerConnection::new(ptx.clone());
+        conn.secret = Some(Uuid::new_v4());
+        state.add_player(*id, conn);
+    });
+    let old_secret = state.get_player_secret(&ids[1]);
+
+    // only the host may revoke secrets
+    assert!(state.revoke_secret(ids[2], ids[1]).is_err());
+    assert_eq!(state.get_player_secret(&ids[1]), old_secret);
+
+    let new_secret = state.revoke_secret(ids[0], ids[1]).unwrap();
+    assert_eq!(state.get_player_secret(&ids[1]), Some(new_secret));
+    assert_ne!(Some(new_secret), old_secret);
+}
+
+#[test]
+fn test_waitlist() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+
+    let mut state = GameState::with_options(GameOptions { max_players: Some(5), ..GameOptions::default() });
+    let ids: Vec<Uuid> = (0..7).map(|_| Uuid::new_v4()).collect();
+    ids.iter().for_each(|id| {
+        assert!(state.add_player(*id, PlayerConnection::new(ptx.clone())));
+    });
+
+    // extra players are queued in join order
+    let snapshot = get_state_snapshot(&state, &ids[6]);
+    assert_eq!(snapshot.players.len(), 5);
+    assert_eq!(snapshot.waitlist_position, Some(2));
+    assert_eq!(get_state_snapshot(&state, &ids[5]).waitlist_position, Some(1));
+    assert_eq!(get_state_snapshot(&state, &ids[0]).waitlist_position, None);
+
+    // the first queued player takes the open seat
+    state.delete_player(ids[2]);
+    let snapshot = get_state_snapshot(&state, &ids[5]);
+    assert!(snapshot.players.contains_key(&ids[5]));
+    assert_eq!(snapshot.waitlist_position, None);
+    assert_eq!(get_state_snapshot(&state, &ids[6]).waitlist_position, Some(1));
+}
+
+#[test]
+fn test_practice_game_waits_on_player() {
+    let (ptx, _) = mpsc::channel();
+    let player = Uuid::new_v4();
+    let mut conn = PlayerConnection::new(Arc::new(ptx));
+    conn.name = Some("player".into());
+
+    let mut state = GameState::new_practice(player, conn, 4);
+    assert!(state.is_practice());
+    assert_eq!(state.living_players().len(), 5);
+
+    // the bots should have played until the human has something to do
+    for _ in 0..20 {
+        match state.turn_phase() {
+            TurnPhase::Voting => {
+                assert!(!state.has_voted(&player));
+                state.vote_chancellor(player, true).unwrap();
+            },
+            TurnPhase::Ended { winner: _ } => break,
+            _ => {
+                assert!(state.president() == Some(player) || state.chancellor() == Some(player));
+                break
+            }
+        }
+        state.run_bots();
+    }
+}
+
+#[test]
+fn test_rematch_reseats_in_join_order() {
+    let mut state = GameState::with_options(GameOptions { max_players: Some(7), ..GameOptions::default() });
+    let ids: Vec<Uuid> = (0..9).map(|_| Uuid::new_v4()).collect();
+    ids.iter().enumerate().for_each(|(i, id)| {
+        assert!(state.add_player(*id, PlayerConnection::bot(format!("bot {}", i))));
+    });
+    assert!(state.rematch(ids[0]).is_err());
+    state.start(ids[0]).unwrap();
+
+    // let the bots play the whole game
+    for _ in 0..50 {
+        if state.winner().is_some() {
+            break
+        }
+        state.run_bots();
+    }
+    assert!(state.winner().is_some());
+
+    assert!(state.rematch(ids[1]).is_err());
+    state.rematch(ids[0]).unwrap();
+    assert!(matches!(state.turn_phase(), TurnPhase::Lobby));
+    let snapshot = get_state_snapshot(&state, &ids[8]);
+    assert_eq!(snapshot.players.len(), 7);
+    assert!(ids[..7].iter().all(|id| snapshot.players.contains_key(id) && snapshot.players[id]["dead"] == false));
+    assert_eq!(snapshot.waitlist_position, Some(2));
+    assert!(state.start(ids[0]).is_ok());
+}
+
+#[test]
+fn test_rules_for_player_count() {
+    let rules = Rules::new(5);
+    assert_eq!((rules.liberals, rules.facists), (3, 1));
+    assert_eq!(rules.power_track, vec![None, None, Some(PresidentialPower::PolicyPeek), Some(PresidentialPower::Execution), Some(PresidentialPower::Execution)]);
+    assert!(rules.hitler_knows_facists);
+    assert!(!rules.last_president_ineligible(5));
+
+    let rules = Rules::new(9);
+    assert_eq!((rules.liberals, rules.facists), (5, 3));
+    assert_eq!(rules.power_track[0], Some(PresidentialPower::InvestigateLoyalty));
+    assert_eq!(rules.power_track[2], Some(PresidentialPower::CallSpecialElection));
+    assert!(!rules.hitler_knows_facists);
+    assert!(rules.last_president_ineligible(6));
+}
+
+#[test]
+fn test_election_tracker_warning() {
+    let (ptx, prx) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+
+    let mut state = GameState::new();
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    ids.iter().for_each(|id| {
+        state.add_player(*id, PlayerConnection::new(ptx.clone()));
+    });
+    state.start(ids[0]).unwrap();
+
+    // fail two governments in a row
+    for _ in 0..2 {
+        let president = state.president().unwrap();
+        let candidates = state.living_players().to_vec();
+        let candidate = candidates.into_iter().find(|p| *p != president && state.choose_chancellor(president, *p).is_ok()).unwrap();
+        assert_eq!(state.chancellor(), Some(candidate));
+        ids.iter().for_each(|id| state.vote_chancellor(*id, false).unwrap());
+    }
+
+    let mut warnings = vec![];
+    while let Ok(msg) = prx.try_recv() {
+        let msg: serde_json::Value = serde_json::from_str(&msg).unwrap();
+        if msg["type"] == "ElectionTrackerAdvanced" {
+            warnings.push((msg["value"].as_u64().unwrap(), msg["chaos_imminent"].as_bool().unwrap()));
+        }
+    }
+    // every connection receives each warning
+    warnings.dedup();
+    assert_eq!(warnings, vec![(1, false), (2, true)]);
+
+    let timeline = serde_json::to_value(state.timeline()).unwrap();
+    let timeline: Vec<&serde_json::Value> = timeline.as_array().unwrap().iter().filter(|entry| entry["event"]["type"] == "ElectionTrackerAdvanced").collect();
+    assert_eq!(timeline.len(), 2);
+    assert_eq!(timeline[1]["event"]["chaos_imminent"], true);
+}
+
+#[test]
+fn test_nomination_timeout() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+
+    for fails in [false, true] {
+        let mut state = GameState::with_options(GameOptions { turn_timer: Some(30), nomination_timeout_fails: fails, ..GameOptions::default() });
+        let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+        ids.iter().for_each(|id| {
+            state.add_player(*id, PlayerConnection::new(ptx.clone()));
+        });
+        state.start(ids[0]).unwrap();
+        let president = state.president().unwrap();
+
+        // the president still has time to nominate
+        assert!(!state.expire_nomination(SystemTime::now()));
+        assert_eq!(state.president(), Some(president));
+
+        assert!(state.expire_nomination(SystemTime::now() + Duration::from_secs(31)));
+        assert!(matches!(state.turn_phase(), TurnPhase::Electing));
+        assert_ne!(state.president(), Some(president));
+        assert_eq!(get_state_snapshot(&state, &president).election_tracker, if fails { 1 } else { 0 });
+        // the expiry is part of the game's history
+        let rebuilt = state.rebuild().unwrap();
+        assert_eq!(rebuilt.president(), state.president());
+        assert_eq!(get_state_snapshot(&rebuilt, &president).election_tracker, if fails { 1 } else { 0 });
+
+        // the skipped president was never in government, so they may be nominated
+        let new_president = state.president().unwrap();
+        assert!(state.choose_chancellor(new_president, president).is_ok());
+        assert!(!state.expire_nomination(SystemTime::now() + Duration::from_secs(31)));
+    }
+}
+
+#[test]
+fn test_awaiting_players() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+
+    let mut state = GameState::with_options(GameOptions { asynchronous: true, turn_timer: Some(12 * 60 * 60), ..GameOptions::default() });
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    ids.iter().for_each(|id| {
+        state.add_player(*id, PlayerConnection::new(ptx.clone()));
+    });
+    assert!(state.awaiting().is_empty());
+
+    state.start(ids[0]).unwrap();
+    let president = state.president().unwrap();
+    assert_eq!(state.awaiting(), vec![president]);
+
+    let chancellor = *state.living_players().iter().find(|p| **p != president).unwrap();
+    state.choose_chancellor(president, chancellor).unwrap();
+    assert_eq!(state.awaiting().len(), 5);
+    state.vote_chancellor(ids[0], true).unwrap();
+    assert_eq!(state.awaiting().len(), 4);
+    assert!(!state.awaiting().contains(&ids[0]));
+
+    ids[1..].iter().for_each(|id| state.vote_chancellor(*id, true).unwrap());
+    assert_eq!(state.awaiting(), vec![president]);
+}
+
+#[test]
+fn test_spectator_delay() {
+    let (ptx, prx) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+    let (stx, srx) = mpsc::channel();
+
+    let mut state = GameState::with_options(GameOptions { max_players: Some(5), spectator_delay: Some(120), ..GameOptions::default() });
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    ids.iter().for_each(|id| {
+        state.add_player(*id, PlayerConnection::new(ptx.clone()));
+    });
+    let spectator = Uuid::new_v4();
+    state.add_player(spectator, PlayerConnection::new(Arc::new(stx)));
+    while srx.try_recv().is_ok() {}
+
+    state.start(ids[0]).unwrap();
+    state.broadcast_game_state();
+    assert!(prx.try_recv().is_ok());
+    assert!(srx.try_recv().is_err());
+
+    // the spectator sees the game once the delay has passed
+    state.flush_spectators(SystemTime::now() + Duration::from_secs(60));
+    assert!(srx.try_recv().is_err());
+    state.flush_spectators(SystemTime::now() + Duration::from_secs(121));
+    assert!(srx.try_recv().is_ok());
+}
+
+#[test]
+fn test_scheduled_lobby() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+
+    let now = SystemTime::now();
+    let opens_at = now + Duration::from_secs(60 * 60);
+    let mut state = GameState::with_options(GameOptions { scheduled_at: Some(epoch_millis(opens_at)), ..GameOptions::default() });
+    let host = Uuid::new_v4();
+    assert!(state.add_player(host, PlayerConnection::new(ptx.clone())));
+    assert!(!state.add_player(Uuid::new_v4(), PlayerConnection::new(ptx.clone())));
+
+    assert_eq!(state.advance_schedule(now), None);
+    assert_eq!(state.advance_schedule(opens_at - Duration::from_secs(10 * 60)), Some(ScheduleEvent::Reminder));
+    assert_eq!(state.advance_schedule(opens_at - Duration::from_secs(5 * 60)), None);
+    assert_eq!(state.advance_schedule(opens_at), Some(ScheduleEvent::Opened));
+    assert!(state.opens_at().is_none());
+    assert!(state.add_player(Uuid::new_v4(), PlayerConnection::new(ptx.clone())));
+}
+
+#[test]
+fn test_ready_check() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+
+    let mut state = GameState::with_options(GameOptions { require_ready: true, ..GameOptions::default() });
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    for id in ids.iter() {
+        state.add_player(*id, PlayerConnection::new(ptx.clone()));
+    }
+    assert_eq!(get_state_snapshot(&state, &ids[0]).players[&ids[1]]["ready"], false);
+
+    // the host does not have to ready up
+    for id in ids[1..4].iter() {
+        assert!(state.set_ready(*id, true).is_ok());
+    }
+    assert!(state.set_ready(ids[4], true).is_ok());
+    assert!(state.set_ready(ids[4], false).is_ok());
+    // a start that fails leaves a vote to remove the host running
+    let now = SystemTime::now();
+    assert!(state.call_lobby_vote(ids[1], Motion::Kick { player: ids[0] }, now).is_ok());
+    assert_eq!(state.start(ids[0]), Err(GameError::NotReady { players: vec![ids[4]] }));
+    assert!(state.cast_lobby_vote(ids[2], true, now).is_ok());
+
+    assert!(state.set_ready(ids[4], true).is_ok());
+    assert_eq!(get_state_snapshot(&state, &ids[0]).players[&ids[4]]["ready"], true);
+    assert!(state.start(ids[0]).is_ok());
+    assert!(get_state_snapshot(&state, &ids[0]).players[&ids[4]].get("ready").is_none());
+    assert!(state.set_ready(ids[4], false).is_err());
+}
+
+#[test]
+fn test_lobby_countdown() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+
+    let mut state = GameState::with_options(GameOptions { require_ready: true, ..GameOptions::default() });
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    for id in ids.iter() {
+        state.add_player(*id, PlayerConnection::new(ptx.clone()));
+    }
+    let now = SystemTime::now();
+    assert_eq!(state.start_countdown(ids[1], 10, now), Err(GameError::NotHost { action: "start the countdown" }));
+    assert!(state.start_countdown(ids[0], 10, now).is_ok());
+    assert_eq!(state.start_countdown(ids[0], 10, now), Err(GameError::CountdownRunning));
+    assert_eq!(state.countdown(), Some(now + Duration::from_secs(10)));
+
+    // nobody new can join while it runs, but players already seated can come back
+    assert!(!state.add_player(Uuid::new_v4(), PlayerConnection::new(ptx.clone())));
+    assert!(state.add_player(ids[2], PlayerConnection::new(ptx.clone())));
+
+    // the host can call it off, which opens the lobby again
+    assert!(state.cancel_countdown(ids[0]).is_ok());
+    assert_eq!(state.cancel_countdown(ids[0]), Err(GameError::NoCountdown));
+    let late = Uuid::new_v4();
+    assert!(state.add_player(late, PlayerConnection::new(ptx.clone())));
+    state.delete_player(late);
+
+    // a countdown that runs out while someone is not ready is called off
+    assert!(state.start_countdown(ids[0], 500, now).is_ok());
+    assert_eq!(state.countdown(), Some(now + Duration::from_secs(60)));
+    assert!(!state.expire_countdown(now + Duration::from_secs(59)));
+    assert!(state.expire_countdown(now + Duration::from_secs(60)));
+    assert!(matches!(state.turn_phase(), TurnPhase::Lobby));
+    assert_eq!(state.countdown(), None);
+
+    // players can ready up while it runs, and the game starts once it reaches zero
+    assert!(state.start_countdown(ids[0], 5, now).is_ok());
+    for id in ids[1..].iter() {
+        assert!(state.set_ready(*id, true).is_ok());
+    }
+    assert!(state.expire_countdown(now + Duration::from_secs(5)));
+    assert!(matches!(state.turn_phase(), TurnPhase::Electing));
+    assert_eq!(state.start_countdown(ids[0], 5, now), Err(GameError::AlreadyStarted));
+}
+
+#[test]
+fn test_role_reveal() {
+    for players in [5, 7] {
+        let mut state = GameState::new();
+        let mut seats = Vec::new();
+        for _ in 0..players {
+            let (tx, rx) = mpsc::channel();
+            let id = Uuid::new_v4();
+            state.add_player(id, PlayerConnection::new(Arc::new(tx)));
+            seats.push((id, rx));
+        }
+        assert!(state.start(seats[0].0).is_ok());
+
+        for (id, rx) in seats.iter() {
+            let messages: Vec<serde_json::Value> = rx.try_iter().map(|message| serde_json::from_str(&message).unwrap()).collect();
+            let order = messages.iter().find(|message| message["type"] == "TurnOrderAssigned").unwrap();
+            assert_eq!(order["turn_order"].as_array().unwrap().len(), players);
+            let reveal: Vec<&str> = order["reveal"].as_array().unwrap().iter().map(|step| step.as_str().unwrap()).collect();
+            assert_eq!(reveal.contains(&"HitlerRaisesThumb"), players > 6);
+            assert_eq!(reveal.contains(&"HitlerOpensEyes"), players <= 6);
+
+            // each player is told their own role alone, and fascists are told who the others are
+            let assigned: Vec<&serde_json::Value> = messages.iter().filter(|message| message["type"] == "RoleAssigned").collect();
+            assert_eq!(assigned.len(), 1);
+            let role = state.role(id).unwrap();
+            assert_eq!(assigned[0]["role"], serde_json::to_value(role).unwrap());
+            let known = assigned[0]["known"].as_object().unwrap();
+            let knows = match role {
+                PlayerType::Liberal => false,
+                PlayerType::Facist => true,
+                PlayerType::Hitler => players <= 6
+            };
+            assert_eq!(known.len(), if knows { players - 1 } else { 0 });
+            assert!(!known.contains_key(&id.to_string()));
+        }
+    }
+}
+
+#[test]
+fn test_role_acknowledgement() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+
+    let mut state = GameState::with_options(GameOptions { require_role_ack: true, turn_timer: Some(30), ..GameOptions::default() });
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    for id in ids.iter() {
+        state.add_player(*id, PlayerConnection::new(ptx.clone()));
+    }
+    let bot = Uuid::new_v4();
+    state.add_player(bot, PlayerConnection::bot("Bot".to_string()));
+    assert_eq!(state.acknowledge_role(ids[0]), Err(GameError::WrongPhase));
+    assert!(state.start(ids[0]).is_ok());
+
+    // bots have nothing to look at, so only the people are waited on
+    let mut waiting = ids.clone();
+    waiting.sort();
+    assert_eq!(state.unacknowledged(), waiting);
+    assert_eq!(state.awaiting(), waiting);
+    assert_eq!(state.phase_deadline(), None);
+    let president = state.president().unwrap();
+    let target = *state.living_players().iter().find(|p| **p != president).unwrap();
+    assert_eq!(state.choose_chancellor(president, target), Err(GameError::RolesNotAcknowledged { players: waiting.clone() }));
+
+    let view = serde_json::to_value(GameStatePlayerView { state: &state, player: ids[0] }).unwrap();
+    assert_eq!(view["players"][ids[0].to_string()]["acknowledged"], false);
+    assert_eq!(view["players"][bot.to_string()]["acknowledged"], true);
+
+    for id in ids[..3].iter() {
+        assert!(state.acknowledge_role(*id).is_ok());
+    }
+
+    // players who walk out are not waited on, and the hold survives an export
+    state.delete_player(ids[3]);
+    assert_eq!(state.unacknowledged(), vec![ids[4]]);
+    let imported = GameState::import(serde_json::from_value(serde_json::to_value(state.export().unwrap()).unwrap()).unwrap());
+    assert_eq!(imported.unacknowledged(), vec![ids[4]]);
+    assert!(imported.role_ack_deadline().is_some());
+
+    // nor is anyone else forever, once the hold runs out
+    let deadline = state.role_ack_deadline().unwrap();
+    assert!(deadline >= SystemTime::now() + ROLE_ACK_TIMEOUT - Duration::from_secs(5));
+    assert!(!state.expire_role_ack(deadline - Duration::from_secs(1)));
+    assert!(state.expire_role_ack(deadline));
+    assert_eq!(state.acknowledge_role(ids[0]), Err(GameError::WrongPhase));
+    assert!(state.unacknowledged().is_empty());
+    assert!(state.role_ack_deadline().is_none());
+    assert!(state.phase_deadline().is_some());
+    let view = serde_json::to_value(GameStatePlayerView { state: &state, player: ids[0] }).unwrap();
+    assert!(view["players"][ids[0].to_string()].get("acknowledged").is_none());
+    assert!(state.choose_chancellor(president, target).is_ok());
+}
+
+#[test]
+fn test_nickname_suggestions() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let nickname = nicknames::suggest(&mut rng, ThemePreset::Starship, |_| false);
+    assert_eq!(nickname.split(' ').count(), 2);
+
+    // once every pairing of words is taken, a number tells the suggestion apart
+    let nickname = nicknames::suggest(&mut rng, ThemePreset::Classic, |nickname| nickname.split(' ').count() == 2);
+    assert!(nickname.ends_with(" 2"));
+
+    let mut state = GameState::new();
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+    for _ in 0..3 {
+        let mut conn = PlayerConnection::new(ptx.clone());
+        conn.name = Some(state.suggest_nickname(&mut rng));
+        state.add_player(Uuid::new_v4(), conn);
+    }
+    let mut names: Vec<String> = state.conn.values().map(|conn| conn.name.clone().unwrap().to_lowercase()).collect();
+    names.sort();
+    names.dedup();
+    assert_eq!(names.len(), 3);
+}
+
+#[test]
+fn test_lobby_kick_vote() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+
+    let mut state = GameState::new();
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    for id in ids.iter() {
+        let mut conn = PlayerConnection::new(ptx.clone());
+        conn.secret = Some(*id);
+        state.add_player(*id, conn);
+    }
+    let now = SystemTime::now();
+    let kick_host = Motion::Kick { player: ids[0] };
+
+    // a vote fails once a majority of the other players is out of reach
+    assert!(state.call_lobby_vote(ids[1], kick_host.clone(), now).is_ok());
+    assert_eq!(state.call_lobby_vote(ids[2], kick_host.clone(), now), Err(GameError::LobbyVoteInProgress));
+    assert_eq!(state.cast_lobby_vote(ids[0], false, now), Err(GameError::NotAPlayer));
+    assert!(state.cast_lobby_vote(ids[2], false, now).is_ok());
+    assert!(state.cast_lobby_vote(ids[3], false, now).is_ok());
+    assert_eq!(state.cast_lobby_vote(ids[4], true, now), Err(GameError::NoLobbyVote));
+    assert!(state.has_player(&ids[0]));
+
+    // and lapses when time runs out
+    assert!(state.call_lobby_vote(ids[1], kick_host.clone(), now).is_ok());
+    assert!(!state.expire_lobby_vote(now));
+    assert!(state.expire_lobby_vote(now + LOBBY_VOTE_DURATION));
+    assert_eq!(state.cast_lobby_vote(ids[2], true, now), Err(GameError::NoLobbyVote));
+
+    // three of the four other players remove the host, who cannot come back with the same secret
+    assert!(state.call_lobby_vote(ids[1], kick_host.clone(), now).is_ok());
+    assert!(state.cast_lobby_vote(ids[2], true, now).is_ok());
+    assert!(state.has_player(&ids[0]));
+    assert!(state.cast_lobby_vote(ids[3], true, now).is_ok());
+    assert!(!state.has_player(&ids[0]));
+    let host = serde_json::to_value(GameStatePlayerView { state: &state, player: ids[1] }).unwrap()["host"].clone();
+    assert!(ids[1..].iter().any(|id| host == id.to_string()));
+    let mut conn = PlayerConnection::new(ptx.clone());
+    conn.secret = Some(ids[0]);
+    assert!(!state.add_player(Uuid::new_v4(), conn));
+}
+
+#[test]
+fn test_moderator_kick() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+
+    let mut state = GameState::new();
+    let ids: Vec<Uuid> = (0..6).map(|_| Uuid::new_v4()).collect();
+    for id in ids.iter() {
+        let mut conn = PlayerConnection::new(ptx.clone());
+        conn.secret = Some(*id);
+        state.add_player(*id, conn);
+    }
+
+    // a moderator removes a player without a vote, and they cannot come back with the same secret
+    let stranger = Uuid::new_v4();
+    assert_eq!(state.kick(stranger), Err(GameError::PlayerNotFound { player: stranger }));
+    assert!(state.kick(ids[5]).is_ok());
+    assert!(!state.has_player(&ids[5]));
+    let mut conn = PlayerConnection::new(ptx.clone());
+    conn.secret = Some(ids[5]);
+    assert!(!state.add_player(Uuid::new_v4(), conn));
+
+    // seats cannot be emptied once the game is under way
+    state.start(ids[0]).unwrap();
+    assert_eq!(state.kick(ids[1]), Err(GameError::AlreadyStarted));
+    assert!(state.has_player(&ids[1]));
+}
+
+#[test]
+fn test_message_language() {
+    let (ptx, prx) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+    let (kicked_tx, kicked_rx) = mpsc::channel();
+
+    let mut state = GameState::with_options(GameOptions { language: Language::Spanish, ..GameOptions::default() });
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    for (i, id) in ids.iter().enumerate() {
+        let mut conn = PlayerConnection::new(if i == 0 { Arc::new(kicked_tx.clone()) } else { ptx.clone() });
+        if i == 0 {
+            conn.language = Some(Language::English);
+        }
+        state.add_player(*id, conn);
+    }
+    while prx.try_recv().is_ok() {}
+    let now = SystemTime::now();
+    state.call_lobby_vote(ids[1], Motion::Kick { player: ids[0] }, now).unwrap();
+    for id in ids[2..4].iter() {
+        state.cast_lobby_vote(*id, true, now).unwrap();
+    }
+
+    // chat lines use the game's language, and the player who was removed is told in their own
+    let chat: Vec<String> = prx.try_iter().map(|message| serde_json::from_str::<serde_json::Value>(&message).unwrap()).filter(|message| message["type"] == "ReceiveChat").map(|message| message["message"].as_str().unwrap().to_string()).collect();
+    assert!(chat.iter().any(|line| line.starts_with("La votación ha salido adelante")));
+    let alerts: Vec<serde_json::Value> = kicked_rx.try_iter().map(|message| serde_json::from_str::<serde_json::Value>(&message).unwrap()).filter(|message| message["type"] == "Alert").collect();
+    assert_eq!(alerts.last().unwrap()["message"], Message::RemovedByVote.text(Language::English, &Theme::default()));
+    assert_eq!(state.language_for(&ids[1]), Language::Spanish);
+}
+
+#[test]
+fn test_card_faces() {
+    let theme = Theme { liberal: Some("Crew".into()), facist: Some("   ".into()), ..Theme::default() };
+    let state = GameState::with_options(GameOptions { theme, ..GameOptions::default() });
+    let view: PlayerView = serde_json::from_str(&serde_json::to_string(&GameStatePlayerView { player: Uuid::new_v4(), state: &state }).unwrap()).unwrap();
+
+    // renaming a faction keeps its identifier, and a blank name falls back to the usual one
+    let liberal = view.card_faces.iter().find(|face| face.color == CardColor::Liberal).unwrap();
+    let facist = view.card_faces.iter().find(|face| face.color == CardColor::Facist).unwrap();
+    assert_eq!((liberal.id.as_str(), liberal.name.as_str()), ("liberal", "Crew"));
+    assert_eq!((facist.id.as_str(), facist.name.as_str()), ("facist", "facist"));
+    assert_ne!(liberal.pattern, facist.pattern);
+    assert_ne!(liberal.shape, facist.shape);
+}
+
+#[test]
+fn test_theme() {
+    let (ptx, prx) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+    let theme = Theme { preset: ThemePreset::Starship, chancellor: Some("pilot".into()), ..Theme::default() };
+    let mut state = GameState::with_options(GameOptions { theme, ..GameOptions::default() });
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    for (i, id) in ids.iter().enumerate() {
+        let mut conn = PlayerConnection::new(ptx.clone());
+        conn.name = Some(format!("player {}", i));
+        state.add_player(*id, conn);
+    }
+    let view: PlayerView = serde_json::from_str(&serde_json::to_string(&GameStatePlayerView { player: ids[0], state: &state }).unwrap()).unwrap();
+    assert_eq!((view.theme.president.as_str(), view.theme.chancellor.as_str(), view.theme.hitler.as_str()), ("captain", "pilot", "Mastermind"));
+
+    // the chat announces the policy in the theme's words
+    state.apply(ids[0], Action::Start).unwrap();
+    let president = state.president().unwrap();
+    let chancellor = *ids.iter().find(|id| **id != president).unwrap();
+    state.apply(president, Action::Nominate { chancellor }).unwrap();
+    for id in ids.iter() {
+        state.apply(*id, Action::Vote { approve: true }).unwrap();
+    }
+    state.apply(president, Action::PickCard { index: 0 }).unwrap();
+    let enact = state.hand(chancellor).unwrap()[0];
+    state.apply(chancellor, Action::PickCard { index: 0 }).unwrap();
+    let chat: Vec<String> = prx.try_iter().map(|message| serde_json::from_str::<serde_json::Value>(&message).unwrap()).filter(|message| message["type"] == "ReceiveChat").map(|message| message["message"].as_str().unwrap().to_string()).collect();
+    let faction = if enact == CardColor::Liberal { "crew" } else { "saboteur" };
+    assert!(chat.iter().any(|line| line.starts_with("Captain ") && line.contains(" and pilot ") && line.ends_with(&format!("have enacted a {} order.", faction))));
+}
+
+#[test]
+fn test_deck_odds() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+    for deck_odds in [false, true] {
+        let mut state = GameState::with_options(GameOptions { deck_odds, ..GameOptions::default() });
+        let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+        for id in ids.iter() {
+            state.add_player(*id, PlayerConnection::new(ptx.clone()));
+        }
+        state.apply(ids[0], Action::Start).unwrap();
+        let view: PlayerView = serde_json::from_str(&serde_json::to_string(&GameStatePlayerView { player: ids[1], state: &state }).unwrap()).unwrap();
+        assert_eq!(view.deck_odds.is_some(), deck_odds);
+    }
+
+    // a full deck holds 6 liberals and 11 facists, and all 3 drawn are facist in 165 of 680 draws
+    let odds = DeckOdds::new(0, 0);
+    assert_eq!((odds.liberals_left, odds.facists_left), (6, 11));
+    assert!((odds.at_least_one_liberal - (1.0 - 165.0 / 680.0)).abs() < 1e-9);
+    assert!((odds.all_liberal - 20.0 / 680.0).abs() < 1e-9);
+    assert!((odds.expected_liberals - 18.0 / 17.0).abs() < 1e-9);
+    assert!(DeckOdds::new(4, 0).at_least_one_liberal < odds.at_least_one_liberal);
+}
+
+#[test]
+fn test_host_migration() {
+    let (ptx, prx) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+
+    let mut state = GameState::new();
+    let ids: Vec<Uuid> = (0..6).map(|_| Uuid::new_v4()).collect();
+    let since = SystemTime::now();
+    for (i, id) in ids.iter().enumerate() {
+        let mut conn = PlayerConnection::new(ptx.clone());
+        conn.connected_since = since + Duration::from_secs(i as u64);
+        state.add_player(*id, conn);
+    }
+    let host = |state: &GameState| serde_json::to_value(GameStatePlayerView { state, player: ids[5] }).unwrap()["host"].clone();
+    assert_eq!(host(&state), ids[0].to_string());
+
+    // the player who has been connected longest takes over when the host leaves the lobby
+    while prx.try_recv().is_ok() {}
+    state.remove_player(ids[0]);
+    assert_eq!(host(&state), ids[1].to_string());
+    let changes: Vec<serde_json::Value> = prx.try_iter().map(|message| serde_json::from_str::<serde_json::Value>(&message).unwrap()).filter(|message| message["type"] == "HostChanged").collect();
+    assert!(!changes.is_empty());
+    assert!(changes.iter().all(|change| change["host"] == ids[1].to_string()));
+
+    // and during a game, passing over players who are away
+    state.apply(ids[1], Action::Start).unwrap();
+    state.remove_player(ids[2]);
+    assert_eq!(host(&state), ids[1].to_string());
+    state.remove_player(ids[1]);
+    assert_eq!(host(&state), ids[3].to_string());
+}
+
+#[test]
+fn test_apply_actions() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+
+    let mut state = GameState::new();
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    for id in ids.iter() {
+        state.add_player(*id, PlayerConnection::new(ptx.clone()));
+    }
+
+    // actions out of turn are refused before anything else is looked at
+    assert_eq!(state.apply(ids[1], Action::Vote { approve: true }).err(), Some(GameError::WrongPhase));
+    assert_eq!(state.apply(ids[1], Action::Start).err(), Some(GameError::NotHost { action: "start the game" }));
+    let events = state.apply(ids[0], Action::Start).unwrap();
+    assert!(matches!(events.as_slice(), [GameEvent::GameStarted { .. }]));
+    assert_eq!(state.apply(ids[0], Action::Start).err(), Some(GameError::AlreadyStarted));
+
+    // the events caused by an action are returned, and only once it completes
+    let president = state.president().unwrap();
+    let chancellor = *ids.iter().find(|id| **id != president).unwrap();
+    assert!(state.apply(president, Action::Nominate { chancellor }).unwrap().is_empty());
+    for id in ids.iter().skip(1) {
+        assert!(state.apply(*id, Action::Vote { approve: false }).unwrap().is_empty());
+    }
+    let events = state.apply(ids[0], Action::Vote { approve: false }).unwrap();
+    assert!(matches!(events.as_slice(), [GameEvent::VoteHeld { elected: false, .. }, GameEvent::ElectionTrackerAdvanced { value: 1, .. }]));
+    assert!(matches!(state.turn_phase(), TurnPhase::Electing));
+
+    // the transition table only allows the moves the rules make
+    assert!(TurnPhase::Lobby.can_become(&TurnPhase::Electing));
+    assert!(!TurnPhase::Lobby.can_become(&TurnPhase::Voting));
+    assert!(!TurnPhase::PresidentSelect.can_become(&TurnPhase::Electing));
+    assert!(Action::RequestVeto.allowed_in(&TurnPhase::ChancellorSelect));
+    assert!(!Action::RespondVeto { accept: true }.allowed_in(&TurnPhase::PresidentSelect));
+}
+
+#[test]
+fn test_rebuild_from_history() {
+    for seed in 0..10 {
+        let game = Simulation::new(Some(seed)).play(7);
+        let rebuilt = game.rebuild().expect("a started game has a history");
+        assert!(game.history().commands().len() < 50);
+
+        // the game rebuilt from its snapshot and log looks the same to every player
+        let events = |state: &GameState| serde_json::to_value(state.timeline().iter().map(|entry| &entry.event).collect::<Vec<_>>()).unwrap();
+        assert_eq!(events(&rebuilt), events(&game));
+        for player in game.living_players().iter().chain(rebuilt.living_players()) {
+            let view = |state| serde_json::to_value(GameStatePlayerView { state, player: *player }).unwrap();
+            assert_eq!(view(&rebuilt), view(&game));
+        }
+    }
+    assert!(GameState::new().rebuild().is_none());
+}
+
+#[test]
+fn test_undo_last_action() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+
+    for ranked in [false, true] {
+        let mut state = GameState::with_options(GameOptions { ranked, ..GameOptions::default() });
+        let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+        for id in ids.iter() {
+            state.add_player(*id, PlayerConnection::new(ptx.clone()));
+        }
+        let now = SystemTime::now();
+        state.apply(ids[0], Action::Start).unwrap();
+        if ranked {
+            assert_eq!(state.request_undo(ids[0], now), Err(GameError::UndoDisabled));
+            continue
+        }
+        assert_eq!(state.request_undo(ids[0], now), Err(GameError::NothingToUndo));
+        assert_eq!(state.request_undo(ids[1], now), Err(GameError::NotHost { action: "undo an action" }));
+
+        // the president misclicks a nomination, which both they and the nominee have to agree to take back
+        let president = state.president().unwrap();
+        let others: Vec<Uuid> = ids.iter().copied().filter(|id| *id != president && *id != ids[0]).collect();
+        state.apply(president, Action::Nominate { chancellor: others[0] }).unwrap();
+        assert!(state.request_undo(ids[0], now).is_ok());
+        assert!(matches!(state.turn_phase(), TurnPhase::Voting));
+        assert_eq!(state.cast_lobby_vote(others[1], true, now), Err(GameError::NotAPlayer));
+        assert!(state.cast_lobby_vote(others[0], true, now).is_ok());
+        if president != ids[0] {
+            assert!(matches!(state.turn_phase(), TurnPhase::Voting));
+            assert!(state.cast_lobby_vote(president, true, now).is_ok());
+        }
+        assert!(matches!(state.turn_phase(), TurnPhase::Electing));
+        assert_eq!(state.chancellor(), None);
+        assert!(state.history().commands().is_empty());
+        assert!(state.conn.values().all(|conn| conn.connected));
+
+        // the game carries on from the rewound state
+        state.apply(president, Action::Nominate { chancellor: others[1] }).unwrap();
+        assert_eq!(state.chancellor(), Some(others[1]));
+
+        // a refused undo leaves the game as it is
+        assert!(state.request_undo(ids[0], now).is_ok());
+        assert!(state.cast_lobby_vote(others[1], false, now).is_ok());
+        assert_eq!(state.chancellor(), Some(others[1]));
+    }
+}
+
+#[test]
+fn test_undo_across_snapshot() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+    let mut state = GameState::with_options(GameOptions { max_players: Some(5), ..GameOptions::default() });
+    let ids: Vec<Uuid> = (0..6).map(|_| Uuid::new_v4()).collect();
+    let secret = Uuid::new_v4();
+    for id in ids.iter() {
+        let mut conn = PlayerConnection::new(ptx.clone());
+        conn.secret = Some(if *id == ids[5] { secret } else { Uuid::new_v4() });
+        state.add_player(*id, conn);
+    }
+    let now = SystemTime::now();
+    state.apply(ids[0], Action::Start).unwrap();
+
+    // a spectator removed during the game stays removed when an action is undone
+    assert!(state.kick(ids[5]).is_ok());
+    let mut returning = PlayerConnection::new(ptx.clone());
+    returning.secret = Some(secret);
+    assert!(state.is_banned(&returning));
+
+    // fail elections until the history takes a new snapshot, which happens right after a vote
+    let voter = 'elections: loop {
+        let president = state.president().unwrap();
+        for chancellor in ids[..5].iter().copied() {
+            if state.apply(president, Action::Nominate { chancellor }).is_ok() {
+                break
+            }
+        }
+        for id in ids[..5].iter() {
+            state.apply(*id, Action::Vote { approve: false }).unwrap();
+            if state.history().commands().is_empty() {
+                break 'elections *id
+            }
+        }
+    };
+
+    // the vote that set off the snapshot can still be undone
+    assert!(state.request_undo(ids[0], now).is_ok());
+    if voter != ids[0] {
+        assert!(state.cast_lobby_vote(voter, true, now).is_ok());
+    }
+    assert_eq!(state.history().commands().len(), 49);
+    assert!(matches!(state.turn_phase(), TurnPhase::Voting));
+    assert!(!state.has_player(&ids[5]));
+    assert!(state.is_banned(&returning));
+}
+
+#[test]
+fn test_simulated_games_finish() {
+    let mut timelines = vec![];
+    let report = Simulation::new(None).run(7, 20, |_, game| timelines.push(serde_json::to_string(game.timeline()).unwrap()));
+    assert_eq!(report.games, 20);
+    assert_eq!(report.unfinished, 0);
+    assert_eq!(report.liberal_wins + report.facist_wins, 20);
+    assert_eq!(timelines.len(), 20);
+
+    // saved timelines can be read back for replays
+    let timeline: Vec<TimelineEntry> = serde_json::from_str(&timelines[0]).unwrap();
+    assert!(timeline.iter().any(|entry| matches!(entry.event, GameEvent::PolicyEnacted { .. })));
+}
+
+#[test]
+fn test_analysis() {
+    for seed in 0..10 {
+        let game = Simulation::new(Some(seed)).play(7);
+        let analysis = Analysis::new(game.timeline()).unwrap();
+        assert_eq!(analysis.players.len(), 7);
+        assert_eq!(analysis.steps.len(), game.timeline().len());
+        for step in &analysis.steps {
+            assert!((step.facist.values().sum::<f64>() - 3.0).abs() < 0.01);
+            assert!((step.hitler.values().sum::<f64>() - 1.0).abs() < 0.01);
+        }
+
+        // a player executed without ending the game cannot have been Hitler
+        let executions = game.timeline().iter().enumerate().filter_map(|(i, entry)| match entry.event {
+            GameEvent::PowerUsed { power: PresidentialPower::Execution, target: Some(target), .. } if i + 1 < game.timeline().len() => Some((i, target)),
+            _ => None
+        });
+        for (i, target) in executions {
+            assert_eq!(analysis.steps[i].hitler[&target], 0.0);
+        }
+    }
+}
+
+#[test]
+fn test_hitler_elected() {
+    let mut found = false;
+    for seed in 0..200 {
+        let game = Simulation::new(Some(seed)).play(5 + seed as usize % 6);
+        let events: Vec<&GameEvent> = game.timeline().iter().map(|entry| &entry.event).collect();
+        let reveal = events.iter().position(|event| matches!(event, GameEvent::HitlerElected { .. }));
+        let i = match reveal {
+            Some(i) => i,
+            None => continue
+        };
+        found = true;
+
+        // the reveal ends the game, right after the vote that elected Hitler
+        assert_eq!(i, events.len() - 1);
+        assert_eq!(game.winner(), Some(CardColor::Facist));
+        let (president, hitler, facist_policies, required) = match (events[i - 1], events[i]) {
+            (GameEvent::VoteHeld { president, chancellor, votes, elected: true }, GameEvent::HitlerElected { president: p, hitler, votes: record, ayes, nays, facist_policies, required }) => {
+                assert_eq!((president, chancellor, votes), (p, hitler, record));
+                assert_eq!(votes.values().filter(|vote| **vote).count(), *ayes);
+                assert_eq!(votes.len(), ayes + nays);
+                (*president, *hitler, *facist_policies, *required)
+            },
+            _ => panic!("Hitler was revealed without being elected")
+        };
+        assert_eq!(game.president(), Some(president));
+        assert!(matches!(game.role(&hitler), Some(PlayerType::Hitler)));
+        assert!(facist_policies >= required);
+
+        // replays can tell who Hitler was
+        let analysis = Analysis::new(game.timeline()).unwrap();
+        assert_eq!(analysis.steps.last().unwrap().hitler[&hitler], 1.0);
+    }
+    assert!(found);
+}
+
+#[test]
+fn test_hitler_chancellor_threshold() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+    let mut state = GameState::new();
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    for id in ids.iter() {
+        state.add_player(*id, PlayerConnection::new(ptx.clone()));
+    }
+    state.apply(ids[0], Action::Start).unwrap();
+    let hitler = *ids.iter().find(|id| matches!(state.role(id), Some(PlayerType::Hitler))).unwrap();
+    let president = *ids.iter().find(|id| **id != hitler).unwrap();
+    let export = serde_json::to_value(state.export().unwrap()).unwrap();
+
+    // elect Hitler on a board with some facist policies, under a threshold picked by the host
+    let elect_hitler = |facist_policies: u8, threshold: Option<u8>| {
+        let mut export = export.clone();
+        export["facist_policies"] = facist_policies.into();
+        export["president"] = serde_json::to_value(president).unwrap();
+        export["options"]["hitler_chancellor_policies"] = serde_json::to_value(threshold).unwrap();
+        let mut state = GameState::import(serde_json::from_value(export).unwrap());
+        state.apply(president, Action::Nominate { chancellor: hitler }).unwrap();
+        for id in ids.iter() {
+            state.apply(*id, Action::Vote { approve: true }).unwrap();
+        }
+        (state.winner(), state.rules().hitler_chancellor_policies)
+    };
+
+    // three facist policies are enough by default, as in the official rules
+    assert_eq!(Rules::new(5).hitler_chancellor_policies, 3);
+    assert_eq!(elect_hitler(2, None), (None, 3));
+    assert_eq!(elect_hitler(3, None), (Some(CardColor::Facist), 3));
+
+    // house rules can move the threshold, within what the board allows
+    assert_eq!(elect_hitler(3, Some(4)), (None, 4));
+    assert_eq!(elect_hitler(4, Some(4)), (Some(CardColor::Facist), 4));
+    assert_eq!(elect_hitler(1, Some(1)), (Some(CardColor::Facist), 1));
+    assert_eq!(elect_hitler(4, Some(9)), (None, 5));
+    assert_eq!(elect_hitler(0, Some(0)), (None, 1));
+}
+
+#[test]
+fn test_executions_mid_rotation() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+    let mut state = GameState::new();
+    let ids: Vec<Uuid> = (0..7).map(|_| Uuid::new_v4()).collect();
+    for (i, id) in ids.iter().enumerate() {
+        let mut conn = PlayerConnection::new(ptx.clone());
+        conn.name = Some(format!("player {}", i));
+        state.add_player(*id, conn);
+    }
+    state.apply(ids[0], Action::Start).unwrap();
+    let order = state.living_players().to_vec();
+    let name = |player: Uuid| format!("player {}", ids.iter().position(|id| *id == player).unwrap());
+    let mut export = serde_json::to_value(state.export().unwrap()).unwrap();
+    for player in export["players"].as_object_mut().unwrap().values_mut() {
+        player["role"] = "Liberal".into();
+    }
+
+    // the president at a place in the turn order, which differs from the rotation during a special election, executes a player
+    let execute = |president: usize, rotation: usize, chancellor: usize, target: usize| {
+        let mut export = export.clone();
+        export["turn_phase"] = serde_json::json!({ "type": "PresidentialPower", "power": "Execution" });
+        export["president"] = serde_json::to_value(order[president]).unwrap();
+        export["chancellor"] = serde_json::to_value(order[chancellor]).unwrap();
+        export["rotation"] = rotation.into();
+        let mut state = GameState::import(serde_json::from_value(export).unwrap());
+        state.apply(order[president], Action::UsePower { target: Some(order[target]) }).unwrap();
+        let view: PlayerView = serde_json::from_str(&serde_json::to_string(&GameStatePlayerView { player: order[president], state: &state }).unwrap()).unwrap();
+        assert!(!view.turn_order.contains(&order[target]));
+        assert!(matches!(view.turn_phase, TurnPhase::Electing));
+        // the announcement names the president who gave the order, not the one who takes over
+        assert_eq!(state.chat_log.back().unwrap().message, format!("President {} has killed {}.", name(order[president]), name(order[target])));
+        (view.president.unwrap(), view.last_president, view.last_chancellor)
+    };
+
+    // the presidency moves to the next living seat, whoever was executed
+    assert_eq!(execute(1, 1, 4, 0).0, order[2]);
+    assert_eq!(execute(1, 1, 4, 2).0, order[3]);
+    assert_eq!(execute(3, 3, 4, 1).0, order[4]);
+    assert_eq!(execute(6, 6, 2, 5).0, order[0]);
+    assert_eq!(execute(6, 6, 2, 0).0, order[1]);
+
+    // after a special election it goes back to the seat after the president who called it, even if they were executed
+    assert_eq!(execute(4, 1, 5, 6).0, order[2]);
+    assert_eq!(execute(4, 1, 5, 1).0, order[2]);
+    assert_eq!(execute(2, 6, 3, 6).0, order[0]);
+
+    // an executed member of the last government is no longer term limited
+    assert_eq!(execute(1, 1, 4, 4), (order[2], Some(order[1]), None));
+    assert_eq!(execute(1, 1, 4, 3), (order[2], Some(order[1]), Some(order[4])));
+    assert_eq!(execute(4, 1, 1, 1), (order[2], Some(order[4]), None));
+}
+
+#[test]
+fn test_vote_after_execution() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+    let mut state = GameState::new();
+    let ids: Vec<Uuid> = (0..6).map(|_| Uuid::new_v4()).collect();
+    for id in ids.iter() {
+        state.add_player(*id, PlayerConnection::new(ptx.clone()));
+    }
+    state.apply(ids[0], Action::Start).unwrap();
+    let order = state.living_players().to_vec();
+    let mut export = serde_json::to_value(state.export().unwrap()).unwrap();
+    for player in export["players"].as_object_mut().unwrap().values_mut() {
+        player["role"] = "Liberal".into();
+    }
+    export["president"] = serde_json::to_value(order[0]).unwrap();
+    export["players"][order[5].to_string()]["dead"] = true.into();
+    let mut state = GameState::import(serde_json::from_value(export).unwrap());
+
+    // three of the five living players approve, and the executed player does not count against the government
+    state.apply(order[0], Action::Nominate { chancellor: order[1] }).unwrap();
+    for (i, id) in order[..5].iter().enumerate() {
+        state.apply(*id, Action::Vote { approve: i < 3 }).unwrap();
+    }
+    assert!(matches!(state.timeline().last().unwrap().event, GameEvent::VoteHeld { elected: true, .. }));
+    assert!(matches!(state.turn_phase(), TurnPhase::PresidentSelect));
+}
+
+#[test]
+fn test_game_builder() {
+    let ids: Vec<Uuid> = (0..11).map(|_| Uuid::new_v4()).collect();
+    let build = |seed| GameState::builder().options(GameOptions { anonymous_votes: true, ..GameOptions::default() }).players(ids.clone()).seed(seed).build();
+
+    // players are seated in order without a connection, and the table's overflow waits
+    let mut game = build(4);
+    assert!(game.options.anonymous_votes);
+    assert!(ids.iter().all(|id| game.has_player(id)));
+    assert_eq!(game.act_as(ids[1]).start().err(), Some(GameError::NotHost { action: "start the game" }));
+    let events = game.act_as(ids[0]).start().unwrap();
+    assert!(matches!(&events[..], [GameEvent::GameStarted { players, .. }] if players.len() == 10 && !players.contains(&ids[10])));
+
+    // every move is a method returning its events
+    let president = game.president().unwrap();
+    let chancellor = *game.living_players().iter().find(|id| **id != president).unwrap();
+    assert!(game.act_as(president).nominate(chancellor).unwrap().is_empty());
+    assert_eq!(game.act_as(president).pick_card(0).err(), Some(GameError::WrongPhase));
+    let mut events = vec![];
+    for id in game.living_players().to_vec() {
+        events = game.act_as(id).vote(false).unwrap();
+    }
+    assert!(matches!(events.as_slice(), [GameEvent::VoteHeld { elected: false, .. }, GameEvent::ElectionTrackerAdvanced { value: 1, .. }]));
+
+    // the same seed deals the same game
+    let deal = |game: &GameState| (game.living_players().to_vec(), ids.iter().map(|id| serde_json::to_value(game.role(id)).unwrap()).collect::<Vec<_>>());
+    let (mut first, mut second) = (build(9), build(9));
+    first.act_as(ids[0]).start().unwrap();
+    second.act_as(ids[0]).start().unwrap();
+    assert_eq!(deal(&first), deal(&second));
+}
+
+#[test]
+fn test_seats_without_transport() {
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    let posted = Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let log = posted.clone();
+    let chat_sink = move |message: String| {
+        log.lock().unwrap().push(message);
+        Ok(())
+    };
+
+    // one seat posts its messages through a function, and another has no transport at all
+    let mut game = GameState::builder().players(ids[..3].to_vec()).player(ids[3], PlayerConnection::new(Arc::new(chat_sink))).player(ids[4], PlayerConnection::detached()).build();
+    assert!(!game.conn[&ids[4]].connected);
+    game.act_as(ids[0]).start().unwrap();
+    game.broadcast_game_state();
+    assert!(posted.lock().unwrap().iter().any(|message| message.contains("\"GameState\"")));
+    let view: PlayerView = serde_json::from_str(&serde_json::to_string(&GameStatePlayerView { player: ids[0], state: &game }).unwrap()).unwrap();
+    assert_eq!(view.players.len(), 5);
+
+    // the seat without a transport gets everything it missed once one is attached
+    let (tx, rx) = mpsc::channel();
+    game.conn[&ids[4]].tx.attach(Arc::new(tx));
+    let missed: Vec<serde_json::Value> = rx.try_iter().map(|message| serde_json::from_str(&message).unwrap()).collect();
+    assert!(missed.iter().any(|message| message["type"] == "GameState"));
+}
+
+#[test]
+fn test_seeded_simulation() {
+    let play = |seed| {
+        let game = Simulation::new(Some(seed)).play(8);
+        (serde_json::to_value(game.summary()).unwrap(), game.rounds(), serde_json::to_value(game.timeline().iter().map(|e| &e.event).collect::<Vec<_>>()).unwrap())
+    };
+    // the same seed plays out the same game
+    assert_eq!(play(7), play(7));
+    assert!((0..10).any(|seed| play(seed) != play(7)));
+}
+
+/// The `type` of every message in a protocol schema.
+fn message_types(schema: &RootSchema) -> Vec<String> {
+    let schema = serde_json::to_value(schema).unwrap();
+    schema["oneOf"].as_array().unwrap().iter().map(|variant| variant["properties"]["type"]["enum"][0].as_str().unwrap().to_string()).collect()
+}
+
+#[test]
+fn test_protocol_schema() {
+    let server_types = message_types(&schema::server_schema());
+    let client_types = message_types(&schema::client_schema());
+    assert!(server_types.contains(&"GameState".to_string()) && client_types.contains(&"HostGame".to_string()));
+
+    // play games to the end, checking every view and message against the schema along the way
+    let mut rng = StdRng::seed_from_u64(7);
+    for players in [5, 7, 10] {
+        let (ptx, prx) = mpsc::channel();
+        let ptx = Arc::new(ptx);
+        let mut state = GameState::with_seed(GameOptions { max_players: Some(players), discussion_timer: Some(30), ..GameOptions::default() }, rng.gen());
+        let ids: Vec<Uuid> = (0..players).map(|_| Uuid::new_v4()).collect();
+        for id in &ids {
+            state.add_player(*id, PlayerConnection::new(ptx.clone()));
+        }
+        state.apply(ids[0], Action::Start).unwrap();
+        for _ in 0..500 {
+            state.broadcast_game_state();
+            for message in prx.try_iter() {
+                let message: serde_json::Value = serde_json::from_str(&message).unwrap();
+                let kind = message["type"].as_str().unwrap();
+                assert!(server_types.iter().any(|known| known == kind), "{} is missing from the schema", kind);
+                if kind == "GameState" {
+                    if let Err(e) = serde_json::from_value::<PlayerView>(message["state"].clone()) {
+                        panic!("the player view does not match its schema: {}", e);
+                    }
+                }
+            }
+            let president = state.president().unwrap_or(ids[0]);
+            let mut living = state.living_players().to_vec();
+            living.shuffle(&mut rng);
+            match state.turn_phase().clone() {
+                TurnPhase::Ended { .. } => break,
+                TurnPhase::Electing => {
+                    living.iter().any(|chancellor| state.apply(president, Action::Nominate { chancellor: *chancellor }).is_ok());
+                },
+                TurnPhase::Discussion => for player in state.awaiting() {
+                    state.apply(player, Action::CallVote).unwrap();
+                },
+                TurnPhase::Voting => for player in living {
+                    state.apply(player, Action::Vote { approve: rng.gen_bool(0.7) }).unwrap();
+                },
+                TurnPhase::PresidentSelect | TurnPhase::ChancellorSelect => {
+                    let player = if matches!(state.turn_phase(), TurnPhase::PresidentSelect) { president } else { state.chancellor().unwrap() };
+                    let index = rng.gen_range(0..state.hand(player).unwrap().len());
+                    state.apply(player, Action::PickCard { index }).unwrap();
+                },
+                TurnPhase::PresidentialPower { .. } => {
+                    let targets = std::iter::once(None).chain(living.iter().map(|target| Some(*target)));
+                    targets.into_iter().any(|target| state.apply(president, Action::UsePower { target }).is_ok());
+                },
+                TurnPhase::Lobby => unreachable!(),
+            }
+        }
+        assert!(state.winner().is_some());
+    }
+
+    // client messages read back the same as they were written
+    let host = Uuid::new_v4();
+    let messages = vec![
+        serde_json::json!({ "type": "HostGame", "nickname": "alice", "options": GameOptions::default(), "avatar": null, "color": null, "player_secret": host, "preset": null, "captcha": null }),
+        serde_json::json!({ "type": "CallLobbyVote", "motion": { "type": "Kick", "player": host }, "request_id": "1" }),
+        serde_json::json!({ "type": "PickCard", "index": 2, "request_id": null }),
+        serde_json::json!({ "type": "CallVote", "request_id": "2" }),
+        serde_json::json!({ "type": "Claim", "cards": ["Facist", "Liberal"], "request_id": null }),
+        serde_json::json!({ "type": "Subscribe", "topics": ["Scoreboard"] }),
+        serde_json::json!({ "type": "Ban", "admin_token": "admin", "target": { "type": "Address", "range": "203.0.113.0/24" }, "reason": "spam", "expires_at": 1 }),
+    ];
+    for message in messages {
+        let parsed: ClientProtocol = serde_json::from_value(message.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), message);
+        assert_eq!(parsed.kind(), message["type"]);
+        assert!(client_types.iter().any(|known| *known == message["type"]));
+    }
+}
+
+#[test]
+fn test_pass_and_play() {
+    let names: Vec<String> = (0..5).map(|i| format!("player {}", i)).collect();
+    assert!(PassAndPlay::new(GameOptions::default(), names[..3].to_vec()).is_err());
+    let mut game = PassAndPlay::new(GameOptions::default(), names).unwrap();
+    let turn_order = game.state().living_players().to_vec();
+
+    // nobody's role shows while the device is down, and each player is shown theirs in turn
+    let view = |game: &PassAndPlay| -> PlayerView { serde_json::from_str(&serde_json::to_string(&game.view()).unwrap()).unwrap() };
+    assert!(view(&game).players.values().all(|seat| seat.role.is_none()));
+    for player in &turn_order {
+        assert_eq!(game.next(), Some(*player));
+        assert!(matches!(game.apply(Action::Vote { approve: true }), Err(GameError::NotHoldingDevice)));
+        game.hand_to(*player).unwrap();
+        assert!(view(&game).players[player].role.is_some());
+        assert_eq!(game.next(), None);
+        game.hide();
+    }
+
+    // then the device goes to whoever the game is waiting on
+    let president = game.state().president().unwrap();
+    assert_eq!(game.next(), Some(president));
+    assert_eq!(game.hand_to(turn_order[1]).unwrap_err(), GameError::NotHoldingDevice);
+    game.hand_to(president).unwrap();
+    let chancellor = *turn_order.iter().find(|player| **player != president).unwrap();
+    game.apply(Action::Nominate { chancellor }).unwrap();
+    game.hide();
+    for player in &turn_order {
+        assert_eq!(game.next(), Some(*player));
+        game.hand_to(*player).unwrap();
+        game.apply(Action::Vote { approve: true }).unwrap();
+        game.hide();
+        if matches!(game.state().turn_phase(), TurnPhase::Voting) {
+            assert!(view(&game).players.values().all(|seat| seat.vote.is_none()));
+        }
+    }
+    assert!(matches!(game.state().turn_phase(), TurnPhase::PresidentSelect));
+    assert_eq!(game.next(), Some(president));
+    game.hand_to(president).unwrap();
+    assert_eq!(view(&game).cards.map(|cards| cards.len()), Some(3));
+    game.hide();
+    assert!(view(&game).cards.is_none());
+}
+
+#[test]
+fn test_discussion_phase() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+    let mut state = GameState::with_options(GameOptions { discussion_timer: Some(60), ..GameOptions::default() });
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    for id in ids.iter() {
+        state.add_player(*id, PlayerConnection::new(ptx.clone()));
+    }
+    state.apply(ids[0], Action::Start).unwrap();
+
+    // a nomination opens a discussion rather than the vote
+    let president = state.president().unwrap();
+    let others: Vec<Uuid> = ids.iter().copied().filter(|id| *id != president).collect();
+    state.apply(president, Action::Nominate { chancellor: others[0] }).unwrap();
+    assert!(matches!(state.turn_phase(), TurnPhase::Discussion));
+    assert!(matches!(state.apply(others[1], Action::Vote { approve: true }), Err(GameError::WrongPhase)));
+    let view: PlayerView = serde_json::from_str(&serde_json::to_string(&GameStatePlayerView { player: others[1], state: &state }).unwrap()).unwrap();
+    assert_eq!(view.vote_called, Some(vec![]));
+    assert!(view.phase_deadline.is_some());
+
+    // both members of the government have to agree to vote early
+    assert!(matches!(state.apply(others[1], Action::CallVote), Err(GameError::NotInGovernment { .. })));
+    state.apply(president, Action::CallVote).unwrap();
+    assert!(matches!(state.turn_phase(), TurnPhase::Discussion));
+    assert_eq!(state.awaiting(), vec![others[0]]);
+    let view: PlayerView = serde_json::from_str(&serde_json::to_string(&GameStatePlayerView { player: others[1], state: &state }).unwrap()).unwrap();
+    assert_eq!(view.vote_called, Some(vec![president]));
+    state.apply(others[0], Action::CallVote).unwrap();
+    assert!(matches!(state.turn_phase(), TurnPhase::Voting));
+    for id in ids.iter() {
+        state.apply(*id, Action::Vote { approve: false }).unwrap();
+    }
+
+    // otherwise the vote opens once the timer runs out, and the rebuilt game agrees
+    let (last_president, president) = (president, state.president().unwrap());
+    let chancellor = *ids.iter().find(|id| **id != president && **id != last_president).unwrap();
+    state.apply(president, Action::Nominate { chancellor }).unwrap();
+    let now = SystemTime::now();
+    assert!(!state.expire_discussion(now));
+    assert!(state.expire_discussion(now + Duration::from_secs(61)));
+    assert!(matches!(state.turn_phase(), TurnPhase::Voting));
+    assert!(matches!(state.rebuild().unwrap().turn_phase(), TurnPhase::Voting));
+}
+
+#[test]
+fn test_policy_claims() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+    let mut state = GameState::new();
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    for id in ids.iter() {
+        state.add_player(*id, PlayerConnection::new(ptx.clone()));
+    }
+    state.apply(ids[0], Action::Start).unwrap();
+    let president = state.president().unwrap();
+    let chancellor = *ids.iter().find(|id| **id != president).unwrap();
+    let bystander = *ids.iter().find(|id| **id != president && **id != chancellor).unwrap();
+    assert_eq!(state.claim(president, vec![CardColor::Liberal; 3]), Err(GameError::WrongPhase));
+
+    state.apply(president, Action::Nominate { chancellor }).unwrap();
+    for id in ids.iter() {
+        state.apply(*id, Action::Vote { approve: true }).unwrap();
+    }
+    state.apply(president, Action::PickCard { index: 0 }).unwrap();
+    let enact = state.hand(chancellor).unwrap()[0];
+    state.apply(chancellor, Action::PickCard { index: 0 }).unwrap();
+
+    // each member of the government claims once, with a hand of the right size, whether or not it is the truth
+    assert_eq!(state.claim(bystander, vec![CardColor::Liberal; 3]), Err(GameError::NotInGovernment { action: "claim the policies of the last government" }));
+    assert_eq!(state.claim(chancellor, vec![CardColor::Facist; 3]), Err(GameError::InvalidClaim));
+    state.claim(chancellor, vec![CardColor::Facist; 2]).unwrap();
+    assert_eq!(state.claim(chancellor, vec![CardColor::Liberal; 2]), Err(GameError::AlreadyClaimed));
+    state.claim(president, vec![CardColor::Facist, CardColor::Facist, CardColor::Liberal]).unwrap();
+    let government = &state.governments()[0];
+    assert_eq!((government.president, government.chancellor, government.enacted), (president, chancellor, enact));
+    assert_eq!(government.chancellor_claim, Some(vec![CardColor::Facist; 2]));
+    assert!(state.timeline().iter().any(|entry| matches!(&entry.event, GameEvent::PoliciesClaimed { player, as_president: true, .. } if *player == president)));
+
+    // claims are part of the game's history and its view
+    assert_eq!(state.rebuild().unwrap().governments()[0].president_claim, state.governments()[0].president_claim);
+    let view: PlayerView = serde_json::from_str(&serde_json::to_string(&GameStatePlayerView { player: bystander, state: &state }).unwrap()).unwrap();
+    assert_eq!(view.governments[0].chancellor_claim, Some(vec![CardColor::Facist; 2]));
+}
+
+#[test]
+fn test_election_results() {
+    for anonymous in [false, true] {
+        let (ptx, prx) = mpsc::channel();
+        let ptx = Arc::new(ptx);
+        let mut state = GameState::with_options(GameOptions { anonymous_votes: anonymous, ..GameOptions::default() });
+        let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+        for id in ids.iter() {
+            state.add_player(*id, PlayerConnection::new(ptx.clone()));
+        }
+        state.apply(ids[0], Action::Start).unwrap();
+        let president = state.president().unwrap();
+        let chancellor = *ids.iter().find(|id| **id != president).unwrap();
+        state.apply(president, Action::Nominate { chancellor }).unwrap();
+        while prx.try_recv().is_ok() {}
+        for (i, id) in ids.iter().enumerate() {
+            state.apply(*id, Action::Vote { approve: i < 3 }).unwrap();
+        }
+
+        // every player is told the result once, with each vote unless votes are anonymous
+        let results: Vec<serde_json::Value> = prx.try_iter().map(|message| serde_json::from_str::<serde_json::Value>(&message).unwrap()).filter(|message| message["type"] == "ElectionResult").collect();
+        assert_eq!(results.len(), ids.len());
+        assert_eq!((results[0]["ayes"].as_u64(), results[0]["nays"].as_u64(), results[0]["passed"].as_bool()), (Some(3), Some(2), Some(true)));
+        assert_eq!(results[0]["votes"].is_null(), anonymous);
+        if !anonymous {
+            assert_eq!(results[0]["votes"][ids[4].to_string()], false);
+        }
+        let view: PlayerView = serde_json::from_str(&serde_json::to_string(&GameStatePlayerView { player: ids[0], state: &state }).unwrap()).unwrap();
+        assert_eq!(view.players[&ids[4]].vote, if anonymous { None } else { Some(false) });
+        assert_eq!(view.players[&ids[0]].vote, Some(true));
+    }
+}
+
+#[test]
+fn test_pick_card_by_index() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+    let mut state = GameState::new();
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    for id in ids.iter() {
+        state.add_player(*id, PlayerConnection::new(ptx.clone()));
+    }
+    state.apply(ids[0], Action::Start).unwrap();
+    let president = state.president().unwrap();
+    let chancellor = *ids.iter().find(|id| **id != president).unwrap();
+    state.apply(president, Action::Nominate { chancellor }).unwrap();
+    let view = |state: &GameState, player: Uuid| -> PlayerView { serde_json::from_str(&serde_json::to_string(&GameStatePlayerView { player, state }).unwrap()).unwrap() };
+    assert_eq!(view(&state, president).cards_in_deck, 17);
+    for id in ids.iter() {
+        state.apply(*id, Action::Vote { approve: true }).unwrap();
+    }
+
+    // the government's hand is drawn from the deck by the vote
+    let session = state.legislative_session().unwrap();
+    assert_eq!((session.drawn.len(), session.stage), (3, LegislativeStage::President));
+    assert_eq!(view(&state, president).cards_in_deck, 14);
+    assert_eq!(view(&state, chancellor).cards, None);
+
+    // deal the president a mixed hand, with the top of the deck last
+    let mut export = serde_json::to_value(state.export().unwrap()).unwrap();
+    export["cards"] = serde_json::to_value(vec![CardColor::Liberal; 5]).unwrap();
+    export["legislative_session"]["drawn"] = serde_json::to_value([CardColor::Facist, CardColor::Liberal, CardColor::Facist]).unwrap();
+
+    let mut state = GameState::import(serde_json::from_value(export.clone()).unwrap());
+    assert_eq!(state.hand(president).unwrap(), vec![CardColor::Facist, CardColor::Liberal, CardColor::Facist]);
+    assert_eq!(state.apply(president, Action::PickCard { index: 3 }).err(), Some(GameError::InvalidPolicy));
+
+    // the chancellor is passed exactly the two cards the president kept, in the order they were drawn
+    state.apply(president, Action::PickCard { index: 0 }).unwrap();
+    assert_eq!(state.hand(chancellor).unwrap(), vec![CardColor::Liberal, CardColor::Facist]);
+    assert_eq!(state.hand(president), None);
+    assert_eq!(state.legislative_session().unwrap().discarded, vec![CardColor::Facist]);
+    assert_eq!(view(&state, chancellor).cards, Some(vec![CardColor::Liberal, CardColor::Facist]));
+    assert_eq!(state.apply(chancellor, Action::PickCard { index: 2 }).err(), Some(GameError::InvalidPolicy));
+    let events = state.apply(chancellor, Action::PickCard { index: 0 }).unwrap();
+    assert!(matches!(events.first(), Some(GameEvent::PolicyEnacted { policy: CardColor::Liberal, chaos: false, deck_position: 1 })));
+    assert!(state.legislative_session().is_none());
+    let after = view(&state, president);
+    assert_eq!((after.liberal_policies, after.cards_in_deck, after.cards_in_discard), (1, 5, 2));
+
+    // discarding the middle card leaves a hand of one color
+    let mut state = GameState::import(serde_json::from_value(export.clone()).unwrap());
+    state.apply(president, Action::PickCard { index: 1 }).unwrap();
+    assert_eq!(state.hand(chancellor).unwrap(), vec![CardColor::Facist; 2]);
+    let events = state.apply(chancellor, Action::PickCard { index: 1 }).unwrap();
+    assert!(matches!(events.first(), Some(GameEvent::PolicyEnacted { policy: CardColor::Facist, chaos: false, deck_position: 0 })));
+
+    // a veto discards both cards the chancellor held, even when it does not bring chaos
+    export["facist_policies"] = 5.into();
+    let mut state = GameState::import(serde_json::from_value(export).unwrap());
+    state.apply(president, Action::PickCard { index: 2 }).unwrap();
+    state.apply(chancellor, Action::RequestVeto).unwrap();
+    state.apply(president, Action::RespondVeto { accept: true }).unwrap();
+    let after = view(&state, president);
+    assert_eq!((after.election_tracker, after.cards_in_deck, after.cards_in_discard), (1, 5, 3));
+}
+
+#[test]
+fn test_veto_requests() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+    let mut state = GameState::new();
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    for (i, id) in ids.iter().enumerate() {
+        let mut conn = PlayerConnection::new(ptx.clone());
+        conn.name = Some(format!("player {}", i));
+        state.add_player(*id, conn);
+    }
+    state.apply(ids[0], Action::Start).unwrap();
+    let president = state.president().unwrap();
+    let chancellor = *ids.iter().find(|id| **id != president).unwrap();
+    state.apply(president, Action::Nominate { chancellor }).unwrap();
+    for id in ids.iter() {
+        state.apply(*id, Action::Vote { approve: true }).unwrap();
+    }
+    state.apply(president, Action::PickCard { index: 0 }).unwrap();
+    assert_eq!(state.apply(chancellor, Action::RequestVeto).err(), Some(GameError::VetoLocked));
+
+    // skip ahead to a board where the veto power is unlocked
+    let mut export = serde_json::to_value(state.export().unwrap()).unwrap();
+    export["facist_policies"] = 5.into();
+    let unlocked: GameExport = serde_json::from_value(export.clone()).unwrap();
+
+    let mut state = GameState::import(unlocked.clone());
+    assert_eq!(state.apply(president, Action::RequestVeto).err(), Some(GameError::NotChancellor { action: "ask for a veto" }));
+    assert_eq!(state.apply(president, Action::RespondVeto { accept: true }).err(), Some(GameError::NoVetoRequested));
+    let events = state.apply(chancellor, Action::RequestVeto).unwrap();
+    assert!(matches!(events.as_slice(), [GameEvent::VetoRequested { .. }]));
+    assert_eq!(state.apply(chancellor, Action::RequestVeto).err(), Some(GameError::VetoAlreadyRequested));
+    assert_eq!(state.awaiting(), vec![president]);
+    assert_eq!(state.apply(chancellor, Action::PickCard { index: 0 }).err(), Some(GameError::VetoPending));
+    let view: PlayerView = serde_json::from_str(&serde_json::to_string(&GameStatePlayerView { player: president, state: &state }).unwrap()).unwrap();
+    assert_eq!((view.veto_requested, view.veto_declined), (Some(true), Some(false)));
+
+    // an accepted veto fails the government
+    assert_eq!(state.apply(chancellor, Action::RespondVeto { accept: true }).err(), Some(GameError::NotPresident { action: "answer a request for a veto" }));
+    state.apply(president, Action::RespondVeto { accept: true }).unwrap();
+    assert!(matches!(state.turn_phase(), TurnPhase::Electing));
+    assert_eq!(state.scoreboard().election_tracker, 1);
+
+    // a declined veto leaves the chancellor to enact a policy, and they cannot ask again
+    let mut state = GameState::import(unlocked);
+    state.apply(chancellor, Action::RequestVeto).unwrap();
+    state.apply(president, Action::RespondVeto { accept: false }).unwrap();
+    assert_eq!(state.awaiting(), vec![chancellor]);
+    assert_eq!(state.apply(chancellor, Action::RequestVeto).err(), Some(GameError::VetoAlreadyRequested));
+    state.apply(chancellor, Action::PickCard { index: 0 }).unwrap();
+    assert_eq!(state.governments().len(), 1);
+
+    // a veto that throws the government into chaos does not credit the vetoed government with the top policy
+    export["election_tracker"] = (Rules::new(5).election_tracker_limit - 1).into();
+    let mut state = GameState::import(serde_json::from_value(export).unwrap());
+    state.apply(chancellor, Action::RequestVeto).unwrap();
+    let events = state.apply(president, Action::RespondVeto { accept: true }).unwrap();
+    assert!(events.iter().any(|event| matches!(event, GameEvent::PolicyEnacted { chaos: true, .. })));
+    let chat: Vec<String> = serde_json::to_value(state.export().unwrap()).unwrap()["chat_log"].as_array().unwrap().iter().map(|line| line["message"].as_str().unwrap().to_string()).collect();
+    assert!(chat.last().unwrap().starts_with("The government has been thrown into chaos!"));
+    assert!(!chat.iter().any(|line| line.contains("have enacted")));
+    assert!(state.governments().is_empty());
+}
+
+#[test]
+fn test_claim_conflicts() {
+    let (ptx, _) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+    let mut state = GameState::new();
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    for id in ids.iter() {
+        state.add_player(*id, PlayerConnection::new(ptx.clone()));
+    }
+    state.apply(ids[0], Action::Start).unwrap();
+    let president = state.president().unwrap();
+    let chancellor = *ids.iter().find(|id| **id != president).unwrap();
+    state.apply(president, Action::Nominate { chancellor }).unwrap();
+    for id in ids.iter() {
+        state.apply(*id, Action::Vote { approve: true }).unwrap();
+    }
+    state.apply(president, Action::PickCard { index: 0 }).unwrap();
+    let enact = state.hand(chancellor).unwrap()[0];
+    state.apply(chancellor, Action::PickCard { index: 0 }).unwrap();
+    let other = if enact == CardColor::Liberal { CardColor::Facist } else { CardColor::Liberal };
+
+    // the chancellor denies holding what they enacted, and the president denies passing what the chancellor claims
+    state.claim(chancellor, vec![other; 2]).unwrap();
+    assert_eq!(state.governments()[0].conflicts, vec![Conflict::ContradictsPolicy { player: chancellor }]);
+    state.claim(president, vec![enact; 3]).unwrap();
+    assert_eq!(state.governments()[0].conflicts, vec![Conflict::ContradictsPolicy { player: chancellor }, Conflict::Disagreement]);
+    assert_eq!(state.timeline().iter().filter(|entry| matches!(entry.event, GameEvent::ClaimConflict { .. })).count(), 2);
+
+    let analysis = Analysis::new(state.timeline()).unwrap();
+    assert_eq!(analysis.conflicts[&chancellor][&chancellor], 1);
+    assert_eq!(analysis.conflicts[&chancellor][&president], 1);
+    assert_eq!(analysis.conflicts[&president][&chancellor], 1);
+    assert!(!analysis.conflicts[&president].contains_key(&president));
+}
+
+#[test]
+fn test_view_versions() {
+    assert_eq!(negotiate_view_version(0), 1);
+    assert_eq!(negotiate_view_version(2), 2);
+    assert_eq!(negotiate_view_version(100), LATEST_VIEW_VERSION);
+
+    let (ptx, prx) = mpsc::channel();
+    let ptx = Arc::new(ptx);
+    let mut state = GameState::with_options(GameOptions { deck_odds: true, ..GameOptions::default() });
+    let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    for id in ids.iter() {
+        state.add_player(*id, PlayerConnection::new(ptx.clone()));
+    }
+    state.apply(ids[0], Action::Start).unwrap();
+
+    // the first version is the view as it has always been sent
+    let v1 = serde_json::to_value(VersionedPlayerView::new(1, ids[1], &state)).unwrap();
+    assert_eq!(v1, serde_json::to_value(GameStatePlayerView { player: ids[1], state: &state }).unwrap());
+
+    // the second adds the options and events to the same fields
+    let mut v2 = serde_json::to_value(VersionedPlayerView::new(2, ids[1], &state)).unwrap();
+    let extra: PlayerViewV2 = serde_json::from_value(v2.clone()).unwrap();
+    assert!(extra.options.deck_odds);
+    assert_eq!(extra.events.len(), state.timeline().len());
+    let fields = v2.as_object_mut().unwrap();
+    fields.remove("options");
+    fields.remove("events");
+    assert_eq!(v2, v1);
+
+    // each seat is sent the version its client asked for
+    prx.try_iter().for_each(drop);
+    state.conn.get_mut(&ids[2]).unwrap().view_version = 2;
+    state.send_game_state(ids[2]);
+    state.send_game_state(ids[3]);
+    let views: Vec<serde_json::Value> = prx.try_iter().map(|message| serde_json::from_str::<serde_json::Value>(&message).unwrap()["state"].clone()).collect();
+    assert!(views[0].get("events").is_some());
+    assert!(views[1].get("events").is_none());
+    serde_json::from_value::<PlayerView>(views[1].clone()).unwrap();
+}
+
+#[test]
+fn test_chat_sanitization() {
+    assert_eq!(chat::strip_html("<b>hi</b> <script>alert(1)</script>there<style>p {}</STYLE> <3"), "hi there <3");
+    assert_eq!(chat::strip_html("<img src=x onerror=alert(1)>a < b"), "a < b");
+    assert_eq!(chat::strip_html("before <SCRIPT>never closed"), "before ");
+
+    let text = |text: &str| ChatSegment::Text { text: text.into() };
+    let link = |text: &str, url: &str| ChatSegment::Link { text: text.into(), url: url.into() };
+    let any = LinkPolicy::any();
+    assert_eq!(chat::segments("see https://example.com/rules?x=1, or www.Example.org.", &any), vec![
+        text("see "), link("https://example.com/rules?x=1", "https://example.com/rules?x=1"), text(", or "), link("www.Example.org", "https://www.Example.org"), text("."),
+    ]);
+    assert_eq!(chat::segments("javascript:alert(1) http://evil.com@example.com http://localhost", &any), vec![text("javascript:alert(1) http://evil.com@example.com http://localhost")]);
+    assert_eq!(chat::segments("", &any), vec![]);
+
+    let only = LinkPolicy::only(vec!["Example.com".into()]);
+    assert_eq!(chat::segments("https://docs.example.com https://example.com.evil.net", &only), vec![link("https://docs.example.com", "https://docs.example.com"), text(" https://example.com.evil.net")]);
+    assert_eq!(chat::segments("https://example.com", &LinkPolicy::only(vec![])), vec![text("https://example.com")]);
+}
@@ -0,0 +1,29 @@
+#![cfg(feature = "wasm")]
+
+use secrethitler_core::{schema::PlayerView, game_state::TurnPhase, wasm::{Engine, action_allowed}};
+
+#[test]
+fn test_engine_plays_by_the_server_rules() {
+    let mut engine = Engine::new("{\"max_players\": 5}").unwrap();
+    let players: Vec<String> = (0..5).map(|i| engine.add_player(format!("player {}", i)).unwrap()).collect();
+    assert_eq!(engine.apply(&players[1], "{\"type\": \"Start\"}").unwrap_err(), "Only the host may start the game!");
+    engine.apply(&players[0], "{\"type\": \"Start\"}").unwrap();
+    assert!(engine.add_player("late".into()).is_err());
+
+    // the view is the same json the server sends, and only shows each player their own role
+    let view: PlayerView = serde_json::from_str(&engine.view(&players[0]).unwrap()).unwrap();
+    assert!(matches!(view.turn_phase, TurnPhase::Electing));
+    assert!(view.players[&players[0].parse().unwrap()].role.is_some());
+    let president = view.president.unwrap().to_string();
+    let chancellor = players.iter().find(|player| **player != president).unwrap();
+    let nominate = format!("{{\"type\": \"Nominate\", \"chancellor\": \"{}\"}}", chancellor);
+    assert!(engine.apply(chancellor, &nominate).is_err());
+    engine.apply(&president, &nominate).unwrap();
+    let view: PlayerView = serde_json::from_str(&engine.view(chancellor).unwrap()).unwrap();
+    assert!(matches!(view.turn_phase, TurnPhase::Voting));
+    assert!(engine.apply("not an id", "{\"type\": \"Vote\", \"approve\": true}").is_err());
+
+    assert!(action_allowed("{\"type\": \"Vote\", \"approve\": true}", "{\"type\": \"Voting\"}").unwrap());
+    assert!(!action_allowed("{\"type\": \"RequestVeto\"}", "{\"type\": \"Electing\"}").unwrap());
+    assert!(action_allowed("{\"type\": \"Dance\"}", "{\"type\": \"Voting\"}").is_err());
+}
@@ -6,10 +6,14 @@ import './App.css';
 
 import reactStringReplace from 'react-string-replace';
 
+// the path the server is mounted at, set with PUBLIC_URL when building
+const BASE_PATH = (process.env.PUBLIC_URL ?? "").replace(/\/$/, "");
+
 enum TurnPhase {
   INTRO = "Intro",
   LOBBY = "Lobby",
   ELECTING = "Electing",
+  DISCUSSION = "Discussion",
   VOTING = "Voting",
   PRESIDENT_SELECT = "PresidentSelect",
   CHANCELLOR_SELECT = "ChancellorSelect",
@@ -17,9 +21,18 @@ enum TurnPhase {
   ENDED = "Ended",
 }
 
-type ChatLine = { id?: Uuid, message: string };
+type ChatSegment = { type: "Text", text: string } | { type: "Link", text: string, url: string };
+type ChatLine = { id?: Uuid, message: string, segments?: ChatSegment[], attachment?: Uuid | null };
+
+// links come from the server already checked, and everything else is shown as plain text
+const ChatMessage = ({ line }: { line: ChatLine }): ReactElement => {
+  if (line.segments == null) {
+    return <>{line.message}</>
+  }
+  return <>{line.segments.map((segment, i) => segment.type === "Link" ? <a key={i} href={segment.url} target="_blank" rel="noopener noreferrer nofollow">{segment.text}</a> : <span key={i}>{segment.text}</span>)}</>
+}
 
-const ChatBox = ({ gameState, lines, onSubmit, playerId }: { playerId: Uuid, gameState: GameState, lines: ChatLine[], onSubmit: (line: string) => void }): ReactElement => {
+const ChatBox = ({ gameState, lines, onSubmit, onAttach, playerId }: { playerId: Uuid, gameState: GameState, lines: ChatLine[], onSubmit: (line: string) => void, onAttach: (file: File) => void }): ReactElement => {
   const [line, setLine] = useState<string>("");
   const chatOutput = useRef<HTMLDivElement>(null);
 
@@ -37,7 +50,7 @@ const ChatBox = ({ gameState, lines, onSubmit, playerId }: { playerId: Uuid, gam
         if (!showDead && gameState.players[l.id]?.dead) {
           return null
         }
-        return <div key={i} className="line"><b>{gameState.players[l.id]?.name ?? "Unknown"}</b> {l.message}</div>
+        return <div key={i} className="line"><b>{gameState.players[l.id]?.name ?? "Unknown"}</b> <ChatMessage line={l} />{l.attachment != null && <img className="attachment" alt="attachment" src={`${BASE_PATH}/upload/${l.attachment}`} />}</div>
       }
       else {
         return <div key={i} className="system">{reactStringReplace(l.message, /\s(liberals?|facists?)/ig, (match) => <>{' '}<span style={{ fontWeight: "bold", color: match.toLowerCase().startsWith("facist") ? "red" : "blue" }}>{match}</span>{' '}</>)}</div>
@@ -50,10 +63,17 @@ const ChatBox = ({ gameState, lines, onSubmit, playerId }: { playerId: Uuid, gam
         setLine("");
       }
     }} />
+    <input type="file" accept="image/png,image/jpeg,image/gif,image/webp" onChange={e => {
+      const file = e.target.files?.[0];
+      if (file != null) {
+        onAttach(file);
+      }
+      e.target.value = "";
+    }} />
   </div>
 }
 
-const IntroPrompt = ({ nickname: initialNickname, suffix, alert, onSubmit, gameId, clickedLink }: { clickedLink: boolean, nickname?: string, suffix: string, gameId?: Uuid | null, alert: string | null, onSubmit: (name: string, game: string | null) => void }): ReactElement => {
+const IntroPrompt = ({ nickname: initialNickname, suffix, alert, onSubmit, onPractice, gameId, clickedLink }: { clickedLink: boolean, nickname?: string, suffix: string, gameId?: Uuid | null, alert: string | null, onSubmit: (name: string, game: string | null) => void, onPractice: (name: string) => void }): ReactElement => {
   const [nickname, setNickname] = useState<string>(initialNickname ?? localStorage.getItem(`nickname${suffix}`) ?? "");
   const [gameCode, setGameCode] = useState<string>(gameId ?? "");
   const [error, setError] = useState<string | null>(null);
@@ -89,13 +109,35 @@ const IntroPrompt = ({ nickname: initialNickname, suffix, alert, onSubmit, gameI
     <div className="mb-3">
       <button disabled={gameCode.length >= 36 && clickedLink} className="btn" onClick={() => onSubmit(nickname, null)}>Host Game</button>
       <button className="btn" onClick={joinGame}>Join Game</button>
+      <button className="btn" onClick={() => onPractice(nickname)}>Practice</button>
     </div>
     <p>Based on <a href="https://www.secrethitler.com/" target="_blank" rel="noopener noreferrer">the board game</a> - CC SA–BY–NC 4.0</p>
     <p>Find the source code <a href="https://github.com/ezwang/secret-hitler" target="_blank" rel="noopener noreferrer">here</a></p>
   </div>;
 };
 
+// find a nonce for which the hash of the challenge and nonce starts with enough zero bits
+const solveProofOfWork = async (challenge: string, difficulty: number): Promise<string> => {
+  const encoder = new TextEncoder();
+  for (let nonce = 0; ; nonce++) {
+    const hash = new Uint8Array(await crypto.subtle.digest("SHA-256", encoder.encode(`${challenge}:${nonce}`)));
+    let zeros = 0;
+    for (const byte of hash) {
+      zeros += byte === 0 ? 8 : Math.clz32(byte) - 24;
+      if (byte !== 0) {
+        break;
+      }
+    }
+    if (zeros >= difficulty) {
+      return `${challenge}:${nonce}`;
+    }
+  }
+};
+
 function App() {
+  if (window.location.pathname === `${BASE_PATH}/local`) {
+    return <PassAndPlay />
+  }
   if (window.location.hostname === "localhost") {
     return <>
       <Game nickname="jack" suffix="0" />
@@ -143,16 +185,26 @@ type GameState = {
   last_president?: Uuid,
   liberal_policies: number,
   num_facists?: number,
-  players: { [key: string]: { name: string, vote: boolean | null, role: "Hitler" | "Facist" | "Liberal" | null, dead: boolean } },
+  phase_deadline?: number,
+  phase_started_at?: number,
+  players: { [key: string]: { name: string, avatar?: string | null, color?: string | null, vote: boolean | null, role: "Hitler" | "Facist" | "Liberal" | null, dead: boolean } },
   president?: Uuid,
   turn_order: Uuid[],
+  waitlist?: { id: Uuid, name: string }[],
+  waitlist_position?: number,
   turn_phase: { type: TurnPhase, winner?: CardColor, power?: PresidentialPower },
+  governments?: { president: Uuid, chancellor: Uuid, enacted: CardColor, president_claim?: CardColor[] | null, chancellor_claim?: CardColor[] | null, conflicts: { type: string, player?: Uuid }[] }[],
+  vote_called?: Uuid[],
+  veto_requested?: boolean,
+  veto_declined?: boolean,
   votes?: number,
+  deck_odds?: { liberals_left: number, facists_left: number, at_least_one_liberal: number, all_liberal: number, expected_liberals: number },
 };
 
-const ElectionTracker = ({ num = 0 }: { num?: number }) => {
-  return <div className="electionTracker">
+const ElectionTracker = ({ num = 0, chaosImminent = false }: { num?: number, chaosImminent?: boolean }) => {
+  return <div className={`electionTracker ${chaosImminent ? "danger" : ""}`}>
     {[...Array(3).keys()].map(idx => <div key={idx} className={`electionDot ${num > idx && "active"}`} />)}
+    {chaosImminent && <p>One more failed government will throw the country into chaos!</p>}
   </div>
 }
 
@@ -166,7 +218,7 @@ const CopyToClipboard = ({ url }: { url: string }) => {
 const Lobby = ({ gameState, playerId, gameId, onStart, onReset }: { gameState: GameState, playerId: Uuid, gameId: Uuid, onStart: () => void, onReset: () => void }) => {
   const numPlayers = Object.keys(gameState.players).length;
   const isHost = playerId === gameState.host;
-  const url = `${window.location.origin}/game/${gameId}`
+  const url = `${window.location.origin}${BASE_PATH}/game/${gameId}`
 
   return <>
     <h1>Secret Hitler Lobby</h1>
@@ -177,6 +229,13 @@ const Lobby = ({ gameState, playerId, gameId, onStart, onReset }: { gameState: G
     <div className="mb-3">
       <PlayerList gameState={gameState} playerId={playerId} />
     </div>
+    {gameState.waitlist_position != null && <p>The lobby is full. You are <b>#{gameState.waitlist_position}</b> on the waitlist.</p>}
+    {(gameState.waitlist?.length ?? 0) > 0 && <div className="mb-3">
+      <b>Waitlist</b>
+      <ol className="playerList">
+        {gameState.waitlist?.map(({ id, name }) => <li key={id} className={playerId === id ? "self" : "other"}>{name}</li>)}
+      </ol>
+    </div>}
 
     <p>New to the game? Check out the rules <a href="https://www.secrethitler.com/assets/Secret_Hitler_Rules.pdf" target="_blank" rel="noopener noreferrer">here</a>.</p>
     {!isHost && <p>Only the host may start the game.</p>}
@@ -217,6 +276,13 @@ function getPowerDescription(power?: PresidentialPower | null): string  {
   }
 }
 
+const PlayerName = ({ player }: { player: { name: string, avatar?: string | null, color?: string | null } }) => {
+  return <>
+    {player.avatar != null && (player.avatar.startsWith("https://") ? <img className="avatar" src={player.avatar} alt="" /> : <span className="avatar">{player.avatar}</span>)}
+    <span style={{ color: player.color ?? undefined }}>{player.name}</span>
+  </>;
+};
+
 const PlayerList = ({ gameState, playerId, onSelect } : { gameState: GameState, playerId: Uuid, onSelect?: (id: Uuid) => void }) => {
   const numPlayers = Object.keys(gameState.players).length;
 
@@ -225,7 +291,7 @@ const PlayerList = ({ gameState, playerId, onSelect } : { gameState: GameState,
     return <>
       <b>Players <span style={{ color: numPlayers >= 5 && numPlayers <= 10 ? "green" : "red" }}>({numPlayers}/10)</span></b>
       <ul className="playerList">
-        {Object.entries(gameState.players).sort((a, b) => a[1].name.localeCompare(b[1].name)).map(([id, data]) => <li key={id} className={playerId === id ? "self" : "other"}>{data.name} {id === gameState.host && " (Host)"}</li>)}
+        {Object.entries(gameState.players).sort((a, b) => a[1].name.localeCompare(b[1].name)).map(([id, data]) => <li key={id} className={playerId === id ? "self" : "other"}><PlayerName player={data} /> {id === gameState.host && " (Host)"}</li>)}
       </ul>
     </>;
   }
@@ -246,9 +312,9 @@ const PlayerList = ({ gameState, playerId, onSelect } : { gameState: GameState,
         return <div key={id} className={`clearfix player ${playerId === id ? "self" : "other"}`}>
           <div className="order">[{playerData.dead ? "Dead" : idx + 1}]</div>
           {playerData.role != null ?
-            <span className={`affiliation ${playerData.role.toLowerCase()}`}><img src={`/images/profiles/${playerData.role.toLowerCase()}.png`} /></span> : 
+            <span className={`affiliation ${playerData.role.toLowerCase()}`}><img src={`${BASE_PATH}/images/profiles/${playerData.role.toLowerCase()}.png`} /></span> : 
             <span className="affiliation"><div className="none">?</div></span>}
-          <div className="name">{playerData.name}{playerId === id && " (You)"}</div>
+          <div className="name"><PlayerName player={playerData} />{playerId === id && " (You)"}</div>
           {gameState.president === id && <div className="role">President</div>}
           {gameState.chancellor === id && <div className="role">{isVoting && "Nominated "}Chancellor</div>}
           {playerData.vote != null && <div className="vote">Voted { gameState.players[id].vote ? "Yes": "No" }</div>}
@@ -268,12 +334,12 @@ const PlayerList = ({ gameState, playerId, onSelect } : { gameState: GameState,
 
 const PlayerVote = ({ gameState, onSelect, playerId }: { gameState: GameState, onSelect: (vote: boolean) => void, playerId: Uuid }) => {
   const remaining = gameState.turn_order.length - (gameState.votes ?? 0);
-  const playerVote = gameState.players[playerId].vote;
+  const playerVote = gameState.players[playerId]?.vote;
 
   return <div className="voteBox">
     {gameState.chancellor != null && <div>Voting to elect <b>{gameState.players[gameState.chancellor].name}</b> as chancellor</div>}
     <p className="voteStatus"><b>{remaining}</b> voters remaining</p>
-    {!gameState.players[playerId].dead && <><button className={playerVote === true ? "active" : undefined} onClick={(e) => {
+    {gameState.players[playerId] != null && !gameState.players[playerId].dead && <><button className={playerVote === true ? "active" : undefined} onClick={(e) => {
       e.preventDefault();
       onSelect(true);
     }}>Ja!<span className="helpText">(Yes)</span></button>
@@ -284,7 +350,62 @@ const PlayerVote = ({ gameState, onSelect, playerId }: { gameState: GameState, o
   </div>
 }
 
-const CardSelect = ({ gameState, onSelect, onVeto } : { gameState: GameState, onSelect: (card: CardColor) => void, onVeto: () => void }) => {
+const Discussion = ({ gameState, playerId, onCallVote }: { gameState: GameState, playerId: Uuid, onCallVote: () => void }) => {
+  const [now, setNow] = useState<number>(Date.now());
+  useEffect(() => {
+    const timer = setInterval(() => setNow(Date.now()), 1000);
+    return () => clearInterval(timer);
+  }, []);
+
+  const secondsLeft = gameState.phase_deadline != null ? Math.max(0, Math.ceil((gameState.phase_deadline - now) / 1000)) : null;
+  const inGovernment = playerId === gameState.president || playerId === gameState.chancellor;
+  const called = gameState.vote_called ?? [];
+
+  return <div className="voteBox">
+    {gameState.chancellor != null && <div>Discuss the nomination of <b>{gameState.players[gameState.chancellor].name}</b> as chancellor</div>}
+    {secondsLeft != null && <p className="voteStatus">The vote opens in <b>{secondsLeft}</b> seconds</p>}
+    {called.map(id => <p key={id}><b>{gameState.players[id]?.name}</b> wants to vote now</p>)}
+    {inGovernment && !called.includes(playerId) && <button onClick={(e) => {
+      e.preventDefault();
+      onCallVote();
+    }}>Vote now</button>}
+  </div>
+}
+
+const ClaimBox = ({ gameState, playerId, onClaim }: { gameState: GameState, playerId: Uuid, onClaim: (cards: CardColor[]) => void }) => {
+  const government = gameState.governments?.[gameState.governments.length - 1];
+  if (government == null) {
+    return null;
+  }
+  const asPresident = government.president === playerId;
+  const unclaimed = asPresident ? government.president_claim == null : government.chancellor === playerId && government.chancellor_claim == null;
+  if (!unclaimed) {
+    if (government.conflicts.length === 0) {
+      return null;
+    }
+    return <div className="infoBox">The claims of President <b>{gameState.players[government.president]?.name}</b> and Chancellor <b>{gameState.players[government.chancellor]?.name}</b> cannot all be true.</div>;
+  }
+  const size = asPresident ? 3 : 2;
+  return <div className="infoBox">Claim the policies you were {asPresident ? "dealt" : "passed"}: {Array.from({ length: size + 1 }, (_, facists) => {
+    const cards = Array.from({ length: size }, (_, i) => i < facists ? CardColor.FACIST : CardColor.LIBERAL);
+    return <button key={facists} className="btn small" onClick={(e) => {
+      e.preventDefault();
+      onClaim(cards);
+    }}>{cards.map(card => card === CardColor.FACIST ? "F" : "L").join("")}</button>;
+  })}</div>;
+}
+
+const CardSelect = ({ gameState, playerId, onSelect, onVeto, onRespondVeto } : { gameState: GameState, playerId: Uuid, onSelect: (index: number) => void, onVeto: () => void, onRespondVeto: (accept: boolean) => void }) => {
+  if (gameState.veto_requested && !gameState.veto_declined && gameState.chancellor != null) {
+    if (playerId !== gameState.president) {
+      return <div className="cardSelectBox"><p>Chancellor <b>{gameState.players[gameState.chancellor].name}</b> has asked the president to veto this agenda</p></div>
+    }
+    return <div className="cardSelectBox vetoPowerBox">
+      <p>Chancellor <b>{gameState.players[gameState.chancellor].name}</b> wishes to veto this agenda. If you agree, both policies will be discarded and the Election Tracker advances by one. If you refuse, the chancellor must enact a policy.</p>
+      <button className="btn" onClick={(e) => {e.preventDefault(); onRespondVeto(true);}}>Veto</button>
+      <button className="btn" onClick={(e) => {e.preventDefault(); onRespondVeto(false);}}>Refuse</button>
+    </div>
+  }
   if (gameState.cards == null) {
     if (gameState.turn_phase.type === TurnPhase.PRESIDENT_SELECT && gameState.president != null) {
       return <div className="cardSelectBox"><p>President <b>{gameState.players[gameState.president].name}</b> is choosing a policy to discard</p></div>
@@ -299,46 +420,46 @@ const CardSelect = ({ gameState, onSelect, onVeto } : { gameState: GameState, on
     <p>{gameState.turn_phase.type === TurnPhase.PRESIDENT_SELECT ? <>Choose the policy you would like to <b>discard</b></> : <>Choose the policy you would like to <b>enact</b></>}</p>
     {gameState.cards.map((card, i) => <button className={`policySlot ${card.toLowerCase()} active`} key={i} onClick={(e) => {
       e.preventDefault();
-      onSelect(card);
-    }}><img src={`/images/${card.toLowerCase()}.png`} alt={`${card} card`} /></button>)}
-    {gameState.facist_policies >= 4 && <div className="vetoPowerBox">
-      <p>If both the president and chancellor agree, both policies will be discarded and the president placard passes.</p>
+      onSelect(i);
+    }}><img src={`${BASE_PATH}/images/${card.toLowerCase()}.png`} alt={`${card} card`} /></button>)}
+    {gameState.turn_phase.type === TurnPhase.CHANCELLOR_SELECT && gameState.facist_policies >= 5 && !gameState.veto_requested && <div className="vetoPowerBox">
+      <p>You may ask the president to veto this agenda. If they agree, both policies will be discarded and the president placard passes.</p>
       <p>Each use of the Veto Power represents an inactive government and advances the Election Tracker by one.</p>
-      <button className="btn" onClick={(e) => {e.preventDefault(); onVeto();}}>Veto</button>
+      <button className="btn" onClick={(e) => {e.preventDefault(); onVeto();}}>Ask to veto</button>
     </div>}
+    {gameState.veto_declined && <p>The president refused the veto, so you must enact a policy.</p>}
   </div>
 }
 
-const CardTable = ({ gameState } : { gameState: GameState }) => {
-  let numPlayers = Object.keys(gameState.players).length;
-  let powers = [null, null, null, PresidentialPower.EXECUTE, PresidentialPower.EXECUTE, null];
+type Rules = {
+  power_track: (PresidentialPower | null)[],
+  liberal_policies_to_win: number,
+  facist_policies_to_win: number,
+  hitler_chancellor_policies: number,
+  veto_policies: number,
+};
 
-  if (numPlayers >= 9) {
-    powers[0] = PresidentialPower.INVESTIGATE;
-  }
-  if (numPlayers >= 7) {
-    powers[1] = PresidentialPower.INVESTIGATE;
-    powers[2] = PresidentialPower.ELECTION;
-  }
-  else {
-    powers[2] = PresidentialPower.POLICY_PEEK;
+const CardTable = ({ gameState, rules } : { gameState: GameState, rules: Rules | null }) => {
+  if (rules == null) {
+    return null;
   }
+  const powers = [...rules.power_track, null];
 
   return <>
     <div className="facist policyTable">
-      {[...Array(6).keys()].map(idx => {
+      {[...Array(rules.facist_policies_to_win).keys()].map(idx => {
         return <div key={idx} className={`facist policySlot ${gameState.facist_policies > idx ? "active" : "inactive"}`}>
-          <img src="/images/facist.png" alt="facist card" />
+          <img src={`${BASE_PATH}/images/facist.png`} alt="facist card" />
           {powers[idx] != null && <p>{getPowerDescription(powers[idx])}</p>}
-          {idx >= 3 && <p>Facists win if Hitler is elected as Chancellor.</p>}
-          {idx === 4 && <p>Veto power is unlocked.</p>}
+          {idx >= rules.hitler_chancellor_policies - 1 && <p>Facists win if Hitler is elected as Chancellor.</p>}
+          {idx === rules.veto_policies - 1 && <p>Veto power is unlocked.</p>}
         </div>
       })}
     </div>
     <div className="liberal policyTable">
-      {[...Array(5).keys()].map(idx => {
+      {[...Array(rules.liberal_policies_to_win).keys()].map(idx => {
         return <div key={idx} className={`liberal policySlot ${gameState.liberal_policies > idx ? "active" : "inactive"}`}>
-          <img src="/images/liberal.png" alt="liberal card" />
+          <img src={`${BASE_PATH}/images/liberal.png`} alt="liberal card" />
         </div>
       })}
     </div>
@@ -349,7 +470,7 @@ const PolicyPeek = ({ cards, onConfirm }: { cards: CardColor[], onConfirm: () =>
   return <div className="policyPeek">
     <h3>Peek at the next 3 cards</h3>
     <div className="mb-1">
-      {cards.map((card, idx) => <div key={idx} className={`${card.toLowerCase()} policySlot active`}><img src={`/images/${card.toLowerCase()}.png`} alt={`${card.toLowerCase()} card`} /></div>)}
+      {cards.map((card, idx) => <div key={idx} className={`${card.toLowerCase()} policySlot active`}><img src={`${BASE_PATH}/images/${card.toLowerCase()}.png`} alt={`${card.toLowerCase()} card`} /></div>)}
     </div>
     <button className="btn" onClick={(e) => {
       e.preventDefault();
@@ -358,7 +479,7 @@ const PolicyPeek = ({ cards, onConfirm }: { cards: CardColor[], onConfirm: () =>
   </div>
 };
 
-const GameOver = ({ gameState }: { gameState: GameState }) => {
+const GameOver = ({ gameState, playerId, onRematch }: { gameState: GameState, playerId: Uuid, onRematch: () => void }) => {
   let reason = "The game has ended.";
   const [hitlerId, hitlerPlayer] = Object.entries(gameState.players).find(plr => plr[1].role === "Hitler") ?? [null, null];
 
@@ -377,7 +498,7 @@ const GameOver = ({ gameState }: { gameState: GameState }) => {
   else if (gameState.facist_policies >= 6) {
     reason = "Facists have enacted 6 policies.";
   }
-  else if (gameState.facist_policies > 3 && hitlerId == gameState.chancellor) {
+  else if (gameState.turn_phase.winner === CardColor.FACIST && hitlerId == gameState.chancellor) {
     reason = "Hitler has been elected chancellor.";
   }
   else if (hitlerPlayer?.dead) {
@@ -387,6 +508,7 @@ const GameOver = ({ gameState }: { gameState: GameState }) => {
   return <div className="gameOverBox">
     <h1>Game Over! <span className={`affiliation ${gameState.turn_phase.winner?.toLowerCase()}`}>{gameState.turn_phase.winner}s</span> win!</h1>
     <p>{reason}</p>
+    {playerId === gameState.host && <button className="btn" onClick={onRematch}>Rematch</button>}
   </div>;
 }
 
@@ -426,9 +548,9 @@ const TipDialog = ({ role, onClose }: { role: "Hitler" | "Facist" | "Liberal" |
     }}>Close</button>
     <h1>Your role is <b className={`affiliation ${role.toLowerCase()}`}>{role}</b>!</h1>
     <div className="roleIcons">
-      <div className={role === "Liberal" ? "active" : undefined}><img src="/images/profiles/liberal.png" alt="liberals" /><div>Liberals</div></div>
-      <div className={role === "Facist" ? "active" : undefined}><img src="/images/profiles/facist.png" alt="facists" /><div>Facists</div></div>
-      <div className={role === "Hitler" ? "active" : undefined}><img src="/images/profiles/hitler.png" alt="hitlers" /><div>Hitler</div></div>
+      <div className={role === "Liberal" ? "active" : undefined}><img src={`${BASE_PATH}/images/profiles/liberal.png`} alt="liberals" /><div>Liberals</div></div>
+      <div className={role === "Facist" ? "active" : undefined}><img src={`${BASE_PATH}/images/profiles/facist.png`} alt="facists" /><div>Facists</div></div>
+      <div className={role === "Hitler" ? "active" : undefined}><img src={`${BASE_PATH}/images/profiles/hitler.png`} alt="hitlers" /><div>Hitler</div></div>
     </div>
     {role === "Liberal" ?
       <div>
@@ -459,8 +581,24 @@ const TipDialog = ({ role, onClose }: { role: "Hitler" | "Facist" | "Liberal" |
   </div></Draggable>;
 };
 
+type TutorialStep = { topic: string, title: string, text: string };
+
+const TutorialDialog = ({ step, onClose }: { step: TutorialStep | null, onClose: () => void }) => {
+  if (step == null) {
+    return null;
+  }
+  return <Draggable cancel=".btn"><div className="tipDialog clearfix">
+    <button className="btn small float-right" onClick={(e) => {
+      e.preventDefault();
+      onClose();
+    }}>Close</button>
+    <h1>{step.title}</h1>
+    <p>{step.text}</p>
+  </div></Draggable>;
+};
+
 function getWindowGameId(): string | null {
-  const match = window.location.pathname.match(/^\/game\/(.*?)(\/|$)/);
+  const match = window.location.pathname.slice(BASE_PATH.length).match(/^\/game\/(.*?)(\/|$)/);
   if (match == null) {
     return null;
   }
@@ -474,14 +612,22 @@ function Game({ nickname, gameId: initialGameId, suffix = "" }: GameProps) {
   
   const [playerId, setPlayerId] = useState<Uuid | null>(localStorage.getItem(`playerId${suffix}`));
   const [playerSecret, setPlayerSecret] = useState<Uuid | null>(localStorage.getItem(`playerSecret${suffix}`));
+  const [resumeToken, setResumeToken] = useState<string | null>(new URLSearchParams(window.location.search).get("resume") ?? sessionStorage.getItem(`resumeToken${suffix}`));
   
   const windowGameId = getWindowGameId();
   const [gameId, setGameId] = useState<Uuid | null>(windowGameId ?? initialGameId ?? localStorage.getItem("gameId"));
   const [connected, setConnected] = useState<boolean>(false);
   const [loading, setLoading] = useState<boolean>(gameId != null);
   const [showTips, setShowTips] = useState<boolean>(true);
+  const [tutorialStep, setTutorialStep] = useState<TutorialStep | null>(null);
+  const [rules, setRules] = useState<Rules | null>(null);
+  const [chaosImminent, setChaosImminent] = useState<boolean>(false);
+  const [chaosAt, setChaosAt] = useState<number>(0);
+  const [electionResult, setElectionResult] = useState<{ votes: { [key: string]: boolean } | null, ayes: number, nays: number, passed: boolean } | null>(null);
   
   const ws = useRef<WebSocket | null>(null);
+  // doubles with each failed connection, so clients do not all come back at once after a restart
+  const reconnectDelay = useRef(100);
 
   useEffect(() => {
     if (playerId != null) {
@@ -495,6 +641,12 @@ function Game({ nickname, gameId: initialGameId, suffix = "" }: GameProps) {
     }
   }, [gameId]);
 
+  useEffect(() => {
+    if (resumeToken != null) {
+      sessionStorage.setItem(`resumeToken${suffix}`, resumeToken);
+    }
+  }, [resumeToken]);
+
   useEffect(() => {
     if (playerSecret != null) {
       localStorage.setItem(`playerSecret${suffix}`, playerSecret);
@@ -507,8 +659,20 @@ function Game({ nickname, gameId: initialGameId, suffix = "" }: GameProps) {
       localStorage.removeItem(`playerId${suffix}`);
       localStorage.removeItem("gameId");
     }
+    // a rematch puts the game back in the lobby
+    else if (gameState.turn_phase.type === TurnPhase.LOBBY && playerId != null && gameId != null) {
+      localStorage.setItem(`playerId${suffix}`, playerId);
+      localStorage.setItem("gameId", gameId);
+    }
   }, [gameState]);
   
+  // the rules depend on the number of players, which is fixed once the game starts
+  useEffect(() => {
+    if (connected && gameState.turn_order.length > 0) {
+      ws.current?.send(JSON.stringify({ type: "GetRules" }));
+    }
+  }, [connected, gameState.turn_order.length > 0]);
+
   const reset = () => {
     if (ws.current?.readyState === WebSocket.OPEN) {
       ws.current?.send(JSON.stringify({ type: "Leave" }));
@@ -520,43 +684,122 @@ function Game({ nickname, gameId: initialGameId, suffix = "" }: GameProps) {
     localStorage.removeItem("gameId");
     localStorage.removeItem(`playerId${suffix}`);
     localStorage.removeItem(`playerSecret${suffix}`);
+    sessionStorage.removeItem(`resumeToken${suffix}`);
+    setResumeToken(null);
     setGameState((state) => ({ ...state, turn_phase: { type: TurnPhase.INTRO } }));
   };
 
+  const hostWith = async (challenge: { type: string, challenge?: string, difficulty?: number }) => {
+    let captcha = null;
+    if (challenge.type === "ProofOfWork") {
+      captcha = await solveProofOfWork(challenge.challenge!, challenge.difficulty!);
+    }
+    else if (challenge.type === "HCaptcha") {
+      setAlert("This server asks for a captcha before hosting, which this page cannot show yet.");
+      return;
+    }
+    ws.current?.send(JSON.stringify({ "type": "HostGame", "nickname": localStorage.getItem(`nickname${suffix}`), "player_secret": playerSecret, "captcha": captcha }));
+  };
+
   const connect = () => {
-    ws.current = new WebSocket(`${window.location.protocol.replace('http', 'ws')}//${window.location.hostname === "localhost" ? "localhost:8000" : window.location.host}/ws/`);
+    ws.current = new WebSocket(`${window.location.protocol.replace('http', 'ws')}//${window.location.hostname === "localhost" ? "localhost:8000" : window.location.host}${BASE_PATH}/ws/`);
     ws.current.onopen = () => {
       setConnected(true);
+      reconnectDelay.current = 100;
       const finalPlayerId = playerId ?? localStorage.getItem(`playerId${suffix}`);
       const nickname = localStorage.getItem(`nickname${suffix}`);
       const finalPlayerSecret = playerSecret ?? localStorage.getItem(`playerSecret${suffix}`);
-      if (finalPlayerId != null && nickname != null && finalPlayerSecret != null) {
+      const finalResumeToken = resumeToken ?? sessionStorage.getItem(`resumeToken${suffix}`);
+      if (finalResumeToken != null && nickname != null && gameId != null) {
+        ws.current?.send(JSON.stringify({type: "JoinGame", "nickname": nickname, "id": gameId, "resume_token": finalResumeToken}));
+        ws.current?.send(JSON.stringify({ type: "GetChatLog" }));
+      }
+      else if (gameId != null && finalPlayerId != null && nickname != null && finalPlayerSecret != null) {
         ws.current?.send(JSON.stringify({type: "JoinGame", "nickname": nickname, "id": gameId, "player_id": finalPlayerId, "player_secret": finalPlayerSecret}));
         ws.current?.send(JSON.stringify({ type: "GetChatLog" }));
       }
+      else if (finalPlayerSecret != null && nickname != null) {
+        // the game was forgotten, ask the server whether we are still in one
+        ws.current?.send(JSON.stringify({ type: "WhereAmI", "player_secret": finalPlayerSecret }));
+      }
       else {
         setLoading(false);
       }
     };
     ws.current.onclose = () => {
       setConnected(false);
-      setTimeout(connect, 100);
+      const delay = reconnectDelay.current;
+      reconnectDelay.current = Math.min(delay * 2, 30000);
+      setTimeout(connect, delay * (0.5 + Math.random()));
     };
     ws.current.onmessage = (msg) => {
       const packet = JSON.parse(msg.data);
       switch (packet.type) {
         case "Alert":
+        case "Error":
+          setLoading(false);
+          setAlert(packet.message);
+          break;
+        case "ServerBusy":
+          setLoading(false);
+          setAlert(`The server is busy right now. Please try again in ${packet.retry_after} seconds.`);
+          break;
+        case "ReconnectThrottled":
+          // rejoin once the server is ready for us
+          setTimeout(() => ws.current?.close(), packet.retry_after_ms);
+          break;
+        case "MessagesThrottled":
+          setLoading(false);
+          setAlert("You are doing that too quickly. Please slow down.");
+          break;
+        case "HostChallenge":
+          hostWith(packet.challenge);
+          break;
+        case "Banned":
           setLoading(false);
+          setAlert(packet.expires_at != null ? `You are banned until ${new Date(packet.expires_at).toLocaleString()}: ${packet.reason}` : `You are banned: ${packet.reason}`);
+          break;
+        case "Motd":
+        case "Announcement":
           setAlert(packet.message);
           break;
+        case "Achievements":
+          if (packet.earned.length > 0) {
+            setAlert(`Achievement unlocked: ${packet.earned.join(", ")}! You have ${packet.season_wins} wins this season.`);
+          }
+          break;
         case "SetIdentifiers":
           setGameId(packet.game_id);
           setPlayerId(packet.player_id);
           setPlayerSecret(packet.secret);
           break;
+        case "CurrentGame":
+          if (packet.game_id != null) {
+            setGameId(packet.game_id);
+            setPlayerId(packet.player_id);
+            ws.current?.send(JSON.stringify({type: "JoinGame", "nickname": localStorage.getItem(`nickname${suffix}`), "id": packet.game_id, "player_id": packet.player_id, "player_secret": playerSecret ?? localStorage.getItem(`playerSecret${suffix}`)}));
+            ws.current?.send(JSON.stringify({ type: "GetChatLog" }));
+          }
+          else {
+            setLoading(false);
+          }
+          break;
+        case "ResumeToken":
+          setResumeToken(packet.token);
+          break;
         case "ReceiveChat":
           setChatLines(l => [...l, packet]);
           break;
+        case "ElectionResult":
+          setElectionResult(packet);
+          break;
+        case "ElectionTrackerAdvanced":
+          setChaosImminent(packet.chaos_imminent);
+          setChaosAt(packet.value);
+          break;
+        case "Rules":
+          setRules(packet.rules);
+          break;
         case "ChatLog":
           setChatLines(packet.log);
           setLoading(false);
@@ -564,6 +807,10 @@ function Game({ nickname, gameId: initialGameId, suffix = "" }: GameProps) {
         case "GameState":
           setGameState(packet.state);
           break;
+        case "TutorialStep":
+          setShowTips(false);
+          setTutorialStep(packet);
+          break;
       }
     };
   }
@@ -587,7 +834,22 @@ function Game({ nickname, gameId: initialGameId, suffix = "" }: GameProps) {
         <IntroPrompt suffix={suffix} nickname={nickname} gameId={gameId} alert={alert} clickedLink={!!windowGameId} onSubmit={(nick, game) => {
           localStorage.setItem(`nickname${suffix}`, nick);
           if (ws.current?.readyState === WebSocket.OPEN) {
-            ws.current?.send(JSON.stringify({ "type": game != null ? "JoinGame" : "HostGame", "nickname": nick, "id": game }));
+            if (game != null) {
+              ws.current?.send(JSON.stringify({ "type": "JoinGame", "nickname": nick, "id": game, "player_secret": playerSecret }));
+            }
+            else {
+              // the server may want proof that we are not a bot before hosting
+              ws.current?.send(JSON.stringify({ "type": "GetHostChallenge" }));
+            }
+            setAlert(null);
+          }
+          else {
+            setAlert("The websocket connection has not been established yet.");
+          }
+        }} onPractice={(nick) => {
+          localStorage.setItem(`nickname${suffix}`, nick);
+          if (ws.current?.readyState === WebSocket.OPEN) {
+            ws.current?.send(JSON.stringify({ "type": "HostPractice", "nickname": nick }));
             setAlert(null);
           }
           else {
@@ -617,29 +879,139 @@ function Game({ nickname, gameId: initialGameId, suffix = "" }: GameProps) {
           }
         }} />
         <p style={{textAlign: "center"}}>There are <b>{gameState.cards_in_deck ?? 0}</b> cards in the draw pile and <b>{gameState.cards_in_discard ?? 0}</b> cards in the discard pile</p>
-        <ElectionTracker num={gameState.election_tracker} />
-        <CardTable gameState={gameState} />
+        {gameState.deck_odds && <p style={{textAlign: "center"}}>The next draw has a <b>{Math.round(gameState.deck_odds.at_least_one_liberal * 100)}%</b> chance of holding a liberal policy, with <b>{gameState.deck_odds.expected_liberals.toFixed(1)}</b> liberals on average</p>}
+        <ElectionTracker num={gameState.election_tracker} chaosImminent={chaosImminent && gameState.election_tracker === chaosAt} />
+        <CardTable gameState={gameState} rules={rules} />
+        {electionResult != null && gameState.turn_phase.type !== TurnPhase.DISCUSSION && gameState.turn_phase.type !== TurnPhase.VOTING && <div className="infoBox">
+          The last government was {electionResult.passed ? "elected" : "rejected"} {electionResult.ayes} to {electionResult.nays}
+          {electionResult.votes != null && <>: {Object.entries(electionResult.votes).map(([id, vote]) => `${gameState.players[id]?.name ?? "?"} ${vote ? "Ja" : "Nein"}`).join(", ")}</>}
+        </div>}
+        <ClaimBox gameState={gameState} playerId={playerId} onClaim={(cards) => {
+          ws.current?.send(JSON.stringify({ "type": "Claim", cards: cards }));
+        }} />
+        {gameState.turn_phase.type === TurnPhase.DISCUSSION && <Discussion gameState={gameState} playerId={playerId} onCallVote={() => {
+          ws.current?.send(JSON.stringify({ "type": "CallVote" }));
+        }} />}
         {gameState.turn_phase.type === TurnPhase.VOTING && <PlayerVote gameState={gameState} playerId={playerId} onSelect={(vote) => {
           ws.current?.send(JSON.stringify({ "type": "VoteChancellor", vote: vote }));
         }} />}
-        {(gameState.turn_phase.type === TurnPhase.PRESIDENT_SELECT || gameState.turn_phase.type === TurnPhase.CHANCELLOR_SELECT) && <CardSelect gameState={gameState} onSelect={(card) => {
-          ws.current?.send(JSON.stringify({ "type": "PickCard", color: card === CardColor.FACIST }));
+        {(gameState.turn_phase.type === TurnPhase.PRESIDENT_SELECT || gameState.turn_phase.type === TurnPhase.CHANCELLOR_SELECT) && <CardSelect gameState={gameState} playerId={playerId} onSelect={(index) => {
+          ws.current?.send(JSON.stringify({ "type": "PickCard", index: index }));
         }} onVeto={() => {
-          ws.current?.send(JSON.stringify({ "type": "VetoCard" }));
+          ws.current?.send(JSON.stringify({ "type": "RequestVeto" }));
+        }} onRespondVeto={(accept) => {
+          ws.current?.send(JSON.stringify({ "type": "RespondVeto", accept: accept }));
         }} />}
         {gameState.turn_phase.type === TurnPhase.POWER && gameState.turn_phase.power === PresidentialPower.POLICY_PEEK && playerId === gameState.president && <PolicyPeek cards={gameState.cards ?? []} onConfirm={() => {
           ws.current?.send(JSON.stringify({ "type": "PresidentialPower" }));
         }} />}
-        {gameState.turn_phase.type === TurnPhase.ENDED && <GameOver gameState={gameState} />}
+        {gameState.turn_phase.type === TurnPhase.ENDED && <GameOver gameState={gameState} playerId={playerId} onRematch={() => {
+          ws.current?.send(JSON.stringify({ "type": "Rematch" }));
+        }} />}
         {gameState.turn_phase.type === TurnPhase.ELECTING && gameState.president != null && <div className="infoBox">President <b>{gameState.players[gameState.president].name}</b> is electing a chancellor</div>}
+        <TutorialDialog step={tutorialStep} onClose={() => setTutorialStep(null)} />
         {showTips && <TipDialog onClose={() => setShowTips(false)} role={gameState.players[playerId]?.role ?? null} />}
       </div>}
     </div>
-    <ChatBox playerId={playerId} gameState={gameState} lines={chatLines} onSubmit={(line) => ws.current?.send(JSON.stringify({type: "SendChat", message: line}))} />
+    <ChatBox playerId={playerId} gameState={gameState} lines={chatLines} onSubmit={(line) => ws.current?.send(JSON.stringify({type: "SendChat", message: line}))} onAttach={async (file) => {
+      const response = await fetch(`${BASE_PATH}/upload?game_id=${gameId}&player_id=${playerId}&player_secret=${playerSecret}`, { method: "POST", body: file });
+      if (!response.ok) {
+        setAlert(await response.text());
+        return;
+      }
+      const { token } = await response.json();
+      ws.current?.send(JSON.stringify({type: "SendChat", message: "", attachment: token}));
+    }} />
     <div className="footer">
       <a href="https://www.secrethitler.com/assets/Secret_Hitler_Rules.pdf" target="_blank" rel="noopener noreferrer">Rules</a> - <a href="#" onClick={(e) => {e.preventDefault(); setShowTips(tips => !tips)}}>Tips</a> - <QuitButton gameState={gameState} playerId={playerId} onQuit={reset} />
      {!connected && <> - <span className="disconnected">Disconnected</span></>}</div>
   </div>;
 }
 
+// the wasm build of the engine, loaded from public/wasm
+type PassAndPlayEngine = { next(): Uuid | undefined, handTo(player: Uuid): void, hide(): void, apply(action: string): string, view(): string };
+
+const PassAndPlay = (): ReactElement => {
+  const engine = useRef<PassAndPlayEngine | null>(null);
+  const [names, setNames] = useState<string>("");
+  const [gameState, setGameState] = useState<GameState | null>(null);
+  const [holder, setHolder] = useState<Uuid | null>(null);
+  const [alert, setAlert] = useState<string | null>(null);
+
+  const refresh = () => setGameState(JSON.parse(engine.current!.view()));
+  const act = (action: object) => {
+    try {
+      engine.current!.apply(JSON.stringify(action));
+      setAlert(null);
+    }
+    catch (e) {
+      setAlert(String(e));
+    }
+    refresh();
+  };
+
+  if (engine.current == null || gameState == null) {
+    return <div className="content"><div className="game">
+      {alert != null && <div className="alert">{alert}</div>}
+      <h1>Pass and play</h1>
+      <p>Enter everyone's names, one per line, then pass the device to whoever it asks for.</p>
+      <textarea value={names} onChange={e => setNames(e.target.value)} />
+      <button className="btn" onClick={async (e) => {
+        e.preventDefault();
+        try {
+          const wasm = await import(/* webpackIgnore: true */ `${window.location.origin}${BASE_PATH}/wasm/secrethitler_core.js`);
+          await wasm.default();
+          engine.current = new wasm.PassAndPlay("{}", names.split("\n").map(name => name.trim()).filter(name => name.length > 0));
+          setAlert(null);
+          refresh();
+        }
+        catch (e) {
+          setAlert(String(e));
+        }
+      }}>Start</button>
+    </div></div>;
+  }
+
+  const phase = gameState.turn_phase.type;
+  if (holder == null) {
+    const next = engine.current.next();
+    return <div className="content"><div className="game">
+      <PlayerList gameState={gameState} playerId="" />
+      <CardTable gameState={gameState} rules={null} />
+      {next != null && <div className="infoBox">Pass the device to <b>{gameState.players[next].name}</b> <button className="btn" onClick={(e) => {
+        e.preventDefault();
+        engine.current!.handTo(next);
+        setHolder(next);
+        refresh();
+      }}>I am {gameState.players[next].name}</button></div>}
+    </div></div>;
+  }
+
+  return <div className="content"><div className="game">
+    {alert != null && <div className="alert">{alert}</div>}
+    {phase !== TurnPhase.LOBBY && <p>You are <b>{gameState.players[holder].role}</b>. Press done before passing the device on.</p>}
+    <PlayerList gameState={gameState} playerId={holder} onSelect={(id) => {
+      if (phase === TurnPhase.ELECTING) {
+        act({ type: "Nominate", chancellor: id });
+      }
+      if (phase === TurnPhase.POWER) {
+        act({ type: "UsePower", target: id });
+      }
+    }} />
+    <CardTable gameState={gameState} rules={null} />
+    {phase === TurnPhase.DISCUSSION && <Discussion gameState={gameState} playerId={holder} onCallVote={() => act({ type: "CallVote" })} />}
+    {phase === TurnPhase.VOTING && <PlayerVote gameState={gameState} playerId={holder} onSelect={(vote) => act({ type: "Vote", approve: vote })} />}
+    {(phase === TurnPhase.PRESIDENT_SELECT || phase === TurnPhase.CHANCELLOR_SELECT) && <CardSelect gameState={gameState} playerId={holder} onSelect={(index) => act({ type: "PickCard", index: index })} onVeto={() => act({ type: "RequestVeto" })} onRespondVeto={(accept) => act({ type: "RespondVeto", accept: accept })} />}
+    {phase === TurnPhase.POWER && gameState.turn_phase.power === PresidentialPower.POLICY_PEEK && holder === gameState.president && <PolicyPeek cards={gameState.cards ?? []} onConfirm={() => act({ type: "UsePower", target: null })} />}
+    {phase === TurnPhase.ENDED && <GameOver gameState={gameState} playerId={holder} onRematch={() => act({ type: "Rematch" })} />}
+    {phase === TurnPhase.LOBBY && <button className="btn" onClick={() => act({ type: "Start" })}>Start</button>}
+    <button className="btn" onClick={(e) => {
+      e.preventDefault();
+      engine.current!.hide();
+      setHolder(null);
+      refresh();
+    }}>Done</button>
+  </div></div>;
+};
+
 export default App;
@@ -0,0 +1,1054 @@
+// Generated from the Rust protocol types by `secrethitler generate-types`. Do not edit by hand.
+
+/** Milestones players earn over their games. */
+export type Achievement = ("HitlerWin" | "SurvivedExecution" | "WinStreak");
+
+export type Ban = {
+  /** What the player said when they appealed, noted by an admin. */
+  appeal_note?: string | null;
+  /** The community the ban keeps the player out of, or none for the whole server. */
+  community?: string | null;
+  /** When the ban was made, in milliseconds since the epoch. */
+  created_at: number;
+  /** When the ban ends, in milliseconds since the epoch, or none if it lasts until it is lifted. */
+  expires_at?: number | null;
+  id: string;
+  reason: string;
+  target: BanTarget;
+};
+
+/** Who a ban applies to. */
+export type BanTarget = ({
+  friend_id: string;
+  type: "Account";
+} | {
+  range: string;
+  type: "Address";
+} | {
+  fingerprint: string;
+  type: "Fingerprint";
+});
+
+export type CardColor = "Facist" | "Liberal";
+
+/** How a frontend should draw one kind of policy, listed in the game state for boards that do not rely on color. */
+export type CardFace = {
+  color: CardColor;
+  id: string;
+  /** The faction's name, which the host may have changed. */
+  name: string;
+  pattern: CardPattern;
+  shape: CardShape;
+};
+
+/** A fill for each kind of policy, so they can be told apart without color. */
+export type CardPattern = "Plain" | "Striped";
+
+/** A symbol for each kind of policy, so they can be told apart without color. */
+export type CardShape = "Circle" | "Triangle";
+
+export type ChatLine = {
+  /** The token of an image uploaded for the game, fetched from `/upload/{token}`. */
+  attachment?: string | null;
+  id?: string | null;
+  message: string;
+  /** The message split into text and links, for clients to show without treating any of it as markup. */
+  segments?: ChatSegment[];
+};
+
+/** A piece of a chat message, so clients can show links without rendering anything the sender wrote as markup. */
+export type ChatSegment = ({
+  text: string;
+  type: "Text";
+} | {
+  text: string;
+  type: "Link";
+  url: string;
+});
+
+/** Messages that change the game accept an optional `request_id`. Sending the same id again returns the original result instead of applying the action twice. */
+export type ClientProtocol = ({
+  avatar?: string | null;
+  captcha?: string | null;
+  color?: string | null;
+  nickname: string;
+  options?: GameOptions;
+  player_secret?: string | null;
+  preset?: string | null;
+  type: "HostGame";
+} | {
+  type: "GetHostChallenge";
+} | {
+  nickname: string;
+  type: "HostPractice";
+} | {
+  game_id?: string | null;
+  type: "SuggestNickname";
+} | {
+  avatar?: string | null;
+  color?: string | null;
+  id: string;
+  nickname: string;
+  player_id?: string | null;
+  player_secret?: string | null;
+  resume_token?: string | null;
+  type: "JoinGame";
+} | {
+  attachment?: string | null;
+  message: string;
+  type: "SendChat";
+} | {
+  request_id?: string | null;
+  type: "StartGame";
+} | {
+  motion: Motion;
+  request_id?: string | null;
+  type: "CallLobbyVote";
+} | {
+  approve: boolean;
+  request_id?: string | null;
+  type: "CastLobbyVote";
+} | {
+  request_id?: string | null;
+  type: "UndoLastAction";
+} | {
+  request_id?: string | null;
+  seconds: number;
+  type: "StartCountdown";
+} | {
+  request_id?: string | null;
+  type: "CancelCountdown";
+} | {
+  ready: boolean;
+  request_id?: string | null;
+  type: "SetReady";
+} | {
+  request_id?: string | null;
+  type: "AckRole";
+} | {
+  request_id?: string | null;
+  type: "Rematch";
+} | {
+  player: string;
+  request_id?: string | null;
+  type: "ChooseChancellor";
+} | {
+  request_id?: string | null;
+  type: "CallVote";
+} | {
+  request_id?: string | null;
+  type: "VoteChancellor";
+  vote: boolean;
+} | {
+  index: number;
+  request_id?: string | null;
+  type: "PickCard";
+} | {
+  request_id?: string | null;
+  type: "RequestVeto";
+} | {
+  accept: boolean;
+  request_id?: string | null;
+  type: "RespondVeto";
+} | {
+  cards: CardColor[];
+  request_id?: string | null;
+  type: "Claim";
+} | {
+  player?: string | null;
+  request_id?: string | null;
+  type: "PresidentialPower";
+} | {
+  type: "GetChatLog";
+} | {
+  type: "GetRules";
+} | {
+  type: "GetTimeline";
+} | {
+  type: "Leave";
+} | {
+  type: "RotateSecret";
+} | {
+  player: string;
+  request_id?: string | null;
+  type: "RevokeSecret";
+} | {
+  message: ClientProtocol;
+  player_secret?: string | null;
+  resume_token?: string | null;
+  type: "Authenticated";
+} | {
+  topics: Topic[];
+  type: "Subscribe";
+} | {
+  player_secret: string;
+  type: "WhereAmI";
+} | {
+  language?: (Language | null);
+  type: "SetLanguage";
+} | {
+  type: "SetViewVersion";
+  version: number;
+} | {
+  email?: string | null;
+  type: "SetEmail";
+} | {
+  email: string;
+  type: "InviteByEmail";
+} | {
+  player: string;
+  reason: string;
+  type: "ReportPlayer";
+} | {
+  comment?: string | null;
+  fun?: number | null;
+  reports?: FeedbackReport[];
+  rules_unclear?: boolean;
+  type: "SubmitFeedback";
+} | {
+  name: string;
+  options: GameOptions;
+  player_secret: string;
+  type: "SavePreset";
+} | {
+  name: string;
+  player_secret: string;
+  type: "DeletePreset";
+} | {
+  player_secret: string;
+  type: "ListPresets";
+} | {
+  friend_id: string;
+  player_secret: string;
+  type: "AddFriend";
+} | {
+  friend_id: string;
+  player_secret: string;
+  type: "RemoveFriend";
+} | {
+  player_secret: string;
+  type: "ListFriends";
+} | {
+  friend_id: string;
+  game_id: string;
+  type: "InviteFriend";
+} | {
+  accept: boolean;
+  invite_id: string;
+  player_secret: string;
+  type: "RespondToInvite";
+} | {
+  friend_id?: string | null;
+  player?: string | null;
+  player_secret: string;
+  type: "BlockPlayer";
+} | {
+  friend_id: string;
+  player_secret: string;
+  type: "UnblockPlayer";
+} | {
+  player_secret: string;
+  type: "ListBlocked";
+} | {
+  admin_token: string;
+  game_id: string;
+  type: "ExportGame";
+} | {
+  admin_token: string;
+  game_id: string;
+  redact_deck?: boolean;
+  type: "DebugGame";
+} | {
+  admin_token: string;
+  game: GameExport;
+  game_id: string;
+  type: "ImportGame";
+} | {
+  admin_token: string;
+  community?: string | null;
+  message?: string | null;
+  type: "SetMotd";
+} | {
+  admin_token: string;
+  game_id: string;
+  message: string;
+  type: "Announce";
+} | {
+  admin_token: string;
+  community?: string | null;
+  expires_at?: number | null;
+  reason: string;
+  target: BanTarget;
+  type: "Ban";
+} | {
+  admin_token: string;
+  ban_id: string;
+  type: "Unban";
+} | {
+  admin_token: string;
+  ban_id: string;
+  note?: string | null;
+  type: "SetBanAppeal";
+} | {
+  admin_token: string;
+  type: "ListBans";
+} | {
+  action: ModeratorAction;
+  community: string;
+  player_secret: string;
+  type: "Moderate";
+});
+
+/** Ways a government's claims cannot all be true. */
+export type Conflict = ({
+  type: "Disagreement";
+} | {
+  player: string;
+  type: "ContradictsPolicy";
+} | {
+  player: string;
+  type: "ImpossibleDraw";
+});
+
+export type ConnectionDebug = {
+  /** Whether a transport is attached, so messages are delivered rather than held for when the player returns. */
+  attached: boolean;
+  connected: boolean;
+  connected_since: number;
+  is_bot: boolean;
+  name?: string | null;
+};
+
+/** The chances for the next policies drawn, worked out only from what every player can see: how many of each policy have been enacted. Until they are enacted, policies in the deck and the discard pile are equally likely to be anywhere, so claims and policy peeks are not taken into account. */
+export type DeckOdds = {
+  /** The chance that the next three policies drawn are all liberal. */
+  all_liberal: number;
+  /** The chance that the next three policies drawn include at least one liberal. */
+  at_least_one_liberal: number;
+  /** How many liberals the next three policies drawn hold on average. */
+  expected_liberals: number;
+  facists_left: number;
+  /** Policies of each kind that have not been enacted, and so are in the deck or the discard pile. */
+  liberals_left: number;
+};
+
+/** Who may not be nominated as chancellor. */
+export type Eligibility = {
+  last_chancellor_ineligible: boolean;
+  /** The last president may be nominated again once only this many players are left alive. */
+  last_president_eligible_at: number;
+};
+
+/** A player reported along with feedback on a game. */
+export type FeedbackReport = {
+  player: string;
+  reason: string;
+};
+
+/** An invitation from a friend to join their game. */
+export type FriendInvite = {
+  /** The friend id of the player who sent it. */
+  from: string;
+  game_id: string;
+  id: string;
+  /** The sender's name in the game. */
+  name: string;
+};
+
+/** Everything about a game that helps work out why it is stuck, for admins. */
+export type GameDebug = {
+  /** The players the game is waiting on. */
+  awaiting: string[];
+  connections: { [key: string]: ConnectionDebug };
+  countdown_ends_at?: number | null;
+  /** The whole game as it would be exported, or none for practice games. */
+  game?: (GameExport | null);
+  /** When the game was left without anyone connected, which starts the countdown to it being cleaned up. */
+  idle_since?: number | null;
+  lobby_vote_expires_at?: number | null;
+  opens_at?: number | null;
+  /** When each pending timer runs out, in milliseconds since the epoch. */
+  phase_deadline?: number | null;
+};
+
+/** Things that happen during a game which clients may want to animate. These are sent alongside the game state, which only shows the end result, and kept in the game's timeline. */
+export type GameEvent = ({
+  /** Number of facists, not counting Hitler. */
+  facists: number;
+  /** Facist policies needed on the board for Hitler's election to win, which the host may have changed. */
+  hitler_chancellor_policies?: number;
+  /** Every seat, in turn order. */
+  players: string[];
+  type: "GameStarted";
+} | {
+  chancellor: string;
+  elected: boolean;
+  president: string;
+  type: "VoteHeld";
+  /** How each player voted, which is left empty in games with anonymous votes. */
+  votes: { [key: string]: boolean };
+} | {
+  /** The policy came from the top of the deck after three failed elections instead of from the chancellor. */
+  chaos: boolean;
+  /** Where the card was in the draw pile when it was drawn, counting down from the top at 0. */
+  deck_position: number;
+  policy: CardColor;
+  type: "PolicyEnacted";
+} | {
+  /** One more failed government will enact the top policy of the deck. */
+  chaos_imminent: boolean;
+  type: "ElectionTrackerAdvanced";
+  value: number;
+} | {
+  president: string;
+  type: "NominationExpired";
+} | {
+  power: PresidentialPower;
+  president: string;
+  target?: string | null;
+  type: "PowerUsed";
+} | {
+  /** Whether they claim as the president, who draws three policies, or the chancellor, who is passed two. */
+  as_president: boolean;
+  cards: CardColor[];
+  player: string;
+  type: "PoliciesClaimed";
+} | {
+  chancellor: string;
+  conflict: Conflict;
+  president: string;
+  type: "ClaimConflict";
+} | {
+  chancellor: string;
+  type: "VetoRequested";
+} | {
+  accepted: boolean;
+  president: string;
+  type: "VetoAnswered";
+} | {
+  /** Size of the draw pile after the reshuffle. */
+  cards_in_deck: number;
+  type: "DeckReshuffled";
+} | {
+  ayes: number;
+  /** Facist policies on the board at the time of the vote. */
+  facist_policies: number;
+  hitler: string;
+  nays: number;
+  president: string;
+  /** Facist policies needed on the board for Hitler's election to win. */
+  required: number;
+  type: "HitlerElected";
+  /** How each player voted, which is left empty in games with anonymous votes. */
+  votes: { [key: string]: boolean };
+});
+
+/** Everything needed to carry a game over to another server, or across a restart. Connections cannot be saved, so every player rejoins the imported game with their player id and secret. Lobby votes, countdowns, and retried requests are left out, and the deck is shuffled with a fresh random source from then on. */
+export type GameExport = {
+  banned: ([string | null, string | null])[];
+  cards: CardColor[];
+  chancellor?: string | null;
+  chancellor_called_vote: boolean;
+  chat_log: ChatLine[];
+  community?: string | null;
+  discarded: CardColor[];
+  election_tracker: number;
+  facist_policies: number;
+  governments: Government[];
+  host?: string | null;
+  investigated: { [key: string]: string[] };
+  last_chancellor?: string | null;
+  last_president?: string | null;
+  legislative_session?: (LegislativeSession | null);
+  liberal_policies: number;
+  num_facists: number;
+  opens_at?: number | null;
+  options: GameOptions;
+  phase_started_at: number;
+  players: { [key: string]: PlayerState };
+  president?: string | null;
+  president_called_vote: boolean;
+  rotation: number;
+  schedule_reminded: boolean;
+  seating: Seating;
+  seats: { [key: string]: SeatExport };
+  timeline: TimelineEntry[];
+  turn_counter: number;
+  turn_order: string[];
+  turn_phase: TurnPhase;
+  unacknowledged?: string[];
+  veto_declined: boolean;
+  veto_requested: boolean;
+};
+
+/** Settings chosen by the host when the game is created. */
+export type GameOptions = {
+  /** Keep each player's vote to themselves, so only whether a government was elected and by how much is ever shown. */
+  anonymous_votes?: boolean;
+  /** Played over days rather than in one sitting. The game is kept while nobody is connected, and players are notified when it is their turn. Turn timers of several hours are expected for these games. */
+  asynchronous?: boolean;
+  /** Show every player the chances for the next policies drawn, for groups who would rather not work them out. */
+  deck_odds?: boolean;
+  /** Number of seconds the table has to discuss a nomination before voting, or none to vote straight away. The president and chancellor can agree to call the vote early. */
+  discussion_timer?: number | null;
+  /** Number of facist policies that must be enacted before electing Hitler as chancellor wins, or none for the usual three. This is kept between 1 and one fewer than the facist policies needed to win. */
+  hitler_chancellor_policies?: number | null;
+  /** The language the game writes its chat messages in. */
+  language?: Language;
+  /** Number of seats in the lobby, between 5 and 10. Extra players are placed on a waitlist. */
+  max_players?: number | null;
+  /** Whether a president who runs out of time to nominate a chancellor counts as a failed government. */
+  nomination_timeout_fails?: boolean;
+  /** A competitive game, in which the host cannot undo actions. */
+  ranked?: boolean;
+  /** Only let the host start once every seated player has said they are ready. */
+  require_ready?: boolean;
+  /** Hold the first election until every player has said they have seen their role, so nobody still loading misses it. */
+  require_role_ack?: boolean;
+  /** When the lobby opens for joining, in milliseconds since the epoch. Until then only the host is seated. */
+  scheduled_at?: number | null;
+  /** Number of seconds that spectators see the game behind the players, so that a streamed game cannot be used to cheat. */
+  spectator_delay?: number | null;
+  /** What the game calls its factions, roles, and boards. */
+  theme?: Theme;
+  /** Number of seconds each turn phase lasts, or none if timers are disabled. */
+  turn_timer?: number | null;
+};
+
+/** Game options saved under a name, so a host can set up the same kind of game again. */
+export type GamePreset = {
+  name: string;
+  options: GameOptions;
+};
+
+/** A government that enacted a policy, and what its members say they were dealt. Claims are only what the players declare, so they may well be lies. */
+export type Government = {
+  chancellor: string;
+  /** The two policies the chancellor says they were passed. */
+  chancellor_claim?: CardColor[] | null;
+  /** Ways the claims made so far cannot all be true. */
+  conflicts: Conflict[];
+  enacted: CardColor;
+  facists_left: number;
+  /** Policies of each color that had not been enacted when the president drew, so no hand could hold more of them. */
+  liberals_left: number;
+  president: string;
+  /** The three policies the president says they drew. */
+  president_claim?: CardColor[] | null;
+};
+
+/** What a server asks of hosts before creating a game for them, so bots cannot fill it with lobbies. */
+export type HostChallenge = ({
+  type: "None";
+} | {
+  challenge: string;
+  difficulty: number;
+  type: "ProofOfWork";
+} | {
+  site_key: string;
+  type: "HCaptcha";
+});
+
+/** Languages that the game's own messages can be written in. */
+export type Language = "en" | "es";
+
+/** The policies drawn for an elected government, held from the vote until one is enacted or the rest are vetoed. */
+export type LegislativeSession = {
+  /** The policies thrown away so far, which go on the discard pile once the session ends. */
+  discarded: CardColor[];
+  /** The policies still in hand, in the order they lay in the deck, so the last was on top. */
+  drawn: CardColor[];
+  stage: LegislativeStage;
+};
+
+/** Whose turn it is to pick a policy in a legislative session. */
+export type LegislativeStage = ({
+  type: "President";
+} | {
+  /** Where the president's discard was among the three drawn. */
+  discarded_slot: number;
+  type: "Chancellor";
+});
+
+export type LobbyVoteView = {
+  called_by: string;
+  expires_at: number;
+  motion: Motion;
+  /** Votes in favor needed for the motion to pass. */
+  needed: number;
+  voters: string[];
+  votes: { [key: string]: boolean };
+};
+
+/** What a community's moderators can do within it. Only its owners can choose who its moderators are. */
+export type ModeratorAction = ({
+  game_id: string;
+  player: string;
+  type: "Kick";
+} | {
+  expires_at?: number | null;
+  reason: string;
+  target: BanTarget;
+  type: "Ban";
+} | {
+  ban_id: string;
+  type: "Unban";
+} | {
+  type: "ListBans";
+} | {
+  message?: string | null;
+  type: "SetMotd";
+} | {
+  game_id: string;
+  message: string;
+  type: "Announce";
+} | {
+  friend_id: string;
+  type: "AddModerator";
+} | {
+  friend_id: string;
+  type: "RemoveModerator";
+} | {
+  type: "ListModerators";
+});
+
+/** What a lobby vote decides. */
+export type Motion = ({
+  player: string;
+  type: "Kick";
+} | {
+  commands: number;
+  type: "Undo";
+});
+
+export type PlayerState = {
+  dead: boolean;
+  /** Whether the player has said they are ready to start, in the lobby. */
+  ready: boolean;
+  role: PlayerType;
+  vote?: boolean | null;
+};
+
+export type PlayerType = "Liberal" | "Facist" | "Hitler";
+
+/** The game as one player sees it, as sent in `GameState` messages. The view is built by hand so that each player only sees what they are allowed to, so this spells out its shape. Unknown fields are refused, so a view that gains a field this does not list fails to deserialize. */
+export type PlayerView = {
+  /** How to draw each kind of policy without relying on color, and what the host calls its faction. */
+  card_faces: CardFace[];
+  /** The policies in the player's hand, while they are choosing one. */
+  cards?: CardColor[] | null;
+  cards_in_deck: number;
+  cards_in_discard: number;
+  chancellor?: string | null;
+  /** When the host's countdown to the start of the game runs out, in milliseconds since the epoch. */
+  countdown_ends_at?: number | null;
+  /** The chances for the next policies drawn, in games with the `deck_odds` option. */
+  deck_odds?: (DeckOdds | null);
+  election_tracker: number;
+  facist_cards: number;
+  facist_policies: number;
+  /** Every government that has enacted a policy this game, with its members' claims. */
+  governments: Government[];
+  host?: string | null;
+  last_chancellor?: string | null;
+  last_president?: string | null;
+  liberal_cards: number;
+  liberal_policies: number;
+  lobby_vote?: (LobbyVoteView | null);
+  num_facists: number;
+  /** When a scheduled game opens, in milliseconds since the epoch. */
+  opens_at?: number | null;
+  /** When the current phase times out, in milliseconds since the epoch. */
+  phase_deadline?: number | null;
+  /** When the current phase started, in milliseconds since the epoch, for phases with a time limit. */
+  phase_started_at?: number | null;
+  players: { [key: string]: SeatView };
+  president?: string | null;
+  /** Groups of seats taken from the same device, shown to the host in the lobby. */
+  shared_devices?: string[][] | null;
+  /** What the game calls its factions, roles, and boards, in the player's language. */
+  theme: ThemeNames;
+  turn_order: string[];
+  turn_phase: TurnPhase;
+  veto_declined?: boolean | null;
+  /** Whether the chancellor has asked for a veto, and whether the president declined it, while the chancellor chooses a policy. */
+  veto_requested?: boolean | null;
+  /** Who in the government has agreed to call the vote early, during a discussion. */
+  vote_called?: string[] | null;
+  /** How many players have voted, while voting. */
+  votes?: number | null;
+  waitlist: WaitlistView[];
+  /** The player's place on the waitlist, counting from 1. */
+  waitlist_position?: number | null;
+};
+
+/** The second version of the player view, sent to clients that ask for it with `SetViewVersion`. It has every field of the first version as well as these. */
+export type PlayerViewV2 = {
+  /** Everything that has happened in the game so far, as returned by `GetTimeline`. */
+  events: TimelineEntry[];
+  /** The options the game is played with. */
+  options: GameOptions;
+} & (PlayerView);
+
+export type PresidentialPower = "InvestigateLoyalty" | "CallSpecialElection" | "PolicyPeek" | "Execution";
+
+/** A step of the reveal at the start of the game, when the fascists find out who each other are while everyone else has their eyes closed. */
+export type RevealStep = ("EveryoneCloseEyes" | "FacistsOpenEyes" | "HitlerLowersThumb" | "EveryoneOpenEyes" | "HitlerOpensEyes" | "HitlerRaisesThumb" | "FacistsCloseEyes");
+
+/** Rules that depend on the size of the table. The game logic reads these, and the same values are sent to clients that ask for the rules. */
+export type Rules = {
+  /** Failed governments in a row before the top policy is enacted. */
+  election_tracker_limit: number;
+  eligibility: Eligibility;
+  facist_policies_to_win: number;
+  /** Number of fascists, not counting Hitler. */
+  facists: number;
+  /** Fascist policies that must be enacted before electing Hitler as chancellor wins the game. */
+  hitler_chancellor_policies: number;
+  /** Whether Hitler is told who the other fascists are. */
+  hitler_knows_facists: boolean;
+  liberal_policies_to_win: number;
+  liberals: number;
+  players: number;
+  /** The power granted by each fascist policy slot, in the order they are filled. */
+  power_track: ((PresidentialPower | null))[];
+  /** Fascist policies that must be enacted before the government may veto. */
+  veto_policies: number;
+};
+
+/** The public score of a game, for lightweight displays that do not need the full state. */
+export type Scoreboard = {
+  election_tracker: number;
+  facist_policies: number;
+  liberal_policies: number;
+  turn_phase: TurnPhase;
+};
+
+/** A seat's connection details, kept in an export so players can take their seats again. */
+export type SeatExport = {
+  avatar?: string | null;
+  color?: string | null;
+  fingerprint?: string | null;
+  is_bot: boolean;
+  language?: (Language | null);
+  name?: string | null;
+  secret?: string | null;
+  topics: Topic[];
+};
+
+export type SeatView = {
+  /** Whether the player has seen their role, while the first election waits on everyone to. */
+  acknowledged?: boolean | null;
+  avatar?: string | null;
+  color?: string | null;
+  dead: boolean;
+  name: string;
+  /** Whether the player is ready to start, in the lobby. */
+  ready?: boolean | null;
+  /** The player's role, if the viewer knows it. */
+  role?: (PlayerType | null);
+  vote?: boolean | null;
+};
+
+/** Keeps track of who waits for a seat, and the order everyone joined in. Both the lobby waitlist and rematches fill seats from here, so seating is always first come first served. */
+export type Seating = {
+  join_order: string[];
+  waitlist: string[];
+};
+
+export type ServerProtocol = ({
+  game_id: string;
+  player_id: string;
+  secret: string;
+  type: "SetIdentifiers";
+} | {
+  expires_at: number;
+  token: string;
+  type: "ResumeToken";
+} | {
+  game_id?: string | null;
+  player_id?: string | null;
+  type: "CurrentGame";
+} | {
+  message: string;
+  type: "Alert";
+} | {
+  message: string;
+  request_id?: string | null;
+  type: "Error";
+} & ({
+  code: "WrongPhase";
+} | {
+  code: "AlreadyStarted";
+} | {
+  action: string;
+  code: "NotHost";
+} | {
+  code: "InvalidPlayerCount";
+  players: number;
+} | {
+  action: string;
+  code: "NotPresident";
+} | {
+  action: string;
+  code: "NotChancellor";
+} | {
+  action: string;
+  code: "NotInGovernment";
+} | {
+  code: "SelfTarget";
+} | {
+  code: "TermLimited";
+  player: string;
+} | {
+  code: "MissingTarget";
+} | {
+  code: "PlayerNotFound";
+  player: string;
+} | {
+  code: "PlayerDead";
+  player: string;
+} | {
+  code: "VoterDead";
+} | {
+  code: "NotAPlayer";
+} | {
+  code: "InvalidPolicy";
+} | {
+  code: "VetoLocked";
+} | {
+  code: "VetoAlreadyRequested";
+} | {
+  code: "NoVetoRequested";
+} | {
+  code: "VetoPending";
+} | {
+  code: "NotReady";
+  players: string[];
+} | {
+  code: "RolesNotAcknowledged";
+  players: string[];
+} | {
+  code: "CountdownRunning";
+} | {
+  code: "NoCountdown";
+} | {
+  code: "LobbyVoteInProgress";
+} | {
+  code: "NoLobbyVote";
+} | {
+  code: "UndoDisabled";
+} | {
+  code: "NothingToUndo";
+} | {
+  code: "AlreadyClaimed";
+} | {
+  code: "InvalidClaim";
+} | {
+  code: "InvalidExport";
+  reason: string;
+} | {
+  code: "NotHoldingDevice";
+} | {
+  code: "Unauthenticated";
+} | {
+  code: "Internal";
+}) | {
+  request_id: string;
+  type: "Ack";
+} | {
+  retry_after: number;
+  retry_after_ms: number;
+  type: "ServerBusy";
+} | {
+  retry_after_ms: number;
+  type: "ReconnectThrottled";
+} | {
+  retry_after_ms: number;
+  type: "MessagesThrottled";
+} | {
+  latest: number;
+  type: "ViewVersion";
+  version: number;
+} | {
+  attachment?: string | null;
+  id?: string | null;
+  message: string;
+  segments: ChatSegment[];
+  type: "ReceiveChat";
+} | {
+  state: VersionedPlayerView;
+  type: "GameState";
+} | {
+  state: Scoreboard;
+  type: "Scoreboard";
+} | {
+  log: ChatLine[];
+  type: "ChatLog";
+} | {
+  rules: Rules;
+  type: "Rules";
+} | {
+  text: string;
+  title: string;
+  topic: TutorialTopic;
+  type: "TutorialStep";
+} | {
+  event: GameEvent;
+  type: "GameEvent";
+} | {
+  chaos_imminent: boolean;
+  type: "ElectionTrackerAdvanced";
+  value: number;
+} | {
+  host: string;
+  type: "HostChanged";
+} | {
+  president?: string | null;
+  reveal: RevealStep[];
+  turn_order: string[];
+  type: "TurnOrderAssigned";
+} | {
+  known: { [key: string]: PlayerType };
+  role: PlayerType;
+  type: "RoleAssigned";
+} | {
+  seconds: number;
+  type: "Countdown";
+} | {
+  type: "CountdownCancelled";
+} | {
+  chancellor: string;
+  type: "VetoRequested";
+} | {
+  ayes: number;
+  nays: number;
+  passed: boolean;
+  type: "ElectionResult";
+  votes?: { [key: string]: boolean } | null;
+} | {
+  events: TimelineEntry[];
+  type: "Timeline";
+} | {
+  presets: GamePreset[];
+  type: "Presets";
+} | {
+  friend_id: string;
+  friends: string[];
+  invites: FriendInvite[];
+  requests: string[];
+  type: "Friends";
+} | {
+  blocked: string[];
+  type: "Blocked";
+} | {
+  game: GameExport;
+  game_id: string;
+  type: "GameExport";
+} | {
+  debug: GameDebug;
+  game_id: string;
+  type: "GameDebug";
+} | {
+  invite: FriendInvite;
+  type: "InviteReceived";
+} | {
+  message: string;
+  type: "Motd";
+} | {
+  message: string;
+  type: "Announcement";
+} | {
+  challenge: HostChallenge;
+  type: "HostChallenge";
+} | {
+  nickname: string;
+  type: "NicknameSuggestion";
+} | {
+  bans: Ban[];
+  type: "Bans";
+} | {
+  community: string;
+  moderators: string[];
+  owners: string[];
+  type: "Moderators";
+} | {
+  expires_at?: number | null;
+  reason: string;
+  type: "Banned";
+} | {
+  earned: Achievement[];
+  season: number;
+  season_wins: number;
+  type: "Achievements";
+});
+
+/** What the game calls its factions, roles, and boards, so the same rules can be played as a different game. Any name given here replaces the one from the preset. */
+export type Theme = {
+  chancellor?: string | null;
+  election_tracker?: string | null;
+  facist?: string | null;
+  facist_board?: string | null;
+  hitler?: string | null;
+  liberal?: string | null;
+  liberal_board?: string | null;
+  policy?: string | null;
+  preset?: ThemePreset;
+  president?: string | null;
+};
+
+/** Every name in a theme, with the preset's filling in any the host left out. */
+export type ThemeNames = {
+  chancellor: string;
+  election_tracker: string;
+  facist: string;
+  facist_board: string;
+  hitler: string;
+  liberal: string;
+  liberal_board: string;
+  policy: string;
+  president: string;
+};
+
+/** Themes built into the server, which a host can pick instead of naming everything themselves. */
+export type ThemePreset = ("Classic" | "Starship");
+
+/** An event in the game's timeline and when it happened, in milliseconds since the epoch. */
+export type TimelineEntry = {
+  at: number;
+  event: GameEvent;
+};
+
+/** Groups of server messages that a connection can choose to receive. Alerts and identifiers are always delivered. */
+export type Topic = ("Chat" | "GameState" | "Scoreboard" | "Events");
+
+/** Where a game is in a round. The phases a game can move between are listed in [`TurnPhase::can_become`]. */
+export type TurnPhase = ({
+  type: "Lobby";
+} | {
+  type: "Ended";
+  winner: CardColor;
+} | {
+  type: "Electing";
+} | {
+  type: "Discussion";
+} | {
+  type: "Voting";
+} | {
+  type: "PresidentSelect";
+} | {
+  type: "ChancellorSelect";
+} | {
+  power: PresidentialPower;
+  type: "PresidentialPower";
+});
+
+export type TutorialTopic = "Roles" | "Nomination" | "Voting" | "Legislation" | "PresidentLegislation" | "ChancellorLegislation" | "PresidentialPower" | "GameOver";
+
+/** The player view in the version a connection asked for. Clients get the first version unless they ask for another with `SetViewVersion`. */
+export type VersionedPlayerView = (PlayerView | PlayerViewV2);
+
+export type WaitlistView = {
+  id: string;
+  name: string;
+};
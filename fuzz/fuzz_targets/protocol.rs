@@ -0,0 +1,114 @@
+//! Feeds arbitrary input to the message handler, one message per line, as a websocket would.
+//!
+//! Each input plays against a fresh game of five that has just started. A line starting with a digit from 0 to 4 is sent by that seat,
+//! and any other line by a connection without a seat. `$game` and `$player0` to `$player4` are replaced with the real ids first,
+//! so the seeds in `seeds/protocol` can reach the game itself:
+//!
+//! ```sh
+//! cargo +nightly fuzz run protocol seeds/protocol -- -dict=protocol.dict
+//! ```
+//!
+//! The handler must never panic, and a message it rejects with an error must leave the game as it was.
+
+#![no_main]
+
+use std::{sync::{Arc, mpsc}, time::Duration};
+
+use libfuzzer_sys::fuzz_target;
+use secrethitler::{email::EmailDispatcher, limits::ServerLimits, server::{ActivePlayers, ConnectionContext, GlobalState, ServerState, handle_message, parse_message}, tokens::ResumeTokens, webhooks::WebhookDispatcher};
+use secrethitler_core::{game_state::GameOptions, protocol::ClientProtocol};
+
+fn server() -> ServerState {
+    ServerState {
+        games: GlobalState::default(),
+        webhooks: WebhookDispatcher::start(vec![], None),
+        email: EmailDispatcher::start(None, None),
+        tokens: Arc::new(ResumeTokens::new(vec![], Duration::from_secs(60))),
+        limits: Arc::new(ServerLimits::new(None, None, Duration::from_secs(10))),
+        audit: Arc::default(),
+        presets: Arc::default(),
+        friends: Arc::default(),
+        replays: Arc::default(),
+        archive: Arc::default(),
+        ratings: Arc::default(),
+        bans: Arc::default(),
+        addresses: Arc::default(),
+        host_gate: Arc::default(),
+        achievements: Arc::default(),
+        watchdog: Arc::default(),
+        active_players: ActivePlayers::default(),
+        motd: Arc::default(),
+        communities: Arc::default(),
+        admin_token: Some("admin".into()),
+        allow_multiple_games: false,
+        require_action_auth: false,
+        chat_links: Arc::default(),
+        uploads: Arc::default(),
+        blocks: Arc::default(),
+    }
+}
+
+fn connect() -> (ConnectionContext, mpsc::Receiver<String>) {
+    let (tx, rx) = mpsc::channel();
+    (ConnectionContext::with_sink(Arc::new(tx)), rx)
+}
+
+fn replies(rx: &mpsc::Receiver<String>) -> Vec<serde_json::Value> {
+    rx.try_iter().filter_map(|message| serde_json::from_str(&message).ok()).collect()
+}
+
+fuzz_target!(|data: &[u8]| {
+    let input = match std::str::from_utf8(data) {
+        Ok(input) => input,
+        Err(_) => return
+    };
+    let server = server();
+    let mut seats: Vec<(ConnectionContext, mpsc::Receiver<String>)> = (0..5).map(|_| connect()).collect();
+    for i in 0..seats.len() {
+        let nickname = format!("player {}", i);
+        let message = match seats.first().and_then(|(ctx, _)| ctx.game) {
+            Some(id) => ClientProtocol::JoinGame { id, nickname, player_id: None, player_secret: None, resume_token: None, avatar: None, color: None },
+            None => ClientProtocol::HostGame { nickname, options: GameOptions::default(), avatar: None, color: None, player_secret: None, preset: None, captcha: None }
+        };
+        handle_message(&server, &mut seats[i].0, message);
+    }
+    handle_message(&server, &mut seats[0].0, ClientProtocol::StartGame { request_id: None });
+    let game_id = seats[0].0.game.unwrap();
+    let game = server.games.read().get(&game_id).unwrap().clone();
+    let (mut stranger, stranger_rx) = connect();
+
+    for line in input.lines() {
+        let mut line = line.replace("$game", &game_id.to_string());
+        for (i, (ctx, _)) in seats.iter().enumerate() {
+            line = line.replace(&format!("$player{}", i), &ctx.player.map(|id| id.to_string()).unwrap_or_default());
+        }
+        let seat = line.chars().next().and_then(|c| c.to_digit(10)).map(|seat| seat as usize).filter(|seat| *seat < seats.len());
+        let raw = if seat.is_some() { &line[1..] } else { &line[..] };
+
+        let before = serde_json::to_value(game.lock().export()).unwrap();
+        let message = match parse_message(raw) {
+            Some(message) => message,
+            None => continue
+        };
+        let (ctx, rx) = match seat {
+            Some(seat) => {
+                let (ctx, rx) = &mut seats[seat];
+                (ctx, &*rx)
+            },
+            None => (&mut stranger, &stranger_rx)
+        };
+        rx.try_iter().for_each(drop);
+        handle_message(&server, ctx, message);
+
+        let replies = replies(rx);
+        assert!(!replies.iter().any(|reply| reply["type"] == "Error" && reply["code"] == "Internal"), "the handler panicked on {}", raw);
+        if replies.iter().any(|reply| reply["type"] == "Error") {
+            let after = serde_json::to_value(game.lock().export()).unwrap();
+            assert_eq!(before, after, "{} was rejected but changed the game", raw);
+        }
+        // the stranger may have hosted or joined a game of its own, which has nothing to do with this one
+        if server.games.read().get(&game_id).is_none() {
+            return
+        }
+    }
+});
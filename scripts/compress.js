@@ -0,0 +1,22 @@
+// Write gzip and brotli copies of the frontend build next to the originals, so the server can send them as is.
+const fs = require("fs");
+const path = require("path");
+const zlib = require("zlib");
+
+const COMPRESSIBLE = /\.(html|js|css|json|svg|txt|map|ico)$/;
+
+function walk(dir) {
+  for (const entry of fs.readdirSync(dir, { withFileTypes: true })) {
+    const file = path.join(dir, entry.name);
+    if (entry.isDirectory()) {
+      walk(file);
+    }
+    else if (COMPRESSIBLE.test(entry.name)) {
+      const contents = fs.readFileSync(file);
+      fs.writeFileSync(`${file}.gz`, zlib.gzipSync(contents, { level: 9 }));
+      fs.writeFileSync(`${file}.br`, zlib.brotliCompressSync(contents));
+    }
+  }
+}
+
+walk(path.join(__dirname, "..", "frontend", "build"));
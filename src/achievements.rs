@@ -0,0 +1,201 @@
+use std::{collections::{BTreeSet, HashMap, VecDeque}, sync::Arc, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use parking_lot::RwLock;
+use serde::Serialize;
+use uuid::Uuid;
+use warp::{Filter, Rejection, Reply, http::StatusCode};
+
+use secrethitler_core::{achievements::{Achievement, WIN_STREAK}, game_state::{CardColor, GameState, PlayerType}};
+
+use crate::{communities, friends};
+
+/// How long a season lasts unless configured otherwise.
+pub const DEFAULT_SEASON_LENGTH: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+/// Finished seasons kept for their leaderboards. The oldest are dropped first.
+const PAST_SEASONS: usize = 8;
+
+/// Most players shown on a season leaderboard.
+const LEADERBOARD_SIZE: usize = 100;
+
+/// Most players the server keeps achievements for.
+const MAX_PLAYERS: usize = 100_000;
+
+#[derive(Default)]
+struct Player {
+    earned: BTreeSet<Achievement>,
+    /// Games won in a row, up to the last one played.
+    streak: u32,
+}
+
+#[derive(Clone)]
+struct SeasonRecord {
+    name: String,
+    games: u32,
+    wins: u32,
+}
+
+struct Season {
+    number: u64,
+    /// Each community keeps its own standings, so players are known by their community along with their friend id.
+    players: HashMap<(Option<String>, Uuid), SeasonRecord>,
+}
+
+/// A row of a season leaderboard, with the player known by their friend id.
+#[derive(Serialize)]
+pub struct SeasonStanding {
+    pub id: Uuid,
+    pub name: String,
+    pub games: u32,
+    pub wins: u32,
+}
+
+/// A season and its leaderboard, sorted by wins.
+#[derive(Serialize)]
+pub struct SeasonLeaderboard {
+    pub season: u64,
+    pub starts_at: u64,
+    pub ends_at: u64,
+    pub standings: Vec<SeasonStanding>,
+}
+
+/// What a player got out of a finished game.
+pub struct GameResult {
+    /// The player's id in the game, not their secret.
+    pub player: Uuid,
+    /// Achievements earned for the first time.
+    pub earned: Vec<Achievement>,
+    pub season: u64,
+    pub season_wins: u32,
+}
+
+/// Achievements players have earned, and how they have done in each season.
+/// Seasons are numbered from the unix epoch and a new one starts every season length, so every server agrees on them.
+/// Like ratings, these belong to a player secret and are held in memory.
+pub struct Achievements {
+    season_length: Duration,
+    players: RwLock<HashMap<Uuid, Player>>,
+    /// The current season last, after the finished seasons that are still kept.
+    seasons: RwLock<VecDeque<Season>>,
+}
+
+impl Default for Achievements {
+    fn default() -> Achievements {
+        Achievements::new(DEFAULT_SEASON_LENGTH)
+    }
+}
+
+impl Achievements {
+    pub fn new(season_length: Duration) -> Achievements {
+        Achievements { season_length: season_length.max(Duration::from_secs(1)), players: RwLock::default(), seasons: RwLock::default() }
+    }
+
+    /// The number of the season a moment falls in.
+    pub fn season_at(&self, now: SystemTime) -> u64 {
+        now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / self.season_length.as_secs()
+    }
+
+    /// Update achievements and the season leaderboard from a finished game, returning what each seat got out of it.
+    /// Bots and players without a secret are left out.
+    pub fn record_game(&self, state: &GameState, now: SystemTime) -> Vec<GameResult> {
+        let winner = match state.winner() {
+            Some(winner) => winner,
+            None => return vec![]
+        };
+        let number = self.season_at(now);
+        let mut game_achievements = state.game_achievements();
+        let mut players = self.players.write();
+        let mut seasons = self.seasons.write();
+        if seasons.back().is_none_or(|season| season.number != number) {
+            seasons.push_back(Season { number, players: HashMap::new() });
+            while seasons.len() > PAST_SEASONS + 1 {
+                seasons.pop_front();
+            }
+        }
+        let season = seasons.back_mut().unwrap();
+        let mut results = vec![];
+        for (player_id, conn) in &state.conn {
+            let (role, secret) = match (state.role(player_id), conn.secret) {
+                (Some(role), Some(secret)) if !conn.is_bot => (role, secret),
+                _ => continue
+            };
+            let id = friends::friend_id(secret);
+            if !players.contains_key(&id) && players.len() >= MAX_PLAYERS {
+                continue
+            }
+            let won = match role {
+                PlayerType::Liberal => winner == CardColor::Liberal,
+                PlayerType::Facist | PlayerType::Hitler => winner == CardColor::Facist,
+            };
+            let player = players.entry(id).or_default();
+            player.streak = if won { player.streak + 1 } else { 0 };
+            let mut candidates = game_achievements.remove(player_id).unwrap_or_default();
+            if player.streak >= WIN_STREAK {
+                candidates.push(Achievement::WinStreak);
+            }
+            let earned = candidates.into_iter().filter(|achievement| player.earned.insert(*achievement)).collect();
+            let record = season.players.entry((state.community.clone(), id)).or_insert_with(|| SeasonRecord { name: String::new(), games: 0, wins: 0 });
+            record.name = conn.name.clone().unwrap_or_default();
+            record.games += 1;
+            record.wins += won as u32;
+            results.push(GameResult { player: *player_id, earned, season: number, season_wins: record.wins });
+        }
+        results
+    }
+
+    /// The achievements earned by the player with this friend id.
+    pub fn earned(&self, id: Uuid) -> Vec<Achievement> {
+        self.players.read().get(&id).map(|player| player.earned.iter().copied().collect()).unwrap_or_default()
+    }
+
+    /// The leaderboard of a season in a community, or none if it has not started or is no longer kept.
+    pub fn leaderboard(&self, community: Option<&str>, number: u64, now: SystemTime) -> Option<SeasonLeaderboard> {
+        if number > self.season_at(now) {
+            return None
+        }
+        let seasons = self.seasons.read();
+        let mut standings: Vec<SeasonStanding> = match seasons.iter().find(|season| season.number == number) {
+            Some(season) => season.players.iter().filter(|((player_community, _), _)| player_community.as_deref() == community).map(|((_, id), record)| SeasonStanding { id: *id, name: record.name.clone(), games: record.games, wins: record.wins }).collect(),
+            // the current season may simply not have had any games yet
+            None if number == self.season_at(now) => vec![],
+            None => return None
+        };
+        standings.sort_by(|a, b| b.wins.cmp(&a.wins).then(a.games.cmp(&b.games)).then(a.id.cmp(&b.id)));
+        standings.truncate(LEADERBOARD_SIZE);
+        let length = self.season_length.as_secs();
+        Some(SeasonLeaderboard { season: number, starts_at: number * length * 1000, ends_at: (number + 1) * length * 1000, standings })
+    }
+}
+
+/// Serve the current season's leaderboard at `/leaderboard/season`, past seasons at `/leaderboard/season/{number}`, and players' achievements at `/achievements/{friend id}`.
+/// Leaderboards are a community's with `?community=`.
+pub fn route(achievements: Arc<Achievements>) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone {
+    let current = {
+        let achievements = achievements.clone();
+        warp::path!("leaderboard" / "season")
+            .and(warp::get())
+            .and(communities::community())
+            .map(move |community: Option<String>| -> Box<dyn Reply> {
+                let now = SystemTime::now();
+                Box::new(warp::reply::json(&achievements.leaderboard(community.as_deref(), achievements.season_at(now), now)))
+            })
+    };
+    let past = {
+        let achievements = achievements.clone();
+        warp::path!("leaderboard" / "season" / u64)
+            .and(warp::get())
+            .and(communities::community())
+            .map(move |number, community: Option<String>| -> Box<dyn Reply> {
+                match achievements.leaderboard(community.as_deref(), number, SystemTime::now()) {
+                    Some(leaderboard) => Box::new(warp::reply::json(&leaderboard)),
+                    None => Box::new(warp::reply::with_status("season not found", StatusCode::NOT_FOUND))
+                }
+            })
+    };
+    let earned = warp::path!("achievements" / Uuid)
+        .and(warp::get())
+        .map(move |id| -> Box<dyn Reply> {
+            Box::new(warp::reply::json(&achievements.earned(id)))
+        });
+    current.or(past).unify().or(earned).unify()
+}
@@ -0,0 +1,118 @@
+use std::{net::{IpAddr, SocketAddr}, sync::Arc};
+
+use warp::{Filter, Rejection, Reply, http::StatusCode, reply::Response};
+
+/// Parse an address range such as `203.0.113.0/24`. An address alone covers just itself.
+pub fn parse_range(range: &str) -> Option<(IpAddr, u32)> {
+    let (address, prefix) = match range.split_once('/') {
+        Some((address, prefix)) => (address.trim().parse::<IpAddr>().ok()?, Some(prefix.trim().parse::<u32>().ok()?)),
+        None => (range.trim().parse::<IpAddr>().ok()?, None)
+    };
+    let bits = if address.is_ipv4() { 32 } else { 128 };
+    match prefix {
+        Some(prefix) if prefix > bits => None,
+        prefix => Some((address, prefix.unwrap_or(bits)))
+    }
+}
+
+pub fn in_range(address: IpAddr, (network, prefix): (IpAddr, u32)) -> bool {
+    let (address, network, bits) = match (address.to_canonical(), network) {
+        (IpAddr::V4(address), IpAddr::V4(network)) => (u32::from(address) as u128, u32::from(network) as u128, 32),
+        (IpAddr::V6(address), IpAddr::V6(network)) => (u128::from(address), u128::from(network), 128),
+        _ => return false
+    };
+    // shifting by the full width would overflow, and a zero prefix matches everything anyway
+    prefix == 0 || (address ^ network) >> (bits - prefix) == 0
+}
+
+fn parse_ranges(ranges: &[String]) -> Result<Vec<(IpAddr, u32)>, String> {
+    ranges.iter().map(|range| parse_range(range).ok_or_else(|| format!("{} is not a valid address range", range))).collect()
+}
+
+/// The addresses named by the `for` parameters of a `Forwarded` header, in the order the proxies added them.
+/// Obfuscated and unknown addresses are left out.
+fn forwarded_addresses(header: &str) -> Vec<IpAddr> {
+    header.split(',').filter_map(|element| {
+        let node = element.split(';').find_map(|pair| pair.trim().split_once('=').filter(|(name, _)| name.eq_ignore_ascii_case("for")))?.1;
+        let node = node.trim().trim_matches('"');
+        match node.strip_prefix('[') {
+            Some(v6) => v6.split(']').next()?.parse().ok(),
+            // an ipv4 address may come with a port
+            None => node.split(':').next()?.parse().ok().filter(|_| node.matches(':').count() <= 1)
+        }
+    }).collect()
+}
+
+/// Which proxies are believed about where a client connected from, and which addresses may use the server at all.
+pub struct AddressPolicy {
+    trusted_proxies: Vec<(IpAddr, u32)>,
+    allowed: Vec<(IpAddr, u32)>,
+    denied: Vec<(IpAddr, u32)>,
+}
+
+impl Default for AddressPolicy {
+    /// Trust a proxy on this machine and let everyone in.
+    fn default() -> AddressPolicy {
+        AddressPolicy::new(None, &[], &[]).unwrap()
+    }
+}
+
+impl AddressPolicy {
+    /// Trust the proxies in the ranges given, or only ones on this machine if none are given, and refuse addresses that are denied or, if any are allowed, not allowed.
+    pub fn new(trusted_proxies: Option<&[String]>, allowed: &[String], denied: &[String]) -> Result<AddressPolicy, String> {
+        let trusted_proxies = match trusted_proxies {
+            Some(ranges) => parse_ranges(ranges)?,
+            None => vec![(IpAddr::from([127, 0, 0, 0]), 8), (IpAddr::from([0, 0, 0, 0, 0, 0, 0, 1]), 128)]
+        };
+        Ok(AddressPolicy { trusted_proxies, allowed: parse_ranges(allowed)?, denied: parse_ranges(denied)? })
+    }
+
+    fn trusts(&self, address: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|range| in_range(address, *range))
+    }
+
+    /// The address a client connected from. Forwarded addresses are only believed from a trusted proxy, or over a unix socket, which has no address.
+    /// Each proxy adds the address it was reached from to the end, so the client is the last address before the trusted proxies.
+    pub fn resolve(&self, remote: Option<IpAddr>, forwarded_for: Option<&str>, forwarded: Option<&str>) -> Option<IpAddr> {
+        if let Some(remote) = remote.filter(|remote| !self.trusts(*remote)) {
+            return Some(remote)
+        }
+        let chain: Vec<IpAddr> = match (forwarded_for, forwarded) {
+            (Some(header), _) => header.split(',').filter_map(|address| address.trim().parse().ok()).collect(),
+            (None, Some(header)) => forwarded_addresses(header),
+            (None, None) => vec![]
+        };
+        chain.iter().rev().find(|address| !self.trusts(**address)).or(chain.first()).copied().or(remote)
+    }
+
+    /// Whether a client at this address may use the server. Clients whose address is unknown are only let in when no addresses are singled out as allowed.
+    pub fn admits(&self, address: Option<IpAddr>) -> bool {
+        match address {
+            Some(address) => !self.denied.iter().any(|range| in_range(address, *range)) && (self.allowed.is_empty() || self.allowed.iter().any(|range| in_range(address, *range))),
+            None => self.allowed.is_empty()
+        }
+    }
+}
+
+/// The address the client connected from, worked out once from the connection and any headers set by trusted proxies.
+pub fn client(policy: Arc<AddressPolicy>) -> impl Filter<Extract = (Option<IpAddr>,), Error = Rejection> + Clone {
+    warp::addr::remote()
+        .and(warp::header::optional::<String>("x-forwarded-for"))
+        .and(warp::header::optional::<String>("forwarded"))
+        .map(move |remote: Option<SocketAddr>, forwarded_for: Option<String>, forwarded: Option<String>| {
+            policy.resolve(remote.map(|remote| remote.ip()), forwarded_for.as_deref(), forwarded.as_deref())
+        })
+}
+
+/// Turn away clients whose address the policy does not admit, passing everyone else on to the routes that follow.
+pub fn guard(policy: Arc<AddressPolicy>) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+    client(policy.clone()).and_then(move |address: Option<IpAddr>| {
+        let admitted = policy.admits(address);
+        async move {
+            if admitted {
+                return Err(warp::reject::not_found())
+            }
+            Ok(warp::reply::with_status("address not allowed", StatusCode::FORBIDDEN).into_response())
+        }
+    })
+}
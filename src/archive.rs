@@ -0,0 +1,277 @@
+use std::{collections::VecDeque, fs::{self, OpenOptions}, io::Write, path::PathBuf, sync::Arc};
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use warp::{Filter, Rejection, Reply, http::StatusCode};
+
+use secrethitler_core::game_state::{CardColor, GameState};
+
+/// Most finished games kept. The oldest are dropped first.
+const MAX_ARCHIVED: usize = 100_000;
+
+/// Most games that went badly listed in the feedback stats.
+const MAX_TROUBLED: usize = 50;
+
+/// Games returned on each page of the history.
+pub const PAGE_SIZE: usize = 20;
+
+/// What is kept of a finished game for the history.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArchivedGame {
+    pub game_id: Uuid,
+    pub players: Vec<String>,
+    pub winner: CardColor,
+    pub liberal_policies: u8,
+    pub facist_policies: u8,
+    pub ranked: bool,
+    /// When the game started and ended, in milliseconds since the epoch.
+    pub started_at: u64,
+    pub ended_at: u64,
+    pub duration_secs: u64,
+    /// The community the game was played in, or none for the server's own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub community: Option<String>,
+    /// What the players said about the game after it ended. Only admins see it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub feedback: Vec<Feedback>,
+}
+
+/// One player's feedback on a game they played.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Feedback {
+    pub player: Uuid,
+    /// How fun the game was, from 1 to 5.
+    pub fun: Option<u8>,
+    pub rules_unclear: bool,
+    pub comment: Option<String>,
+    /// The players they reported.
+    pub reported: Vec<Uuid>,
+}
+
+/// Feedback on one game, for finding games that went badly.
+#[derive(Serialize)]
+pub struct GameFeedback {
+    pub game_id: Uuid,
+    pub ended_at: u64,
+    pub responses: usize,
+    pub average_fun: Option<f64>,
+    pub rules_unclear: usize,
+    pub reports: usize,
+    pub comments: Vec<String>,
+}
+
+impl GameFeedback {
+    fn new(game: &ArchivedGame) -> GameFeedback {
+        let ratings: Vec<f64> = game.feedback.iter().filter_map(|feedback| feedback.fun).map(f64::from).collect();
+        GameFeedback {
+            game_id: game.game_id,
+            ended_at: game.ended_at,
+            responses: game.feedback.len(),
+            average_fun: Some(ratings.iter().sum::<f64>() / ratings.len() as f64).filter(|_| !ratings.is_empty()),
+            rules_unclear: game.feedback.iter().filter(|feedback| feedback.rules_unclear).count(),
+            reports: game.feedback.iter().map(|feedback| feedback.reported.len()).sum(),
+            comments: game.feedback.iter().filter_map(|feedback| feedback.comment.clone()).collect(),
+        }
+    }
+
+    /// Whether an admin should look at the game: anyone was reported, it was rated 2 or less on average, or half the players found the rules unclear.
+    fn troubled(&self) -> bool {
+        self.reports > 0 || self.average_fun.is_some_and(|fun| fun <= 2.0) || self.rules_unclear * 2 >= self.responses.max(1)
+    }
+}
+
+/// Feedback across every game in the history, for the admins.
+#[derive(Serialize)]
+pub struct FeedbackStats {
+    pub games: usize,
+    pub responses: usize,
+    pub average_fun: Option<f64>,
+    /// How many responses said the rules were unclear.
+    pub rules_unclear: usize,
+    pub reports: usize,
+    /// The most recent games that went badly, newest first.
+    pub troubled_games: Vec<GameFeedback>,
+}
+
+impl ArchivedGame {
+    /// The summary of a game that has just ended, or none if it has not.
+    pub fn from_game(game_id: Uuid, state: &GameState) -> Option<ArchivedGame> {
+        let summary = state.summary();
+        let started_at = state.timeline().first()?.at;
+        let ended_at = state.timeline().last()?.at;
+        Some(ArchivedGame {
+            game_id,
+            players: summary.players,
+            winner: summary.winner?,
+            liberal_policies: summary.liberal_policies,
+            facist_policies: summary.facist_policies,
+            ranked: state.options.ranked,
+            started_at,
+            ended_at,
+            duration_secs: ended_at.saturating_sub(started_at) / 1000,
+            community: state.community.clone(),
+            feedback: Vec::new(),
+        })
+    }
+
+    fn same_round(&self, other: &ArchivedGame) -> bool {
+        self.game_id == other.game_id && self.started_at == other.started_at
+    }
+}
+
+/// Which games to list, as given in the query string of `/games`.
+#[derive(Debug, Default, Deserialize)]
+pub struct HistoryQuery {
+    /// Only games this player sat in.
+    pub player: Option<String>,
+    /// Only games won by this side, `liberal` or `facist`.
+    pub result: Option<String>,
+    /// Only games that ended in this range, in milliseconds since the epoch.
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    /// The page to return, counting from 1.
+    pub page: Option<usize>,
+    /// The community whose games to list, or none for the server's own.
+    pub community: Option<String>,
+}
+
+/// A page of the history, newest games first.
+#[derive(Serialize)]
+pub struct HistoryPage {
+    pub games: Vec<ArchivedGame>,
+    pub page: usize,
+    pub pages: usize,
+    pub total: usize,
+}
+
+/// Finished games, for browsing past results.
+/// Each game is appended to a file as a line of JSON when it ends, if one is set, so the history survives restarts.
+#[derive(Default)]
+pub struct GameArchive {
+    path: Option<PathBuf>,
+    games: RwLock<VecDeque<ArchivedGame>>,
+}
+
+impl GameArchive {
+    /// Load the games kept in a file, starting with none if it does not exist yet.
+    pub fn load(path: Option<PathBuf>) -> GameArchive {
+        let mut games: VecDeque<ArchivedGame> = VecDeque::new();
+        if let Some(contents) = path.as_ref().and_then(|path| fs::read_to_string(path).ok()) {
+            for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+                match serde_json::from_str(line) {
+                    Ok(game) => {
+                        games.retain(|archived| !archived.same_round(&game));
+                        games.push_back(game);
+                    }
+                    Err(e) => eprintln!("skipping a game in the history that could not be read: {}", e)
+                }
+            }
+            while games.len() > MAX_ARCHIVED {
+                games.pop_front();
+            }
+        }
+        GameArchive { path, games: RwLock::new(games) }
+    }
+
+    /// Add a finished game, replacing an earlier round of the same game.
+    pub fn record(&self, game: ArchivedGame) {
+        self.append(&game);
+        let mut games = self.games.write();
+        games.retain(|archived| !archived.same_round(&game));
+        games.push_back(game);
+        if games.len() > MAX_ARCHIVED {
+            games.pop_front();
+        }
+    }
+
+    /// Write a game to the end of the file, where it takes the place of any earlier line for the same round when loaded.
+    fn append(&self, game: &ArchivedGame) {
+        if let Some(path) = &self.path {
+            let line = format!("{}\n", serde_json::to_string(&game).unwrap());
+            if let Err(e) = OpenOptions::new().create(true).append(true).open(path).and_then(|mut file| file.write_all(line.as_bytes())) {
+                eprintln!("could not add game {} to the history in {}: {}", game.game_id, path.display(), e);
+            }
+        }
+    }
+
+    /// Keep a player's feedback with the latest round of a game. Each player can only give feedback once a round.
+    pub fn add_feedback(&self, game_id: Uuid, feedback: Feedback) -> Result<(), &'static str> {
+        let mut games = self.games.write();
+        let game = games.iter_mut().rev().find(|game| game.game_id == game_id).ok_or("This game has not finished yet.")?;
+        if game.feedback.iter().any(|given| given.player == feedback.player) {
+            return Err("You have already given feedback on this game.")
+        }
+        game.feedback.push(feedback);
+        self.append(game);
+        Ok(())
+    }
+
+    pub fn feedback_stats(&self) -> FeedbackStats {
+        let games = self.games.read();
+        let rated: Vec<GameFeedback> = games.iter().rev().filter(|game| !game.feedback.is_empty()).map(GameFeedback::new).collect();
+        let ratings: Vec<f64> = games.iter().flat_map(|game| game.feedback.iter().filter_map(|feedback| feedback.fun)).map(f64::from).collect();
+        FeedbackStats {
+            games: rated.len(),
+            responses: rated.iter().map(|game| game.responses).sum(),
+            average_fun: Some(ratings.iter().sum::<f64>() / ratings.len() as f64).filter(|_| !ratings.is_empty()),
+            rules_unclear: rated.iter().map(|game| game.rules_unclear).sum(),
+            reports: rated.iter().map(|game| game.reports).sum(),
+            troubled_games: rated.into_iter().filter(GameFeedback::troubled).take(MAX_TROUBLED).collect(),
+        }
+    }
+
+    /// The games matching the query, a page at a time.
+    pub fn query(&self, query: &HistoryQuery) -> Result<HistoryPage, &'static str> {
+        let winner = match query.result.as_deref() {
+            None | Some("") => None,
+            Some(result) => Some([CardColor::Liberal, CardColor::Facist].iter().copied().find(|color| color.id() == result).ok_or("result must be liberal or facist")?)
+        };
+        let page = query.page.unwrap_or(1);
+        if page == 0 {
+            return Err("pages count from 1")
+        }
+        let player = query.player.as_deref().map(str::to_lowercase);
+        let games = self.games.read();
+        let matching: Vec<&ArchivedGame> = games.iter().rev().filter(|game| {
+            player.as_ref().is_none_or(|player| game.players.iter().any(|name| name.to_lowercase() == *player))
+                && winner.is_none_or(|winner| game.winner == winner)
+                && query.from.is_none_or(|from| game.ended_at >= from)
+                && query.to.is_none_or(|to| game.ended_at <= to)
+                && game.community == query.community
+        }).collect();
+        Ok(HistoryPage {
+            games: matching.iter().skip((page - 1) * PAGE_SIZE).take(PAGE_SIZE).map(|game| ArchivedGame { feedback: Vec::new(), ..(*game).clone() }).collect(),
+            page,
+            pages: matching.len().div_ceil(PAGE_SIZE),
+            total: matching.len(),
+        })
+    }
+}
+
+/// Serve the history of finished games at `/games`, or a community's with `?community=`.
+pub fn route(archive: Arc<GameArchive>) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone {
+    warp::path!("games")
+        .and(warp::get())
+        .and(warp::query::<HistoryQuery>())
+        .map(move |query: HistoryQuery| -> Box<dyn Reply> {
+            match archive.query(&query) {
+                Ok(page) => Box::new(warp::reply::json(&page)),
+                Err(message) => Box::new(warp::reply::with_status(message, StatusCode::BAD_REQUEST))
+            }
+        })
+}
+
+/// Summarize the feedback players gave at `/admin/feedback` for requests bearing the admin token.
+pub fn feedback_route(archive: Arc<GameArchive>, admin_token: Option<String>) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone {
+    warp::path!("admin" / "feedback")
+        .and(warp::get())
+        .and(warp::header::optional::<String>("authorization"))
+        .map(move |authorization: Option<String>| -> Box<dyn Reply> {
+            let authorized = admin_token.as_ref().is_some_and(|token| authorization.as_deref() == Some(format!("Bearer {}", token).as_str()));
+            if !authorized {
+                return Box::new(warp::reply::with_status("invalid admin token", StatusCode::UNAUTHORIZED))
+            }
+            Box::new(warp::reply::json(&archive.feedback_stats()))
+        })
+}
@@ -0,0 +1,129 @@
+use std::{fs::Metadata, path::{Path, PathBuf}, time::UNIX_EPOCH};
+
+use warp::{Filter, Rejection, http::{Response, StatusCode, header}, hyper::Body, path::Tail};
+
+/// Files under this directory have a content hash in their name, so they never change once published.
+const HASHED_ASSETS: &str = "static/";
+
+/// First path segments that belong to the server rather than the frontend, so a miss under them stays a 404.
+const API_PATHS: [&str; 11] = ["ws", "events", "healthz", "version", "schema", "admin", "leaderboard", "achievements", "discord", "telegram", "graphql"];
+
+/// Pre-compressed copies of a file that may be sent instead of it, best first.
+const ENCODINGS: [(&str, &str); 2] = [("br", "br"), ("gzip", "gz")];
+
+/// Serve files from the frontend build directory, with cache headers and pre-compressed copies where they exist.
+pub fn dir(root: PathBuf) -> impl Filter<Extract = (Response<Body>,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path::tail())
+        .and(warp::header::optional::<String>("accept-encoding"))
+        .and(warp::header::optional::<String>("if-none-match"))
+        .and_then(move |tail: Tail, accept_encoding: Option<String>, if_none_match: Option<String>| {
+            let path = asset_path(&root, tail.as_str());
+            let immutable = tail.as_str().starts_with(HASHED_ASSETS);
+            async move {
+                serve(path.ok_or_else(warp::reject::not_found)?, immutable, accept_encoding, if_none_match).await
+            }
+        })
+}
+
+/// Serve a single file, such as index.html for pages routed by the frontend.
+pub fn file(path: PathBuf) -> impl Filter<Extract = (Response<Body>,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::header::optional::<String>("accept-encoding"))
+        .and(warp::header::optional::<String>("if-none-match"))
+        .and_then(move |accept_encoding: Option<String>, if_none_match: Option<String>| {
+            serve(path.clone(), false, accept_encoding, if_none_match)
+        })
+}
+
+/// Serve index.html for any other page, so the frontend can route it.
+/// Paths under the server's own routes and paths that look like files, such as a missing script, are still refused.
+pub fn fallback(index: PathBuf) -> impl Filter<Extract = (Response<Body>,), Error = Rejection> + Clone {
+    warp::path::tail()
+        .and_then(|tail: Tail| async move {
+            if is_page(tail.as_str()) { Ok(()) } else { Err(warp::reject::not_found()) }
+        })
+        .untuple_one()
+        .and(file(index))
+}
+
+fn is_page(tail: &str) -> bool {
+    let first = tail.split('/').next().unwrap_or_default();
+    let last = tail.trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+    !API_PATHS.contains(&first) && !last.contains('.')
+}
+
+/// The file a request path refers to, or none if the path tries to leave the build directory.
+fn asset_path(root: &Path, tail: &str) -> Option<PathBuf> {
+    let mut path = root.to_path_buf();
+    for segment in tail.split('/').filter(|s| !s.is_empty()) {
+        if segment.starts_with('.') || segment.contains('\\') {
+            return None
+        }
+        path.push(segment);
+    }
+    if tail.is_empty() || tail.ends_with('/') {
+        path.push("index.html");
+    }
+    Some(path)
+}
+
+async fn serve(path: PathBuf, immutable: bool, accept_encoding: Option<String>, if_none_match: Option<String>) -> Result<Response<Body>, Rejection> {
+    let (encoding, served, metadata) = pick_encoding(&path, accept_encoding.as_deref()).await.ok_or_else(warp::reject::not_found)?;
+    let etag = etag(&metadata, encoding);
+    let cache_control = if immutable { "public, max-age=31536000, immutable" } else { "no-cache" };
+
+    let response = Response::builder()
+        .header(header::ETAG, &etag)
+        .header(header::CACHE_CONTROL, cache_control)
+        .header(header::VARY, "Accept-Encoding");
+    if if_none_match.is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*")) {
+        return Ok(response.status(StatusCode::NOT_MODIFIED).body(Body::empty()).unwrap())
+    }
+
+    let contents = tokio::fs::read(&served).await.map_err(|_| warp::reject::not_found())?;
+    let mut response = response
+        .header(header::CONTENT_TYPE, mime_guess::from_path(&path).first_or_octet_stream().as_ref())
+        .header(header::CONTENT_LENGTH, contents.len());
+    if let Some(encoding) = encoding {
+        response = response.header(header::CONTENT_ENCODING, encoding);
+    }
+    Ok(response.body(Body::from(contents)).unwrap())
+}
+
+/// Find the best copy of the file that the client accepts, returning its encoding, path, and metadata.
+async fn pick_encoding(path: &Path, accept_encoding: Option<&str>) -> Option<(Option<&'static str>, PathBuf, Metadata)> {
+    for (encoding, extension) in ENCODINGS {
+        if !accept_encoding.is_some_and(|accepted| accepts(accepted, encoding)) {
+            continue
+        }
+        let mut compressed = path.as_os_str().to_owned();
+        compressed.push(".");
+        compressed.push(extension);
+        let compressed = PathBuf::from(compressed);
+        if let Ok(metadata) = tokio::fs::metadata(&compressed).await {
+            if metadata.is_file() {
+                return Some((Some(encoding), compressed, metadata))
+            }
+        }
+    }
+    let metadata = tokio::fs::metadata(path).await.ok().filter(|m| m.is_file())?;
+    Some((None, path.to_path_buf(), metadata))
+}
+
+/// Whether an Accept-Encoding header allows the encoding, treating a quality of zero as a refusal.
+fn accepts(header: &str, encoding: &str) -> bool {
+    header.split(',').any(|entry| {
+        let mut parts = entry.split(';').map(str::trim);
+        parts.next() == Some(encoding) && !parts.any(|param| param.strip_prefix("q=").and_then(|q| q.parse::<f32>().ok()) == Some(0.0))
+    })
+}
+
+/// A validator that changes whenever the file on disk does, kept separate for each encoding.
+fn etag(metadata: &Metadata, encoding: Option<&str>) -> String {
+    let modified = metadata.modified().ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()).map(|d| d.as_secs()).unwrap_or(0);
+    match encoding {
+        Some(encoding) => format!("\"{:x}-{:x}-{}\"", modified, metadata.len(), encoding),
+        None => format!("\"{:x}-{:x}\"", modified, metadata.len())
+    }
+}
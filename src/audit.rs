@@ -0,0 +1,199 @@
+use std::{collections::{HashMap, HashSet, VecDeque}, net::IpAddr, sync::Arc, time::{Duration, SystemTime}};
+
+use parking_lot::RwLock;
+use serde::Serialize;
+use uuid::Uuid;
+use warp::{Filter, Rejection, Reply, http::StatusCode};
+
+use secrethitler_core::game_state::epoch_millis;
+
+use crate::communities::{Communities, Role, community};
+
+/// Most entries kept in memory. Every entry is also written to the server log.
+const MAX_ENTRIES: usize = 1000;
+
+const MAX_REASON_LEN: usize = 500;
+
+/// Actions taken sooner than this after a phase starts are quicker than a person can read the screen.
+const MIN_REACTION: Duration = Duration::from_millis(300);
+
+/// How many of those quick actions one seat can take before the game is flagged.
+const RAPID_ACTIONS: usize = 5;
+
+/// How many governments with and without a facist a liberal has to judge perfectly before the game is flagged.
+const KNOWLEDGE_VOTES: usize = 4;
+
+/// Why a game was flagged for review.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub enum AuditReason {
+    /// A player reported another player.
+    Reported,
+    /// Two seats in the same game connected from the same address.
+    SharedAddress,
+    /// A liberal voted as though they knew who the facists were.
+    ImpossibleKnowledge,
+    /// A seat kept acting faster than a person could.
+    RapidActions,
+}
+
+#[derive(Clone, Serialize)]
+pub struct AuditEntry {
+    /// When the game was flagged, in milliseconds since the epoch.
+    pub at: u64,
+    pub game_id: Uuid,
+    /// The community the game was played in, or none for the server's own.
+    pub community: Option<String>,
+    pub reason: AuditReason,
+    /// The players involved, starting with the reporting player for reports.
+    pub players: Vec<Uuid>,
+    pub detail: String,
+}
+
+/// What the heuristics have seen from one seat.
+#[derive(Default)]
+struct Seat {
+    address: Option<IpAddr>,
+    rapid_actions: usize,
+    facist_governments: usize,
+    liberal_governments: usize,
+    misjudged: usize,
+    flagged: HashSet<AuditReason>,
+}
+
+/// Games flagged for an admin to look at, either by players reporting each other or by heuristics that look for cheating.
+#[derive(Default)]
+pub struct AuditLog {
+    entries: RwLock<VecDeque<AuditEntry>>,
+    seats: RwLock<HashMap<(Uuid, Uuid), Seat>>,
+    /// Reports already made, by game, reporting player, and reported player.
+    reports: RwLock<HashSet<(Uuid, Uuid, Uuid)>>,
+}
+
+impl AuditLog {
+    /// The flagged games, oldest first.
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        self.entries.read().iter().cloned().collect()
+    }
+
+    /// The flagged games of one community, oldest first.
+    pub fn community_entries(&self, community: &str) -> Vec<AuditEntry> {
+        self.entries.read().iter().filter(|entry| entry.community.as_deref() == Some(community)).cloned().collect()
+    }
+
+    fn flag(&self, game_id: Uuid, community: Option<&str>, reason: AuditReason, players: Vec<Uuid>, detail: String) {
+        eprintln!("audit: game {} flagged for {:?}: {}", game_id, reason, detail);
+        let mut entries = self.entries.write();
+        if entries.len() >= MAX_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(AuditEntry { at: epoch_millis(SystemTime::now()), game_id, community: community.map(str::to_string), reason, players, detail });
+    }
+
+    /// Flag a game when a player reports another.
+    pub fn report(&self, game_id: Uuid, community: Option<&str>, reporter: Uuid, player: Uuid, reason: &str) -> Result<(), &'static str> {
+        let reason = check_reason(reason)?;
+        if !self.reports.write().insert((game_id, reporter, player)) {
+            return Err("You have already reported this player.")
+        }
+        self.flag(game_id, community, AuditReason::Reported, vec![reporter, player], reason.to_string());
+        Ok(())
+    }
+
+    /// Note where a seat is connecting from, flagging the game if another seat in it shares the address.
+    pub fn record_address(&self, game_id: Uuid, community: Option<&str>, player_id: Uuid, address: IpAddr) {
+        let mut seats = self.seats.write();
+        let seat = seats.entry((game_id, player_id)).or_default();
+        if seat.address == Some(address) {
+            return
+        }
+        seat.address = Some(address);
+        let others: Vec<Uuid> = seats.iter()
+            .filter(|((game, player), seat)| *game == game_id && *player != player_id && seat.address == Some(address) && !seat.flagged.contains(&AuditReason::SharedAddress))
+            .map(|((_, player), _)| *player)
+            .collect();
+        if others.is_empty() {
+            return
+        }
+        for player in others.iter().chain(std::iter::once(&player_id)) {
+            seats.get_mut(&(game_id, *player)).unwrap().flagged.insert(AuditReason::SharedAddress);
+        }
+        drop(seats);
+        self.flag(game_id, community, AuditReason::SharedAddress, std::iter::once(player_id).chain(others).collect(), format!("seats connected from {}", address));
+    }
+
+    /// Note how long a seat took to act once the phase started.
+    pub fn record_action(&self, game_id: Uuid, community: Option<&str>, player_id: Uuid, reaction: Duration) {
+        if reaction >= MIN_REACTION {
+            return
+        }
+        let mut seats = self.seats.write();
+        let seat = seats.entry((game_id, player_id)).or_default();
+        seat.rapid_actions += 1;
+        if seat.rapid_actions >= RAPID_ACTIONS && seat.flagged.insert(AuditReason::RapidActions) {
+            drop(seats);
+            self.flag(game_id, community, AuditReason::RapidActions, vec![player_id], format!("{} actions within {}ms of their phase starting", RAPID_ACTIONS, MIN_REACTION.as_millis()));
+        }
+    }
+
+    /// Note a liberal's vote on a government they are not part of.
+    /// Liberals do not know who the facists are, so a long run of rejecting exactly the governments with a facist in them is suspicious.
+    pub fn record_vote(&self, game_id: Uuid, community: Option<&str>, player_id: Uuid, approved: bool, facist_government: bool) {
+        let mut seats = self.seats.write();
+        let seat = seats.entry((game_id, player_id)).or_default();
+        if facist_government {
+            seat.facist_governments += 1;
+        }
+        else {
+            seat.liberal_governments += 1;
+        }
+        if approved == facist_government {
+            seat.misjudged += 1;
+        }
+        if seat.misjudged == 0 && seat.facist_governments >= KNOWLEDGE_VOTES && seat.liberal_governments >= KNOWLEDGE_VOTES && seat.flagged.insert(AuditReason::ImpossibleKnowledge) {
+            let detail = format!("a liberal rejected all {} governments with a facist and approved all {} without", seat.facist_governments, seat.liberal_governments);
+            drop(seats);
+            self.flag(game_id, community, AuditReason::ImpossibleKnowledge, vec![player_id], detail);
+        }
+    }
+
+    /// Forget the seats and reports of games that no longer exist. Entries are kept.
+    pub fn retain_games(&self, exists: impl Fn(&Uuid) -> bool) {
+        self.seats.write().retain(|(game_id, _), _| exists(game_id));
+        self.reports.write().retain(|(game_id, _, _)| exists(game_id));
+    }
+}
+
+/// The reason given for a report, trimmed, if it is neither empty nor too long.
+pub fn check_reason(reason: &str) -> Result<&str, &'static str> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err("Please say why you are reporting this player.")
+    }
+    if reason.chars().count() > MAX_REASON_LEN {
+        return Err("Please keep your report under 500 characters.")
+    }
+    Ok(reason)
+}
+
+/// List the flagged games at `/admin/audit` for requests bearing the admin token. Nothing is listed if no token is configured.
+/// Moderators can list the flagged games of their own community by adding `?community=` and bearing their player secret instead.
+pub fn route(audit: Arc<AuditLog>, communities: Arc<Communities>, admin_token: Option<String>) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone {
+    warp::path!("admin" / "audit")
+        .and(warp::get())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(community())
+        .map(move |authorization: Option<String>, community: Option<String>| -> Box<dyn Reply> {
+            let bearer = authorization.as_deref().and_then(|authorization| authorization.strip_prefix("Bearer "));
+            if admin_token.as_deref().is_some_and(|token| bearer == Some(token)) {
+                return match community {
+                    Some(community) => Box::new(warp::reply::json(&audit.community_entries(&community))),
+                    None => Box::new(warp::reply::json(&audit.entries()))
+                }
+            }
+            let secret = bearer.and_then(|bearer| bearer.parse::<Uuid>().ok());
+            match (community, secret) {
+                (Some(community), Some(secret)) if communities.authorize(&community, secret, Role::Moderator).is_ok() => Box::new(warp::reply::json(&audit.community_entries(&community))),
+                _ => Box::new(warp::reply::with_status("invalid admin token", StatusCode::UNAUTHORIZED))
+            }
+        })
+}
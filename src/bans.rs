@@ -0,0 +1,120 @@
+use std::{fs, net::IpAddr, path::PathBuf, time::SystemTime};
+
+use parking_lot::RwLock;
+use uuid::Uuid;
+
+use secrethitler_core::{game_state::epoch_millis, protocol::{Ban, BanTarget}};
+
+use crate::{addresses::{in_range, parse_range}, friends};
+
+const MAX_REASON_LEN: usize = 500;
+
+/// Most bans kept at once, since each connection is checked against all of them.
+const MAX_BANS: usize = 10_000;
+
+/// Who is asking to play, as far as the server can tell.
+#[derive(Default)]
+pub struct Visitor<'a> {
+    pub secret: Option<Uuid>,
+    pub address: Option<IpAddr>,
+    pub fingerprint: Option<&'a str>,
+}
+
+fn applies_to(ban: &Ban, visitor: &Visitor) -> bool {
+    match &ban.target {
+        BanTarget::Account { friend_id } => visitor.secret.is_some_and(|secret| friends::friend_id(secret) == *friend_id),
+        BanTarget::Address { range } => visitor.address.zip(parse_range(range)).is_some_and(|(address, range)| in_range(address, range)),
+        BanTarget::Fingerprint { fingerprint } => visitor.fingerprint == Some(fingerprint.as_str()),
+    }
+}
+
+fn expired(ban: &Ban, now: u64) -> bool {
+    ban.expires_at.is_some_and(|expires_at| expires_at <= now)
+}
+
+/// Players, address ranges, and devices kept out by the admins.
+/// Bans are written to a file whenever they change, if one is set, so they survive restarts.
+#[derive(Default)]
+pub struct BanList {
+    path: Option<PathBuf>,
+    bans: RwLock<Vec<Ban>>,
+}
+
+impl BanList {
+    /// Load the bans kept in a file, starting with none if it does not exist yet.
+    pub fn load(path: Option<PathBuf>) -> BanList {
+        let bans = path.as_ref().and_then(|path| match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| eprintln!("could not read bans from {}: {}", path.display(), e)).ok(),
+            Err(_) => None
+        });
+        BanList { path, bans: RwLock::new(bans.unwrap_or_default()) }
+    }
+
+    /// Drop expired bans and write the rest out.
+    fn save(&self, bans: &mut Vec<Ban>) {
+        let now = epoch_millis(SystemTime::now());
+        bans.retain(|ban| !expired(ban, now));
+        if let Some(path) = &self.path {
+            // written to the side first, so a crash partway through leaves the old bans in place
+            let temp = path.with_extension("tmp");
+            if let Err(e) = fs::write(&temp, serde_json::to_vec_pretty(bans).unwrap()).and_then(|_| fs::rename(&temp, path)) {
+                eprintln!("could not save bans to {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// The ban that keeps this visitor out of a community, or out of the server's own games with none, if any.
+    /// Bans made for the whole server keep visitors out of every community.
+    pub fn find(&self, visitor: &Visitor, community: Option<&str>, now: SystemTime) -> Option<Ban> {
+        let now = epoch_millis(now);
+        self.bans.read().iter().find(|ban| !expired(ban, now) && ban.community.as_deref().is_none_or(|banned| Some(banned) == community) && applies_to(ban, visitor)).cloned()
+    }
+
+    pub fn list(&self) -> Vec<Ban> {
+        let now = epoch_millis(SystemTime::now());
+        self.bans.read().iter().filter(|ban| !expired(ban, now)).cloned().collect()
+    }
+
+    pub fn add(&self, target: BanTarget, reason: &str, expires_at: Option<u64>, community: Option<String>) -> Result<Ban, &'static str> {
+        let reason = reason.trim();
+        if reason.chars().count() > MAX_REASON_LEN {
+            return Err("Please keep the reason under 500 characters.")
+        }
+        if let BanTarget::Address { range } = &target {
+            if parse_range(range).is_none() {
+                return Err("That is not a valid address range.")
+            }
+        }
+        let now = epoch_millis(SystemTime::now());
+        if expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return Err("Bans must expire in the future.")
+        }
+        let mut bans = self.bans.write();
+        if bans.len() >= MAX_BANS {
+            return Err("There are too many bans. Please lift some first.")
+        }
+        let ban = Ban { id: Uuid::new_v4(), target, reason: reason.to_string(), appeal_note: None, created_at: now, expires_at, community };
+        bans.push(ban.clone());
+        self.save(&mut bans);
+        Ok(ban)
+    }
+
+    pub fn remove(&self, ban_id: Uuid) -> Result<(), &'static str> {
+        let mut bans = self.bans.write();
+        let index = bans.iter().position(|ban| ban.id == ban_id).ok_or("There is no ban with that id.")?;
+        bans.remove(index);
+        self.save(&mut bans);
+        Ok(())
+    }
+
+    pub fn set_appeal(&self, ban_id: Uuid, note: Option<String>) -> Result<(), &'static str> {
+        let note = note.map(|note| note.trim().to_string()).filter(|note| !note.is_empty());
+        if note.as_ref().is_some_and(|note| note.chars().count() > MAX_REASON_LEN) {
+            return Err("Please keep the appeal note under 500 characters.")
+        }
+        let mut bans = self.bans.write();
+        bans.iter_mut().find(|ban| ban.id == ban_id).ok_or("There is no ban with that id.")?.appeal_note = note;
+        self.save(&mut bans);
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use std::{collections::{BTreeSet, HashMap, HashSet}, fs, path::PathBuf};
+
+use parking_lot::RwLock;
+use uuid::Uuid;
+
+use secrethitler_core::game_state::GameState;
+
+use crate::friends::friend_id;
+
+/// Most players one player can block.
+const MAX_BLOCKS: usize = 500;
+
+/// Most players the server keeps block lists for, since nothing else bounds how many secrets can be used.
+const MAX_PLAYERS: usize = 100_000;
+
+/// Players who have blocked others, so they never see their chat and are not seated with them.
+/// Players are known by their friend id, so secrets are never written out. Block lists are written to a file whenever they change, if one is set, so they survive restarts.
+#[derive(Default)]
+pub struct BlockList {
+    path: Option<PathBuf>,
+    blocks: RwLock<HashMap<Uuid, BTreeSet<Uuid>>>,
+}
+
+impl BlockList {
+    /// Load the block lists kept in a file, starting with none if it does not exist yet.
+    pub fn load(path: Option<PathBuf>) -> BlockList {
+        let blocks = path.as_ref().and_then(|path| match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| eprintln!("could not read block lists from {}: {}", path.display(), e)).ok(),
+            Err(_) => None
+        });
+        BlockList { path, blocks: RwLock::new(blocks.unwrap_or_default()) }
+    }
+
+    fn save(&self, blocks: &HashMap<Uuid, BTreeSet<Uuid>>) {
+        if let Some(path) = &self.path {
+            // written to the side first, so a crash partway through leaves the old lists in place
+            let temp = path.with_extension("tmp");
+            if let Err(e) = fs::write(&temp, serde_json::to_vec(blocks).unwrap()).and_then(|_| fs::rename(&temp, path)) {
+                eprintln!("could not save block lists to {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// The friend ids of the players this player has blocked.
+    pub fn list(&self, secret: Uuid) -> Vec<Uuid> {
+        self.blocks.read().get(&friend_id(secret)).map(|blocked| blocked.iter().copied().collect()).unwrap_or_default()
+    }
+
+    pub fn block(&self, secret: Uuid, blocked: Uuid) -> Result<(), &'static str> {
+        let id = friend_id(secret);
+        if blocked == id {
+            return Err("You cannot block yourself.")
+        }
+        let mut blocks = self.blocks.write();
+        if !blocks.contains_key(&id) && blocks.len() >= MAX_PLAYERS {
+            return Err("The server cannot keep track of any more block lists.")
+        }
+        let list = blocks.entry(id).or_default();
+        if list.len() >= MAX_BLOCKS && !list.contains(&blocked) {
+            return Err("You cannot block more than 500 players.")
+        }
+        list.insert(blocked);
+        self.save(&blocks);
+        Ok(())
+    }
+
+    pub fn unblock(&self, secret: Uuid, blocked: Uuid) {
+        let id = friend_id(secret);
+        let mut blocks = self.blocks.write();
+        if let Some(list) = blocks.get_mut(&id) {
+            if list.remove(&blocked) {
+                if list.is_empty() {
+                    blocks.remove(&id);
+                }
+                self.save(&blocks);
+            }
+        }
+    }
+
+    /// Whether the player with this secret has blocked the player with the friend id.
+    pub fn has_blocked(&self, secret: Uuid, blocked: Uuid) -> bool {
+        self.blocks.read().get(&friend_id(secret)).is_some_and(|list| list.contains(&blocked))
+    }
+
+    /// Whether the players with these secrets have each blocked the other.
+    pub fn mutual(&self, a: Uuid, b: Uuid) -> bool {
+        self.has_blocked(a, friend_id(b)) && self.has_blocked(b, friend_id(a))
+    }
+
+    /// Mute, for each seat in the game, the seats of the players it has blocked.
+    pub fn apply(&self, state: &mut GameState) {
+        let seats: Vec<(Uuid, Uuid)> = state.conn.iter().filter_map(|(player_id, conn)| Some((*player_id, friend_id(conn.secret?)))).collect();
+        for conn in state.conn.values_mut() {
+            conn.muted = match conn.secret {
+                Some(secret) => seats.iter().filter(|(_, other)| self.has_blocked(secret, *other)).map(|(player_id, _)| *player_id).collect(),
+                None => HashSet::new()
+            };
+        }
+    }
+}
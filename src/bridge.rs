@@ -0,0 +1,368 @@
+use std::{collections::HashMap, sync::Arc, time::SystemTime};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use secrethitler_core::{error::GameError, game_state::{CardColor, GameState, GameStatePlayerView, PresidentialPower, TurnPhase}, machine::Action as GameAction, protocol::{NullSink, PlayerConnection}, rules};
+use uuid::Uuid;
+
+/// Something a player can do by pressing a button in a chat app.
+#[derive(Clone, Copy)]
+pub enum Action {
+    Join,
+    Start,
+    Nominate(Uuid),
+    CallVote,
+    Vote(bool),
+    PickCard(CardColor),
+    RequestVeto,
+    RespondVeto(bool),
+    Power(Option<Uuid>),
+}
+
+impl Action {
+    /// Encode the action along with its game, short enough for the data attached to a button, which Telegram limits to 64 bytes.
+    pub fn encode(&self, game_id: Uuid) -> String {
+        let (action, argument) = match self {
+            Action::Join => ("join", None),
+            Action::Start => ("start", None),
+            Action::Nominate(player) => ("nominate", Some(encode_id(*player))),
+            Action::CallVote => ("callvote", None),
+            Action::Vote(true) => ("vote", Some("ja".into())),
+            Action::Vote(false) => ("vote", Some("nein".into())),
+            Action::PickCard(color) => ("pick", Some(color.to_string())),
+            Action::RequestVeto => ("veto", None),
+            Action::RespondVeto(true) => ("veto", Some("ja".into())),
+            Action::RespondVeto(false) => ("veto", Some("nein".into())),
+            Action::Power(target) => ("power", target.map(encode_id)),
+        };
+        match argument {
+            Some(argument) => format!("{}:{}:{}", encode_id(game_id), action, argument),
+            None => format!("{}:{}", encode_id(game_id), action)
+        }
+    }
+
+    pub fn decode(data: &str) -> Option<(Uuid, Action)> {
+        let mut parts = data.splitn(3, ':');
+        let game_id = decode_id(parts.next()?)?;
+        let action = match (parts.next()?, parts.next()) {
+            ("join", None) => Action::Join,
+            ("start", None) => Action::Start,
+            ("nominate", Some(player)) => Action::Nominate(decode_id(player)?),
+            ("callvote", None) => Action::CallVote,
+            ("vote", Some("ja")) => Action::Vote(true),
+            ("vote", Some("nein")) => Action::Vote(false),
+            ("pick", Some("liberal")) => Action::PickCard(CardColor::Liberal),
+            ("pick", Some("facist")) => Action::PickCard(CardColor::Facist),
+            ("veto", None) => Action::RequestVeto,
+            ("veto", Some("ja")) => Action::RespondVeto(true),
+            ("veto", Some("nein")) => Action::RespondVeto(false),
+            ("power", None) => Action::Power(None),
+            ("power", Some(player)) => Action::Power(Some(decode_id(player)?)),
+            _ => return None
+        };
+        Some((game_id, action))
+    }
+}
+
+fn encode_id(id: Uuid) -> String {
+    URL_SAFE_NO_PAD.encode(id.as_bytes())
+}
+
+fn decode_id(encoded: &str) -> Option<Uuid> {
+    Uuid::from_slice(&URL_SAFE_NO_PAD.decode(encoded).ok()?).ok()
+}
+
+#[derive(Clone, Copy)]
+pub enum Style {
+    Primary,
+    Secondary,
+    Success,
+    Danger,
+}
+
+pub struct Button {
+    pub label: String,
+    pub data: String,
+    pub style: Style,
+}
+
+impl Button {
+    fn new(label: String, game_id: Uuid, action: Action, style: Style) -> Button {
+        Button { label, data: action.encode(game_id), style }
+    }
+}
+
+/// Who a message is for: everyone at the table, or one user privately.
+pub enum Audience {
+    Table,
+    User(String),
+}
+
+/// A message for the chat app to deliver.
+pub struct Notice {
+    pub to: Audience,
+    pub text: String,
+    pub buttons: Vec<Button>,
+}
+
+/// How a chat app formats text in its messages.
+pub trait Markup {
+    /// Text that notifies the user when it is posted.
+    fn mention(&self, user_id: &str, name: &str) -> String;
+    fn bold(&self, text: &str) -> String;
+    /// Make text from players, such as their names, safe to include in a message.
+    fn escape(&self, text: &str) -> String;
+}
+
+/// The result of a button press.
+pub enum Pressed {
+    /// The press did nothing, with the reason to show only to the player who pressed it.
+    Refused(String),
+    /// A player joined the lobby, so the lobby message should be updated.
+    Joined,
+    /// The action was taken, with a confirmation to show only to the player who pressed it, if there is one.
+    Applied(Option<String>),
+}
+
+/// A game being played in a chat, and the chat user in each seat.
+pub struct Table {
+    pub game_id: Uuid,
+    pub chat_id: String,
+    users: HashMap<Uuid, String>,
+    /// The state the table was last told about, so the game is only announced again once it moves on.
+    announced: Option<String>,
+}
+
+impl Table {
+    /// Open a lobby with the user who asked for it as the host.
+    pub fn host(chat_id: String, user_id: String, name: String) -> (Table, GameState) {
+        let game_id = Uuid::new_v4();
+        let player_id = Uuid::new_v4();
+        let mut state = GameState::new();
+        state.add_player(player_id, seat(name));
+        (Table { game_id, chat_id, users: HashMap::from([(player_id, user_id)]), announced: None }, state)
+    }
+
+    fn player(&self, user_id: &str) -> Option<Uuid> {
+        self.users.iter().find(|(_, user)| *user == user_id).map(|(player, _)| *player)
+    }
+
+    fn name(state: &GameState, player: &Uuid) -> String {
+        state.conn.get(player).and_then(|c| c.name.clone()).unwrap_or_default()
+    }
+
+    fn mention(&self, state: &GameState, player: Option<Uuid>, markup: &dyn Markup) -> String {
+        match player.and_then(|p| self.users.get(&p).map(|user_id| (p, user_id))) {
+            Some((player, user_id)) => markup.mention(user_id, &Table::name(state, &player)),
+            None => "nobody".into()
+        }
+    }
+
+    /// Apply a button press from a chat user, returning the outcome and anything the players should be told as a result.
+    pub fn press(&mut self, state: &mut GameState, user_id: &str, name: String, action: Action, markup: &dyn Markup) -> (Pressed, Vec<Notice>) {
+        let player = self.player(user_id);
+        let investigating = matches!(state.turn_phase(), TurnPhase::PresidentialPower { power: PresidentialPower::InvestigateLoyalty });
+
+        let result = match (action, player) {
+            (Action::Join, Some(_)) => return (Pressed::Refused("You already have a seat in this game.".into()), vec![]),
+            (Action::Join, None) => {
+                let player_id = Uuid::new_v4();
+                if !state.add_player(player_id, seat(name)) {
+                    return (Pressed::Refused("This game has already started.".into()), vec![])
+                }
+                self.users.insert(player_id, user_id.to_string());
+                state.broadcast_game_state();
+                return (Pressed::Joined, vec![])
+            },
+            (_, None) => return (Pressed::Refused("You are not playing in this game.".into()), vec![]),
+            (Action::Start, Some(player)) => state.apply(player, GameAction::Start),
+            (Action::Nominate(chancellor), Some(player)) => state.apply(player, GameAction::Nominate { chancellor }),
+            (Action::Vote(approve), Some(player)) => state.apply(player, GameAction::Vote { approve }),
+            (Action::PickCard(color), Some(player)) => match state.hand(player).and_then(|hand| hand.iter().position(|card| *card == color)) {
+                Some(index) => state.apply(player, GameAction::PickCard { index }),
+                None => Err(GameError::InvalidPolicy)
+            },
+            (Action::RequestVeto, Some(player)) => state.apply(player, GameAction::RequestVeto),
+            (Action::RespondVeto(accept), Some(player)) => state.apply(player, GameAction::RespondVeto { accept }),
+            (Action::CallVote, Some(player)) => state.apply(player, GameAction::CallVote),
+            (Action::Power(target), Some(player)) => state.apply(player, GameAction::UsePower { target }),
+        };
+        if let Err(error) = result {
+            return (Pressed::Refused(error.to_string()), vec![])
+        }
+        state.broadcast_game_state();
+
+        let mut notices = vec![];
+        match (action, player) {
+            (Action::Start, _) => notices.extend(self.roles(state, markup)),
+            (Action::Power(Some(target)), Some(player)) if investigating => {
+                let party = known_roles(state, player).remove(&target).unwrap_or_default();
+                notices.extend(self.private(player, format!("{} is a member of the {} party.", markup.escape(&Table::name(state, &target)), party), vec![]));
+            },
+            _ => {}
+        }
+        notices.extend(self.announce(state, markup));
+
+        if state.winner().is_some() {
+            // nobody is left to keep the game around, so let it be cleaned up like an abandoned web game
+            for player in self.users.keys() {
+                if let Some(conn) = state.conn.get_mut(player) {
+                    conn.connected = false;
+                }
+            }
+            state.timeout = Some(SystemTime::now());
+        }
+
+        let confirmation = match action {
+            Action::Vote(true) => Some("You voted Ja!".into()),
+            Action::Vote(false) => Some("You voted Nein.".into()),
+            _ => None
+        };
+        (Pressed::Applied(confirmation), notices)
+    }
+
+    /// The lobby message, listing who has joined, with buttons to join and start.
+    pub fn lobby(&self, state: &GameState, markup: &dyn Markup) -> Notice {
+        let view = serde_json::to_value(state.summary()).unwrap();
+        let players: Vec<String> = view["players"].as_array().map(|names| names.iter().filter_map(|n| n.as_str()).map(|n| markup.escape(n)).collect()).unwrap_or_default();
+        let text = format!("{} is starting! Players ({}/{}): {}\nThe host can start the game once there are {} players.",
+            markup.bold("Secret Hitler"), players.len(), state.max_players(), players.join(", "), rules::MIN_PLAYERS);
+        Notice { to: Audience::Table, text, buttons: vec![
+            Button::new("Join".into(), self.game_id, Action::Join, Style::Success),
+            Button::new("Start".into(), self.game_id, Action::Start, Style::Primary),
+        ] }
+    }
+
+    fn private(&self, player: Uuid, text: String, buttons: Vec<Button>) -> Option<Notice> {
+        self.users.get(&player).map(|user_id| Notice { to: Audience::User(user_id.clone()), text, buttons })
+    }
+
+    /// Tell every player their role, along with anyone else their role lets them know about.
+    fn roles(&self, state: &GameState, markup: &dyn Markup) -> Vec<Notice> {
+        self.users.keys().filter_map(|player| {
+            let mut roles = known_roles(state, *player);
+            let own = roles.remove(player)?;
+            let mut text = format!("The game has started. Your role is {}.", markup.bold(&own));
+            let mut others: Vec<String> = roles.iter().map(|(id, role)| format!("{} ({})", markup.escape(&Table::name(state, id)), role)).collect();
+            if !others.is_empty() {
+                others.sort();
+                text.push_str(&format!(" You know: {}.", others.join(", ")));
+            }
+            self.private(*player, text, vec![])
+        }).collect()
+    }
+
+    /// Tell the table about the state of the game if it has moved on, and privately send the players who need to act their cards.
+    fn announce(&mut self, state: &GameState, markup: &dyn Markup) -> Vec<Notice> {
+        let key = serde_json::to_string(&(state.scoreboard(), state.president(), state.chancellor())).unwrap();
+        if self.announced.as_ref() == Some(&key) {
+            return vec![]
+        }
+        self.announced = Some(key);
+
+        let game_id = self.game_id;
+        let rules = state.rules();
+        let scoreboard = state.scoreboard();
+        let score = format!("Liberal policies {}/{} · Facist policies {}/{} · Election tracker {}/{}",
+            scoreboard.liberal_policies, rules.liberal_policies_to_win, scoreboard.facist_policies, rules.facist_policies_to_win, scoreboard.election_tracker, rules.election_tracker_limit);
+        let president = state.president();
+        let chancellor = state.chancellor();
+        let targets = |label_for: &dyn Fn(&str) -> String, action: &dyn Fn(Uuid) -> Action| -> Vec<Button> {
+            state.living_players().iter().filter(|p| Some(**p) != president).map(|p| {
+                Button::new(label_for(&Table::name(state, p)), game_id, action(*p), Style::Primary)
+            }).collect()
+        };
+
+        let (text, buttons) = match state.turn_phase() {
+            TurnPhase::Lobby => return vec![],
+            TurnPhase::Electing => (
+                format!("{}\n{} is president and must nominate a chancellor.", score, self.mention(state, president, markup)),
+                targets(&|name| name.to_string(), &Action::Nominate)
+            ),
+            TurnPhase::Discussion => (
+                format!("{}\n{} has nominated {} for chancellor. Discuss before the vote opens, or the government can agree to vote now.", score, self.mention(state, president, markup), self.mention(state, chancellor, markup)),
+                vec![Button::new("Vote now".into(), game_id, Action::CallVote, Style::Primary)]
+            ),
+            TurnPhase::Voting => (
+                format!("{}\n{} has nominated {} for chancellor. Everyone vote!", score, self.mention(state, president, markup), self.mention(state, chancellor, markup)),
+                vec![Button::new("Ja!".into(), game_id, Action::Vote(true), Style::Success), Button::new("Nein".into(), game_id, Action::Vote(false), Style::Danger)]
+            ),
+            TurnPhase::PresidentSelect => (format!("The government has been elected. President {} is choosing a policy to discard.", self.mention(state, president, markup)), vec![]),
+            TurnPhase::ChancellorSelect if state.veto_pending() => (format!("Chancellor {} has asked President {} to veto this agenda.", self.mention(state, chancellor, markup), self.mention(state, president, markup)), vec![]),
+            TurnPhase::ChancellorSelect => (format!("Chancellor {} is choosing a policy to enact.", self.mention(state, chancellor, markup)), vec![]),
+            TurnPhase::PresidentialPower { power: PresidentialPower::PolicyPeek } => (format!("{}\nPresident {} is looking at the top three policies of the deck.", score, self.mention(state, president, markup)), vec![]),
+            TurnPhase::PresidentialPower { power: PresidentialPower::InvestigateLoyalty } => (
+                format!("{}\nPresident {} must investigate the loyalty of a player.", score, self.mention(state, president, markup)),
+                targets(&|name| format!("Investigate {}", name), &|p| Action::Power(Some(p)))
+            ),
+            TurnPhase::PresidentialPower { power: PresidentialPower::CallSpecialElection } => (
+                format!("{}\nPresident {} must choose the next president.", score, self.mention(state, president, markup)),
+                targets(&|name| name.to_string(), &|p| Action::Power(Some(p)))
+            ),
+            TurnPhase::PresidentialPower { power: PresidentialPower::Execution } => (
+                format!("{}\nPresident {} must execute a player.", score, self.mention(state, president, markup)),
+                targets(&|name| format!("Execute {}", name), &|p| Action::Power(Some(p)))
+            ),
+            TurnPhase::Ended { winner } => {
+                let mut roles: Vec<String> = self.users.keys().map(|player| {
+                    let role = state.role(player).map(|role| serde_json::to_value(role).unwrap().as_str().unwrap_or_default().to_string()).unwrap_or_default();
+                    format!("{}: {}", self.mention(state, Some(*player), markup), role)
+                }).collect();
+                roles.sort();
+                (format!("{}\nThe {}s have won the game!\n{}", score, winner, roles.join("\n")), vec![])
+            }
+        };
+        let mut notices = vec![Notice { to: Audience::Table, text, buttons }];
+
+        // the cards in play are only shown to the players holding them
+        let hand = |player: Uuid| state.hand(player).unwrap_or_default();
+        let describe = |cards: &[CardColor]| cards.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ");
+        let choices = |cards: &[CardColor], verb: &str| {
+            let mut colors: Vec<CardColor> = vec![];
+            for card in cards {
+                if !colors.contains(card) {
+                    colors.push(*card);
+                }
+            }
+            colors.into_iter().map(|c| Button::new(format!("{} {}", verb, c), game_id, Action::PickCard(c), Style::Secondary)).collect::<Vec<Button>>()
+        };
+        match (state.turn_phase(), president, chancellor) {
+            (TurnPhase::PresidentSelect, Some(president), _) => {
+                let cards = hand(president);
+                notices.extend(self.private(president, format!("You drew {}. Choose a policy to discard.", describe(&cards)), choices(&cards, "Discard")));
+            },
+            (TurnPhase::ChancellorSelect, Some(president), Some(_)) if state.veto_pending() => {
+                let buttons = vec![Button::new("Veto".into(), game_id, Action::RespondVeto(true), Style::Danger), Button::new("Refuse".into(), game_id, Action::RespondVeto(false), Style::Secondary)];
+                notices.extend(self.private(president, "The chancellor has asked to veto this agenda. Do you agree?".into(), buttons));
+            },
+            (TurnPhase::ChancellorSelect, Some(_), Some(chancellor)) => {
+                let cards = hand(chancellor);
+                let mut buttons = choices(&cards, "Enact");
+                if scoreboard.facist_policies >= rules.veto_policies && !state.veto_requested() {
+                    buttons.push(Button::new("Ask to veto".into(), game_id, Action::RequestVeto, Style::Danger));
+                }
+                notices.extend(self.private(chancellor, format!("The president passed you {}. Choose a policy to enact.", describe(&cards)), buttons));
+            },
+            (TurnPhase::PresidentialPower { power: PresidentialPower::PolicyPeek }, Some(president), _) => {
+                let mut cards = hand(president);
+                cards.reverse();
+                notices.extend(self.private(president, format!("The top three policies are, from the top: {}.", describe(&cards)), vec![Button::new("Done".into(), game_id, Action::Power(None), Style::Primary)]));
+            },
+            _ => {}
+        }
+        notices
+    }
+}
+
+/// A seat for a chat user, who is told about the game through the bridge rather than a socket.
+fn seat(name: String) -> PlayerConnection {
+    let mut conn = PlayerConnection::new(Arc::new(NullSink));
+    conn.name = Some(name);
+    conn
+}
+
+/// The roles of every player that this player is allowed to know about, including their own.
+fn known_roles(state: &GameState, player: Uuid) -> HashMap<Uuid, String> {
+    let view = serde_json::to_value(GameStatePlayerView { state, player }).unwrap();
+    let players = view["players"].as_object().cloned().unwrap_or_default();
+    players.into_iter().filter_map(|(id, seat)| Some((id.parse().ok()?, seat["role"].as_str()?.to_string()))).collect()
+}
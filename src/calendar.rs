@@ -0,0 +1,73 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use uuid::Uuid;
+use warp::{Filter, Rejection, Reply, http::StatusCode};
+
+use crate::server::{GlobalState, get_game};
+
+/// Serve a calendar event for each scheduled game at `/game/{id}/calendar.ics`, so players can add it to their calendars.
+pub fn route(games: GlobalState, public_url: Option<String>) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone {
+    warp::path!("game" / Uuid / "calendar.ics")
+        .and(warp::get())
+        .map(move |game_id: Uuid| -> Box<dyn Reply> {
+            let game = get_game(&games, &game_id);
+            let scheduled = game.and_then(|game| {
+                let state = game.lock();
+                Some((state.options.scheduled_at?, state.summary().host))
+            });
+            match scheduled {
+                Some((at, host)) => {
+                    let url = public_url.as_ref().map(|base| format!("{}/game/{}", base, game_id));
+                    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+                    Box::new(warp::reply::with_header(event(game_id, at, now, host.as_deref(), url.as_deref()), "content-type", "text/calendar; charset=utf-8"))
+                },
+                None => Box::new(warp::reply::with_status("no scheduled game found", StatusCode::NOT_FOUND))
+            }
+        })
+}
+
+/// An iCalendar file with one event for the game, and an alarm when the lobby is about to open.
+pub fn event(game_id: Uuid, at: u64, now: u64, host: Option<&str>, url: Option<&str>) -> String {
+    let summary = match host {
+        Some(host) => format!("Secret Hitler hosted by {}", host),
+        None => "Secret Hitler".into()
+    };
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".into(),
+        "PRODID:-//secrethitler//scheduled games//EN".into(),
+        "BEGIN:VEVENT".into(),
+        format!("UID:{}@secrethitler", game_id),
+        format!("DTSTAMP:{}", timestamp(now)),
+        format!("DTSTART:{}", timestamp(at)),
+        "DURATION:PT1H".into(),
+        format!("SUMMARY:{}", escape(&summary)),
+    ];
+    if let Some(url) = url {
+        lines.push(format!("URL:{}", url));
+        lines.push(format!("DESCRIPTION:{}", escape(&format!("Join the game at {}", url))));
+    }
+    lines.extend(["BEGIN:VALARM", "ACTION:DISPLAY", "TRIGGER:-PT15M", "DESCRIPTION:Secret Hitler opens soon", "END:VALARM", "END:VEVENT", "END:VCALENDAR"].iter().map(|line| line.to_string()));
+    lines.iter().map(|line| format!("{}\r\n", line)).collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
+
+/// Format milliseconds since the epoch as a UTC date and time, such as `20240301T190000Z`.
+fn timestamp(millis: u64) -> String {
+    let secs = millis / 1000;
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // convert days since the epoch to a civil date, from Howard Hinnant's date algorithms
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}{:02}{:02}T{:02}{:02}{:02}Z", year, month, day, secs / 3600, secs % 3600 / 60, secs % 60)
+}
@@ -0,0 +1,115 @@
+use std::{collections::HashMap, time::{Duration, Instant}};
+
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use secrethitler_core::protocol::HostChallenge;
+
+const HCAPTCHA_VERIFY_URL: &str = "https://api.hcaptcha.com/siteverify";
+
+/// How long a challenge or a verified captcha token can be used to host a game.
+const CHALLENGE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Most challenges and verified tokens waiting to be used, so clients cannot pile them up.
+const MAX_PENDING: usize = 10_000;
+
+/// Most leading zero bits a proof of work can ask for. Each bit doubles the work a client does.
+pub const MAX_DIFFICULTY: u32 = 32;
+
+/// What hosts have to do before a game is created for them.
+#[derive(Clone)]
+pub enum HostCheck {
+    /// Find a nonce that, hashed with a challenge from the server, gives a SHA-256 hash starting with this many zero bits.
+    ProofOfWork { difficulty: u32 },
+    HCaptcha { site_key: String, secret: String },
+}
+
+#[derive(Deserialize)]
+struct VerifyResponse {
+    success: bool,
+}
+
+/// Whether the hash of a challenge and nonce starts with enough zero bits.
+pub fn proof_of_work_valid(challenge: &str, nonce: &str, difficulty: u32) -> bool {
+    let hash = Sha256::new().chain_update(challenge).chain_update(b":").chain_update(nonce).finalize();
+    let mut zeros = 0;
+    for byte in hash {
+        zeros += byte.leading_zeros();
+        if byte != 0 {
+            break
+        }
+    }
+    zeros >= difficulty
+}
+
+/// Keeps bots from filling the server with lobbies by asking hosts to solve a proof of work or a captcha first.
+/// Proofs are checked against challenges the server handed out, and captcha tokens are checked with hCaptcha before the message is handled, so each can only be used once.
+#[derive(Default)]
+pub struct HostGate {
+    check: Option<HostCheck>,
+    /// Challenges and verified captcha tokens that have not been used yet, with when they were handed out.
+    pending: Mutex<HashMap<String, Instant>>,
+    client: reqwest::Client,
+}
+
+impl HostGate {
+    pub fn new(check: Option<HostCheck>) -> HostGate {
+        HostGate { check, ..HostGate::default() }
+    }
+
+    fn add_pending(&self, key: String) {
+        let mut pending = self.pending.lock();
+        pending.retain(|_, issued| issued.elapsed() < CHALLENGE_TTL);
+        if pending.len() < MAX_PENDING {
+            pending.insert(key, Instant::now());
+        }
+    }
+
+    fn take_pending(&self, key: &str) -> bool {
+        self.pending.lock().remove(key).is_some_and(|issued| issued.elapsed() < CHALLENGE_TTL)
+    }
+
+    /// What a client has to do to host a game.
+    pub fn challenge(&self) -> HostChallenge {
+        match &self.check {
+            None => HostChallenge::None,
+            Some(HostCheck::ProofOfWork { difficulty }) => {
+                let challenge = format!("{:032x}", rand::thread_rng().gen::<u128>());
+                self.add_pending(challenge.clone());
+                HostChallenge::ProofOfWork { challenge, difficulty: *difficulty }
+            },
+            Some(HostCheck::HCaptcha { site_key, .. }) => HostChallenge::HCaptcha { site_key: site_key.clone() },
+        }
+    }
+
+    /// Check a captcha token with hCaptcha ahead of the `HostGame` message it came with, since that is handled without waiting on anything.
+    pub async fn verify(&self, token: &str) {
+        let secret = match &self.check {
+            Some(HostCheck::HCaptcha { secret, .. }) => secret,
+            _ => return
+        };
+        let response = self.client.post(HCAPTCHA_VERIFY_URL).form(&[("secret", secret.as_str()), ("response", token)]).send().await;
+        let body = match response {
+            Ok(response) => response.text().await.unwrap_or_default(),
+            Err(e) => return eprintln!("could not verify captcha: {}", e)
+        };
+        if serde_json::from_str::<VerifyResponse>(&body).is_ok_and(|response| response.success) {
+            self.add_pending(token.to_string());
+        }
+    }
+
+    /// Use up the proof or captcha token sent with `HostGame`, returning whether the game can be hosted.
+    pub fn admit(&self, proof: Option<&str>) -> bool {
+        match (&self.check, proof) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(HostCheck::ProofOfWork { difficulty }), Some(proof)) => match proof.split_once(':') {
+                Some((challenge, nonce)) => proof_of_work_valid(challenge, nonce, *difficulty) && self.take_pending(challenge),
+                None => false
+            },
+            (Some(HostCheck::HCaptcha { .. }), Some(token)) => self.take_pending(token),
+        }
+    }
+}
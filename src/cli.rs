@@ -0,0 +1,129 @@
+use std::{fs, path::PathBuf, time::Duration};
+
+use clap::{Arg, ArgAction, ArgMatches, Command, value_parser};
+use secrethitler::{loadtest::{self, LoadTest, percentile}, typescript};
+use secrethitler_core::{game_state::TimelineEntry, rules::{MAX_PLAYERS, MIN_PLAYERS}, simulation::Simulation};
+
+/// The command line interface. Running without a subcommand starts the server.
+pub fn command() -> Command {
+    Command::new("secrethitler")
+        .about("Secret Hitler game server and tools")
+        .arg(resume_arg())
+        .subcommand(Command::new("serve").about("Run the game server, configured from environment variables").arg(resume_arg()))
+        .subcommand(Command::new("simulate")
+            .about("Play games between bots and report how often each team wins")
+            .arg(Arg::new("players").long("players").value_delimiter(',').action(ArgAction::Append).help("Table sizes to simulate, every size if not given").value_parser(value_parser!(u64).range(MIN_PLAYERS as u64..=MAX_PLAYERS as u64)))
+            .arg(Arg::new("games").long("games").default_value("1000").help("Games to play at each table size").value_parser(value_parser!(usize)))
+            .arg(Arg::new("seed").long("seed").help("Play the same games every time").value_parser(value_parser!(u64)))
+            .arg(Arg::new("save").long("save").value_name("DIR").help("Write the timeline of each game to this directory").value_parser(value_parser!(PathBuf))))
+        .subcommand(Command::new("loadtest")
+            .about("Play many games at once against a running server over websockets, and report how quickly it answers")
+            .arg(Arg::new("url").long("url").default_value("ws://localhost:8000/ws").help("The websocket endpoint of the server"))
+            .arg(Arg::new("games").long("games").default_value("100").help("Games to play at the same time").value_parser(value_parser!(usize)))
+            .arg(Arg::new("players").long("players").default_value("5").help("Players in each game, each with their own connection").value_parser(value_parser!(u64).range(MIN_PLAYERS as u64..=MAX_PLAYERS as u64)))
+            .arg(Arg::new("ramp-up").long("ramp-up").value_name("SECONDS").default_value("10").help("Spread the start of the games over this long").value_parser(value_parser!(u64)))
+            .arg(Arg::new("timeout").long("timeout").value_name("SECONDS").default_value("600").help("Count a game as failed if it has not ended by then").value_parser(value_parser!(u64))))
+        .subcommand(Command::new("replay")
+            .about("Print the timeline of a game saved by simulate or returned by GetTimeline")
+            .arg(Arg::new("file").required(true).value_parser(value_parser!(PathBuf))))
+        .subcommand(Command::new("generate-types")
+            .about("Write TypeScript definitions of the protocol messages and the player view")
+            .arg(Arg::new("out").long("out").value_name("FILE").help("Where to write them, instead of printing them").value_parser(value_parser!(PathBuf))))
+}
+
+fn resume_arg() -> Arg {
+    Arg::new("resume").long("resume").action(ArgAction::SetTrue).help("Bring back the games saved in SNAPSHOT_DIR before accepting connections")
+}
+
+pub fn simulate(args: &ArgMatches) -> Result<(), String> {
+    let players: Vec<usize> = match args.get_many::<u64>("players") {
+        Some(players) => players.map(|p| *p as usize).collect(),
+        None => (MIN_PLAYERS..=MAX_PLAYERS).collect()
+    };
+    let games = *args.get_one::<usize>("games").unwrap();
+    let save = args.get_one::<PathBuf>("save");
+    if let Some(dir) = save {
+        fs::create_dir_all(dir).map_err(|e| format!("could not create {}: {}", dir.display(), e))?;
+    }
+
+    let mut simulation = Simulation::new(args.get_one::<u64>("seed").copied());
+    let mut save_error = None;
+    println!("{:>7} {:>6} {:>12} {:>12} {:>10} {:>7}", "players", "games", "liberal wins", "facist wins", "unfinished", "rounds");
+    for players in players {
+        let report = simulation.run(players, games, |i, game| {
+            if let (Some(dir), None) = (save, &save_error) {
+                let path = dir.join(format!("game-{}p-{}.json", players, i + 1));
+                if let Err(e) = fs::write(&path, serde_json::to_string(game.timeline()).unwrap()) {
+                    save_error = Some(format!("could not write {}: {}", path.display(), e));
+                }
+            }
+        });
+        if let Some(e) = save_error {
+            return Err(e)
+        }
+        let liberal_rate = report.liberal_win_rate() * 100.0;
+        let facist_rate = if report.liberal_wins + report.facist_wins > 0 { 100.0 - liberal_rate } else { 0.0 };
+        println!("{:>7} {:>6} {:>11.1}% {:>11.1}% {:>10} {:>7.1}", players, report.games, liberal_rate, facist_rate, report.unfinished, report.average_rounds());
+    }
+    Ok(())
+}
+
+pub async fn loadtest(args: &ArgMatches) -> Result<(), String> {
+    let test = LoadTest {
+        url: args.get_one::<String>("url").unwrap().clone(),
+        games: *args.get_one::<usize>("games").unwrap(),
+        players: *args.get_one::<u64>("players").unwrap() as usize,
+        ramp_up: Duration::from_secs(*args.get_one::<u64>("ramp-up").unwrap()),
+        timeout: Duration::from_secs(*args.get_one::<u64>("timeout").unwrap()),
+    };
+    println!("playing {} games of {} against {}", test.games, test.players, test.url);
+    let report = loadtest::run(&test).await;
+
+    let millis = |latency: Duration| latency.as_secs_f64() * 1000.0;
+    println!("{:<18} {:>7} {:>8} {:>8} {:>8} {:>8} {:>8}", "action", "count", "errors", "p50 ms", "p90 ms", "p99 ms", "max ms");
+    for (kind, latencies) in &report.latencies {
+        let rejected = report.rejected.get(kind).copied().unwrap_or_default();
+        println!("{:<18} {:>7} {:>8} {:>8.1} {:>8.1} {:>8.1} {:>8.1}", kind, latencies.len(), rejected, millis(percentile(latencies, 50.0)), millis(percentile(latencies, 90.0)), millis(percentile(latencies, 99.0)), millis(latencies.last().copied().unwrap_or_default()));
+    }
+    for (code, count) in &report.errors {
+        println!("error {}: {}", code, count);
+    }
+    println!("{} games finished and {} failed in {:.1}s, {} connections refused, {:.2}% of actions rejected", report.games_finished, report.games_failed.len(), report.elapsed.as_secs_f64(), report.connect_failures, report.error_rate() * 100.0);
+    for failure in report.games_failed.iter().take(10) {
+        println!("  {}", failure);
+    }
+    if report.games_failed.is_empty() {
+        Ok(())
+    }
+    else {
+        Err(format!("{} of {} games failed", report.games_failed.len(), test.games))
+    }
+}
+
+pub fn replay(args: &ArgMatches) -> Result<(), String> {
+    let path = args.get_one::<PathBuf>("file").unwrap();
+    let contents = fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+    let mut value: serde_json::Value = serde_json::from_str(&contents).map_err(|e| format!("invalid replay: {}", e))?;
+    // accept the whole Timeline message as well as just its events
+    if let Some(events) = value.get_mut("events") {
+        value = events.take();
+    }
+    let timeline: Vec<TimelineEntry> = serde_json::from_value(value).map_err(|e| format!("invalid replay: {}", e))?;
+
+    let start = timeline.first().map(|entry| entry.at).unwrap_or(0);
+    for entry in &timeline {
+        println!("{:>8.1}s  {}", entry.at.saturating_sub(start) as f64 / 1000.0, entry.event);
+    }
+    Ok(())
+}
+
+pub fn generate_types(args: &ArgMatches) -> Result<(), String> {
+    let types = typescript::protocol_types();
+    match args.get_one::<PathBuf>("out") {
+        Some(path) => fs::write(path, types).map_err(|e| format!("could not write {}: {}", path.display(), e)),
+        None => {
+            print!("{}", types);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::{collections::{BTreeMap, BTreeSet}, fs, path::PathBuf, sync::Arc};
+
+use parking_lot::RwLock;
+use serde::Deserialize;
+use uuid::Uuid;
+use warp::{Filter, Rejection, Reply, http::StatusCode, reply::Response};
+
+use crate::friends::friend_id;
+
+/// Longest slug a community can have.
+const MAX_SLUG_LENGTH: usize = 32;
+
+/// Most moderators one community can have.
+const MAX_MODERATORS: usize = 100;
+
+/// Whether a slug can name a community: lowercase letters, digits, and dashes.
+pub fn valid_slug(slug: &str) -> bool {
+    !slug.is_empty() && slug.len() <= MAX_SLUG_LENGTH && slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// The communities sharing this server, each with its own lobbies, leaderboards, bans, and message of the day.
+/// Connections pick one with `?community=` in the URL, and those that do not use the server's own.
+/// Each community is run by owners set in the server's configuration, who can make other players its moderators.
+/// Players are known by their friend id, so secrets are never written out. Moderators are written to a file whenever they change, if one is set, so they survive restarts.
+#[derive(Default)]
+pub struct Communities {
+    slugs: BTreeSet<String>,
+    owners: BTreeMap<String, BTreeSet<Uuid>>,
+    path: Option<PathBuf>,
+    moderators: RwLock<BTreeMap<String, BTreeSet<Uuid>>>,
+}
+
+/// What a player may do in a community they help run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// Can remove players from its games, ban them from it, and make announcements to it.
+    Moderator,
+    /// Can also choose the moderators.
+    Owner,
+}
+
+impl Communities {
+    pub fn new(slugs: &[String]) -> Result<Communities, String> {
+        let slugs = slugs.iter().map(|slug| match valid_slug(slug) {
+            true => Ok(slug.clone()),
+            false => Err(format!("{} is not a valid community slug", slug))
+        }).collect::<Result<_, _>>()?;
+        Ok(Communities { slugs, ..Communities::default() })
+    }
+
+    /// Make the players with these friend ids owners, each given as `slug:friend_id`.
+    pub fn with_owners(mut self, owners: &[String]) -> Result<Communities, String> {
+        for owner in owners {
+            let parsed = owner.split_once(':').and_then(|(slug, id)| Some((slug.trim(), id.trim().parse::<Uuid>().ok()?)));
+            match parsed {
+                Some((slug, id)) if self.slugs.contains(slug) => self.owners.entry(slug.to_string()).or_default().insert(id),
+                Some((slug, _)) => return Err(format!("{} is not one of the server's communities", slug)),
+                None => return Err(format!("{} is not a community slug and friend id", owner))
+            };
+        }
+        Ok(self)
+    }
+
+    /// Load the moderators kept in a file, starting with none if it does not exist yet.
+    pub fn with_moderator_file(mut self, path: Option<PathBuf>) -> Communities {
+        let moderators = path.as_ref().and_then(|path| match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| eprintln!("could not read moderators from {}: {}", path.display(), e)).ok(),
+            Err(_) => None
+        });
+        self.path = path;
+        self.moderators = RwLock::new(moderators.unwrap_or_default());
+        self
+    }
+
+    fn save(&self, moderators: &BTreeMap<String, BTreeSet<Uuid>>) {
+        if let Some(path) = &self.path {
+            // written to the side first, so a crash partway through leaves the old moderators in place
+            let temp = path.with_extension("tmp");
+            if let Err(e) = fs::write(&temp, serde_json::to_vec(moderators).unwrap()).and_then(|_| fs::rename(&temp, path)) {
+                eprintln!("could not save moderators to {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// Whether a connection may use this community, where none is the server's own.
+    pub fn exists(&self, community: Option<&str>) -> bool {
+        community.is_none_or(|slug| self.slugs.contains(slug))
+    }
+
+    /// What the player with this secret may do in a community, if anything.
+    pub fn role(&self, community: &str, secret: Uuid) -> Option<Role> {
+        let id = friend_id(secret);
+        if self.owners.get(community).is_some_and(|owners| owners.contains(&id)) {
+            return Some(Role::Owner)
+        }
+        self.moderators.read().get(community).filter(|moderators| moderators.contains(&id)).map(|_| Role::Moderator)
+    }
+
+    /// Check that the player with this secret holds at least the role needed in a community.
+    pub fn authorize(&self, community: &str, secret: Uuid, needed: Role) -> Result<Role, &'static str> {
+        if !self.slugs.contains(community) {
+            return Err("There is no community with that name.")
+        }
+        match self.role(community, secret) {
+            Some(role) if role >= needed => Ok(role),
+            Some(_) => Err("Only the owners of this community can do that."),
+            None => Err("You are not a moderator of this community.")
+        }
+    }
+
+    /// The friend ids of a community's owners.
+    pub fn owners(&self, community: &str) -> Vec<Uuid> {
+        self.owners.get(community).map(|owners| owners.iter().copied().collect()).unwrap_or_default()
+    }
+
+    /// The friend ids of a community's moderators, not counting its owners.
+    pub fn moderators(&self, community: &str) -> Vec<Uuid> {
+        self.moderators.read().get(community).map(|moderators| moderators.iter().copied().collect()).unwrap_or_default()
+    }
+
+    pub fn add_moderator(&self, community: &str, moderator: Uuid) -> Result<(), &'static str> {
+        if self.owners.get(community).is_some_and(|owners| owners.contains(&moderator)) {
+            return Err("That player already owns this community.")
+        }
+        let mut moderators = self.moderators.write();
+        let list = moderators.entry(community.to_string()).or_default();
+        if list.len() >= MAX_MODERATORS && !list.contains(&moderator) {
+            return Err("A community cannot have more than 100 moderators.")
+        }
+        list.insert(moderator);
+        self.save(&moderators);
+        Ok(())
+    }
+
+    pub fn remove_moderator(&self, community: &str, moderator: Uuid) -> Result<(), &'static str> {
+        let mut moderators = self.moderators.write();
+        let removed = moderators.get_mut(community).is_some_and(|list| list.remove(&moderator));
+        if !removed {
+            return Err("That player is not a moderator of this community.")
+        }
+        moderators.retain(|_, list| !list.is_empty());
+        self.save(&moderators);
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct CommunityQuery {
+    #[serde(default)]
+    community: Option<String>,
+}
+
+/// The community named by `?community=` in the query string, if any.
+pub fn community() -> impl Filter<Extract = (Option<String>,), Error = Rejection> + Clone {
+    warp::query::<CommunityQuery>().map(|query: CommunityQuery| query.community.filter(|slug| !slug.is_empty()))
+}
+
+/// Turn away requests for a community the server does not have, passing everything else on to the routes that follow.
+pub fn guard(communities: Arc<Communities>) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+    community().and_then(move |community: Option<String>| {
+        let exists = communities.exists(community.as_deref());
+        async move {
+            if exists {
+                return Err(warp::reject::not_found())
+            }
+            Ok(warp::reply::with_status("no such community", StatusCode::NOT_FOUND).into_response())
+        }
+    })
+}
@@ -0,0 +1,189 @@
+use std::{path::PathBuf, str::FromStr, time::Duration};
+
+#[cfg(feature = "discord")]
+use secrethitler::discord::{DiscordConfig, decode_hex};
+#[cfg(feature = "telegram")]
+use secrethitler::telegram::TelegramConfig;
+
+use secrethitler::{captcha::{HostCheck, MAX_DIFFICULTY}, email::EmailConfig, watchdog::StuckPolicy};
+
+use crate::listen::ListenAddr;
+
+/// Server settings, read from environment variables at startup.
+#[derive(Clone)]
+pub struct ServerConfig {
+    pub port: u16,
+    /// Addresses to accept connections on, as socket addresses or `unix:` paths.
+    /// When empty, sockets passed in by systemd are used, or every IPv4 interface on the port otherwise.
+    pub listen: Vec<ListenAddr>,
+    /// Directory holding the built frontend.
+    pub static_dir: PathBuf,
+    /// Path the server is mounted at behind a reverse proxy, such as `/hitler`, or empty when it is served from the root.
+    pub base_path: String,
+    /// Public address of this server, used when linking to games from outside.
+    pub public_url: Option<String>,
+    /// Sites that may use the server from a browser, or `*` for any.
+    pub allowed_origins: Vec<String>,
+    /// Outgoing webhook endpoints notified when games are created, started, or finished.
+    pub webhook_urls: Vec<String>,
+    /// Keys used to sign resume tokens. The first signs new tokens and the rest are still accepted.
+    pub resume_token_keys: Vec<String>,
+    pub resume_token_ttl: Duration,
+    /// Most games that may exist at once, unlimited if unset.
+    pub max_games: Option<usize>,
+    /// Most websockets that may be open at once, unlimited if unset.
+    pub max_sockets: Option<usize>,
+    /// How long players turned away for load are told to wait before trying again.
+    pub busy_retry_after: Duration,
+    /// How many messages a second each websocket may send on average, unlimited if unset.
+    pub message_rate: Option<f64>,
+    /// How many messages a websocket may send at once before the rate applies.
+    pub message_burst: u32,
+    /// Let the same player sit in more than one game at a time.
+    pub allow_multiple_games: bool,
+    /// Only accept actions that carry the seat's secret or resume token.
+    pub require_action_auth: bool,
+    /// The only hosts that links in chat are made clickable for, or any host if unset.
+    pub chat_link_hosts: Option<Vec<String>>,
+    /// Proxies whose forwarded headers are believed, or only ones on this machine if unset.
+    pub trusted_proxies: Option<Vec<String>>,
+    /// The only address ranges that may use the server, or any if empty.
+    pub allowed_addresses: Vec<String>,
+    /// Address ranges that may not use the server.
+    pub denied_addresses: Vec<String>,
+    /// Slugs of the communities sharing the server, each with its own games, leaderboards, bans, and message of the day.
+    pub communities: Vec<String>,
+    /// Who owns each community, given as `slug:friend_id`. Owners choose the community's moderators.
+    pub community_owners: Vec<String>,
+    /// What hosts have to solve before a game is created, if anything.
+    pub host_check: Option<HostCheck>,
+    /// Token that admins send to read the audit log and to export and import games, which cannot be done if unset.
+    pub admin_token: Option<String>,
+    /// Memory set aside for replays of finished games, in bytes.
+    pub replay_cache_size: usize,
+    /// How long each season of the leaderboard lasts.
+    pub season_length: Duration,
+    /// File that bans are kept in, so they survive restarts. Bans are only held in memory if unset.
+    pub ban_file: Option<PathBuf>,
+    /// File that block lists are saved to, so they survive restarts. They are only held in memory if unset.
+    pub block_file: Option<PathBuf>,
+    /// File that the moderators of each community are saved to, so they survive restarts. They are only held in memory if unset.
+    pub moderator_file: Option<PathBuf>,
+    /// File that finished games are added to, so the history at `/games` survives restarts. The history is only held in memory if unset.
+    pub history_file: Option<PathBuf>,
+    /// Where replays that do not fit in memory are written, or none to forget them.
+    pub replay_dir: Option<PathBuf>,
+    /// Where snapshots of the games in progress are written, or none to not take any.
+    pub snapshot_dir: Option<PathBuf>,
+    /// How often the games in progress are written to the snapshot directory.
+    pub snapshot_interval: Duration,
+    /// How long a game can wait on players who have all lost their connection before the watchdog steps in, or none to never.
+    pub stuck_game_timeout: Option<Duration>,
+    /// What the watchdog does with a stuck game.
+    pub stuck_game_policy: StuckPolicy,
+    /// How to send email notifications, which are enabled when an SMTP server and sender are set.
+    pub email: Option<EmailConfig>,
+    /// Credentials for the Discord bot, which is enabled when all of them are set.
+    #[cfg(feature = "discord")]
+    pub discord: Option<DiscordConfig>,
+    /// Credentials for the Telegram bot, which is enabled when the token and webhook secret are set.
+    #[cfg(feature = "telegram")]
+    pub telegram: Option<TelegramConfig>,
+}
+
+impl ServerConfig {
+    pub fn from_env() -> ServerConfig {
+        let public_url = std::env::var("PUBLIC_URL").ok().map(|url| url.trim_end_matches('/').to_string());
+        ServerConfig {
+            port: parse_var("PORT", 8000),
+            listen: list_var("LISTEN").iter().map(|addr| addr.parse().unwrap_or_else(|e| panic!("LISTEN: {}", e))).collect(),
+            static_dir: std::env::var("STATIC_DIR").ok().filter(|dir| !dir.is_empty()).map(PathBuf::from).unwrap_or_else(|| PathBuf::from("frontend/build")),
+            base_path: std::env::var("BASE_PATH").ok().map(|path| path.trim_matches('/').to_string()).filter(|path| !path.is_empty()).map(|path| format!("/{}", path)).unwrap_or_default(),
+            public_url: public_url.clone(),
+            allowed_origins: list_var("ALLOWED_ORIGINS"),
+            webhook_urls: list_var("WEBHOOK_URLS"),
+            resume_token_keys: list_var("RESUME_TOKEN_KEYS"),
+            resume_token_ttl: Duration::from_secs(parse_var("RESUME_TOKEN_TTL", 24 * 60 * 60)),
+            max_games: std::env::var("MAX_GAMES").ok().and_then(|v| v.parse().ok()),
+            max_sockets: std::env::var("MAX_SOCKETS").ok().and_then(|v| v.parse().ok()),
+            busy_retry_after: Duration::from_secs(parse_var("BUSY_RETRY_AFTER", 30)),
+            message_rate: std::env::var("MESSAGE_RATE_LIMIT").ok().and_then(|v| v.parse().ok()),
+            message_burst: parse_var("MESSAGE_BURST", 20),
+            allow_multiple_games: parse_var("ALLOW_MULTIPLE_GAMES", false),
+            require_action_auth: parse_var("REQUIRE_ACTION_AUTH", false),
+            chat_link_hosts: std::env::var("CHAT_LINK_HOSTS").ok().map(|_| list_var("CHAT_LINK_HOSTS")),
+            trusted_proxies: std::env::var("TRUSTED_PROXIES").ok().map(|_| list_var("TRUSTED_PROXIES")),
+            allowed_addresses: list_var("ALLOWED_ADDRESSES"),
+            denied_addresses: list_var("DENIED_ADDRESSES"),
+            communities: list_var("COMMUNITIES"),
+            community_owners: list_var("COMMUNITY_OWNERS"),
+            host_check: host_check(),
+            admin_token: std::env::var("ADMIN_TOKEN").ok().filter(|token| !token.is_empty()),
+            replay_cache_size: parse_var("REPLAY_CACHE_MB", 64) * 1024 * 1024,
+            season_length: Duration::from_secs(parse_var("SEASON_DAYS", 90) * 24 * 60 * 60),
+            ban_file: std::env::var("BAN_FILE").ok().filter(|file| !file.is_empty()).map(PathBuf::from),
+            block_file: std::env::var("BLOCK_FILE").ok().filter(|file| !file.is_empty()).map(PathBuf::from),
+            moderator_file: std::env::var("MODERATOR_FILE").ok().filter(|file| !file.is_empty()).map(PathBuf::from),
+            history_file: std::env::var("HISTORY_FILE").ok().filter(|file| !file.is_empty()).map(PathBuf::from),
+            replay_dir: std::env::var("REPLAY_DIR").ok().filter(|dir| !dir.is_empty()).map(PathBuf::from),
+            snapshot_dir: std::env::var("SNAPSHOT_DIR").ok().filter(|dir| !dir.is_empty()).map(PathBuf::from),
+            snapshot_interval: Duration::from_secs(parse_var("SNAPSHOT_INTERVAL", 60).max(1)),
+            stuck_game_timeout: std::env::var("STUCK_GAME_TIMEOUT").ok().and_then(|v| v.parse().ok()).map(Duration::from_secs),
+            stuck_game_policy: std::env::var("STUCK_GAME_POLICY").ok().filter(|policy| !policy.is_empty()).map(|policy| policy.parse().unwrap_or_else(|e| panic!("STUCK_GAME_POLICY: {}", e))).unwrap_or(StuckPolicy::Skip),
+            email: email_config(),
+            #[cfg(feature = "discord")]
+            discord: discord_config(),
+            #[cfg(feature = "telegram")]
+            telegram: telegram_config(public_url.as_deref()),
+        }
+    }
+}
+
+/// An hCaptcha when its keys are set, or else a proof of work when a difficulty is set.
+fn host_check() -> Option<HostCheck> {
+    let hcaptcha = std::env::var("HCAPTCHA_SITE_KEY").ok().zip(std::env::var("HCAPTCHA_SECRET").ok());
+    match hcaptcha {
+        Some((site_key, secret)) => Some(HostCheck::HCaptcha { site_key, secret }),
+        None => match parse_var("HOST_POW_DIFFICULTY", 0) {
+            0 => None,
+            difficulty => Some(HostCheck::ProofOfWork { difficulty: difficulty.min(MAX_DIFFICULTY) })
+        }
+    }
+}
+
+fn email_config() -> Option<EmailConfig> {
+    Some(EmailConfig {
+        smtp_url: std::env::var("SMTP_URL").ok()?,
+        from: std::env::var("EMAIL_FROM").ok()?,
+        per_minute: parse_var("EMAIL_PER_MINUTE", 30),
+        template_dir: std::env::var("EMAIL_TEMPLATE_DIR").ok(),
+    })
+}
+
+#[cfg(feature = "discord")]
+fn discord_config() -> Option<DiscordConfig> {
+    Some(DiscordConfig {
+        application_id: std::env::var("DISCORD_APPLICATION_ID").ok()?,
+        bot_token: std::env::var("DISCORD_BOT_TOKEN").ok()?,
+        public_key: decode_hex(&std::env::var("DISCORD_PUBLIC_KEY").ok()?).unwrap_or_else(|| panic!("DISCORD_PUBLIC_KEY must be hex")),
+    })
+}
+
+#[cfg(feature = "telegram")]
+fn telegram_config(public_url: Option<&str>) -> Option<TelegramConfig> {
+    Some(TelegramConfig {
+        bot_token: std::env::var("TELEGRAM_BOT_TOKEN").ok()?,
+        webhook_secret: std::env::var("TELEGRAM_WEBHOOK_SECRET").ok()?,
+        webhook_url: public_url.map(|url| format!("{}/telegram/webhook", url)),
+    })
+}
+
+/// Read a comma separated list from an environment variable, ignoring empty entries.
+fn list_var(name: &str) -> Vec<String> {
+    std::env::var(name).unwrap_or_default().split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()
+}
+
+/// Parse an environment variable, falling back to the default if it is missing or invalid.
+fn parse_var<T: FromStr>(name: &str, default: T) -> T {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use warp::{Filter, Rejection, Reply, http::{HeaderValue, StatusCode, header}, reply::Response};
+
+/// Which other sites may use the server from a browser, so frontends hosted elsewhere can connect to it.
+/// With no origins listed, no CORS headers are sent and websockets are accepted from any page.
+#[derive(Clone, Default)]
+pub struct CorsPolicy {
+    origins: Vec<String>,
+    any: bool,
+}
+
+impl CorsPolicy {
+    /// Allow the origins given, such as `https://example.com`, or every origin with `*`.
+    pub fn new(origins: Vec<String>) -> CorsPolicy {
+        let any = origins.iter().any(|origin| origin == "*");
+        let origins = origins.into_iter().filter(|origin| origin != "*").map(|origin| origin.trim_end_matches('/').to_ascii_lowercase()).collect();
+        CorsPolicy { origins, any }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.any || !self.origins.is_empty()
+    }
+
+    pub fn allows(&self, origin: &str) -> bool {
+        self.any || self.origins.iter().any(|allowed| allowed.eq_ignore_ascii_case(origin))
+    }
+
+    /// Whether a websocket may be opened from a page at this origin, given the host it connected to.
+    /// Clients outside a browser send no origin, and the frontend served by this server shares its host.
+    pub fn allows_websocket(&self, origin: Option<&str>, host: Option<&str>) -> bool {
+        let origin = match origin {
+            Some(origin) if self.is_enabled() => origin,
+            _ => return true
+        };
+        let same_host = host.is_some_and(|host| origin.split_once("://").is_some_and(|(_, rest)| rest.eq_ignore_ascii_case(host)));
+        same_host || self.allows(origin)
+    }
+}
+
+/// The request's origin, if the policy lets it read responses.
+fn allowed_origin(policy: Arc<CorsPolicy>) -> impl Filter<Extract = (Option<String>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("origin").map(move |origin: Option<String>| origin.filter(|origin| policy.allows(origin)))
+}
+
+/// Answer preflight requests from allowed origins.
+pub fn preflight(policy: Arc<CorsPolicy>) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+    warp::options()
+        .and(allowed_origin(policy))
+        .and_then(|origin: Option<String>| async move {
+            let origin = origin.ok_or_else(warp::reject::not_found)?;
+            let mut response = allow(origin, StatusCode::NO_CONTENT.into_response());
+            let headers = response.headers_mut();
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, HeaderValue::from_static("GET, POST"));
+            headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, HeaderValue::from_static("authorization, content-type"));
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from_static("86400"));
+            Ok::<_, Rejection>(response)
+        })
+}
+
+/// Add CORS headers to the responses of a set of routes when the request comes from an allowed origin.
+pub fn wrap<F, R>(policy: Arc<CorsPolicy>, routes: F) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    allowed_origin(policy).and(routes).map(|origin: Option<String>, reply: R| {
+        let response = reply.into_response();
+        match origin {
+            Some(origin) => allow(origin, response),
+            None => response
+        }
+    })
+}
+
+fn allow(origin: String, mut response: Response) -> Response {
+    let headers = response.headers_mut();
+    if let Ok(origin) = HeaderValue::from_str(&origin) {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    }
+    headers.append(header::VARY, HeaderValue::from_static("Origin"));
+    response
+}
@@ -0,0 +1,77 @@
+use serde::Serialize;
+use uuid::Uuid;
+use warp::{Filter, Rejection, Reply, http::StatusCode};
+
+use secrethitler_core::game_state::{TurnPhase, epoch_millis};
+
+use crate::server::{ServerState, all_games};
+
+/// A game the player has a seat in, with a token to take the seat back from a new device or tab.
+#[derive(Serialize)]
+pub struct MyGame {
+    pub game_id: Uuid,
+    pub player_id: Uuid,
+    pub name: Option<String>,
+    pub players: Vec<String>,
+    pub turn_phase: TurnPhase,
+    pub resume_token: String,
+    pub resume_url: String,
+    /// When the resume token stops working, in milliseconds since the epoch.
+    pub expires_at: u64,
+}
+
+/// The games a player has a seat in, most recently played first.
+#[derive(Serialize)]
+pub struct MyGames {
+    /// Lobbies and games under way.
+    pub active: Vec<MyGame>,
+    /// Games that have ended but are still held, which can be rejoined for a rematch.
+    pub recent: Vec<MyGame>,
+}
+
+/// Find every game held by the server where the player with this secret has a seat.
+pub fn my_games(server: &ServerState, secret: Uuid, public_url: Option<&str>) -> MyGames {
+    let mut active = Vec::new();
+    let mut recent = Vec::new();
+    for (game_id, game) in all_games(&server.games) {
+        let state = game.lock();
+        let seat = state.conn.iter().find(|(player_id, conn)| conn.secret == Some(secret) && state.has_player(player_id));
+        if let Some((player_id, conn)) = seat {
+            let (resume_token, expires) = server.tokens.issue(game_id, *player_id, secret);
+            let game = MyGame {
+                game_id,
+                player_id: *player_id,
+                name: conn.name.clone(),
+                players: state.summary().players,
+                turn_phase: state.turn_phase().clone(),
+                resume_url: format!("{}/game/{}?resume={}", public_url.unwrap_or_default(), game_id, resume_token),
+                resume_token,
+                expires_at: epoch_millis(expires),
+            };
+            let last_played = state.timeline().last().map_or(0, |entry| entry.at);
+            if state.winner().is_some() {
+                recent.push((last_played, game));
+            }
+            else {
+                active.push((last_played, game));
+            }
+        }
+    }
+    active.sort_by_key(|(last_played, _)| std::cmp::Reverse(*last_played));
+    recent.sort_by_key(|(last_played, _)| std::cmp::Reverse(*last_played));
+    MyGames { active: active.into_iter().map(|(_, game)| game).collect(), recent: recent.into_iter().map(|(_, game)| game).collect() }
+}
+
+/// List the games of the player whose secret is sent as a bearer token at `/me/games`, so they can find their way back to a game without its link.
+pub fn route(server: ServerState, public_url: Option<String>) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone {
+    warp::path!("me" / "games")
+        .and(warp::get())
+        .and(warp::header::optional::<String>("authorization"))
+        .map(move |authorization: Option<String>| -> Box<dyn Reply> {
+            let secret = authorization.as_deref().and_then(|header| header.strip_prefix("Bearer ")).and_then(|secret| Uuid::parse_str(secret.trim()).ok());
+            match secret {
+                Some(secret) => Box::new(warp::reply::json(&my_games(&server, secret, public_url.as_deref()))),
+                None => Box::new(warp::reply::with_status("send your player secret as a bearer token", StatusCode::UNAUTHORIZED))
+            }
+        })
+}
@@ -0,0 +1,326 @@
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::{FairMutex, RwLock};
+use reqwest::Method;
+use ring::signature::{ED25519, UnparsedPublicKey};
+use secrethitler_core::game_state::GameState;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+use warp::{Filter, Rejection, Reply, http::StatusCode, hyper::body::Bytes};
+
+use crate::{bridge::{Action, Audience, Button, Markup, Pressed, Style, Table}, limits::ServerLimits, server::{GlobalState, get_game}};
+
+const API_URL: &str = "https://discord.com/api/v10";
+
+/// The slash command that opens a game in the channel it is used in.
+const COMMAND: &str = "secrethitler";
+
+// interaction types
+const PING: u8 = 1;
+const APPLICATION_COMMAND: u8 = 2;
+const MESSAGE_COMPONENT: u8 = 3;
+
+// interaction response types
+const PONG: u8 = 1;
+const CHANNEL_MESSAGE: u8 = 4;
+const DEFERRED_UPDATE: u8 = 6;
+const UPDATE_MESSAGE: u8 = 7;
+
+/// Message flag that shows a reply only to the user who caused it.
+const EPHEMERAL: u64 = 64;
+
+// button styles
+const PRIMARY: u8 = 1;
+const SECONDARY: u8 = 2;
+const SUCCESS: u8 = 3;
+const DANGER: u8 = 4;
+
+/// Credentials for the bot, from the application's page in the Discord developer portal.
+#[derive(Clone)]
+pub struct DiscordConfig {
+    pub application_id: String,
+    pub bot_token: String,
+    /// The application's ed25519 public key, used to check that interactions really come from Discord.
+    pub public_key: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct Interaction {
+    #[serde(rename = "type")]
+    kind: u8,
+    channel_id: Option<String>,
+    /// Set for interactions in a server channel.
+    member: Option<Member>,
+    /// Set for interactions in a direct message.
+    user: Option<User>,
+    data: Option<InteractionData>,
+}
+
+#[derive(Deserialize)]
+struct Member {
+    user: User,
+    nick: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct User {
+    id: String,
+    username: String,
+    global_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct InteractionData {
+    custom_id: Option<String>,
+}
+
+impl Interaction {
+    /// The id and display name of the user who caused the interaction.
+    fn user(&self) -> Option<(String, String)> {
+        match (&self.member, &self.user) {
+            (Some(member), _) => Some((member.user.id.clone(), member.nick.clone().or_else(|| member.user.global_name.clone()).unwrap_or_else(|| member.user.username.clone()))),
+            (None, Some(user)) => Some((user.id.clone(), user.global_name.clone().unwrap_or_else(|| user.username.clone()))),
+            (None, None) => None
+        }
+    }
+}
+
+/// A message for the bot to post, either in a game's channel or privately to one player.
+enum Delivery {
+    Channel { channel_id: String, message: Value },
+    Direct { user_id: String, message: Value },
+}
+
+/// Discord's markdown, where a mention is the user's id in angle brackets.
+struct DiscordMarkup;
+
+impl Markup for DiscordMarkup {
+    fn mention(&self, user_id: &str, _name: &str) -> String {
+        format!("<@{}>", user_id)
+    }
+
+    fn bold(&self, text: &str) -> String {
+        format!("**{}**", text)
+    }
+
+    fn escape(&self, text: &str) -> String {
+        text.chars().fold(String::new(), |mut escaped, c| {
+            if "\\*_~`|>#<@[]()".contains(c) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+            escaped
+        })
+    }
+}
+
+/// Bridges Discord channels to games, so that a game can be played with a slash command, buttons, and direct messages.
+/// The games are kept alongside the server's other games, so they can also be watched from the website.
+#[derive(Clone)]
+pub struct DiscordBridge {
+    config: Arc<DiscordConfig>,
+    games: GlobalState,
+    limits: Arc<ServerLimits>,
+    tables: Arc<RwLock<HashMap<Uuid, Table>>>,
+    outbox: mpsc::UnboundedSender<Delivery>,
+}
+
+impl DiscordBridge {
+    /// Register the slash command and spawn the task that posts messages to Discord.
+    pub fn start(config: DiscordConfig, games: GlobalState, limits: Arc<ServerLimits>) -> DiscordBridge {
+        let config = Arc::new(config);
+        let (outbox, rx) = mpsc::unbounded_channel();
+        tokio::spawn(deliver(config.clone(), rx));
+
+        let register = config.clone();
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            let commands = json!([{ "name": COMMAND, "description": "Start a game of Secret Hitler in this channel", "type": 1 }]);
+            if let Err(e) = api(&client, &register, Method::PUT, &format!("/applications/{}/commands", register.application_id), &commands).await {
+                eprintln!("discord: could not register commands: {}", e);
+            }
+        });
+
+        DiscordBridge { config, games, limits, tables: Arc::default(), outbox }
+    }
+
+    /// Handle an interaction sent to the bot's endpoint, returning the response for Discord.
+    /// Returns none if the request was not signed by Discord.
+    pub fn handle(&self, signature: &str, timestamp: &str, body: &[u8]) -> Option<Value> {
+        let signature = decode_hex(signature)?;
+        let signed = [timestamp.as_bytes(), body].concat();
+        UnparsedPublicKey::new(&ED25519, &self.config.public_key).verify(&signed, &signature).ok()?;
+
+        let interaction: Interaction = match serde_json::from_slice(body) {
+            Ok(interaction) => interaction,
+            Err(_) => return Some(reply("Sorry, I did not understand that."))
+        };
+        Some(match interaction.kind {
+            PING => json!({ "type": PONG }),
+            APPLICATION_COMMAND => self.host(&interaction),
+            MESSAGE_COMPONENT => self.press(&interaction),
+            _ => reply("Sorry, I did not understand that.")
+        })
+    }
+
+    /// Open a lobby in the channel the command was used in, with the user who used it as the host.
+    fn host(&self, interaction: &Interaction) -> Value {
+        let (channel_id, (user_id, name)) = match (interaction.channel_id.clone(), interaction.user()) {
+            (Some(channel_id), Some(user)) => (channel_id, user),
+            _ => return reply("Games can only be started in a channel.")
+        };
+        if !self.limits.can_host(self.games.read().len()) {
+            return reply("The server is too busy to start a new game right now. Try again later.")
+        }
+
+        let (table, state) = Table::host(channel_id, user_id, name);
+        let lobby = lobby(&table, &state);
+        self.games.write().insert(table.game_id, Arc::new(FairMutex::new(state)));
+        self.tables.write().insert(table.game_id, table);
+        json!({ "type": CHANNEL_MESSAGE, "data": lobby })
+    }
+
+    /// Apply a button press to the game it belongs to.
+    fn press(&self, interaction: &Interaction) -> Value {
+        let (game_id, action) = match interaction.data.as_ref().and_then(|data| data.custom_id.as_deref()).and_then(Action::decode) {
+            Some(pressed) => pressed,
+            None => return reply("That button does not do anything.")
+        };
+        let (user_id, name) = match interaction.user() {
+            Some(user) => user,
+            None => return reply("Sorry, I could not tell who you are.")
+        };
+        let game = match get_game(&self.games, &game_id) {
+            Some(game) => game,
+            None => return reply("This game is over.")
+        };
+        let state = &mut game.lock();
+        let mut tables = self.tables.write();
+        let table = match tables.get_mut(&game_id) {
+            Some(table) => table,
+            None => return reply("This game is over.")
+        };
+
+        let (pressed, notices) = table.press(state, &user_id, name, action, &DiscordMarkup);
+        for notice in notices {
+            self.send(match notice.to {
+                Audience::Table => Delivery::Channel { channel_id: table.chat_id.clone(), message: message(notice.text, notice.buttons) },
+                Audience::User(user_id) => Delivery::Direct { user_id, message: message(notice.text, notice.buttons) }
+            });
+        }
+        let response = match pressed {
+            Pressed::Refused(reason) => reply(&reason),
+            Pressed::Joined => json!({ "type": UPDATE_MESSAGE, "data": lobby(table, state) }),
+            Pressed::Applied(Some(confirmation)) => reply(&confirmation),
+            Pressed::Applied(None) => json!({ "type": DEFERRED_UPDATE })
+        };
+        if state.winner().is_some() {
+            tables.remove(&game_id);
+        }
+        response
+    }
+
+    fn send(&self, delivery: Delivery) {
+        if self.outbox.send(delivery).is_err() {
+            eprintln!("discord: delivery task has stopped");
+        }
+    }
+}
+
+/// Accept interactions from Discord at `/discord/interactions`, if the bridge is enabled.
+pub fn route(bridge: Option<DiscordBridge>) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone {
+    warp::path!("discord" / "interactions")
+        .and(warp::post())
+        .and(warp::header::<String>("x-signature-ed25519"))
+        .and(warp::header::<String>("x-signature-timestamp"))
+        .and(warp::body::content_length_limit(64 * 1024))
+        .and(warp::body::bytes())
+        .and_then(move |signature: String, timestamp: String, body: Bytes| {
+            let bridge = bridge.clone();
+            async move {
+                let bridge = bridge.ok_or_else(warp::reject::not_found)?;
+                Ok::<Box<dyn Reply>, Rejection>(match bridge.handle(&signature, &timestamp, &body) {
+                    Some(response) => Box::new(warp::reply::json(&response)),
+                    None => Box::new(warp::reply::with_status("invalid request signature", StatusCode::UNAUTHORIZED))
+                })
+            }
+        })
+}
+
+fn lobby(table: &Table, state: &GameState) -> Value {
+    let notice = table.lobby(state, &DiscordMarkup);
+    message(notice.text, notice.buttons)
+}
+
+/// A message with its buttons laid out in rows of five.
+fn message(content: String, buttons: Vec<Button>) -> Value {
+    let buttons: Vec<Value> = buttons.into_iter().map(|button| {
+        let style = match button.style {
+            Style::Primary => PRIMARY,
+            Style::Secondary => SECONDARY,
+            Style::Success => SUCCESS,
+            Style::Danger => DANGER
+        };
+        json!({ "type": 2, "style": style, "label": button.label, "custom_id": button.data })
+    }).collect();
+    let rows: Vec<Value> = buttons.chunks(5).map(|row| json!({ "type": 1, "components": row })).collect();
+    json!({ "content": content, "components": rows, "allowed_mentions": { "parse": ["users"] } })
+}
+
+/// A response only shown to the user who pressed the button or used the command.
+fn reply(content: &str) -> Value {
+    json!({ "type": CHANNEL_MESSAGE, "data": { "content": content, "flags": EPHEMERAL } })
+}
+
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+/// Post queued messages in order, opening direct message channels as they are needed.
+async fn deliver(config: Arc<DiscordConfig>, mut rx: mpsc::UnboundedReceiver<Delivery>) {
+    let client = reqwest::Client::new();
+    let mut dm_channels: HashMap<String, String> = HashMap::new();
+    while let Some(delivery) = rx.recv().await {
+        let (channel_id, message) = match delivery {
+            Delivery::Channel { channel_id, message } => (channel_id, message),
+            Delivery::Direct { user_id, message } => {
+                let channel_id = match dm_channels.get(&user_id) {
+                    Some(channel_id) => channel_id.clone(),
+                    None => match api(&client, &config, Method::POST, "/users/@me/channels", &json!({ "recipient_id": user_id })).await {
+                        Ok(channel) => {
+                            let channel_id = channel["id"].as_str().unwrap_or_default().to_string();
+                            dm_channels.insert(user_id, channel_id.clone());
+                            channel_id
+                        },
+                        Err(e) => {
+                            eprintln!("discord: could not open direct messages with {}: {}", user_id, e);
+                            continue
+                        }
+                    }
+                };
+                (channel_id, message)
+            }
+        };
+        if let Err(e) = api(&client, &config, Method::POST, &format!("/channels/{}/messages", channel_id), &message).await {
+            eprintln!("discord: could not post to channel {}: {}", channel_id, e);
+        }
+    }
+}
+
+async fn api(client: &reqwest::Client, config: &DiscordConfig, method: Method, path: &str, body: &Value) -> Result<Value, String> {
+    let response = client.request(method, format!("{}{}", API_URL, path))
+        .header("Authorization", format!("Bot {}", config.bot_token))
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("status {}", response.status()))
+    }
+    let text = response.text().await.map_err(|e| e.to_string())?;
+    serde_json::from_str(&text).map_err(|e| e.to_string())
+}
@@ -0,0 +1,86 @@
+use std::{panic::{self, AssertUnwindSafe}, time::Instant};
+
+use secrethitler_core::{error::GameError, protocol::{ClientProtocol, PlayerConnection, ServerProtocol}};
+use uuid::Uuid;
+
+use crate::server::{ConnectionContext, ServerState, get_game};
+
+/// Handles the messages sent over one connection.
+/// Middleware wraps another dispatcher to check, answer, or drop a message before passing it on, so concerns such as rate limiting stay out of the handlers.
+pub trait Dispatcher {
+    fn dispatch(&self, server: &ServerState, ctx: &mut ConnectionContext, msg: ClientProtocol);
+}
+
+impl<F: Fn(&ServerState, &mut ConnectionContext, ClientProtocol)> Dispatcher for F {
+    fn dispatch(&self, server: &ServerState, ctx: &mut ConnectionContext, msg: ClientProtocol) {
+        self(server, ctx, msg)
+    }
+}
+
+/// Catches a panic while handling a message and reports it to the player. Locks are not poisoned by a panic, so the game and the server carry on.
+pub struct CatchPanics<D>(pub D);
+
+impl<D: Dispatcher> Dispatcher for CatchPanics<D> {
+    fn dispatch(&self, server: &ServerState, ctx: &mut ConnectionContext, msg: ClientProtocol) {
+        // only the kind of message is logged, since messages can carry secrets
+        let kind = msg.kind();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| self.0.dispatch(server, ctx, msg)));
+        if let Err(panic) = result {
+            let reason = panic.downcast_ref::<&str>().copied().or_else(|| panic.downcast_ref::<String>().map(String::as_str)).unwrap_or("unknown panic");
+            eprintln!("panic while handling {} in game {:?} from player {:?}: {}", kind, ctx.game, ctx.player, reason);
+            let error = GameError::Internal;
+            PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::Error { message: error.to_string(), error: &error, request_id: None });
+        }
+    }
+}
+
+/// Drops messages from a connection that is sending faster than its rate limiter allows, and tells the client how long to wait.
+pub struct RateLimit<D>(pub D);
+
+impl<D: Dispatcher> Dispatcher for RateLimit<D> {
+    fn dispatch(&self, server: &ServerState, ctx: &mut ConnectionContext, msg: ClientProtocol) {
+        if let Some(Err(wait)) = ctx.rate_limiter.as_mut().map(|limiter| limiter.check(Instant::now())) {
+            PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::MessagesThrottled { retry_after_ms: wait.as_millis() as u64 });
+            return
+        }
+        self.0.dispatch(server, ctx, msg)
+    }
+}
+
+/// Checks the credentials an `Authenticated` message carries against the seat the connection holds, and unwraps the action inside.
+/// When the server requires it, actions sent without credentials are rejected, so a connection that was handed someone else's seat cannot act for them.
+pub struct Authenticate<D>(pub D);
+
+impl<D: Dispatcher> Dispatcher for Authenticate<D> {
+    fn dispatch(&self, server: &ServerState, ctx: &mut ConnectionContext, msg: ClientProtocol) {
+        match msg {
+            ClientProtocol::Authenticated { player_secret, resume_token, message } => {
+                if seat_matches(server, ctx, player_secret, resume_token.as_deref()) {
+                    self.0.dispatch(server, ctx, *message)
+                }
+                else {
+                    unauthenticated(ctx)
+                }
+            },
+            msg if server.require_action_auth && msg.is_action() => unauthenticated(ctx),
+            msg => self.0.dispatch(server, ctx, msg)
+        }
+    }
+}
+
+/// Whether the secret or resume token belongs to the seat the connection holds.
+fn seat_matches(server: &ServerState, ctx: &ConnectionContext, player_secret: Option<Uuid>, resume_token: Option<&str>) -> bool {
+    let seat = ctx.game.zip(ctx.player).and_then(|(game_id, player_id)| {
+        let secret = get_game(&server.games, &game_id)?.lock().get_player_secret(&player_id)?;
+        Some((game_id, player_id, secret))
+    });
+    seat.is_some_and(|(game_id, player_id, secret)| {
+        let token_valid = resume_token.is_some_and(|token| server.tokens.claims(token) == Some((game_id, player_id)) && server.tokens.verify(token, secret));
+        token_valid || player_secret == Some(secret)
+    })
+}
+
+fn unauthenticated(ctx: &ConnectionContext) {
+    let error = GameError::Unauthenticated;
+    PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::Error { message: error.to_string(), error: &error, request_id: None });
+}
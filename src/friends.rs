@@ -0,0 +1,158 @@
+use std::{collections::{HashMap, HashSet, VecDeque}, sync::Arc};
+
+use parking_lot::RwLock;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use secrethitler_core::protocol::{FriendInvite, Sink};
+
+/// Most friends and unanswered friend requests one player can have.
+const MAX_FRIENDS: usize = 100;
+
+/// Most invitations waiting for one player. The oldest are dropped first.
+const MAX_PENDING_INVITES: usize = 20;
+
+/// Most players the server keeps friends for, since nothing else bounds how many secrets can be used.
+const MAX_PLAYERS: usize = 100_000;
+
+/// The id that others use to add the player with this secret as a friend. The secret cannot be worked out from it.
+pub fn friend_id(secret: Uuid) -> Uuid {
+    let hash = Sha256::new().chain_update(b"friend").chain_update(secret.as_bytes()).finalize();
+    Uuid::from_slice(&hash[..16]).unwrap()
+}
+
+#[derive(Default)]
+struct Player {
+    secret: Uuid,
+    friends: HashSet<Uuid>,
+    /// Players who have asked to be friends with this player.
+    requests: HashSet<Uuid>,
+    invites: VecDeque<FriendInvite>,
+}
+
+/// What a player sees of their friends.
+pub struct FriendList {
+    pub friend_id: Uuid,
+    pub friends: Vec<Uuid>,
+    pub requests: Vec<Uuid>,
+    pub invites: Vec<FriendInvite>,
+}
+
+/// Friendships between players and the game invitations they send each other.
+/// A player is whoever holds a secret, which stands in for an account, and is known to others by their friend id.
+/// Friends are kept in memory, so they last until the server restarts.
+#[derive(Default)]
+pub struct Friends {
+    players: RwLock<HashMap<Uuid, Player>>,
+    /// The connection each player last listed their friends from, which receives invitations as they arrive.
+    inboxes: RwLock<HashMap<Uuid, Sink>>,
+}
+
+impl Friends {
+    fn with_player<T>(&self, secret: Uuid, f: impl FnOnce(&mut HashMap<Uuid, Player>, Uuid) -> Result<T, &'static str>) -> Result<T, &'static str> {
+        let id = friend_id(secret);
+        let mut players = self.players.write();
+        if !players.contains_key(&id) {
+            if players.len() >= MAX_PLAYERS {
+                return Err("The server cannot keep track of any more friends.")
+            }
+            players.insert(id, Player { secret, ..Player::default() });
+        }
+        f(&mut players, id)
+    }
+
+    pub fn list(&self, secret: Uuid) -> Result<FriendList, &'static str> {
+        self.with_player(secret, |players, id| {
+            let player = &players[&id];
+            let mut friends: Vec<Uuid> = player.friends.iter().copied().collect();
+            let mut requests: Vec<Uuid> = player.requests.iter().copied().collect();
+            friends.sort();
+            requests.sort();
+            Ok(FriendList { friend_id: id, friends, requests, invites: player.invites.iter().cloned().collect() })
+        })
+    }
+
+    /// Send invitations for this player to the connection from now on.
+    pub fn set_inbox(&self, secret: Uuid, sink: Sink) {
+        self.inboxes.write().insert(friend_id(secret), sink);
+    }
+
+    /// Stop sending invitations to a connection that has closed.
+    pub fn close_inbox(&self, sink: &Sink) {
+        self.inboxes.write().retain(|_, inbox| !Arc::ptr_eq(inbox, sink));
+    }
+
+    pub fn inbox(&self, friend: Uuid) -> Option<Sink> {
+        self.inboxes.read().get(&friend).cloned()
+    }
+
+    /// Ask to be friends, or accept if the other player has already asked. Returns whether the two are now friends.
+    pub fn add(&self, secret: Uuid, friend: Uuid) -> Result<bool, &'static str> {
+        self.with_player(secret, |players, id| {
+            if friend == id {
+                return Err("You cannot add yourself as a friend.")
+            }
+            if players[&id].friends.contains(&friend) {
+                return Ok(true)
+            }
+            if players[&id].friends.len() + players[&id].requests.len() >= MAX_FRIENDS {
+                return Err("You cannot have more than 100 friends.")
+            }
+            let other = players.get_mut(&friend).ok_or("There is no player with that friend id.")?;
+            if other.friends.len() + other.requests.len() >= MAX_FRIENDS {
+                return Err("That player cannot add any more friends.")
+            }
+            let player = players.get_mut(&id).unwrap();
+            if player.requests.remove(&friend) {
+                player.friends.insert(friend);
+                players.get_mut(&friend).unwrap().friends.insert(id);
+                return Ok(true)
+            }
+            players.get_mut(&friend).unwrap().requests.insert(id);
+            Ok(false)
+        })
+    }
+
+    /// End a friendship, or turn down a friend request.
+    pub fn remove(&self, secret: Uuid, friend: Uuid) -> Result<(), &'static str> {
+        self.with_player(secret, |players, id| {
+            let player = players.get_mut(&id).unwrap();
+            player.friends.remove(&friend);
+            player.requests.remove(&friend);
+            if let Some(other) = players.get_mut(&friend) {
+                other.friends.remove(&id);
+                other.requests.remove(&id);
+            }
+            Ok(())
+        })
+    }
+
+    /// Queue an invitation to a game for a friend, returning it and the friend's secret so it can also be delivered right away.
+    pub fn invite(&self, secret: Uuid, friend: Uuid, name: String, game_id: Uuid) -> Result<(FriendInvite, Uuid), &'static str> {
+        self.with_player(secret, |players, id| {
+            if !players[&id].friends.contains(&friend) {
+                return Err("You can only invite your friends.")
+            }
+            let invite = FriendInvite { id: Uuid::new_v4(), from: id, name, game_id };
+            let other = players.get_mut(&friend).unwrap();
+            let invites = &mut other.invites;
+            invites.retain(|pending| pending.from != id || pending.game_id != game_id);
+            if invites.len() >= MAX_PENDING_INVITES {
+                invites.pop_front();
+            }
+            invites.push_back(invite.clone());
+            Ok((invite, other.secret))
+        })
+    }
+
+    /// Answer an invitation, returning it and the secret of the player who sent it.
+    pub fn respond(&self, secret: Uuid, invite_id: Uuid) -> Result<(FriendInvite, Uuid), &'static str> {
+        self.with_player(secret, |players, id| {
+            let invites = &mut players.get_mut(&id).unwrap().invites;
+            let index = invites.iter().position(|invite| invite.id == invite_id).ok_or("That invitation has expired.")?;
+            let invite = invites.remove(index).unwrap();
+            let from = players.get(&invite.from).map(|player| player.secret).ok_or("That invitation has expired.")?;
+            Ok((invite, from))
+        })
+    }
+}
@@ -0,0 +1,243 @@
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use async_graphql::{Context, EmptyMutation, Error, ID, Json, Object, Schema, SimpleObject, Subscription, http::{WebSocket, WebSocketProtocols, WsMessage}};
+use futures::{SinkExt, Stream, StreamExt, future, stream};
+use secrethitler_core::game_state::{CardColor, GameState, PlayerType, TimelineEntry, TurnPhase};
+use uuid::Uuid;
+use warp::{Filter, Rejection, Reply, ws::Message};
+
+use crate::{replays::ReplayCache, server::{GlobalState, all_games, get_game}};
+
+/// How often subscriptions check their game for new events.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub type GameSchema = Schema<Query, EmptyMutation, Subscription>;
+
+/// Build the schema, which reads from the same games as the websocket protocol, and replays of games that have since been cleaned up.
+pub fn schema(games: GlobalState, replays: Arc<ReplayCache>) -> GameSchema {
+    Schema::build(Query, EmptyMutation, Subscription).data(games).data(replays).finish()
+}
+
+/// The public view of a game, showing nothing that only some of its players may know.
+#[derive(SimpleObject)]
+pub struct Game {
+    id: ID,
+    host: Option<String>,
+    players: Vec<String>,
+    max_players: usize,
+    /// The turn phase, such as Lobby, Voting, or Ended.
+    phase: String,
+    liberal_policies: u8,
+    facist_policies: u8,
+    election_tracker: u8,
+    president: Option<String>,
+    chancellor: Option<String>,
+    winner: Option<String>,
+    rounds: usize,
+}
+
+impl Game {
+    fn new(id: Uuid, state: &GameState) -> Game {
+        let summary = state.summary();
+        let scoreboard = state.scoreboard();
+        let name = |player: Option<Uuid>| player.and_then(|p| state.conn.get(&p)).and_then(|c| c.name.clone());
+        Game {
+            id: ID(id.to_string()),
+            host: summary.host,
+            players: summary.players,
+            max_players: state.max_players(),
+            phase: serde_json::to_value(state.turn_phase()).unwrap()["type"].as_str().unwrap_or_default().to_string(),
+            liberal_policies: scoreboard.liberal_policies,
+            facist_policies: scoreboard.facist_policies,
+            election_tracker: scoreboard.election_tracker,
+            president: name(state.president()),
+            chancellor: name(state.chancellor()),
+            winner: summary.winner.map(|w| w.to_string()),
+            rounds: state.rounds(),
+        }
+    }
+}
+
+/// Something that happened in a game, from its timeline.
+#[derive(SimpleObject)]
+pub struct GameEvent {
+    /// When the event happened, in milliseconds since the epoch.
+    at: u64,
+    /// The kind of event, such as PolicyEnacted.
+    kind: String,
+    /// A sentence describing the event.
+    description: String,
+    /// The event as it is sent over the websocket protocol.
+    data: Json<serde_json::Value>,
+}
+
+impl From<&TimelineEntry> for GameEvent {
+    fn from(entry: &TimelineEntry) -> GameEvent {
+        let data = serde_json::to_value(&entry.event).unwrap();
+        GameEvent {
+            at: entry.at,
+            kind: data["type"].as_str().unwrap_or_default().to_string(),
+            description: entry.event.to_string(),
+            data: Json(data),
+        }
+    }
+}
+
+/// How a player has done in the finished games the server still holds.
+#[derive(SimpleObject)]
+pub struct PlayerStats {
+    name: String,
+    games: usize,
+    wins: usize,
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// Games waiting for players to join, not including practice games.
+    async fn lobbies(&self, ctx: &Context<'_>) -> Vec<Game> {
+        let games = all_games(ctx.data_unchecked::<GlobalState>());
+        let mut lobbies: Vec<Game> = games.iter().filter_map(|(id, game)| {
+            let state = game.lock();
+            (matches!(state.turn_phase(), TurnPhase::Lobby) && !state.is_practice()).then(|| Game::new(*id, &state))
+        }).collect();
+        lobbies.sort_by(|a, b| a.id.cmp(&b.id));
+        lobbies
+    }
+
+    async fn game(&self, ctx: &Context<'_>, id: ID) -> Option<Game> {
+        let id = Uuid::from_str(&id).ok()?;
+        let game = get_game(ctx.data_unchecked::<GlobalState>(), &id)?;
+        let state = game.lock();
+        Some(Game::new(id, &state))
+    }
+
+    /// The timeline of a finished game. Games still being played have no replay, since their timeline could help the players.
+    async fn replay(&self, ctx: &Context<'_>, id: ID) -> Option<Vec<GameEvent>> {
+        let id = Uuid::from_str(&id).ok()?;
+        let game = get_game(ctx.data_unchecked::<GlobalState>(), &id);
+        let game = match game {
+            Some(game) => game,
+            None => return Some(ctx.data_unchecked::<Arc<ReplayCache>>().get(id)?.iter().map(GameEvent::from).collect())
+        };
+        let state = game.lock();
+        state.winner()?;
+        Some(state.timeline().iter().map(GameEvent::from).collect())
+    }
+
+    /// Games played and won by each player name, over finished games the server still holds.
+    async fn player_stats(&self, ctx: &Context<'_>, name: Option<String>) -> Vec<PlayerStats> {
+        let mut stats: HashMap<String, PlayerStats> = HashMap::new();
+        for (_, game) in all_games(ctx.data_unchecked::<GlobalState>()) {
+            let state = game.lock();
+            let winner = match state.winner() {
+                Some(winner) => winner,
+                None => continue
+            };
+            for (player, conn) in state.conn.iter() {
+                let (role, player_name) = match (state.role(player), &conn.name) {
+                    (Some(role), Some(player_name)) => (role, player_name),
+                    _ => continue
+                };
+                if name.as_ref().is_some_and(|name| name != player_name) {
+                    continue
+                }
+                let party = match role {
+                    PlayerType::Liberal => CardColor::Liberal,
+                    PlayerType::Facist | PlayerType::Hitler => CardColor::Facist
+                };
+                let entry = stats.entry(player_name.clone()).or_insert_with(|| PlayerStats { name: player_name.clone(), games: 0, wins: 0 });
+                entry.games += 1;
+                if party == winner {
+                    entry.wins += 1;
+                }
+            }
+        }
+        let mut stats: Vec<PlayerStats> = stats.into_values().collect();
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+        stats
+    }
+}
+
+pub struct Subscription;
+
+#[Subscription]
+impl Subscription {
+    /// Events in a game from the time of subscribing, ending when the game does.
+    /// Games with a spectator delay send each event once the delay has passed.
+    async fn game_events(&self, ctx: &Context<'_>, id: ID) -> Result<impl Stream<Item = GameEvent>, Error> {
+        let games = ctx.data_unchecked::<GlobalState>().clone();
+        let id = Uuid::from_str(&id).map_err(|_| Error::new("invalid game id"))?;
+        let seen = match get_game(&games, &id) {
+            Some(game) => game.lock().timeline().len(),
+            None => return Err(Error::new("game not found"))
+        };
+        let interval = tokio::time::interval(POLL_INTERVAL);
+        let events = stream::unfold((games, seen, interval), move |(games, mut seen, mut interval)| async move {
+            loop {
+                interval.tick().await;
+                let game = get_game(&games, &id)?;
+                let (events, ended) = {
+                    let state = game.lock();
+                    let delay = state.options.spectator_delay.unwrap_or(0) * 1000;
+                    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+                    let events: Vec<GameEvent> = state.timeline()[seen..].iter().take_while(|entry| entry.at + delay <= now).map(GameEvent::from).collect();
+                    let ended = state.winner().is_some() && seen + events.len() == state.timeline().len();
+                    (events, ended)
+                };
+                if !events.is_empty() {
+                    seen += events.len();
+                    return Some((events, (games, seen, interval)))
+                }
+                if ended {
+                    return None
+                }
+            }
+        });
+        Ok(events.flat_map(stream::iter))
+    }
+}
+
+/// Answer queries posted to `/graphql`, and subscriptions over a websocket at `/graphql/ws`.
+pub fn route(schema: GameSchema) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone {
+    let query_schema = schema.clone();
+    let query = warp::path!("graphql")
+        .and(warp::post())
+        .and(warp::body::content_length_limit(64 * 1024))
+        .and(warp::body::json())
+        .and_then(move |request: async_graphql::Request| {
+            let schema = query_schema.clone();
+            async move {
+                Ok::<Box<dyn Reply>, Rejection>(Box::new(warp::reply::json(&schema.execute(request).await)))
+            }
+        });
+
+    let subscribe = warp::path!("graphql" / "ws")
+        .and(warp::ws())
+        .and(warp::header::optional::<String>("sec-websocket-protocol"))
+        .map(move |ws: warp::ws::Ws, protocols: Option<String>| {
+            let protocol = protocols.unwrap_or_default().split(',').find_map(|p| WebSocketProtocols::from_str(p.trim()).ok()).unwrap_or(WebSocketProtocols::GraphQLWS);
+            let schema = schema.clone();
+            let reply = ws.on_upgrade(move |socket| async move {
+                let (mut tx, rx) = socket.split();
+                let input = rx.take_while(|msg| future::ready(msg.is_ok())).filter_map(|msg| {
+                    let msg = msg.unwrap();
+                    future::ready((msg.is_text() || msg.is_binary()).then(|| msg.into_bytes()))
+                });
+                let mut output = WebSocket::new(schema, input, protocol);
+                while let Some(msg) = output.next().await {
+                    let msg = match msg {
+                        WsMessage::Text(text) => Message::text(text),
+                        WsMessage::Close(code, reason) => Message::close_with(code, reason)
+                    };
+                    if tx.send(msg).await.is_err() {
+                        break
+                    }
+                }
+            });
+            Box::new(warp::reply::with_header(reply, "sec-websocket-protocol", protocol.sec_websocket_protocol())) as Box<dyn Reply>
+        });
+
+    query.or(subscribe).unify()
+}
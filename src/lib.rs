@@ -1,2 +1,35 @@
-pub mod game_state;
-pub mod protocol;
\ No newline at end of file
+pub mod achievements;
+pub mod addresses;
+pub mod archive;
+pub mod audit;
+pub mod bans;
+pub mod blocks;
+#[cfg(any(feature = "discord", feature = "telegram"))]
+pub mod bridge;
+pub mod calendar;
+pub mod captcha;
+pub mod communities;
+pub mod cors;
+pub mod dashboard;
+#[cfg(feature = "discord")]
+pub mod discord;
+pub mod dispatch;
+pub mod email;
+pub mod friends;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod limits;
+pub mod loadtest;
+pub mod presets;
+pub mod ratings;
+pub mod replays;
+pub mod server;
+pub mod snapshots;
+pub mod sse;
+#[cfg(feature = "telegram")]
+pub mod telegram;
+pub mod tokens;
+pub mod typescript;
+pub mod uploads;
+pub mod watchdog;
+pub mod webhooks;
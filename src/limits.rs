@@ -0,0 +1,149 @@
+use std::{collections::HashMap, sync::{Arc, atomic::{AtomicUsize, Ordering}}, time::{Duration, Instant}};
+
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::Serialize;
+use uuid::Uuid;
+
+use secrethitler_core::protocol::ServerProtocol;
+
+/// How many times a player can return to their seat within `RECONNECT_WINDOW` before being asked to wait.
+const RECONNECT_BURST: u32 = 5;
+
+const RECONNECT_WINDOW: Duration = Duration::from_secs(10);
+
+/// Players tracked for reconnects before the ones outside the window are forgotten.
+const MAX_TRACKED_RECONNECTS: usize = 10_000;
+
+/// Global caps on how much work the server accepts, so it can turn players away instead of falling over.
+pub struct ServerLimits {
+    max_games: Option<usize>,
+    max_sockets: Option<usize>,
+    retry_after: Duration,
+    sockets: AtomicUsize,
+    /// When each player's current reconnect window started, and how many times they have reconnected in it.
+    reconnects: Mutex<HashMap<Uuid, (Instant, u32)>>,
+    /// How many messages a second each connection may send, and how many it may send at once, if limited.
+    message_rate: Option<(f64, u32)>,
+}
+
+/// Counts an open websocket until it is dropped.
+pub struct SocketGuard {
+    limits: Arc<ServerLimits>,
+}
+
+impl Drop for SocketGuard {
+    fn drop(&mut self) {
+        self.limits.sockets.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Current load, reported on the health check.
+#[derive(Serialize)]
+pub struct ServerLoad {
+    games: usize,
+    sockets: usize,
+    max_games: Option<usize>,
+    max_sockets: Option<usize>,
+    busy: bool,
+}
+
+impl ServerLimits {
+    pub fn new(max_games: Option<usize>, max_sockets: Option<usize>, retry_after: Duration) -> ServerLimits {
+        ServerLimits { max_games, max_sockets, retry_after, sockets: AtomicUsize::new(0), reconnects: Mutex::default(), message_rate: None }
+    }
+
+    /// Limit each connection to `per_second` messages a second on average, with bursts of up to `burst`.
+    pub fn with_message_rate(mut self, per_second: Option<f64>, burst: u32) -> ServerLimits {
+        self.message_rate = per_second.filter(|rate| *rate > 0.0).map(|rate| (rate, burst.max(1)));
+        self
+    }
+
+    /// A rate limiter for a new connection, if messages are limited.
+    pub fn message_limiter(&self) -> Option<MessageRateLimiter> {
+        self.message_rate.map(|(per_second, burst)| MessageRateLimiter::new(per_second, burst))
+    }
+
+    /// Register a new websocket, which stays counted until the guard is dropped.
+    pub fn connect(self: &Arc<Self>) -> SocketGuard {
+        self.sockets.fetch_add(1, Ordering::SeqCst);
+        SocketGuard { limits: self.clone() }
+    }
+
+    /// Whether a new player can be let in. The count includes the socket asking.
+    pub fn can_join(&self) -> bool {
+        self.max_sockets.is_none_or(|max| self.sockets.load(Ordering::SeqCst) <= max)
+    }
+
+    /// Whether a new game can be created on top of the games that already exist.
+    pub fn can_host(&self, games: usize) -> bool {
+        self.can_join() && self.max_games.is_none_or(|max| games < max)
+    }
+
+    /// The message sent to players who are turned away.
+    pub fn busy(&self) -> ServerProtocol<'static> {
+        ServerProtocol::ServerBusy { retry_after: self.retry_after.as_secs(), retry_after_ms: jitter(self.retry_after) }
+    }
+
+    /// Count a player returning to their seat. Players who keep reconnecting are told how long to wait instead, so that a whole game coming back at once after a restart is spread out.
+    pub fn reconnect(&self, player_id: Uuid, now: Instant) -> Result<(), ServerProtocol<'static>> {
+        let mut reconnects = self.reconnects.lock();
+        if reconnects.len() >= MAX_TRACKED_RECONNECTS {
+            reconnects.retain(|_, (started, _)| now.duration_since(*started) < RECONNECT_WINDOW);
+        }
+        let (started, count) = reconnects.entry(player_id).or_insert((now, 0));
+        if now.duration_since(*started) >= RECONNECT_WINDOW {
+            *started = now;
+            *count = 0;
+        }
+        *count += 1;
+        if *count > RECONNECT_BURST {
+            let wait = (*started + RECONNECT_WINDOW).saturating_duration_since(now);
+            return Err(ServerProtocol::ReconnectThrottled { retry_after_ms: jitter(wait) })
+        }
+        Ok(())
+    }
+
+    pub fn load(&self, games: usize) -> ServerLoad {
+        let sockets = self.sockets.load(Ordering::SeqCst);
+        ServerLoad {
+            games,
+            sockets,
+            max_games: self.max_games,
+            max_sockets: self.max_sockets,
+            busy: self.max_games.is_some_and(|max| games >= max) || self.max_sockets.is_some_and(|max| sockets >= max),
+        }
+    }
+}
+
+/// How fast one connection may send messages, as a bucket holding up to `burst` messages that refills at `per_second`.
+pub struct MessageRateLimiter {
+    per_second: f64,
+    burst: f64,
+    available: f64,
+    refilled_at: Instant,
+}
+
+impl MessageRateLimiter {
+    pub fn new(per_second: f64, burst: u32) -> MessageRateLimiter {
+        MessageRateLimiter { per_second, burst: burst as f64, available: burst as f64, refilled_at: Instant::now() }
+    }
+
+    /// Take a message out of the bucket, or say how long until there is room for one.
+    pub fn check(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.available = (self.available + elapsed * self.per_second).min(self.burst);
+        self.refilled_at = now;
+        if self.available < 1.0 {
+            return Err(Duration::from_secs_f64((1.0 - self.available) / self.per_second))
+        }
+        self.available -= 1.0;
+        Ok(())
+    }
+}
+
+/// A wait of at least `base` and up to half again as long, so clients told to wait the same time do not all come back at once.
+fn jitter(base: Duration) -> u64 {
+    let base = base.as_millis() as u64;
+    base + rand::thread_rng().gen_range(0..=base / 2)
+}
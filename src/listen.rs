@@ -0,0 +1,107 @@
+use std::{fmt, io, net::SocketAddr, os::unix::io::{FromRawFd, IntoRawFd, RawFd}, path::PathBuf, str::FromStr};
+
+use tokio::net::{TcpListener, UnixListener};
+
+/// File descriptor of the first socket passed in by systemd.
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// An address the server accepts connections on.
+#[derive(Clone, Debug)]
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    /// A unix domain socket, usually for a reverse proxy on the same machine.
+    Unix(PathBuf),
+}
+
+impl FromStr for ListenAddr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("unix:") {
+            Some(path) if !path.is_empty() => Ok(ListenAddr::Unix(path.into())),
+            Some(_) => Err("missing unix socket path".into()),
+            None => s.parse().map(ListenAddr::Tcp).map_err(|_| format!("invalid listen address: {}", s))
+        }
+    }
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddr::Tcp(addr) => write!(f, "{}", addr),
+            ListenAddr::Unix(path) => write!(f, "unix:{}", path.display())
+        }
+    }
+}
+
+/// A bound socket, ready to be served.
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+impl Listener {
+    /// A description of where the socket is listening, for logging.
+    pub fn describe(&self) -> String {
+        let addr = match self {
+            Listener::Tcp(listener) => listener.local_addr().map(|addr| addr.to_string()),
+            Listener::Unix(listener) => listener.local_addr().map(|addr| match addr.as_pathname() {
+                Some(path) => format!("unix:{}", path.display()),
+                None => "unix socket".into()
+            })
+        };
+        addr.unwrap_or_else(|_| "unknown address".into())
+    }
+}
+
+/// Bind every configured address, along with any sockets handed over by systemd socket activation.
+/// If neither gives anything to listen on, listen on every IPv4 interface on the given port.
+pub async fn bind_all(addrs: &[ListenAddr], port: u16) -> io::Result<Vec<Listener>> {
+    let mut listeners = activated_sockets()?;
+    let default = [ListenAddr::Tcp(SocketAddr::from(([0, 0, 0, 0], port)))];
+    let addrs = if addrs.is_empty() && listeners.is_empty() { &default[..] } else { addrs };
+    for addr in addrs {
+        let listener = bind(addr).await.map_err(|e| io::Error::new(e.kind(), format!("could not listen on {}: {}", addr, e)))?;
+        listeners.push(listener);
+    }
+    Ok(listeners)
+}
+
+async fn bind(addr: &ListenAddr) -> io::Result<Listener> {
+    match addr {
+        ListenAddr::Tcp(addr) => Ok(Listener::Tcp(TcpListener::bind(addr).await?)),
+        ListenAddr::Unix(path) => {
+            // a socket file left over from a previous run would make the bind fail
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+            Ok(Listener::Unix(UnixListener::bind(path)?))
+        }
+    }
+}
+
+/// Take the sockets passed in by systemd, following the sd_listen_fds protocol.
+fn activated_sockets() -> io::Result<Vec<Listener>> {
+    let for_us = std::env::var("LISTEN_PID").ok().and_then(|pid| pid.parse::<u32>().ok()) == Some(std::process::id());
+    let count = std::env::var("LISTEN_FDS").ok().and_then(|fds| fds.parse::<RawFd>().ok()).unwrap_or(0);
+    // child processes should not try to take the same sockets
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+    if !for_us {
+        return Ok(vec![])
+    }
+
+    (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + count).map(|fd| {
+        // systemd owns nothing once it has passed the socket on, so this process is the only owner of the descriptor
+        let listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+        if listener.local_addr().is_ok() {
+            listener.set_nonblocking(true)?;
+            return Ok(Listener::Tcp(TcpListener::from_std(listener)?))
+        }
+        // only inet sockets have a socket address, so anything else should be a unix socket
+        let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(listener.into_raw_fd()) };
+        listener.set_nonblocking(true)?;
+        Ok(Listener::Unix(UnixListener::from_std(listener)?))
+    }).collect()
+}
@@ -0,0 +1,278 @@
+use std::{collections::BTreeMap, sync::Arc, time::{Duration, Instant}};
+
+use futures::{SinkExt, StreamExt, future};
+use parking_lot::Mutex;
+use rand::{Rng, rngs::StdRng, SeedableRng, seq::SliceRandom};
+use secrethitler_core::{game_state::{GameOptions, PresidentialPower, TurnPhase}, protocol::ClientProtocol, schema::PlayerView};
+use tokio::{net::TcpStream, time};
+use tokio_tungstenite::{WebSocketStream, connect_async, tungstenite::Message};
+use uuid::Uuid;
+
+/// A client gives up on its game after this many of its actions in a row are rejected.
+const MAX_ERRORS_IN_A_ROW: usize = 10;
+
+/// How a load test is run.
+pub struct LoadTest {
+    /// The websocket endpoint of the server under test, such as `ws://localhost:8000/ws`.
+    pub url: String,
+    pub games: usize,
+    pub players: usize,
+    /// Games are started evenly over this long, instead of all at once.
+    pub ramp_up: Duration,
+    /// How long a game may take before it counts as failed.
+    pub timeout: Duration,
+}
+
+/// What happened over a load test.
+#[derive(Default)]
+pub struct LoadTestReport {
+    /// How long each kind of action took to be answered, in the order they were answered.
+    pub latencies: BTreeMap<&'static str, Vec<Duration>>,
+    /// Actions rejected by the server, by kind of action.
+    pub rejected: BTreeMap<&'static str, usize>,
+    /// Errors sent by the server, by code.
+    pub errors: BTreeMap<String, usize>,
+    pub connect_failures: usize,
+    pub games_finished: usize,
+    /// Games that timed out, lost a connection, or could not be set up, with the first reason each one failed.
+    pub games_failed: Vec<String>,
+    pub elapsed: Duration,
+}
+
+impl LoadTestReport {
+    /// The share of actions the server rejected.
+    pub fn error_rate(&self) -> f64 {
+        let answered: usize = self.latencies.values().map(Vec::len).sum();
+        if answered == 0 {
+            return 0.0
+        }
+        self.rejected.values().sum::<usize>() as f64 / answered as f64
+    }
+}
+
+/// The latency below which `percent` of the samples fall.
+pub fn percentile(sorted: &[Duration], percent: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO
+    }
+    sorted[((sorted.len() - 1) as f64 * percent / 100.0).round() as usize]
+}
+
+/// Only plain `ws://` is supported, so the test measures the server rather than TLS.
+type Socket = WebSocketStream<TcpStream>;
+
+/// Play `test.games` games against the server at once, each between `test.players` clients with their own websocket, and time every action.
+pub async fn run(test: &LoadTest) -> LoadTestReport {
+    let report = Arc::new(Mutex::new(LoadTestReport::default()));
+    let started = Instant::now();
+    let spacing = test.ramp_up / test.games.max(1) as u32;
+    let games = (0..test.games).map(|game| {
+        let report = report.clone();
+        async move {
+            time::sleep(spacing * game as u32).await;
+            let result = match time::timeout(test.timeout, play_game(test, game, &report)).await {
+                Ok(result) => result,
+                Err(_) => Err(format!("did not end within {}s", test.timeout.as_secs()))
+            };
+            let mut report = report.lock();
+            match result {
+                Ok(()) => report.games_finished += 1,
+                Err(e) => report.games_failed.push(format!("game {}: {}", game + 1, e))
+            }
+        }
+    });
+    future::join_all(games).await;
+
+    let mut report = Arc::try_unwrap(report).ok().unwrap().into_inner();
+    report.elapsed = started.elapsed();
+    for latencies in report.latencies.values_mut() {
+        latencies.sort();
+    }
+    report
+}
+
+async fn play_game(test: &LoadTest, game: usize, report: &Arc<Mutex<LoadTestReport>>) -> Result<(), String> {
+    let mut clients = vec![];
+    let mut game_id = None;
+    for seat in 0..test.players {
+        let mut client = Client::connect(test, report.clone()).await?;
+        let nickname = format!("load {}-{}", game + 1, seat + 1);
+        let message = match game_id {
+            Some(id) => ClientProtocol::JoinGame { id, nickname, player_id: None, player_secret: None, resume_token: None, avatar: None, color: None },
+            None => ClientProtocol::HostGame { nickname, options: GameOptions::default(), avatar: None, color: None, player_secret: None, preset: None, captcha: None }
+        };
+        client.send(&message).await?;
+        game_id = Some(client.identify().await?);
+        clients.push(client);
+    }
+    future::try_join_all(clients.into_iter().map(Client::play)).await.map(drop)
+}
+
+/// One simulated player, who makes a legal move whenever the game is waiting on them.
+struct Client {
+    ws: Socket,
+    player: Uuid,
+    table: usize,
+    report: Arc<Mutex<LoadTestReport>>,
+    rng: StdRng,
+    next_request: u64,
+    /// The action waiting on an answer, with its request id and when it was sent.
+    pending: Option<(String, &'static str, Instant)>,
+}
+
+impl Client {
+    async fn connect(test: &LoadTest, report: Arc<Mutex<LoadTestReport>>) -> Result<Client, String> {
+        let ws = match connect_async(test.url.as_str()).await {
+            Ok((ws, _)) => ws,
+            Err(e) => {
+                report.lock().connect_failures += 1;
+                return Err(format!("could not connect: {}", e))
+            }
+        };
+        Ok(Client { ws, player: Uuid::nil(), table: test.players, report, rng: StdRng::from_entropy(), next_request: 0, pending: None })
+    }
+
+    async fn send(&mut self, message: &ClientProtocol) -> Result<(), String> {
+        self.ws.send(Message::Text(serde_json::to_string(message).unwrap())).await.map_err(|e| format!("could not send: {}", e))
+    }
+
+    /// The next message from the server, or none once the connection is closed.
+    async fn receive(&mut self) -> Result<Option<serde_json::Value>, String> {
+        while let Some(message) = self.ws.next().await {
+            match message.map_err(|e| format!("connection failed: {}", e))? {
+                Message::Text(text) => return serde_json::from_str(&text).map(Some).map_err(|e| format!("invalid message from the server: {}", e)),
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    /// Wait to be seated after hosting or joining, returning the game.
+    async fn identify(&mut self) -> Result<Uuid, String> {
+        while let Some(message) = self.receive().await? {
+            match message["type"].as_str() {
+                Some("SetIdentifiers") => {
+                    self.player = serde_json::from_value(message["player_id"].clone()).map_err(|e| e.to_string())?;
+                    return serde_json::from_value(message["game_id"].clone()).map_err(|e| e.to_string())
+                },
+                Some("Alert") | Some("ServerBusy") => return Err(format!("not seated: {}", message)),
+                _ => {}
+            }
+        }
+        Err("connection closed before being seated".into())
+    }
+
+    /// Play until the game ends, acting on each new view of the game that is waiting on this player.
+    async fn play(mut self) -> Result<(), String> {
+        let mut view: Option<PlayerView> = None;
+        // whether the view has changed since the last action was sent
+        let mut fresh = false;
+        let mut errors_in_a_row = 0;
+        while let Some(message) = self.receive().await? {
+            match message["type"].as_str() {
+                Some("GameState") => {
+                    view = Some(serde_json::from_value(message["state"].clone()).map_err(|e| format!("invalid game state: {}", e))?);
+                    fresh = true;
+                },
+                Some("Ack") => {
+                    self.answered(&message, None);
+                    errors_in_a_row = 0;
+                },
+                Some("Error") => {
+                    self.answered(&message, message["code"].as_str());
+                    errors_in_a_row += 1;
+                    if errors_in_a_row >= MAX_ERRORS_IN_A_ROW {
+                        return Err(format!("gave up after {} errors in a row, the last being {}", errors_in_a_row, message["code"]))
+                    }
+                    // try again, with another choice if there was one
+                    fresh = true;
+                },
+                _ => {}
+            }
+
+            let view = match &view {
+                Some(view) => view,
+                None => continue
+            };
+            if self.pending.is_some() {
+                continue
+            }
+            if matches!(view.turn_phase, TurnPhase::Ended { .. }) {
+                return Ok(())
+            }
+            if !fresh {
+                continue
+            }
+            let request_id = self.next_request.to_string();
+            if let Some((kind, message)) = self.next_move(view, Some(request_id.clone())) {
+                self.next_request += 1;
+                self.pending = Some((request_id, kind, Instant::now()));
+                self.send(&message).await?;
+                fresh = false;
+            }
+        }
+        Err("connection closed before the game ended".into())
+    }
+
+    /// Record the answer to an action, timing it if it was the one waiting.
+    fn answered(&mut self, message: &serde_json::Value, error: Option<&str>) {
+        let mut report = self.report.lock();
+        if let Some(code) = error {
+            *report.errors.entry(code.to_string()).or_default() += 1;
+        }
+        let matches_pending = self.pending.as_ref().is_some_and(|(request_id, _, _)| message["request_id"] == request_id.as_str());
+        if !matches_pending {
+            return
+        }
+        if let Some((_, kind, sent)) = self.pending.take() {
+            report.latencies.entry(kind).or_default().push(sent.elapsed());
+            if error.is_some() {
+                *report.rejected.entry(kind).or_default() += 1;
+            }
+        }
+    }
+
+    /// The move to make in the game as it is, if it is waiting on this player.
+    fn next_move(&mut self, view: &PlayerView, request_id: Option<String>) -> Option<(&'static str, ClientProtocol)> {
+        let me = self.player;
+        let seat = view.players.get(&me)?;
+        let is_president = view.president == Some(me);
+        let is_chancellor = view.chancellor == Some(me);
+        let living: Vec<Uuid> = view.turn_order.iter().filter(|p| view.players.get(p).is_some_and(|s| !s.dead) && **p != me).copied().collect();
+
+        match &view.turn_phase {
+            TurnPhase::Lobby if view.host == Some(me) && view.players.len() == self.table => {
+                Some(("StartGame", ClientProtocol::StartGame { request_id }))
+            },
+            TurnPhase::Electing if is_president => {
+                // keep clear of term limits when there is someone else to pick
+                let eligible: Vec<Uuid> = living.iter().filter(|p| Some(**p) != view.last_chancellor && Some(**p) != view.last_president).copied().collect();
+                let player = *eligible.choose(&mut self.rng).or_else(|| living.choose(&mut self.rng))?;
+                Some(("ChooseChancellor", ClientProtocol::ChooseChancellor { player, request_id }))
+            },
+            TurnPhase::Discussion if (is_president || is_chancellor) && !view.vote_called.as_ref().is_some_and(|called| called.contains(&me)) => {
+                Some(("CallVote", ClientProtocol::CallVote { request_id }))
+            },
+            TurnPhase::Voting if !seat.dead && seat.vote.is_none() => {
+                Some(("VoteChancellor", ClientProtocol::VoteChancellor { vote: self.rng.gen_bool(0.7), request_id }))
+            },
+            TurnPhase::PresidentSelect if is_president => self.pick_card(view, request_id),
+            TurnPhase::ChancellorSelect if is_chancellor => self.pick_card(view, request_id),
+            TurnPhase::PresidentialPower { power } if is_president => {
+                let player = match power {
+                    PresidentialPower::PolicyPeek => None,
+                    _ => Some(*living.choose(&mut self.rng)?)
+                };
+                Some(("PresidentialPower", ClientProtocol::PresidentialPower { player, request_id }))
+            },
+            _ => None
+        }
+    }
+
+    /// Discard or enact any policy in hand.
+    fn pick_card(&mut self, view: &PlayerView, request_id: Option<String>) -> Option<(&'static str, ClientProtocol)> {
+        let cards = view.cards.as_ref().filter(|cards| !cards.is_empty())?;
+        Some(("PickCard", ClientProtocol::PickCard { index: self.rng.gen_range(0..cards.len()), request_id }))
+    }
+}
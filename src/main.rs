@@ -1,46 +1,153 @@
-use std::{collections::HashMap, sync::{Arc, RwLock}, time::{Duration, SystemTime}};
+use std::{net::IpAddr, sync::Arc, time::{Duration, SystemTime}};
 
-use game_state::{CardColor, GameState, ChatLine};
-use protocol::{ClientProtocol, PlayerConnection, ServerProtocol};
+use config::ServerConfig;
+use futures::{FutureExt, StreamExt, future};
+use listen::Listener;
+use secrethitler::{achievements::Achievements, archive::GameArchive, addresses::{self, AddressPolicy}, audit::AuditLog, bans::{BanList, Visitor}, blocks::BlockList, captcha::HostGate, communities::{self, Communities}, cors::{self, CorsPolicy}, email::EmailDispatcher, friends::Friends, limits::ServerLimits, presets::Presets, replays::ReplayCache, server::{ActivePlayers, ConnectionContext, GlobalState, MAX_MESSAGE_BYTES, ServerState, fingerprint, get_game, Frame, handle_connect, handle_disconnect, handle_message, read_frame}, snapshots::Snapshots, tokens::ResumeTokens, watchdog::Watchdog, webhooks::WebhookDispatcher};
+use secrethitler_core::{analysis::Analysis, chat::LinkPolicy, protocol::ClientProtocol, schema};
 use tokio::{sync::mpsc, time};
-use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::wrappers::{TcpListenerStream, UnboundedReceiverStream, UnixListenerStream};
 use uuid::Uuid;
-use warp::{Filter, ws::{WebSocket}};
-use futures::{FutureExt, StreamExt};
+use warp::{Filter, filters::BoxedFilter, http::StatusCode, ws::{Message, WebSocket}};
 
-mod protocol;
-mod game_state;
-
-type GlobalState = Arc<RwLock<HashMap<uuid::Uuid, Arc<RwLock<GameState>>>>>;
-
-fn cleanup_global_state(state: &GlobalState) {
-    let threshold = SystemTime::now() - Duration::from_secs(5 * 60);
-    state.write().unwrap().retain(|_, map| {
-        let data = map.read().unwrap();
-        if let Some(timeout) = data.timeout {
-            if timeout < threshold {
-                if !data.conn.values().any(|val| val.connected) {
-                    return false
-                }
-            }
-        }
-        true
-    });
-}
+mod assets;
+mod cli;
+mod config;
+mod listen;
 
 #[tokio::main]
 async fn main() {
-    let orig_global_state = GlobalState::default();
-    let state_ref = orig_global_state.clone();
-    let global_state = warp::any().map(move || orig_global_state.clone());
+    let args = cli::command().get_matches();
+    let result = match args.subcommand() {
+        Some(("simulate", args)) => cli::simulate(args),
+        Some(("loadtest", args)) => cli::loadtest(args).await,
+        Some(("replay", args)) => cli::replay(args),
+        Some(("generate-types", args)) => cli::generate_types(args),
+        Some(("serve", args)) => serve(args.get_flag("resume")).await,
+        _ => serve(args.get_flag("resume")).await,
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
 
-    let ws_route = warp::path("ws").and(warp::ws()).and(global_state).map(|ws: warp::ws::Ws, state: GlobalState| {
-        ws.on_upgrade(|socket| ws_connect(socket, state))
+async fn serve(resume: bool) -> Result<(), String> {
+    let config = ServerConfig::from_env();
+    let server = ServerState {
+        games: GlobalState::default(),
+        webhooks: WebhookDispatcher::start(config.webhook_urls.clone(), config.public_url.clone()),
+        email: EmailDispatcher::start(config.email.clone(), config.public_url.clone()),
+        tokens: Arc::new(ResumeTokens::new(config.resume_token_keys.clone(), config.resume_token_ttl)),
+        limits: Arc::new(ServerLimits::new(config.max_games, config.max_sockets, config.busy_retry_after).with_message_rate(config.message_rate, config.message_burst)),
+        audit: Arc::new(AuditLog::default()),
+        presets: Arc::new(Presets::default()),
+        friends: Arc::new(Friends::default()),
+        replays: Arc::new(ReplayCache::new(config.replay_cache_size, config.replay_dir.clone())),
+        archive: Arc::new(GameArchive::load(config.history_file.clone())),
+        ratings: Arc::default(),
+        bans: Arc::new(BanList::load(config.ban_file.clone())),
+        addresses: Arc::new(AddressPolicy::new(config.trusted_proxies.as_deref(), &config.allowed_addresses, &config.denied_addresses)?),
+        host_gate: Arc::new(HostGate::new(config.host_check.clone())),
+        achievements: Arc::new(Achievements::new(config.season_length)),
+        watchdog: Arc::new(Watchdog::new(config.stuck_game_timeout, config.stuck_game_policy)),
+        active_players: ActivePlayers::default(),
+        motd: Arc::default(),
+        communities: Arc::new(Communities::new(&config.communities)?.with_owners(&config.community_owners)?.with_moderator_file(config.moderator_file.clone())),
+        allow_multiple_games: config.allow_multiple_games,
+        chat_links: Arc::new(config.chat_link_hosts.clone().map_or_else(LinkPolicy::any, LinkPolicy::only)),
+        uploads: Arc::default(),
+        blocks: Arc::new(BlockList::load(config.block_file.clone())),
+        admin_token: config.admin_token.clone(),
+        require_action_auth: config.require_action_auth,
+    };
+    #[cfg(feature = "discord")]
+    let discord = config.discord.clone().map(|discord| secrethitler::discord::DiscordBridge::start(discord, server.games.clone(), server.limits.clone()));
+    #[cfg(feature = "telegram")]
+    let telegram = config.telegram.clone().map(|telegram| secrethitler::telegram::TelegramBridge::start(telegram, server.games.clone(), server.limits.clone()));
+    #[cfg(feature = "graphql")]
+    let graphql = secrethitler::graphql::schema(server.games.clone(), server.replays.clone());
+    let snapshots = config.snapshot_dir.clone().map(|dir| Arc::new(Snapshots::new(dir)));
+    match (&snapshots, resume) {
+        (Some(snapshots), true) => println!("Resumed {} games from snapshots", snapshots.resume(&server)),
+        (None, true) => return Err("--resume needs SNAPSHOT_DIR to be set".into()),
+        _ => {}
+    }
+    let server_ref = server.clone();
+    let timer_ref = server.clone();
+    let snapshot_ref = server.clone();
+    let sse_route = secrethitler::sse::route(server.clone());
+    let calendar_route = secrethitler::calendar::route(server.games.clone(), config.public_url.clone());
+    let audit_route = secrethitler::audit::route(server.audit.clone(), server.communities.clone(), config.admin_token.clone());
+    let leaderboard_route = secrethitler::ratings::route(server.ratings.clone());
+    let history_route = secrethitler::archive::route(server.archive.clone());
+    let feedback_route = secrethitler::archive::feedback_route(server.archive.clone(), config.admin_token.clone());
+    let dashboard_route = secrethitler::dashboard::route(server.clone(), config.public_url.clone());
+    let upload_route = secrethitler::uploads::route(server.clone());
+    let achievements_route = secrethitler::achievements::route(server.achievements.clone());
+    let address_policy = server.addresses.clone();
+    let communities = server.communities.clone();
+    let server = warp::any().map(move || server.clone());
+    let cors_policy = Arc::new(CorsPolicy::new(config.allowed_origins.clone()));
+    let ws_policy = cors_policy.clone();
+
+    let ws_route = warp::path("ws").and(warp::ws()).and(server.clone()).and(addresses::client(address_policy.clone())).and(communities::community()).and(warp::header::optional::<String>("user-agent"))
+        .and(warp::header::optional::<String>("origin")).and(warp::header::optional::<String>("host"))
+        .map(move |ws: warp::ws::Ws, server: ServerState, address: Option<IpAddr>, community: Option<String>, user_agent: Option<String>, origin: Option<String>, host: Option<String>| -> Box<dyn warp::Reply> {
+            if !ws_policy.allows_websocket(origin.as_deref(), host.as_deref()) {
+                return Box::new(warp::reply::with_status("origin not allowed", StatusCode::FORBIDDEN))
+            }
+            let device = address.map(|address| fingerprint(address, user_agent.as_deref()));
+            if let Some(ban) = server.bans.find(&Visitor { secret: None, address, fingerprint: device.as_deref() }, community.as_deref(), SystemTime::now()) {
+                return Box::new(warp::reply::with_status(format!("banned: {}", ban.reason), StatusCode::FORBIDDEN))
+            }
+            // frames and messages past the largest a client may send are refused before they are buffered
+            let ws = ws.max_message_size(MAX_MESSAGE_BYTES).max_frame_size(MAX_MESSAGE_BYTES);
+            Box::new(ws.on_upgrade(move |socket| ws_connect(socket, server, address, community, user_agent)))
+        });
+    let health_route = warp::path!("healthz").and(warp::get()).and(server.clone()).map(|server: ServerState| {
+        let mut health = serde_json::to_value(server.limits.load(server.games.read().len())).unwrap();
+        health["stuck_games"] = serde_json::to_value(server.watchdog.stats()).unwrap();
+        warp::reply::json(&health)
     });
-    let game_route = warp::path!("game" / String).map(|_| ()).untuple_one().and(warp::get()).and(warp::fs::file("frontend/build/index.html"));
-    let static_route = warp::any().and(warp::get()).and(warp::fs::dir("frontend/build"));
-
-    let routes = ws_route.or(game_route).or(static_route);
+    let schema_route = warp::path!("schema").and(warp::get()).map(|| {
+        warp::reply::json(&serde_json::json!({ "client": schema::client_schema(), "server": schema::server_schema(), "player_view": schema::player_view_schema() }))
+    });
+    let version_route = warp::path!("version").and(warp::get()).map(|| {
+        warp::reply::json(&serde_json::json!({ "version": env!("CARGO_PKG_VERSION"), "build": env!("BUILD_HASH") }))
+    });
+    let analysis_route = warp::path!("game" / Uuid / "analysis").and(warp::get()).and(server).map(|game_id: Uuid, server: ServerState| -> Box<dyn warp::Reply> {
+        let game = get_game(&server.games, &game_id);
+        let analysis = match game {
+            Some(game) => {
+                let state = game.lock();
+                // the analysis could help players who are still in the game
+                match Analysis::new(state.timeline()).filter(|_| state.winner().is_some()) {
+                    Some(analysis) => analysis,
+                    None => return Box::new(warp::reply::with_status("the game has not ended", StatusCode::CONFLICT))
+                }
+            },
+            // finished games that have been cleaned up are still in the replay cache
+            None => match server.replays.get(game_id).and_then(|timeline| Analysis::new(&timeline)) {
+                Some(analysis) => analysis,
+                None => return Box::new(warp::reply::with_status("game not found", StatusCode::NOT_FOUND))
+            }
+        };
+        Box::new(warp::reply::json(&analysis))
+    });
+    let static_route = assets::dir(config.static_dir.clone());
+    let page_route = assets::fallback(config.static_dir.join("index.html"));
+
+    let routes = ws_route.or(sse_route).or(audit_route).or(feedback_route).or(achievements_route).or(leaderboard_route).or(history_route).or(dashboard_route).or(upload_route).or(health_route).or(version_route).or(schema_route).or(analysis_route).or(calendar_route);
+    #[cfg(feature = "discord")]
+    let routes = routes.or(secrethitler::discord::route(discord));
+    #[cfg(feature = "telegram")]
+    let routes = routes.or(secrethitler::telegram::route(telegram));
+    #[cfg(feature = "graphql")]
+    let routes = routes.or(secrethitler::graphql::route(graphql));
+    let routes = routes.or(static_route).or(page_route);
+    let routes = addresses::guard(address_policy).or(communities::guard(communities)).unify().or(cors::preflight(cors_policy.clone())).unify().or(cors::wrap(cors_policy, routes));
+    let routes = base_path(&config.base_path).and(routes);
 
     // game cleanup routine
     let mut interval = time::interval(Duration::from_secs(5 * 60));
@@ -48,23 +155,61 @@ async fn main() {
         interval.tick().await;
         loop {
             interval.tick().await;
-            cleanup_global_state(&state_ref);
+            server_ref.cleanup();
+        }
+    });
+
+    // turn timer routine
+    let mut interval = time::interval(Duration::from_secs(1));
+    tokio::spawn(async move {
+        loop {
+            interval.tick().await;
+            timer_ref.expire_nominations();
+            timer_ref.flush_spectators();
+            timer_ref.advance_schedules();
+            timer_ref.recover_stuck_games();
         }
     });
 
+    // snapshot routine
+    if let Some(snapshots) = snapshots {
+        let mut interval = time::interval(config.snapshot_interval);
+        tokio::spawn(async move {
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let (snapshots, snapshot_ref) = (snapshots.clone(), snapshot_ref.clone());
+                // writing to disk blocks, so it is kept off the threads serving connections
+                let _ = tokio::task::spawn_blocking(move || snapshots.save(&snapshot_ref)).await;
+            }
+        });
+    }
+
     // websocket server
-    let port = std::env::var("PORT").unwrap_or("8000".into()).parse::<u16>().unwrap_or(8000);
-    println!("Started server on port {}....", port);
-    warp::serve(routes).run(([0, 0, 0, 0], port)).await;
+    let listeners = listen::bind_all(&config.listen, config.port).await.unwrap_or_else(|e| panic!("{}", e));
+    let servers = listeners.into_iter().map(|listener| {
+        println!("Started server on {}....", listener.describe());
+        match listener {
+            Listener::Tcp(listener) => warp::serve(routes.clone()).serve_incoming(TcpListenerStream::new(listener)).boxed(),
+            Listener::Unix(listener) => warp::serve(routes.clone()).serve_incoming(UnixListenerStream::new(listener)).boxed(),
+        }
+    });
+    future::join_all(servers).await;
+    Ok(())
 }
 
-async fn ws_connect(ws: WebSocket, state: GlobalState) {
-    cleanup_global_state(&state);
+/// Match and strip the path the server is mounted at, one segment at a time.
+fn base_path(base: &str) -> BoxedFilter<()> {
+    base.split('/').filter(|segment| !segment.is_empty()).fold(warp::any().boxed(), |filter, segment| filter.and(warp::path(segment.to_string())).boxed())
+}
+
+async fn ws_connect(ws: WebSocket, server: ServerState, address: Option<IpAddr>, community: Option<String>, user_agent: Option<String>) {
+    server.cleanup();
+    let _socket = server.limits.connect();
 
     let (tx, mut rx) = ws.split();
     
     let (ptx, prx) = mpsc::unbounded_channel();
-    let ptx = Arc::new(ptx);
     let prx = UnboundedReceiverStream::new(prx);
     tokio::task::spawn(prx.forward(tx).map(|result| {
         if let Err(e) = result {
@@ -72,199 +217,34 @@ async fn ws_connect(ws: WebSocket, state: GlobalState) {
         }
     }));
 
-    let mut current_game: Option<Uuid> = Option::None;
-    let mut current_player: Option<Uuid> = Option::None;
-
-    while let Some(Ok(result)) = rx.next().await {
-        if let Ok(raw) = result.to_str() {
-            if let Ok::<ClientProtocol, serde_json::Error>(msg) = serde_json::from_str(raw) {
-                match msg {
-                    ClientProtocol::HostGame { nickname } => {
-                        let mut conn = PlayerConnection::new(ptx.clone());
-                        if match current_game {
-                            Some(game_uuid) => {
-                                let mut found_game = false;
-                                if let Some(game_state) = state.read().unwrap().get(&game_uuid) {
-                                    if game_state.read().unwrap().is_in_game() {
-                                        conn.send(&ServerProtocol::Alert { message: "You cannot join another game while you are currently in a game!".into() });
-                                        found_game = true;
-                                    }
-                                }
-                                !found_game
-                            }
-                            None => true
-                        } {
-                            if nickname.trim().len() <= 0 {
-                                conn.send(&ServerProtocol::Alert { message: "Your nickname cannot be empty.".into() });
-                            }
-                            else {
-                                let mut new_gamestate = GameState::new();
-                                let player_uuid = Uuid::new_v4();
-                                let secret = Uuid::new_v4();
-                                current_game = Some(Uuid::new_v4());
-                                current_player = Some(player_uuid);
-                                conn.secret = Some(secret);
-                                conn.name = Some(nickname.clone());
-                                conn.send(&ServerProtocol::SetIdentifiers { player_id: player_uuid, game_id: current_game.unwrap(), secret });
-                                new_gamestate.add_player(player_uuid, conn);
-                                new_gamestate.send_game_state(player_uuid);
-                                state.write().unwrap().insert(current_game.unwrap(), Arc::new(RwLock::new(new_gamestate)));
-                            }
-                        }
-                    }
-                    ClientProtocol::JoinGame { id, nickname, player_id, player_secret} => {
-                        let mut conn = PlayerConnection::new(ptx.clone());
-                        conn.name = Some(nickname);
-                        conn.secret = player_secret;
-                        if let Some(game_state) = state.read().unwrap().get(&id) {
-                            if let Some(old_player_id) = player_id {
-                                let mut state = game_state.write().unwrap();
-                                state.timeout = None;
-                                if let Some(real_player_secret) = state.get_player_secret(&old_player_id) {
-                                    if Some(real_player_secret) == player_secret {
-                                        current_game = Some(id);
-                                        current_player = Some(old_player_id);
-                                        if state.add_player(old_player_id, conn) {
-                                            state.broadcast_game_state();
-                                        }
-                                        else {
-                                            PlayerConnection::new(ptx.clone()).send(&ServerProtocol::Alert { message: "This game has already started!".into() });
-                                        }
-                                    }
-                                    else {
-                                        conn.send(&ServerProtocol::Alert { message: "Invalid player secret passed to server!".into() });
-                                    }
-                                }
-                                else {
-                                    conn.send(&ServerProtocol::Alert { message: "The player you are trying to join as does not exist!".into() });
-                                }
-                            }
-                            else {
-                                let player_id = player_id.unwrap_or_else(|| {Uuid::new_v4() });
-                                let secret = player_secret.unwrap_or_else(|| { Uuid::new_v4() });
-                                let data = &mut game_state.write().unwrap();
-                                conn.secret = Some(secret);
-                                if data.add_player(player_id, conn) {
-                                    current_game = Some(id);
-                                    current_player = Some(player_id);
-                                    
-                                    // notify players of successful join
-                                    data.conn.get(&player_id).unwrap().send(&ServerProtocol::SetIdentifiers { player_id, game_id: id, secret });
-                                    data.broadcast_game_state();
-                                }
-                                else {
-                                    PlayerConnection::new(ptx.clone()).send( &ServerProtocol::Alert { message: "This game has already started!".into() });
-                                }
-                            }
-                        }
-                        else {
-                            conn.send(&ServerProtocol::Alert { message: "The game that you are looking for does not exist!".into() });
-                        }
-                    },
-                    ClientProtocol::StartGame => {
-                        if !game_state_wrapper(&state, &current_game, &current_player, &|gs: &mut GameState, pid| {
-                            gs.start(*pid)
-                        }) {
-                            let conn = PlayerConnection::new(ptx.clone());
-                            conn.send(&ServerProtocol::Alert { message: "You are not currently in a game!".into() });
-                        }
-                    },
-                    ClientProtocol::SendChat { message } => {
-                        if let Some(game) = current_game {
-                            if let Some(state) = state.read().unwrap().get(&game) {
-                                if let Some(player) = current_player {
-                                    let state = &mut state.write().unwrap();
-                                    state.add_chat(ChatLine { id: Some(player), message: message.clone() });
-                                }
-                            }
-                        }
-                    },
-                    ClientProtocol::ChooseChancellor { player } => {
-                        game_state_wrapper(&state, &current_game, &current_player, &|gs: &mut GameState, pid| {
-                            gs.choose_chancellor(*pid, player)
-                        });
-                    }
-                    ClientProtocol::VoteChancellor { vote } => {
-                        game_state_wrapper(&state, &current_game, &current_player, &|gs: &mut GameState, pid| {
-                            gs.vote_chancellor(*pid, vote)
-                        });
-                    },
-                    ClientProtocol::PickCard { color } => {
-                        game_state_wrapper(&state, &current_game, &current_player, &|gs: &mut GameState, pid| {
-                            gs.pick_card(*pid, if color { CardColor::Facist } else { CardColor::Liberal })
-                        });
-                    },
-                    ClientProtocol::VetoCard => {
-                        game_state_wrapper(&state, &current_game, &current_player, &|gs: &mut GameState, pid| {
-                            gs.veto(*pid)
-                        });
-                    },
-                    ClientProtocol::PresidentialPower { player } => {
-                        game_state_wrapper(&state, &current_game, &current_player, &|gs: &mut GameState, pid| {
-                            gs.execute_presidential_power(*pid, player)
-                        });
-                    },
-                    ClientProtocol::GetChatLog => {
-                        if let Some(game) = current_game {
-                            if let Some(state) = state.read().unwrap().get(&game) {
-                                let log = &state.read().unwrap().chat_log;
-                                PlayerConnection::new(ptx.clone()).send(&ServerProtocol::ChatLog { log });
-                            }
-                        }
-                    },
-                    ClientProtocol::Leave => {
-                        if let Some(game) = current_game {
-                            if let Some(state) = state.read().unwrap().get(&game) {
-                                if let Some(player) = current_player {
-                                    let state = &mut state.write().unwrap();
-                                    state.delete_player(player);
-                                    state.broadcast_game_state();
-                                }
-                                current_game = None;
-                                current_player = None;
-                            }
-                        }
-                    },
+    let pong = ptx.clone();
+    let mut ctx = ConnectionContext::new(ptx);
+    ctx.address = address;
+    ctx.user_agent = user_agent;
+    ctx.community = community;
+    ctx.rate_limiter = server.limits.message_limiter();
+    handle_connect(&server, &ctx);
+
+    while let Some(Ok(frame)) = rx.next().await {
+        match read_frame(&frame) {
+            Frame::Message(msg) => {
+                if let ClientProtocol::HostGame { captcha: Some(token), .. } = &*msg {
+                    server.host_gate.verify(token).await;
                 }
-            }
+                handle_message(&server, &mut ctx, *msg);
+            },
+            Frame::Ping(payload) => {
+                let _ = pong.send(Ok(Message::pong(payload)));
+            },
+            Frame::Close { leave } => {
+                if leave {
+                    handle_message(&server, &mut ctx, ClientProtocol::Leave);
+                }
+                break
+            },
+            Frame::Ignore => {}
         }
     }
 
-    // disconnect
-    if let Some(game_uuid) = current_game {
-        let mut remove_game = false;
-
-        if let Some(player_uuid) = current_player {
-            if let Some(game) = state.read().unwrap().get(&game_uuid) {
-                let game = &mut game.write().unwrap();
-                game.remove_player(player_uuid);
-                game.broadcast_game_state();
-                remove_game = !game.has_connected_players();
-            }
-        }
-
-        if remove_game {
-            if let Some(game) = state.read().unwrap().get(&game_uuid) {
-                game.write().unwrap().timeout = Some(SystemTime::now());
-            }
-        }
-    }
+    handle_disconnect(&server, &ctx);
 }
-
-fn game_state_wrapper(state: &GlobalState, game_id: &Option<Uuid>, player_id: &Option<Uuid>, func: &dyn Fn(&mut GameState, &Uuid) -> Result<(), &'static str>) -> bool {
-    if let (Some(game_id), Some(player_id)) = (game_id, player_id) {
-        if let Some(state) = state.read().unwrap().get(game_id) {
-            let state = &mut state.write().unwrap();
-            match func(state, player_id) {
-                Ok(_) => {
-                    state.broadcast_game_state();
-                },
-                Err(str) => {
-                    state.conn.get(&player_id).unwrap().send(&ServerProtocol::Alert { message: str.into() });
-                }
-            }
-            return true
-        }
-    }
-    false
-}
\ No newline at end of file
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use parking_lot::RwLock;
+use uuid::Uuid;
+
+use secrethitler_core::game_state::{GameOptions, GamePreset};
+
+/// Most presets one player can save.
+const MAX_PRESETS: usize = 20;
+
+/// Most players with saved presets. Presets outlive games, so this bounds how much they can take up.
+const MAX_OWNERS: usize = 10_000;
+
+const MAX_NAME_LEN: usize = 40;
+
+/// Game options that hosts save under a name to set up the same kind of game again.
+/// Presets belong to the player secret that saved them and are kept in memory, so they last until the server restarts.
+#[derive(Default)]
+pub struct Presets {
+    saved: RwLock<HashMap<Uuid, Vec<GamePreset>>>,
+}
+
+impl Presets {
+    /// The presets saved with this secret, in the order they were first saved.
+    pub fn list(&self, owner: Uuid) -> Vec<GamePreset> {
+        self.saved.read().get(&owner).cloned().unwrap_or_default()
+    }
+
+    pub fn get(&self, owner: Uuid, name: &str) -> Option<GameOptions> {
+        self.saved.read().get(&owner)?.iter().find(|preset| preset.name == name.trim()).map(|preset| preset.options.clone())
+    }
+
+    /// Save a preset, replacing any preset with the same name.
+    pub fn save(&self, owner: Uuid, name: &str, options: GameOptions) -> Result<(), &'static str> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+            return Err("Preset names must be between 1 and 40 characters.")
+        }
+        let mut saved = self.saved.write();
+        if !saved.contains_key(&owner) && saved.len() >= MAX_OWNERS {
+            return Err("The server cannot save any more presets.")
+        }
+        let presets = saved.entry(owner).or_default();
+        if let Some(preset) = presets.iter_mut().find(|preset| preset.name == name) {
+            preset.options = options;
+        }
+        else if presets.len() >= MAX_PRESETS {
+            return Err("You cannot save more than 20 presets.")
+        }
+        else {
+            presets.push(GamePreset { name: name.to_string(), options });
+        }
+        Ok(())
+    }
+
+    /// Delete a preset, returning whether it existed.
+    pub fn delete(&self, owner: Uuid, name: &str) -> bool {
+        let mut saved = self.saved.write();
+        let presets = match saved.get_mut(&owner) {
+            Some(presets) => presets,
+            None => return false
+        };
+        let count = presets.len();
+        presets.retain(|preset| preset.name != name.trim());
+        let deleted = presets.len() < count;
+        if presets.is_empty() {
+            saved.remove(&owner);
+        }
+        deleted
+    }
+}
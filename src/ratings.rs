@@ -0,0 +1,188 @@
+use std::{collections::HashMap, sync::Arc, time::{Duration, SystemTime}};
+
+use parking_lot::RwLock;
+use serde::Serialize;
+use uuid::Uuid;
+use warp::{Filter, Rejection, Reply};
+
+use secrethitler_core::game_state::{CardColor, GameState, PlayerType};
+
+use crate::{communities, friends};
+
+/// Rating every player starts at, and that idle ratings drift back toward.
+const INITIAL_RATING: f64 = 1500.0;
+
+/// Ranked games a player plays before they show up on the leaderboard. Their rating moves faster until then.
+const PLACEMENT_GAMES: u32 = 5;
+
+const PLACEMENT_K: f64 = 64.0;
+
+const K: f64 = 24.0;
+
+/// How long a player can go without a ranked game before their rating starts to decay.
+const DECAY_AFTER: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+/// Share of the distance back to the initial rating that an idle player loses each week.
+const DECAY_PER_WEEK: f64 = 0.05;
+
+const WEEK: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Most players shown on the leaderboard.
+const LEADERBOARD_SIZE: usize = 100;
+
+/// Most players the server keeps ratings for.
+const MAX_PLAYERS: usize = 100_000;
+
+/// A player's standing on one side of the game.
+#[derive(Clone, Copy, Serialize)]
+pub struct SideRating {
+    pub rating: f64,
+    pub games: u32,
+    pub wins: u32,
+}
+
+impl Default for SideRating {
+    fn default() -> SideRating {
+        SideRating { rating: INITIAL_RATING, games: 0, wins: 0 }
+    }
+}
+
+struct Player {
+    name: String,
+    liberal: SideRating,
+    facist: SideRating,
+    last_played: SystemTime,
+}
+
+impl Player {
+    fn games(&self) -> u32 {
+        self.liberal.games + self.facist.games
+    }
+
+    fn side(&mut self, party: CardColor) -> &mut SideRating {
+        match party {
+            CardColor::Liberal => &mut self.liberal,
+            CardColor::Facist => &mut self.facist,
+        }
+    }
+
+    /// Pull both ratings back toward the start for every week past the grace period without a ranked game.
+    fn decay(&mut self, now: SystemTime) {
+        let idle = now.duration_since(self.last_played).unwrap_or_default();
+        if idle <= DECAY_AFTER {
+            return
+        }
+        let weeks = ((idle - DECAY_AFTER).as_secs() / WEEK.as_secs()) as i32;
+        let keep = (1.0 - DECAY_PER_WEEK).powi(weeks);
+        for side in [&mut self.liberal, &mut self.facist] {
+            side.rating = INITIAL_RATING + (side.rating - INITIAL_RATING) * keep;
+        }
+    }
+}
+
+/// A row of the leaderboard. Players are known by their friend id, so the secret behind it stays private.
+#[derive(Serialize)]
+pub struct Standing {
+    pub id: Uuid,
+    pub name: String,
+    /// The average of the liberal and facist ratings, which the leaderboard is sorted by.
+    pub rating: f64,
+    pub liberal: SideRating,
+    pub facist: SideRating,
+}
+
+/// A seat in a finished ranked game.
+pub struct RatedSeat {
+    pub secret: Uuid,
+    pub name: String,
+    pub party: CardColor,
+}
+
+impl RatedSeat {
+    /// The seats of a finished game, or none if any seat cannot be rated, such as a bot or a player without a secret.
+    pub fn from_game(state: &GameState) -> Option<Vec<RatedSeat>> {
+        state.conn.iter().filter(|(player, _)| state.role(player).is_some()).map(|(player, conn)| {
+            let party = match state.role(player)? {
+                PlayerType::Liberal => CardColor::Liberal,
+                PlayerType::Facist | PlayerType::Hitler => CardColor::Facist,
+            };
+            match conn.is_bot {
+                true => None,
+                false => Some(RatedSeat { secret: conn.secret?, name: conn.name.clone().unwrap_or_default(), party })
+            }
+        }).collect()
+    }
+}
+
+/// Elo ratings from ranked games, kept separately for playing as a liberal and as a facist.
+/// Each player is rated against the average rating of the other team on the side they played.
+/// Like friends, ratings belong to a player secret and are held in memory. Each community has its own ratings.
+#[derive(Default)]
+pub struct Ratings {
+    players: RwLock<HashMap<(Option<String>, Uuid), Player>>,
+}
+
+impl Ratings {
+    /// Update everyone's ratings in the community from a finished ranked game.
+    pub fn record_game(&self, community: Option<&str>, seats: &[RatedSeat], winner: CardColor, now: SystemTime) {
+        let mut players = self.players.write();
+        let key = |seat: &RatedSeat| (community.map(str::to_string), friends::friend_id(seat.secret));
+        for seat in seats {
+            let id = key(seat);
+            if !players.contains_key(&id) && players.len() >= MAX_PLAYERS {
+                continue
+            }
+            let player = players.entry(id).or_insert_with(|| Player { name: seat.name.clone(), liberal: SideRating::default(), facist: SideRating::default(), last_played: now });
+            player.decay(now);
+        }
+        let team_rating = |party: CardColor, players: &HashMap<(Option<String>, Uuid), Player>| {
+            let ratings: Vec<f64> = seats.iter().filter(|seat| seat.party == party).map(|seat| {
+                players.get(&key(seat)).map_or(INITIAL_RATING, |player| match party {
+                    CardColor::Liberal => player.liberal.rating,
+                    CardColor::Facist => player.facist.rating,
+                })
+            }).collect();
+            ratings.iter().sum::<f64>() / ratings.len().max(1) as f64
+        };
+        let liberals = team_rating(CardColor::Liberal, &players);
+        let facists = team_rating(CardColor::Facist, &players);
+        for seat in seats {
+            let player = match players.get_mut(&key(seat)) {
+                Some(player) => player,
+                None => continue
+            };
+            let placed = player.games() >= PLACEMENT_GAMES;
+            let opponents = if seat.party == CardColor::Liberal { facists } else { liberals };
+            let won = seat.party == winner;
+            let side = player.side(seat.party);
+            let expected = 1.0 / (1.0 + 10f64.powf((opponents - side.rating) / 400.0));
+            side.rating += if placed { K } else { PLACEMENT_K } * (if won { 1.0 } else { 0.0 } - expected);
+            side.games += 1;
+            side.wins += won as u32;
+            player.name = seat.name.clone();
+            player.last_played = now;
+        }
+    }
+
+    /// The highest rated players in the community who have finished their placement games, with idle ratings decayed to now.
+    pub fn leaderboard(&self, community: Option<&str>, now: SystemTime) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = self.players.read().iter().filter(|((player_community, _), player)| player_community.as_deref() == community && player.games() >= PLACEMENT_GAMES).map(|((_, id), player)| {
+            let mut player = Player { name: player.name.clone(), ..*player };
+            player.decay(now);
+            Standing { id: *id, name: player.name, rating: (player.liberal.rating + player.facist.rating) / 2.0, liberal: player.liberal, facist: player.facist }
+        }).collect();
+        standings.sort_by(|a, b| b.rating.total_cmp(&a.rating).then(a.id.cmp(&b.id)));
+        standings.truncate(LEADERBOARD_SIZE);
+        standings
+    }
+}
+
+/// Serve the leaderboard of ranked games at `/leaderboard`, or a community's with `?community=`.
+pub fn route(ratings: Arc<Ratings>) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone {
+    warp::path!("leaderboard")
+        .and(warp::get())
+        .and(communities::community())
+        .map(move |community: Option<String>| -> Box<dyn Reply> {
+            Box::new(warp::reply::json(&ratings.leaderboard(community.as_deref(), SystemTime::now())))
+        })
+}
@@ -0,0 +1,127 @@
+use std::{collections::{HashMap, VecDeque}, fs, path::PathBuf};
+
+use parking_lot::Mutex;
+use uuid::Uuid;
+
+use secrethitler_core::game_state::TimelineEntry;
+
+/// Compression level for stored replays. Timelines are small and repetitive, so low levels already do well.
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Most replays kept on disk once they no longer fit in memory. The oldest are deleted first.
+const MAX_SPILLED: usize = 100_000;
+
+#[derive(Default)]
+struct Cache {
+    /// Compressed timelines, with when each was last used.
+    memory: HashMap<Uuid, (Vec<u8>, u64)>,
+    memory_bytes: usize,
+    /// Replays moved to disk, oldest first.
+    spilled: VecDeque<Uuid>,
+    clock: u64,
+}
+
+/// Timelines of finished games, kept after the games themselves are cleaned up so they can still be replayed and analysed.
+/// Replays are compressed and held in memory up to a budget, past which the least recently used move to disk if a directory is set, or are forgotten.
+pub struct ReplayCache {
+    max_memory: usize,
+    spill_dir: Option<PathBuf>,
+    cache: Mutex<Cache>,
+}
+
+impl Default for ReplayCache {
+    fn default() -> ReplayCache {
+        ReplayCache::new(64 * 1024 * 1024, None)
+    }
+}
+
+impl ReplayCache {
+    pub fn new(max_memory: usize, spill_dir: Option<PathBuf>) -> ReplayCache {
+        if let Some(dir) = &spill_dir {
+            if let Err(e) = fs::create_dir_all(dir) {
+                eprintln!("could not create replay directory {}: {}", dir.display(), e);
+            }
+        }
+        ReplayCache { max_memory, spill_dir, cache: Mutex::default() }
+    }
+
+    fn path(&self, game_id: Uuid) -> Option<PathBuf> {
+        self.spill_dir.as_ref().map(|dir| dir.join(format!("{}.json.zst", game_id)))
+    }
+
+    /// Store the timeline of a finished game, replacing any earlier round of the same game.
+    pub fn insert(&self, game_id: Uuid, timeline: &[TimelineEntry]) {
+        let json = serde_json::to_vec(timeline).unwrap();
+        let compressed = match zstd::encode_all(json.as_slice(), COMPRESSION_LEVEL) {
+            Ok(compressed) => compressed,
+            Err(e) => return eprintln!("could not compress the replay of game {}: {}", game_id, e)
+        };
+        let cache = &mut self.cache.lock();
+        self.store(cache, game_id, compressed);
+    }
+
+    fn store(&self, cache: &mut Cache, game_id: Uuid, compressed: Vec<u8>) {
+        if cache.spilled.contains(&game_id) {
+            cache.spilled.retain(|id| *id != game_id);
+            if let Some(path) = self.path(game_id) {
+                let _ = fs::remove_file(path);
+            }
+        }
+        cache.clock += 1;
+        cache.memory_bytes += compressed.len();
+        if let Some((previous, _)) = cache.memory.insert(game_id, (compressed, cache.clock)) {
+            cache.memory_bytes -= previous.len();
+        }
+        while cache.memory_bytes > self.max_memory && cache.memory.len() > 1 {
+            let oldest = cache.memory.iter().min_by_key(|(_, (_, used))| *used).map(|(id, _)| *id).unwrap();
+            let (data, _) = cache.memory.remove(&oldest).unwrap();
+            cache.memory_bytes -= data.len();
+            self.spill(cache, oldest, &data);
+        }
+    }
+
+    fn spill(&self, cache: &mut Cache, game_id: Uuid, data: &[u8]) {
+        let path = match self.path(game_id) {
+            Some(path) => path,
+            None => return
+        };
+        if let Err(e) = fs::write(&path, data) {
+            return eprintln!("could not write replay {}: {}", path.display(), e);
+        }
+        cache.spilled.retain(|id| *id != game_id);
+        cache.spilled.push_back(game_id);
+        while cache.spilled.len() > MAX_SPILLED {
+            if let Some(path) = cache.spilled.pop_front().and_then(|id| self.path(id)) {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    /// The timeline of a finished game, if it is still held in memory or on disk.
+    pub fn get(&self, game_id: Uuid) -> Option<Vec<TimelineEntry>> {
+        let cache = &mut self.cache.lock();
+        cache.clock += 1;
+        let clock = cache.clock;
+        let compressed = if let Some((data, used)) = cache.memory.get_mut(&game_id) {
+            *used = clock;
+            data.clone()
+        }
+        else if cache.spilled.contains(&game_id) {
+            // a replay read from disk is likely to be read again soon, so it moves back into memory
+            let data = fs::read(self.path(game_id)?).ok()?;
+            self.store(cache, game_id, data.clone());
+            data
+        }
+        else {
+            return None
+        };
+        let json = zstd::decode_all(compressed.as_slice()).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    /// How many replays are held in memory and on disk.
+    pub fn counts(&self) -> (usize, usize) {
+        let cache = self.cache.lock();
+        (cache.memory.len(), cache.spilled.len())
+    }
+}
@@ -0,0 +1,1401 @@
+use std::{collections::HashMap, net::IpAddr, sync::Arc, time::{Duration, Instant, SystemTime}};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use parking_lot::{FairMutex, RwLock};
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+use warp::ws::Message;
+
+use secrethitler_core::{chat::LinkPolicy, error::GameError, game_state::{ChatLine, GameExport, GameState, LATEST_VIEW_VERSION, PlayerType, ScheduleEvent, TurnPhase, epoch_millis, negotiate_view_version}, machine::Action, messages::{Language, Message as SystemMessage}, nicknames, protocol::{Ban, ClientProtocol, DEFAULT_TOPICS, ModeratorAction, MessageSink, PlayerConnection, ServerProtocol, Sink, Topic}, theme::{Theme, ThemePreset}};
+
+use crate::{achievements::Achievements, addresses::AddressPolicy, archive::{ArchivedGame, Feedback, GameArchive}, audit::{self, AuditLog}, bans::{BanList, Visitor}, blocks::BlockList, captcha::HostGate, communities::{Communities, Role}, dispatch::{Authenticate, CatchPanics, Dispatcher, RateLimit}, email::EmailDispatcher, friends::{self, Friends}, limits::{MessageRateLimiter, ServerLimits}, presets::Presets, ratings::{RatedSeat, Ratings}, replays::ReplayCache, tokens::ResumeTokens, uploads::Uploads, watchdog::{StuckPolicy, Watchdog}, webhooks::{WebhookDispatcher, WebhookEvent}};
+
+/// How long a game is kept once everybody has left.
+const GAME_IDLE_LIMIT: Duration = Duration::from_secs(5 * 60);
+
+/// How long an unfinished asynchronous game is kept once everybody has left.
+const ASYNC_GAME_IDLE_LIMIT: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How far ahead a game can be scheduled. Scheduled games are kept until they open, so this bounds how long they are held.
+const MAX_SCHEDULE_AHEAD: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Longest message of the day or announcement an admin can send.
+const MAX_ANNOUNCEMENT_LENGTH: usize = 500;
+
+/// Largest message a client can send, which leaves room for an admin importing a long game.
+pub const MAX_MESSAGE_BYTES: usize = 1024 * 1024;
+
+/// Deepest that objects and arrays can be nested in a client message. Imported games are the most deeply nested messages, and need far less than this.
+const MAX_MESSAGE_DEPTH: usize = 32;
+
+/// A game, locked by one command at a time. Locking is first come first served, so commands from different connections are applied in the order they arrived.
+pub type SharedGame = Arc<FairMutex<GameState>>;
+
+pub type GlobalState = Arc<RwLock<HashMap<Uuid, SharedGame>>>;
+
+/// Look up a game. The map is only locked for the lookup, so the game can be locked afterwards without holding both at once.
+pub fn get_game(games: &GlobalState, id: &Uuid) -> Option<SharedGame> {
+    games.read().get(id).cloned()
+}
+
+/// Every game and its id, taken from the map so each game can be locked in turn without holding the map.
+pub fn all_games(games: &GlobalState) -> Vec<(Uuid, SharedGame)> {
+    games.read().iter().map(|(id, game)| (*id, game.clone())).collect()
+}
+
+/// The game id and player id that each player secret was last given, used to find a player's current game.
+pub type ActivePlayers = Arc<RwLock<HashMap<Uuid, (Uuid, Uuid)>>>;
+
+/// Everything shared between connections.
+#[derive(Clone)]
+pub struct ServerState {
+    pub games: GlobalState,
+    pub webhooks: WebhookDispatcher,
+    pub email: EmailDispatcher,
+    pub tokens: Arc<ResumeTokens>,
+    pub limits: Arc<ServerLimits>,
+    pub audit: Arc<AuditLog>,
+    pub presets: Arc<Presets>,
+    pub friends: Arc<Friends>,
+    pub replays: Arc<ReplayCache>,
+    /// Summaries of finished games, for the history at `/games`.
+    pub archive: Arc<GameArchive>,
+    pub ratings: Arc<Ratings>,
+    pub bans: Arc<BanList>,
+    /// Which proxies are believed about client addresses, and which addresses may connect.
+    pub addresses: Arc<AddressPolicy>,
+    pub host_gate: Arc<HostGate>,
+    pub achievements: Arc<Achievements>,
+    pub watchdog: Arc<Watchdog>,
+    pub active_players: ActivePlayers,
+    /// Messages of the day set by an admin, sent to every new connection: the server's under none, and each community's under its slug.
+    pub motd: Arc<RwLock<HashMap<Option<String>, String>>>,
+    /// The communities sharing the server.
+    pub communities: Arc<Communities>,
+    /// Let the same player sit in more than one game at a time.
+    pub allow_multiple_games: bool,
+    /// Which links in chat are made clickable.
+    pub chat_links: Arc<LinkPolicy>,
+    /// Images uploaded for chat, which are removed along with their game.
+    pub uploads: Arc<Uploads>,
+    pub blocks: Arc<BlockList>,
+    /// Token that admins send to export and import games, which cannot be done if unset.
+    pub admin_token: Option<String>,
+    /// Reject actions that are not sent as `Authenticated` with the seat's secret or resume token.
+    pub require_action_auth: bool,
+}
+
+impl ServerState {
+    /// Remove abandoned games, and forget players that were in them.
+    pub fn cleanup(&self) {
+        cleanup_global_state(&self.games);
+        let games = self.games.read();
+        self.active_players.write().retain(|_, (game_id, _)| games.contains_key(game_id));
+        self.email.retain_games(|game_id| games.contains_key(game_id));
+        self.audit.retain_games(|game_id| games.contains_key(game_id));
+        self.watchdog.retain_games(|game_id| games.contains_key(game_id));
+        self.uploads.retain_games(|game_id| games.contains_key(game_id));
+    }
+
+    /// Rotate the presidency in every game where the president has run out of time to nominate a chancellor, open the vote where the discussion has run out of time, end lobby votes that have run out of time, start games whose countdown has run out, and start first elections that have waited long enough on players to see their roles.
+    pub fn expire_nominations(&self) {
+        let now = SystemTime::now();
+        for (game_id, game) in all_games(&self.games) {
+            let state = &mut game.lock();
+            let awaiting = state.awaiting();
+            let was_in_game = state.is_in_game();
+            if state.expire_nomination(now) || state.expire_discussion(now) || state.expire_lobby_vote(now) || state.expire_countdown(now) || state.expire_role_ack(now) {
+                state.run_bots();
+                state.advance_tutorial();
+                state.broadcast_game_state();
+                self.notify(game_id, state, was_in_game, &awaiting);
+            }
+        }
+    }
+
+    /// Remind players of scheduled games that are about to open, and open the ones whose time has come.
+    pub fn advance_schedules(&self) {
+        let now = SystemTime::now();
+        for (game_id, game) in all_games(&self.games) {
+            let state = &mut game.lock();
+            match state.advance_schedule(now) {
+                Some(ScheduleEvent::Reminder) => {
+                    self.webhooks.notify(WebhookEvent::ScheduleReminder, game_id, state.summary());
+                    self.email.schedule_reminder(game_id, &named(state, state.conn.keys()));
+                },
+                Some(ScheduleEvent::Opened) => {
+                    // the game may have sat empty until now, so it gets the usual grace period from opening
+                    if state.timeout.is_some() {
+                        state.timeout = Some(now);
+                    }
+                    state.broadcast_game_state();
+                    self.webhooks.notify(WebhookEvent::Opened, game_id, state.summary());
+                },
+                None => {}
+            }
+        }
+    }
+
+    /// Report games stuck waiting on players who have all lost their connection, and recover them with the watchdog's policy.
+    pub fn recover_stuck_games(&self) {
+        let (after, policy) = match self.watchdog.config() {
+            Some(config) => config,
+            None => return
+        };
+        let now = SystemTime::now();
+        let mut aborted = vec![];
+        for (game_id, game) in all_games(&self.games) {
+            let state = &mut game.lock();
+            if !state.is_stuck(now, after) || !self.watchdog.report(game_id, state.phase_started_at()) {
+                continue
+            }
+            let awaiting = state.awaiting();
+            let absent: Vec<String> = named(state, awaiting.iter()).into_iter().map(|(_, name)| name).collect();
+            eprintln!("game {} is stuck waiting on {}", game_id, absent.join(", "));
+            self.webhooks.notify_stuck(game_id, state.summary(), absent);
+            match policy {
+                StuckPolicy::Report => {},
+                StuckPolicy::Skip => {
+                    if state.stand_in_for_absent() {
+                        self.watchdog.recovered(policy);
+                        state.run_bots();
+                        state.advance_tutorial();
+                        state.broadcast_game_state();
+                        self.notify(game_id, state, true, &awaiting);
+                    }
+                },
+                StuckPolicy::Abort => {
+                    let message = "This game has ended because it was stuck waiting on players who left.";
+                    state.conn.values().for_each(|conn| conn.send(&ServerProtocol::Alert { message: message.into() }));
+                    aborted.push(game_id);
+                }
+            }
+        }
+        if !aborted.is_empty() {
+            let mut games = self.games.write();
+            for game_id in aborted {
+                games.remove(&game_id);
+                self.watchdog.recovered(policy);
+            }
+        }
+    }
+
+    /// Send spectators of streamed games the updates that have waited out the spectator delay.
+    pub fn flush_spectators(&self) {
+        let now = SystemTime::now();
+        for (_, game) in all_games(&self.games) {
+            game.lock().flush_spectators(now);
+        }
+    }
+
+    /// Send the notifications due once a game has moved on: webhooks when it starts or ends, and reminders when an asynchronous game starts waiting on someone.
+    fn notify(&self, game_id: Uuid, state: &GameState, was_in_game: bool, awaiting: &[Uuid]) {
+        if state.is_practice() {
+            // practice games are not announced
+        }
+        else if !was_in_game && state.is_in_game() {
+            self.webhooks.notify(WebhookEvent::Started, game_id, state.summary());
+        }
+        else if was_in_game && state.winner().is_some() {
+            self.webhooks.notify(WebhookEvent::Ended, game_id, state.summary());
+            self.replays.insert(game_id, state.timeline());
+            if let Some(game) = ArchivedGame::from_game(game_id, state) {
+                self.archive.record(game);
+            }
+            if let (true, Some(seats), Some(winner)) = (state.options.ranked, RatedSeat::from_game(state), state.winner()) {
+                self.ratings.record_game(state.community.as_deref(), &seats, winner, SystemTime::now());
+            }
+            for result in self.achievements.record_game(state, SystemTime::now()) {
+                if let Some(conn) = state.conn.get(&result.player) {
+                    conn.send(&ServerProtocol::Achievements { earned: &result.earned, season: result.season, season_wins: result.season_wins });
+                }
+            }
+            self.email.result(game_id, &state.summary(), &named(state, state.conn.keys().filter(|player| state.role(player).is_some())));
+        }
+        else if state.options.asynchronous {
+            let players = named(state, state.awaiting().iter().filter(|player| !awaiting.contains(player)));
+            if !players.is_empty() {
+                self.webhooks.notify_turn(game_id, state.summary(), players.iter().map(|(_, name)| name.clone()).collect());
+                self.email.turn_reminder(game_id, &players);
+            }
+        }
+    }
+
+    fn is_admin(&self, token: &str) -> bool {
+        self.admin_token.as_deref().is_some_and(|admin_token| admin_token == token)
+    }
+
+    /// Recreate an exported game under its id, unless a game with that id already exists.
+    pub fn import_game(&self, game_id: Uuid, export: GameExport) -> bool {
+        let mut game = GameState::import(export);
+        // nobody is connected yet, so the game is cleaned up as usual if its players do not come back
+        game.timeout = Some(SystemTime::now());
+        let mut games = self.games.write();
+        if games.contains_key(&game_id) {
+            return false
+        }
+        for (player_id, seat) in game.conn.iter() {
+            if let Some(secret) = seat.secret {
+                self.track_player(secret, game_id, *player_id);
+            }
+        }
+        self.blocks.apply(&mut game);
+        games.insert(game_id, Arc::new(FairMutex::new(game)));
+        true
+    }
+
+    fn track_player(&self, secret: Uuid, game_id: Uuid, player_id: Uuid) {
+        self.active_players.write().insert(secret, (game_id, player_id));
+    }
+
+    /// The game and player id of the seat held by the player with this secret, if they are in a game that has not ended.
+    pub fn current_game(&self, secret: Uuid) -> Option<(Uuid, Uuid)> {
+        let (game_id, player_id) = *self.active_players.read().get(&secret)?;
+        let active = get_game(&self.games, &game_id).is_some_and(|game| {
+            let game = game.lock();
+            game.get_player_secret(&player_id) == Some(secret) && game.has_player(&player_id) && game.winner().is_none()
+        });
+        if !active {
+            let mut active_players = self.active_players.write();
+            if active_players.get(&secret) == Some(&(game_id, player_id)) {
+                active_players.remove(&secret);
+            }
+            return None
+        }
+        Some((game_id, player_id))
+    }
+
+    /// Send a message to the player with this secret wherever they can be reached: the connection they listed their friends from, or their seat in a game.
+    /// Returns whether it was sent.
+    fn send_to_player(&self, secret: Uuid, message: &ServerProtocol) -> bool {
+        if let Some(inbox) = self.friends.inbox(friends::friend_id(secret)) {
+            PlayerConnection::new(inbox).send(message);
+            return true
+        }
+        let (game_id, player_id) = match self.current_game(secret) {
+            Some(current) => current,
+            None => return false
+        };
+        match get_game(&self.games, &game_id) {
+            Some(game) => {
+                if let Some(conn) = game.lock().conn.get(&player_id) {
+                    conn.send(message);
+                }
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Update who is muted in every game the player with this secret has a seat in, after their block list changed.
+    fn apply_blocks(&self, secret: Uuid) {
+        for (_, game) in all_games(&self.games) {
+            let mut state = game.lock();
+            if state.conn.values().any(|conn| conn.secret == Some(secret)) {
+                self.blocks.apply(&mut state);
+            }
+        }
+    }
+
+    fn send_friends(&self, tx: Sink, secret: Uuid) {
+        let conn = PlayerConnection::new(tx);
+        match self.friends.list(secret) {
+            Ok(list) => conn.send(&ServerProtocol::Friends { friend_id: list.friend_id, friends: &list.friends, requests: &list.requests, invites: &list.invites }),
+            Err(message) => conn.send(&ServerProtocol::Alert { message: message.into() })
+        }
+    }
+
+    /// Whether the player with this secret is already in a game other than `game_id` and may not join another.
+    /// Practice games do not count.
+    fn in_other_game(&self, secret: Option<Uuid>, game_id: Option<Uuid>) -> bool {
+        if self.allow_multiple_games {
+            return false
+        }
+        match secret.and_then(|secret| self.current_game(secret)) {
+            Some((current, _)) if Some(current) != game_id => {
+                !get_game(&self.games, &current).is_some_and(|game| game.lock().is_practice())
+            },
+            _ => false
+        }
+    }
+}
+
+/// Delivers messages to the task that writes to a websocket.
+struct WebSocketSink(mpsc::UnboundedSender<Result<Message, warp::Error>>);
+
+impl MessageSink for WebSocketSink {
+    fn send(&self, message: String) -> Result<(), String> {
+        self.0.send(Ok(Message::text(message))).map_err(|e| e.to_string())
+    }
+}
+
+/// The state of a single websocket connection, handed to every message it sends.
+pub struct ConnectionContext {
+    pub tx: Sink,
+    pub game: Option<Uuid>,
+    pub player: Option<Uuid>,
+    pub topics: Vec<Topic>,
+    /// The language the client asked for its own messages in.
+    pub language: Option<Language>,
+    /// The version of the player view the client negotiated.
+    pub view_version: u32,
+    /// Where the client connected from, if known.
+    pub address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    /// How fast the connection may send messages, or as fast as it likes if unset.
+    pub rate_limiter: Option<MessageRateLimiter>,
+    /// The community the client connected to, whose games are the only ones it can host and join.
+    pub community: Option<String>,
+}
+
+impl ConnectionContext {
+    pub fn new(tx: mpsc::UnboundedSender<Result<Message, warp::Error>>) -> ConnectionContext {
+        ConnectionContext::with_sink(Arc::new(WebSocketSink(tx)))
+    }
+
+    /// A connection over some other transport, such as server-sent events.
+    pub fn with_sink(tx: Sink) -> ConnectionContext {
+        ConnectionContext { tx, game: None, player: None, topics: DEFAULT_TOPICS.to_vec(), language: None, view_version: 1, address: None, user_agent: None, rate_limiter: None, community: None }
+    }
+
+    /// A hash of the address and user agent, so that seats taken from the same browser can be told apart from the rest without revealing either.
+    pub fn fingerprint(&self) -> Option<String> {
+        Some(fingerprint(self.address?, self.user_agent.as_deref()))
+    }
+
+    /// Who the connection is, for checking against the ban list, along with the player secret it is using if known.
+    fn visitor<'a>(&self, secret: Option<Uuid>, fingerprint: &'a Option<String>) -> Visitor<'a> {
+        Visitor { secret, address: self.address, fingerprint: fingerprint.as_deref() }
+    }
+}
+
+pub fn fingerprint(address: IpAddr, user_agent: Option<&str>) -> String {
+    let hash = Sha256::new().chain_update(address.to_string()).chain_update(b"\0").chain_update(user_agent.unwrap_or_default()).finalize();
+    URL_SAFE_NO_PAD.encode(&hash[..12])
+}
+
+/// Remove games that have had nobody connected for a while.
+/// Asynchronous games are played by people dropping in now and then, so they are kept for much longer, and scheduled games are kept until they open.
+pub fn cleanup_global_state(state: &GlobalState) {
+    let now = SystemTime::now();
+    let expired = |data: &GameState| {
+        if data.opens_at().is_some() {
+            return false
+        }
+        let threshold = now - if data.options.asynchronous && data.winner().is_none() { ASYNC_GAME_IDLE_LIMIT } else { GAME_IDLE_LIMIT };
+        data.timeout.is_some_and(|timeout| timeout < threshold) && !data.conn.values().any(|val| val.connected)
+    };
+    // games are checked without holding the map, and games busy with a message are left for the next pass, so cleanup never waits on a game
+    let candidates: Vec<Uuid> = all_games(state).into_iter().filter(|(_, game)| game.try_lock().is_some_and(|data| expired(&data))).map(|(id, _)| id).collect();
+    if candidates.is_empty() {
+        return
+    }
+    let mut games = state.write();
+    for id in candidates {
+        let still_expired = games.get(&id).and_then(|game| game.try_lock().map(|data| expired(&data)));
+        if still_expired == Some(true) {
+            games.remove(&id);
+        }
+    }
+}
+
+/// Handle a message from a client, through the middleware every connection goes through.
+pub fn handle_message(server: &ServerState, ctx: &mut ConnectionContext, msg: ClientProtocol) {
+    CatchPanics(RateLimit(Authenticate(dispatch_message))).dispatch(server, ctx, msg)
+}
+
+fn dispatch_message(server: &ServerState, ctx: &mut ConnectionContext, msg: ClientProtocol) {
+    let state = &server.games;
+    let webhooks = &server.webhooks;
+    let tokens = &server.tokens;
+    let limits = &server.limits;
+
+    match msg {
+        ClientProtocol::HostGame { nickname, options, avatar, color, player_secret, preset, captcha } => {
+            let options = match &preset {
+                Some(name) => player_secret.and_then(|secret| server.presets.get(secret, name)),
+                None => Some(options)
+            };
+            let mut conn = PlayerConnection::new(ctx.tx.clone());
+            conn.fingerprint = ctx.fingerprint();
+            conn.topics = ctx.topics.clone();
+            conn.language = ctx.language;
+            conn.view_version = ctx.view_version;
+            if match ctx.game {
+                Some(game_uuid) => {
+                    let mut found_game = false;
+                    if let Some(game_state) = get_game(state, &game_uuid) {
+                        if game_state.lock().is_in_game() {
+                            conn.send(&ServerProtocol::Alert { message: "You cannot join another game while you are currently in a game!".into() });
+                            found_game = true;
+                        }
+                    }
+                    !found_game
+                }
+                None => true
+            } {
+                if !limits.can_host(state.read().len()) {
+                    conn.send(&limits.busy());
+                }
+                else if let Some(ban) = server.bans.find(&ctx.visitor(player_secret, &conn.fingerprint), ctx.community.as_deref(), SystemTime::now()) {
+                    conn.send(&ServerProtocol::Banned { reason: &ban.reason, expires_at: ban.expires_at });
+                }
+                else if server.in_other_game(player_secret, None) {
+                    conn.send(&ServerProtocol::Alert { message: "You are already playing in another game!".into() });
+                }
+                else if nickname.trim().is_empty() {
+                    conn.send(&ServerProtocol::Alert { message: "Your nickname cannot be empty.".into() });
+                    conn.send(&ServerProtocol::NicknameSuggestion { nickname: nicknames::suggest(&mut rand::thread_rng(), options.as_ref().map(|options| options.theme.preset).unwrap_or_default(), |_| false) });
+                }
+                else if options.as_ref().and_then(|options| options.scheduled_at).is_some_and(|at| at > epoch_millis(SystemTime::now() + MAX_SCHEDULE_AHEAD)) {
+                    conn.send(&ServerProtocol::Alert { message: "Games can be scheduled at most 30 days ahead.".into() });
+                }
+                else if let Err(message) = conn.set_profile(avatar, color) {
+                    conn.send(&ServerProtocol::Alert { message: message.into() });
+                }
+                // checked last, since a challenge can only be used once
+                else if !server.host_gate.admit(captcha.as_deref()) {
+                    conn.send(&ServerProtocol::Alert { message: "Please complete the captcha to host a game.".into() });
+                }
+                else if let Some(options) = options {
+                    let mut new_gamestate = GameState::with_options(options);
+                    new_gamestate.community = ctx.community.clone();
+                    let player_uuid = Uuid::new_v4();
+                    let secret = player_secret.unwrap_or_else(Uuid::new_v4);
+                    ctx.game = Some(Uuid::new_v4());
+                    ctx.player = Some(player_uuid);
+                    conn.secret = Some(secret);
+                    conn.name = Some(nickname.clone());
+                    send_identifiers(server, &conn, ctx.game.unwrap(), player_uuid, secret);
+                    new_gamestate.add_player(player_uuid, conn);
+                    new_gamestate.send_game_state(player_uuid);
+                    webhooks.notify(WebhookEvent::Created, ctx.game.unwrap(), new_gamestate.summary());
+                    state.write().insert(ctx.game.unwrap(), Arc::new(FairMutex::new(new_gamestate)));
+                }
+                else {
+                    conn.send(&ServerProtocol::Alert { message: "You have no preset with that name.".into() });
+                }
+            }
+        }
+        ClientProtocol::SuggestNickname { game_id } => {
+            let nickname = match game_id.and_then(|game_id| get_game(state, &game_id)) {
+                Some(game) => game.lock().suggest_nickname(&mut rand::thread_rng()),
+                None => nicknames::suggest(&mut rand::thread_rng(), ThemePreset::default(), |_| false)
+            };
+            PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::NicknameSuggestion { nickname });
+        },
+        ClientProtocol::GetHostChallenge => {
+            PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::HostChallenge { challenge: server.host_gate.challenge() });
+        },
+        ClientProtocol::HostPractice { nickname } => {
+            let mut conn = PlayerConnection::new(ctx.tx.clone());
+            conn.topics = ctx.topics.clone();
+            conn.language = ctx.language;
+            conn.view_version = ctx.view_version;
+            if ctx.game.is_some_and(|game_id| get_game(state, &game_id).is_some_and(|game| game.lock().is_in_game())) {
+                conn.send(&ServerProtocol::Alert { message: "You cannot join another game while you are currently in a game!".into() });
+            }
+            else if !limits.can_host(state.read().len()) {
+                conn.send(&limits.busy());
+            }
+            else if nickname.trim().is_empty() {
+                conn.send(&ServerProtocol::Alert { message: "Your nickname cannot be empty.".into() });
+                conn.send(&ServerProtocol::NicknameSuggestion { nickname: nicknames::suggest(&mut rand::thread_rng(), ThemePreset::default(), |_| false) });
+            }
+            else {
+                let game_id = Uuid::new_v4();
+                let player_id = Uuid::new_v4();
+                let secret = Uuid::new_v4();
+                conn.secret = Some(secret);
+                conn.name = Some(nickname);
+                send_identifiers(server, &conn, game_id, player_id, secret);
+                let mut practice = GameState::new_practice(player_id, conn, 4);
+                practice.community = ctx.community.clone();
+                practice.send_game_state(player_id);
+                ctx.game = Some(game_id);
+                ctx.player = Some(player_id);
+                state.write().insert(game_id, Arc::new(FairMutex::new(practice)));
+            }
+        },
+        ClientProtocol::JoinGame { id, nickname, player_id, player_secret, resume_token, avatar, color } => {
+            let mut conn = PlayerConnection::new(ctx.tx.clone());
+            conn.fingerprint = ctx.fingerprint();
+            conn.topics = ctx.topics.clone();
+            conn.language = ctx.language;
+            conn.view_version = ctx.view_version;
+            conn.name = Some(nickname);
+            conn.secret = player_secret;
+            // a valid resume token stands in for both the player id and secret
+            let token_claims = resume_token.as_ref().and_then(|token| tokens.claims(token)).filter(|(game_id, _)| *game_id == id);
+            let player_id = token_claims.map(|(_, player)| player).or(player_id);
+            // a player returning with a resume token sends no secret, so bans are checked against the one their seat holds
+            let returning_secret = player_secret.or_else(|| {
+                let secret = token_claims.and_then(|(_, player)| get_game(state, &id)?.lock().get_player_secret(&player))?;
+                resume_token.as_ref().filter(|token| tokens.verify(token, secret)).map(|_| secret)
+            });
+            // players returning to a seat they already hold are let in even when the server is busy
+            if player_id.is_none() && !limits.can_join() {
+                conn.send(&limits.busy());
+            }
+            else if let Some(ban) = server.bans.find(&ctx.visitor(returning_secret, &conn.fingerprint), ctx.community.as_deref(), SystemTime::now()) {
+                conn.send(&ServerProtocol::Banned { reason: &ban.reason, expires_at: ban.expires_at });
+            }
+            else if resume_token.is_some() && token_claims.is_none() {
+                conn.send(&ServerProtocol::Alert { message: "Your session has expired. Please rejoin the game.".into() });
+            }
+            else if player_id.is_none() && server.in_other_game(player_secret, Some(id)) {
+                conn.send(&ServerProtocol::Alert { message: "You are already playing in another game!".into() });
+            }
+            else if let Err(message) = conn.set_profile(avatar, color) {
+                conn.send(&ServerProtocol::Alert { message: message.into() });
+            }
+            // the games of other communities are treated as though they do not exist
+            else if let Some(game_state) = get_game(state, &id).filter(|game| game.lock().community == ctx.community) {
+                if let Some(old_player_id) = player_id {
+                    let mut state = game_state.lock();
+                    state.timeout = None;
+                    if let Some(real_player_secret) = state.get_player_secret(&old_player_id) {
+                        let token_valid = resume_token.as_ref().is_some_and(|token| tokens.verify(token, real_player_secret));
+                        if !token_valid && Some(real_player_secret) != player_secret {
+                            conn.send(&ServerProtocol::Alert { message: "Invalid player secret passed to server!".into() });
+                        }
+                        // player ids are public, so only returns that prove they hold the seat count towards its reconnect budget
+                        else if let Err(throttled) = limits.reconnect(old_player_id, Instant::now()) {
+                            conn.send(&throttled);
+                        }
+                        else {
+                            ctx.game = Some(id);
+                            ctx.player = Some(old_player_id);
+                            conn.secret = Some(real_player_secret);
+                            if state.add_player(old_player_id, conn) {
+                                server.blocks.apply(&mut state);
+                                send_resume_token(state.conn.get(&old_player_id).unwrap(), tokens, id, old_player_id, real_player_secret);
+                                state.broadcast_game_state();
+                            }
+                            else {
+                                PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::Alert { message: "This game has already started!".into() });
+                            }
+                        }
+                    }
+                    else {
+                        conn.send(&ServerProtocol::Alert { message: "The player you are trying to join as does not exist!".into() });
+                    }
+                }
+                else {
+                    let player_id = player_id.unwrap_or_else(|| {Uuid::new_v4() });
+                    let secret = player_secret.unwrap_or_else(|| { Uuid::new_v4() });
+                    let data = &mut game_state.lock();
+                    conn.secret = Some(secret);
+                    if conn.name.as_deref().is_none_or(|name| name.trim().is_empty()) {
+                        conn.name = Some(data.suggest_nickname(&mut rand::thread_rng()));
+                    }
+                    let banned = data.is_banned(&conn);
+                    let blocked = data.conn.iter().any(|(seated, other)| data.has_player(seated) && other.secret.is_some_and(|other| server.blocks.mutual(secret, other)));
+                    if blocked {
+                        PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::Alert { message: "You and a player in this game have blocked each other.".into() });
+                    }
+                    else if data.add_player(player_id, conn) {
+                        ctx.game = Some(id);
+                        ctx.player = Some(player_id);
+                        server.blocks.apply(data);
+
+                        // notify players of successful join
+                        send_identifiers(server, data.conn.get(&player_id).unwrap(), id, player_id, secret);
+                        data.broadcast_game_state();
+                    }
+                    else {
+                        let message = if banned { "You have been removed from this game." } else if data.opens_at().is_some() { "This game has not opened for joining yet." } else if data.countdown().is_some() { "This game is about to start and is not taking new players." } else { "This game has already started!" };
+                        PlayerConnection::new(ctx.tx.clone()).send( &ServerProtocol::Alert { message: message.into() });
+                    }
+                }
+            }
+            else {
+                conn.send(&ServerProtocol::Alert { message: "The game that you are looking for does not exist!".into() });
+            }
+        },
+        ClientProtocol::StartGame { request_id } => {
+            if !game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                gs.apply(*pid, Action::Start).map(drop)
+            }) {
+                let conn = PlayerConnection::new(ctx.tx.clone());
+                conn.send(&ServerProtocol::Alert { message: "You are not currently in a game!".into() });
+            }
+        },
+        ClientProtocol::CallLobbyVote { motion, request_id } => {
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                gs.call_lobby_vote(*pid, motion.clone(), SystemTime::now())
+            });
+        },
+        ClientProtocol::CastLobbyVote { approve, request_id } => {
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                gs.cast_lobby_vote(*pid, approve, SystemTime::now())
+            });
+        },
+        ClientProtocol::UndoLastAction { request_id } => {
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                gs.request_undo(*pid, SystemTime::now())
+            });
+        },
+        ClientProtocol::StartCountdown { seconds, request_id } => {
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                gs.start_countdown(*pid, seconds, SystemTime::now())
+            });
+        },
+        ClientProtocol::CancelCountdown { request_id } => {
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                gs.cancel_countdown(*pid)
+            });
+        },
+        ClientProtocol::SetReady { ready, request_id } => {
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                gs.set_ready(*pid, ready)
+            });
+        },
+        ClientProtocol::AckRole { request_id } => {
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                gs.acknowledge_role(*pid)
+            });
+        },
+        ClientProtocol::Rematch { request_id } => {
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                gs.apply(*pid, Action::Rematch).map(drop)
+            });
+        },
+        ClientProtocol::SendChat { message, attachment } => {
+            if let Some(game) = ctx.game {
+                if let Some(state) = get_game(state, &game) {
+                    if let Some(player) = ctx.player {
+                        let mut line = ChatLine::new(player, &message, &server.chat_links);
+                        line.attachment = attachment;
+                        if attachment.is_some_and(|token| !server.uploads.belongs_to(&token, &game)) {
+                            PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::Alert { message: "That attachment is no longer available.".into() });
+                        }
+                        // nothing is left of a message that was only markup
+                        else if !line.message.trim().is_empty() || line.attachment.is_some() {
+                            state.lock().add_chat(line);
+                        }
+                    }
+                }
+            }
+        },
+        ClientProtocol::ChooseChancellor { player, request_id } => {
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                gs.apply(*pid, Action::Nominate { chancellor: player }).map(drop)
+            });
+        }
+        ClientProtocol::CallVote { request_id } => {
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                gs.apply(*pid, Action::CallVote).map(drop)
+            });
+        },
+        ClientProtocol::VoteChancellor { vote, request_id } => {
+            let game_id = ctx.game.unwrap_or_default();
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                let government = [gs.president(), gs.chancellor()];
+                let judging = matches!(gs.role(pid), Some(PlayerType::Liberal)) && !government.contains(&Some(*pid)) && !gs.is_practice();
+                let facist_government = government.iter().flatten().any(|p| !matches!(gs.role(p), Some(PlayerType::Liberal)));
+                gs.apply(*pid, Action::Vote { approve: vote })?;
+                if judging {
+                    server.audit.record_vote(game_id, gs.community.as_deref(), *pid, vote, facist_government);
+                }
+                Ok(())
+            });
+        },
+        ClientProtocol::PickCard { index, request_id } => {
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                gs.apply(*pid, Action::PickCard { index }).map(drop)
+            });
+        },
+        ClientProtocol::RequestVeto { request_id } => {
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                gs.apply(*pid, Action::RequestVeto).map(drop)
+            });
+        },
+        ClientProtocol::RespondVeto { accept, request_id } => {
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                gs.apply(*pid, Action::RespondVeto { accept }).map(drop)
+            });
+        },
+        ClientProtocol::Claim { cards, request_id } => {
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                gs.claim(*pid, cards.clone())
+            });
+        },
+        ClientProtocol::PresidentialPower { player, request_id } => {
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                gs.apply(*pid, Action::UsePower { target: player }).map(drop)
+            });
+        },
+        ClientProtocol::GetChatLog => {
+            if let Some(game) = ctx.game {
+                if let Some(state) = get_game(state, &game) {
+                    let state = state.lock();
+                    // lines from players the reader has blocked are left out
+                    let muted = ctx.player.and_then(|player| state.conn.get(&player)).map(|conn| &conn.muted);
+                    let log = state.chat_log.iter().filter(|line| !line.id.zip(muted).is_some_and(|(id, muted)| muted.contains(&id))).cloned().collect();
+                    PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::ChatLog { log: &log });
+                }
+            }
+        },
+        ClientProtocol::GetRules => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            match ctx.game.and_then(|game| get_game(state, &game).map(|game| game.lock().rules())) {
+                Some(rules) => conn.send(&ServerProtocol::Rules { rules }),
+                None => conn.send(&ServerProtocol::Alert { message: "You are not currently in a game!".into() })
+            }
+        },
+        ClientProtocol::GetTimeline => {
+            if let Some(game) = ctx.game {
+                if let Some(state) = get_game(state, &game) {
+                    let state = state.lock();
+                    PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::Timeline { events: state.timeline() });
+                }
+            }
+        },
+        ClientProtocol::RotateSecret => {
+            if let (Some(game_id), Some(player_id)) = (ctx.game, ctx.player) {
+                if let Some(game) = get_game(state, &game_id) {
+                    let game = &mut game.lock();
+                    if let Some(secret) = game.rotate_secret(player_id) {
+                        send_identifiers(server, game.conn.get(&player_id).unwrap(), game_id, player_id, secret);
+                    }
+                }
+            }
+        },
+        ClientProtocol::RevokeSecret { player, request_id } => {
+            let game_id = ctx.game.unwrap_or_default();
+            game_state_wrapper(server, &ctx.game, &ctx.player, request_id.as_deref(), &|gs: &mut GameState, pid| {
+                let secret = gs.revoke_secret(*pid, player)?;
+                send_identifiers(server, gs.conn.get(&player).unwrap(), game_id, player, secret);
+                Ok(())
+            });
+        },
+        // unwrapped by `Authenticate` before it gets here, so one nested in another is ignored
+        ClientProtocol::Authenticated { .. } => {},
+        ClientProtocol::WhereAmI { player_secret } => {
+            let current = server.current_game(player_secret);
+            PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::CurrentGame { game_id: current.map(|(game, _)| game), player_id: current.map(|(_, player)| player) });
+        },
+        ClientProtocol::SetEmail { email } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            let result = match (ctx.game, ctx.player) {
+                _ if email.is_some() && !server.email.is_enabled() => Err("Email is not set up on this server."),
+                (Some(game_id), Some(player_id)) => server.email.set_address(game_id, player_id, email.as_deref()),
+                _ => Err("You are not in a game.")
+            };
+            let text = |message: SystemMessage| match (ctx.game.and_then(|game_id| get_game(state, &game_id)), ctx.player) {
+                (Some(game), Some(player_id)) => game.lock().message_for(&player_id, message),
+                _ => message.text(ctx.language.unwrap_or_default(), &Theme::default())
+            };
+            match result {
+                Ok(()) if email.is_some() => conn.send(&ServerProtocol::Alert { message: text(SystemMessage::EmailEnabled) }),
+                Ok(()) => conn.send(&ServerProtocol::Alert { message: text(SystemMessage::EmailDisabled) }),
+                Err(message) => conn.send(&ServerProtocol::Alert { message: message.into() })
+            }
+        },
+        ClientProtocol::InviteByEmail { email } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            let game = ctx.game.and_then(|game_id| get_game(state, &game_id));
+            let result = match (game, ctx.player) {
+                (Some(game), Some(player_id)) => {
+                    let game = game.lock();
+                    let name = game.conn.get(&player_id).and_then(|c| c.name.clone()).unwrap_or_default();
+                    if game.is_practice() || !matches!(game.turn_phase(), TurnPhase::Lobby) {
+                        Err("Players can only be invited to a game that has not started.")
+                    }
+                    else {
+                        server.email.invite(ctx.game.unwrap(), &name, &email)
+                    }
+                },
+                _ => Err("You are not in a game.")
+            };
+            match result {
+                Ok(()) => conn.send(&ServerProtocol::Alert { message: format!("An invitation is on its way to {}.", email.trim()) }),
+                Err(message) => conn.send(&ServerProtocol::Alert { message: message.into() })
+            }
+        },
+        ClientProtocol::ReportPlayer { player, reason } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            let game = ctx.game.and_then(|game_id| get_game(state, &game_id));
+            let result = match (game, ctx.player) {
+                (Some(_), Some(reporter)) if reporter == player => Err("You cannot report yourself."),
+                (Some(game), Some(reporter)) => {
+                    let game = game.lock();
+                    if game.conn.contains_key(&player) {
+                        server.audit.report(ctx.game.unwrap(), game.community.as_deref(), reporter, player, &reason)
+                    }
+                    else {
+                        Err("That player is not in this game.")
+                    }
+                },
+                _ => Err("You are not in a game.")
+            };
+            match result {
+                Ok(()) => conn.send(&ServerProtocol::Alert { message: "Thanks, your report has been passed on to the admins.".into() }),
+                Err(message) => conn.send(&ServerProtocol::Alert { message: message.into() })
+            }
+        },
+        ClientProtocol::SubmitFeedback { fun, rules_unclear, comment, reports } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            let comment = comment.map(|comment| comment.trim().to_string()).filter(|comment| !comment.is_empty());
+            let result = match (ctx.game, ctx.player, ctx.game.and_then(|game_id| get_game(state, &game_id))) {
+                (Some(game_id), Some(player), Some(game)) => {
+                    let (seated, community) = {
+                        let state = game.lock();
+                        let seated = if state.winner().is_none() { Err("You can give feedback once the game has ended.") } else { Ok(state.conn.keys().copied().collect::<Vec<Uuid>>()) };
+                        (seated, state.community.clone())
+                    };
+                    seated.and_then(|seated| {
+                        if fun.is_some_and(|fun| !(1..=5).contains(&fun)) {
+                            return Err("Please rate the game from 1 to 5.")
+                        }
+                        if comment.as_ref().is_some_and(|comment| comment.chars().count() > 500) {
+                            return Err("Please keep your feedback under 500 characters.")
+                        }
+                        for report in &reports {
+                            if report.player == player {
+                                return Err("You cannot report yourself.")
+                            }
+                            if !seated.contains(&report.player) {
+                                return Err("That player is not in this game.")
+                            }
+                            audit::check_reason(&report.reason)?;
+                        }
+                        let reported = reports.iter().map(|report| report.player).collect();
+                        server.archive.add_feedback(game_id, Feedback { player, fun, rules_unclear, comment, reported })?;
+                        for report in &reports {
+                            // the player may have reported someone during the game already, which is fine
+                            let _ = server.audit.report(game_id, community.as_deref(), player, report.player, &report.reason);
+                        }
+                        Ok(())
+                    })
+                },
+                _ => Err("You are not in a game.")
+            };
+            match result {
+                Ok(()) => conn.send(&ServerProtocol::Alert { message: "Thanks for your feedback!".into() }),
+                Err(message) => conn.send(&ServerProtocol::Alert { message: message.into() })
+            }
+        },
+        ClientProtocol::SavePreset { player_secret, name, options } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            match server.presets.save(player_secret, &name, options) {
+                Ok(()) => conn.send(&ServerProtocol::Presets { presets: &server.presets.list(player_secret) }),
+                Err(message) => conn.send(&ServerProtocol::Alert { message: message.into() })
+            }
+        },
+        ClientProtocol::DeletePreset { player_secret, name } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            if server.presets.delete(player_secret, &name) {
+                conn.send(&ServerProtocol::Presets { presets: &server.presets.list(player_secret) });
+            }
+            else {
+                conn.send(&ServerProtocol::Alert { message: "You have no preset with that name.".into() });
+            }
+        },
+        ClientProtocol::ListPresets { player_secret } => {
+            PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::Presets { presets: &server.presets.list(player_secret) });
+        },
+        ClientProtocol::AddFriend { player_secret, friend_id } => {
+            match server.friends.add(player_secret, friend_id) {
+                Ok(_) => server.send_friends(ctx.tx.clone(), player_secret),
+                Err(message) => PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::Alert { message: message.into() })
+            }
+        },
+        ClientProtocol::RemoveFriend { player_secret, friend_id } => {
+            match server.friends.remove(player_secret, friend_id) {
+                Ok(()) => server.send_friends(ctx.tx.clone(), player_secret),
+                Err(message) => PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::Alert { message: message.into() })
+            }
+        },
+        ClientProtocol::ListFriends { player_secret } => {
+            server.send_friends(ctx.tx.clone(), player_secret);
+            server.friends.set_inbox(player_secret, ctx.tx.clone());
+        },
+        ClientProtocol::BlockPlayer { player_secret, friend_id, player } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            // players in a game only know each other by player id
+            let seated = player.and_then(|player| {
+                let game = get_game(state, &ctx.game?)?;
+                let secret = game.lock().get_player_secret(&player)?;
+                Some(friends::friend_id(secret))
+            });
+            let result = match friend_id.or(seated) {
+                Some(blocked) => server.blocks.block(player_secret, blocked),
+                None => Err("There is no such player to block.")
+            };
+            match result {
+                Ok(()) => {
+                    server.apply_blocks(player_secret);
+                    conn.send(&ServerProtocol::Blocked { blocked: &server.blocks.list(player_secret) });
+                },
+                Err(message) => conn.send(&ServerProtocol::Alert { message: message.into() })
+            }
+        },
+        ClientProtocol::UnblockPlayer { player_secret, friend_id } => {
+            server.blocks.unblock(player_secret, friend_id);
+            server.apply_blocks(player_secret);
+            PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::Blocked { blocked: &server.blocks.list(player_secret) });
+        },
+        ClientProtocol::ListBlocked { player_secret } => {
+            PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::Blocked { blocked: &server.blocks.list(player_secret) });
+        },
+        ClientProtocol::InviteFriend { friend_id, game_id } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            let game = get_game(state, &game_id);
+            let result = match (game, ctx.player) {
+                (Some(game), Some(player_id)) if ctx.game == Some(game_id) => {
+                    let game = game.lock();
+                    let sender = game.conn.get(&player_id).and_then(|c| Some((c.secret?, c.name.clone().unwrap_or_default())));
+                    match sender {
+                        _ if game.is_practice() || !matches!(game.turn_phase(), TurnPhase::Lobby) => Err("Players can only be invited to a game that has not started."),
+                        Some((secret, name)) => server.friends.invite(secret, friend_id, name, game_id),
+                        None => Err("You are not in that game.")
+                    }
+                },
+                _ => Err("You are not in that game.")
+            };
+            match result {
+                Ok((invite, friend_secret)) => {
+                    let delivered = server.send_to_player(friend_secret, &ServerProtocol::InviteReceived { invite: &invite });
+                    let message = if delivered { "Your friend has been invited." } else { "Your friend will see the invitation when they next come online." };
+                    conn.send(&ServerProtocol::Alert { message: message.into() });
+                },
+                Err(message) => conn.send(&ServerProtocol::Alert { message: message.into() })
+            }
+        },
+        ClientProtocol::RespondToInvite { player_secret, invite_id, accept } => {
+            match server.friends.respond(player_secret, invite_id) {
+                Ok((_, from)) => {
+                    let message = if accept { "A friend accepted your invitation." } else { "A friend turned down your invitation." };
+                    server.send_to_player(from, &ServerProtocol::Alert { message: message.into() });
+                    server.send_friends(ctx.tx.clone(), player_secret);
+                },
+                Err(message) => PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::Alert { message: message.into() })
+            }
+        },
+        ClientProtocol::ExportGame { admin_token, game_id } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            let game = get_game(state, &game_id);
+            match game {
+                _ if !server.is_admin(&admin_token) => conn.send(&ServerProtocol::Alert { message: "Invalid admin token.".into() }),
+                Some(game) => match game.lock().export() {
+                    Some(export) => conn.send(&ServerProtocol::GameExport { game_id, game: &export }),
+                    None => conn.send(&ServerProtocol::Alert { message: "Practice games cannot be exported.".into() })
+                },
+                None => conn.send(&ServerProtocol::Alert { message: "The game that you are looking for does not exist!".into() })
+            }
+        },
+        ClientProtocol::DebugGame { admin_token, game_id, redact_deck } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            match get_game(state, &game_id) {
+                _ if !server.is_admin(&admin_token) => conn.send(&ServerProtocol::Alert { message: "Invalid admin token.".into() }),
+                Some(game) => {
+                    let debug = game.lock().debug(redact_deck);
+                    conn.send(&ServerProtocol::GameDebug { game_id, debug: &debug });
+                },
+                None => conn.send(&ServerProtocol::Alert { message: "The game that you are looking for does not exist!".into() })
+            }
+        },
+        ClientProtocol::ImportGame { admin_token, game_id, game } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            if !server.is_admin(&admin_token) {
+                conn.send(&ServerProtocol::Alert { message: "Invalid admin token.".into() });
+            }
+            else if let Err(error) = game.validate() {
+                conn.send(&ServerProtocol::Error { message: error.to_string(), error: &error, request_id: None });
+            }
+            else if server.import_game(game_id, *game) {
+                conn.send(&ServerProtocol::Alert { message: "The game has been imported.".into() });
+            }
+            else {
+                conn.send(&ServerProtocol::Alert { message: "A game with that id already exists.".into() });
+            }
+        },
+        ClientProtocol::SetMotd { admin_token, message, community } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            if !server.is_admin(&admin_token) {
+                conn.send(&ServerProtocol::Alert { message: "Invalid admin token.".into() });
+            }
+            else if !server.communities.exists(community.as_deref()) {
+                conn.send(&ServerProtocol::Alert { message: "There is no community with that name.".into() });
+            }
+            else {
+                conn.send(&ServerProtocol::Alert { message: set_motd(server, community, message).map_or_else(|message| message, |_| "The message of the day has been updated.").into() });
+            }
+        },
+        ClientProtocol::Announce { admin_token, game_id, message } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            match server.is_admin(&admin_token) {
+                true => conn.send(&ServerProtocol::Alert { message: announce(server, game_id, None, &message).map_or_else(|message| message, |_| "The announcement has been sent.").into() }),
+                false => conn.send(&ServerProtocol::Alert { message: "Invalid admin token.".into() })
+            }
+        },
+        ClientProtocol::Ban { admin_token, target, reason, expires_at, community } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            if !server.is_admin(&admin_token) {
+                conn.send(&ServerProtocol::Alert { message: "Invalid admin token.".into() });
+            }
+            else if !server.communities.exists(community.as_deref()) {
+                conn.send(&ServerProtocol::Alert { message: "There is no community with that name.".into() });
+            }
+            else {
+                match server.bans.add(target, &reason, expires_at, community) {
+                    Ok(ban) => conn.send(&ServerProtocol::Bans { bans: &[ban] }),
+                    Err(message) => conn.send(&ServerProtocol::Alert { message: message.into() })
+                }
+            }
+        },
+        ClientProtocol::Unban { admin_token, ban_id } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            match server.is_admin(&admin_token) {
+                true => conn.send(&ServerProtocol::Alert { message: server.bans.remove(ban_id).map_or_else(|message| message, |_| "The ban has been lifted.").into() }),
+                false => conn.send(&ServerProtocol::Alert { message: "Invalid admin token.".into() })
+            }
+        },
+        ClientProtocol::SetBanAppeal { admin_token, ban_id, note } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            match server.is_admin(&admin_token) {
+                true => conn.send(&ServerProtocol::Alert { message: server.bans.set_appeal(ban_id, note).map_or_else(|message| message, |_| "The appeal has been noted.").into() }),
+                false => conn.send(&ServerProtocol::Alert { message: "Invalid admin token.".into() })
+            }
+        },
+        ClientProtocol::ListBans { admin_token } => {
+            let conn = PlayerConnection::new(ctx.tx.clone());
+            match server.is_admin(&admin_token) {
+                true => conn.send(&ServerProtocol::Bans { bans: &server.bans.list() }),
+                false => conn.send(&ServerProtocol::Alert { message: "Invalid admin token.".into() })
+            }
+        },
+        ClientProtocol::Moderate { player_secret, community, action } => {
+            moderate(server, &PlayerConnection::new(ctx.tx.clone()), player_secret, &community, action);
+        },
+        ClientProtocol::SetLanguage { language } => {
+            ctx.language = language;
+            if let (Some(game_id), Some(player_id)) = (ctx.game, ctx.player) {
+                if let Some(game) = get_game(state, &game_id) {
+                    if let Some(conn) = game.lock().conn.get_mut(&player_id) {
+                        conn.language = language;
+                    }
+                }
+            }
+        },
+        ClientProtocol::SetViewVersion { version } => {
+            ctx.view_version = negotiate_view_version(version);
+            PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::ViewVersion { version: ctx.view_version, latest: LATEST_VIEW_VERSION });
+            if let (Some(game_id), Some(player_id)) = (ctx.game, ctx.player) {
+                if let Some(game) = get_game(state, &game_id) {
+                    let game = &mut game.lock();
+                    if let Some(conn) = game.conn.get_mut(&player_id) {
+                        conn.view_version = ctx.view_version;
+                    }
+                    game.send_game_state(player_id);
+                }
+            }
+        },
+        ClientProtocol::Subscribe { topics: new_topics } => {
+            ctx.topics = new_topics;
+            if let (Some(game_id), Some(player_id)) = (ctx.game, ctx.player) {
+                if let Some(game) = get_game(state, &game_id) {
+                    let game = &mut game.lock();
+                    if let Some(conn) = game.conn.get_mut(&player_id) {
+                        conn.topics = ctx.topics.clone();
+                        conn.language = ctx.language;
+                        conn.view_version = ctx.view_version;
+                    }
+                    game.send_game_state(player_id);
+                }
+            }
+        },
+        ClientProtocol::Leave => {
+            if let Some(game) = ctx.game {
+                if let Some(state) = get_game(state, &game) {
+                    if let Some(player) = ctx.player {
+                        let state = &mut state.lock();
+                        state.delete_player(player);
+                        state.broadcast_game_state();
+                    }
+                    ctx.game = None;
+                    ctx.player = None;
+                }
+            }
+        },
+    }
+
+    // the audit log looks for seats in the same game sharing an address
+    if let (Some(game_id), Some(player_id), Some(address)) = (ctx.game, ctx.player, ctx.address) {
+        server.audit.record_address(game_id, ctx.community.as_deref(), player_id, address);
+    }
+}
+
+/// Set or clear the message of the day for the server, or for one community, and pass it on to the lobbies it applies to.
+fn set_motd(server: &ServerState, community: Option<String>, message: Option<String>) -> Result<(), &'static str> {
+    let message = message.map(|message| message.trim().to_string()).filter(|message| !message.is_empty());
+    if message.as_ref().is_some_and(|message| message.chars().count() > MAX_ANNOUNCEMENT_LENGTH) {
+        return Err("Announcements can be at most 500 characters long.")
+    }
+    match &message {
+        Some(message) => server.motd.write().insert(community.clone(), message.clone()),
+        None => server.motd.write().remove(&community)
+    };
+    // players already in a game are left alone, and see the new message when they next connect
+    if let Some(message) = &message {
+        for (_, game) in all_games(&server.games) {
+            let game = game.lock();
+            // the server's own message goes to every community, and a community's only to its own lobbies
+            if matches!(game.turn_phase(), TurnPhase::Lobby) && (community.is_none() || game.community == community) {
+                game.conn.values().for_each(|conn| conn.send(&ServerProtocol::Motd { message }));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Send a message to everyone in a game, which has to belong to the community if one is given.
+fn announce(server: &ServerState, game_id: Uuid, community: Option<&str>, message: &str) -> Result<(), &'static str> {
+    let message = message.trim();
+    if message.is_empty() || message.chars().count() > MAX_ANNOUNCEMENT_LENGTH {
+        return Err("Announcements must be between 1 and 500 characters long.")
+    }
+    let game = get_game(&server.games, &game_id).ok_or("The game that you are looking for does not exist!")?;
+    let game = game.lock();
+    if community.is_some_and(|community| game.community.as_deref() != Some(community)) {
+        return Err("That game is not part of this community.")
+    }
+    game.conn.values().for_each(|conn| conn.send(&ServerProtocol::Announcement { message }));
+    Ok(())
+}
+
+/// Carry out an action for one of a community's owners or moderators, within that community alone.
+fn moderate(server: &ServerState, conn: &PlayerConnection, secret: Uuid, community: &str, action: ModeratorAction) {
+    let needed = match action {
+        ModeratorAction::AddModerator { .. } | ModeratorAction::RemoveModerator { .. } => Role::Owner,
+        _ => Role::Moderator
+    };
+    if let Err(message) = server.communities.authorize(community, secret, needed) {
+        conn.send(&ServerProtocol::Alert { message: message.into() });
+        return
+    }
+    let alert = |result: Result<(), &'static str>, done: &'static str| conn.send(&ServerProtocol::Alert { message: result.map_or_else(|message| message, |_| done).into() });
+    let bans = || server.bans.list().into_iter().filter(|ban| ban.community.as_deref() == Some(community)).collect::<Vec<Ban>>();
+    match action {
+        ModeratorAction::Kick { game_id, player } => {
+            let result = match get_game(&server.games, &game_id) {
+                Some(game) => {
+                    let state = &mut game.lock();
+                    if state.community.as_deref() != Some(community) {
+                        Err("That game is not part of this community.")
+                    }
+                    else {
+                        let result = state.kick(player).map_err(|error| match error {
+                            GameError::AlreadyStarted => "Seated players can only be removed before the game starts.",
+                            _ => "That player is not in this game."
+                        });
+                        if result.is_ok() {
+                            state.broadcast_game_state();
+                        }
+                        result
+                    }
+                },
+                None => Err("The game that you are looking for does not exist!")
+            };
+            alert(result, "The player has been removed from the game.");
+        },
+        ModeratorAction::Ban { target, reason, expires_at } => {
+            match server.bans.add(target, &reason, expires_at, Some(community.to_string())) {
+                Ok(ban) => conn.send(&ServerProtocol::Bans { bans: &[ban] }),
+                Err(message) => conn.send(&ServerProtocol::Alert { message: message.into() })
+            }
+        },
+        ModeratorAction::Unban { ban_id } => {
+            // moderators can only lift the bans of their own community
+            let result = match bans().iter().any(|ban| ban.id == ban_id) {
+                true => server.bans.remove(ban_id),
+                false => Err("There is no ban with that id.")
+            };
+            alert(result, "The ban has been lifted.");
+        },
+        ModeratorAction::ListBans => conn.send(&ServerProtocol::Bans { bans: &bans() }),
+        ModeratorAction::SetMotd { message } => alert(set_motd(server, Some(community.to_string()), message), "The message of the day has been updated."),
+        ModeratorAction::Announce { game_id, message } => alert(announce(server, game_id, Some(community), &message), "The announcement has been sent."),
+        ModeratorAction::AddModerator { friend_id } => alert(server.communities.add_moderator(community, friend_id), "The player is now a moderator."),
+        ModeratorAction::RemoveModerator { friend_id } => alert(server.communities.remove_moderator(community, friend_id), "The player is no longer a moderator."),
+        ModeratorAction::ListModerators => conn.send(&ServerProtocol::Moderators { community, owners: &server.communities.owners(community), moderators: &server.communities.moderators(community) }),
+    }
+}
+
+/// Greet a new connection with the server's message of the day and its community's, if there are any.
+pub fn handle_connect(server: &ServerState, ctx: &ConnectionContext) {
+    let motd = server.motd.read();
+    let messages = [motd.get(&None), ctx.community.as_ref().and_then(|community| motd.get(&Some(community.clone())))];
+    for message in messages.iter().flatten() {
+        PlayerConnection::new(ctx.tx.clone()).send(&ServerProtocol::Motd { message });
+    }
+}
+
+/// Read a message as it arrived from a client. Anything that is not a valid message gives none, and is ignored.
+/// Messages that are too large or too deeply nested are turned away before they are parsed.
+pub fn parse_message(raw: &str) -> Option<ClientProtocol> {
+    if raw.len() > MAX_MESSAGE_BYTES || nested_deeper_than(raw, MAX_MESSAGE_DEPTH) {
+        return None
+    }
+    serde_json::from_str(raw).ok()
+}
+
+/// What the server does with a frame read from a websocket.
+#[derive(Debug)]
+pub enum Frame {
+    Message(Box<ClientProtocol>),
+    /// A ping, answered with a pong carrying the same payload.
+    Ping(Vec<u8>),
+    /// The client closed the connection. A normal closure means the player chose to leave, while any other, such as from a page being closed, keeps their seat as a dropped connection would.
+    Close { leave: bool },
+    /// Pongs, and anything that is not a valid message.
+    Ignore,
+}
+
+/// Work out what a websocket frame asks for. Binary frames are read as text, for clients that send their messages as bytes.
+pub fn read_frame(frame: &Message) -> Frame {
+    if frame.is_ping() {
+        Frame::Ping(frame.as_bytes().to_vec())
+    }
+    else if frame.is_close() {
+        Frame::Close { leave: frame.close_frame().is_some_and(|(code, _)| code == 1000) }
+    }
+    else if frame.is_text() || frame.is_binary() {
+        std::str::from_utf8(frame.as_bytes()).ok().and_then(parse_message).map_or(Frame::Ignore, |msg| Frame::Message(Box::new(msg)))
+    }
+    else {
+        Frame::Ignore
+    }
+}
+
+/// Whether objects and arrays in the text are nested more than `limit` deep, not counting brackets inside strings.
+fn nested_deeper_than(raw: &str, limit: usize) -> bool {
+    let (mut depth, mut in_string, mut escaped) = (0usize, false, false);
+    for byte in raw.bytes() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                if depth > limit {
+                    return true
+                }
+            },
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Clean up after the connection is closed.
+pub fn handle_disconnect(server: &ServerState, ctx: &ConnectionContext) {
+    server.friends.close_inbox(&ctx.tx);
+    let state = &server.games;
+    if let Some(game_uuid) = ctx.game {
+        let mut remove_game = false;
+
+        if let Some(player_uuid) = ctx.player {
+            if let Some(game) = get_game(state, &game_uuid) {
+                let game = &mut game.lock();
+                // a player who has moved to another connection keeps their seat
+                let moved = game.conn.get(&player_uuid).is_some_and(|conn| !conn.tx.is_attached(&ctx.tx));
+                if !moved {
+                    if let Some(conn) = game.conn.get(&player_uuid) {
+                        conn.tx.detach();
+                    }
+                    game.remove_player(player_uuid);
+                    game.broadcast_game_state();
+                }
+                remove_game = !game.has_connected_players();
+            }
+        }
+
+        if remove_game {
+            if let Some(game) = get_game(state, &game_uuid) {
+                game.lock().timeout = Some(SystemTime::now());
+            }
+        }
+    }
+}
+
+/// Pair players with their names, leaving out bots.
+fn named<'a>(state: &GameState, players: impl Iterator<Item = &'a Uuid>) -> Vec<(Uuid, String)> {
+    players.filter_map(|player| {
+        let name = state.conn.get(player).filter(|conn| !conn.is_bot)?.name.clone()?;
+        Some((*player, name))
+    }).collect()
+}
+
+fn send_identifiers(server: &ServerState, conn: &PlayerConnection, game_id: Uuid, player_id: Uuid, secret: Uuid) {
+    conn.send(&ServerProtocol::SetIdentifiers { player_id, game_id, secret });
+    server.track_player(secret, game_id, player_id);
+    send_resume_token(conn, &server.tokens, game_id, player_id, secret);
+}
+
+fn send_resume_token(conn: &PlayerConnection, tokens: &ResumeTokens, game_id: Uuid, player_id: Uuid, secret: Uuid) {
+    let (token, expires) = tokens.issue(game_id, player_id, secret);
+    conn.send(&ServerProtocol::ResumeToken { token, expires_at: epoch_millis(expires) });
+}
+
+fn game_state_wrapper(server: &ServerState, game_id: &Option<Uuid>, player_id: &Option<Uuid>, request_id: Option<&str>, func: &dyn Fn(&mut GameState, &Uuid) -> Result<(), GameError>) -> bool {
+    if let (Some(game_id), Some(player_id)) = (game_id, player_id) {
+        if let Some(state) = get_game(&server.games, game_id) {
+            let state = &mut state.lock();
+            // a retried request gets the original answer without being applied again
+            if let Some(result) = request_id.and_then(|id| state.processed_result(player_id, id)) {
+                if result.is_ok() {
+                    state.send_game_state(*player_id);
+                }
+                send_result(state, player_id, request_id, &result);
+                return true
+            }
+            let was_in_game = state.is_in_game();
+            let awaiting = state.awaiting();
+            let reaction = SystemTime::now().duration_since(state.phase_started_at()).unwrap_or_default();
+            let result = func(state, player_id);
+            if let Some(id) = request_id {
+                state.record_result(*player_id, id.to_string(), result.clone());
+            }
+            match &result {
+                Ok(_) => {
+                    if was_in_game && !state.is_practice() {
+                        server.audit.record_action(*game_id, state.community.as_deref(), *player_id, reaction);
+                    }
+                    state.run_bots();
+                    state.advance_tutorial();
+                    state.broadcast_game_state();
+                    server.notify(*game_id, state, was_in_game, &awaiting);
+                },
+                Err(error) => {
+                    eprintln!("[{}] game {} player {}: {:?}", error.severity(), game_id, player_id, error);
+                }
+            }
+            send_result(state, player_id, request_id, &result);
+            return true
+        }
+    }
+    false
+}
+
+/// Tell the player about a rejected action, or acknowledge a successful one if they asked with a request id.
+fn send_result(state: &GameState, player_id: &Uuid, request_id: Option<&str>, result: &Result<(), GameError>) {
+    if let Some(conn) = state.conn.get(player_id) {
+        match (result, request_id) {
+            (Err(error), _) => conn.send(&ServerProtocol::Error { message: error.to_string(), error, request_id }),
+            (Ok(_), Some(request_id)) => conn.send(&ServerProtocol::Ack { request_id }),
+            (Ok(_), None) => {}
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::{collections::HashSet, fs, io, path::{Path, PathBuf}};
+
+use uuid::Uuid;
+
+use secrethitler_core::game_state::GameExport;
+
+use crate::server::{ServerState, all_games};
+
+/// Snapshots are written here first and renamed into place, so a crash mid-write leaves the previous snapshot intact.
+const PARTIAL_EXTENSION: &str = "partial";
+
+/// Copies of the games in progress, written to a directory every so often so a crashed server can pick up where it left off.
+/// Each game is kept in its own file, named after its id, in the same form as `ExportGame`.
+pub struct Snapshots {
+    dir: PathBuf,
+}
+
+impl Snapshots {
+    pub fn new(dir: PathBuf) -> Snapshots {
+        if let Err(e) = fs::create_dir_all(&dir) {
+            eprintln!("could not create snapshot directory {}: {}", dir.display(), e);
+        }
+        Snapshots { dir }
+    }
+
+    fn path(&self, game_id: Uuid) -> PathBuf {
+        self.dir.join(format!("{}.json", game_id))
+    }
+
+    /// Write every game in progress, and remove the snapshots of games that have ended or gone.
+    /// Returns the number of games written.
+    pub fn save(&self, server: &ServerState) -> usize {
+        let mut saved = HashSet::new();
+        for (game_id, game) in all_games(&server.games) {
+            let export = {
+                let game = game.lock();
+                if !game.is_in_game() {
+                    continue
+                }
+                match game.export() {
+                    Some(export) => export,
+                    None => continue
+                }
+            };
+            match self.write(game_id, &export) {
+                Ok(()) => {
+                    saved.insert(game_id);
+                },
+                Err(e) => eprintln!("could not save a snapshot of game {}: {}", game_id, e)
+            }
+        }
+        for (game_id, path) in self.list() {
+            if !saved.contains(&game_id) {
+                let _ = fs::remove_file(path);
+            }
+        }
+        saved.len()
+    }
+
+    fn write(&self, game_id: Uuid, export: &GameExport) -> io::Result<()> {
+        let path = self.path(game_id);
+        let partial = path.with_extension(PARTIAL_EXTENSION);
+        fs::write(&partial, serde_json::to_vec(export)?)?;
+        fs::rename(partial, path)
+    }
+
+    /// Bring back every game with a snapshot, as though an admin had imported it.
+    /// Returns the number of games restored.
+    pub fn resume(&self, server: &ServerState) -> usize {
+        let mut restored = 0;
+        for (game_id, path) in self.list() {
+            // a snapshot is checked like any other import, so one that was edited or cut short is left out
+            match read(&path).and_then(|export| export.validate().map(|_| export).map_err(|e| e.to_string())) {
+                Ok(export) => if server.import_game(game_id, export) {
+                    restored += 1;
+                },
+                Err(e) => eprintln!("could not resume game {} from {}: {}", game_id, path.display(), e)
+            }
+        }
+        restored
+    }
+
+    /// Every finished snapshot in the directory and the game it belongs to.
+    fn list(&self) -> Vec<(Uuid, PathBuf)> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return vec![]
+        };
+        entries.filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "json" {
+                return None
+            }
+            let game_id = path.file_stem()?.to_str()?.parse().ok()?;
+            Some((game_id, path))
+        }).collect()
+    }
+}
+
+fn read(path: &Path) -> Result<GameExport, String> {
+    let contents = fs::read(path).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&contents).map_err(|e| e.to_string())
+}
@@ -0,0 +1,128 @@
+use std::{convert::Infallible, net::IpAddr, sync::Arc};
+
+use futures::StreamExt;
+use secrethitler_core::protocol::{MessageSink, Sink};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use uuid::Uuid;
+use warp::{Filter, Rejection, Reply, http::StatusCode, hyper::body::Bytes, sse::Event};
+
+use crate::{addresses, server::{ConnectionContext, ServerState, get_game, handle_disconnect, handle_message, parse_message}};
+
+/// The seat that an event stream or a posted message belongs to.
+/// Event streams cannot send headers from the browser, so the secret is passed in the query string.
+#[derive(Deserialize)]
+struct Seat {
+    game_id: Uuid,
+    player_id: Uuid,
+    player_secret: Uuid,
+}
+
+/// Delivers messages to the task that writes to an event stream.
+struct EventSink(mpsc::UnboundedSender<String>);
+
+impl MessageSink for EventSink {
+    fn send(&self, message: String) -> Result<(), String> {
+        self.0.send(message).map_err(|e| e.to_string())
+    }
+}
+
+/// Cleans up after an event stream in the same way as a closed websocket, once the client stops reading it.
+struct Connection {
+    server: ServerState,
+    ctx: ConnectionContext,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        handle_disconnect(&self.server, &self.ctx);
+    }
+}
+
+/// Let players who already hold a seat use server-sent events instead of a websocket, for networks that block websockets.
+/// `GET /events` moves the seat's messages to a new event stream, replaying anything missed, and `POST /events` takes the same messages a websocket would.
+pub fn route(server: ServerState) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone {
+    let stream_server = server.clone();
+    let events = warp::path!("events")
+        .and(warp::get())
+        .and(warp::query::<Seat>())
+        .and(addresses::client(server.addresses.clone()))
+        .map(move |seat: Seat, address: Option<IpAddr>| -> Box<dyn Reply> {
+            let (tx, rx) = mpsc::unbounded_channel();
+            let sink: Sink = Arc::new(EventSink(tx));
+            let mut ctx = match connect(&stream_server, &seat, sink) {
+                Some(ctx) => ctx,
+                None => return Box::new(warp::reply::with_status("invalid player secret", StatusCode::FORBIDDEN))
+            };
+            ctx.address = address;
+            let connection = Connection { server: stream_server.clone(), ctx };
+            let stream = UnboundedReceiverStream::new(rx).map(move |message| {
+                // the connection lives as long as the stream, so the seat is released when the client goes away
+                let _ = &connection;
+                Ok::<Event, Infallible>(Event::default().data(message))
+            });
+            Box::new(warp::sse::reply(warp::sse::keep_alive().stream(stream)))
+        });
+
+    let send = warp::path!("events")
+        .and(warp::post())
+        .and(warp::query::<Seat>())
+        .and(warp::body::content_length_limit(16 * 1024))
+        .and(warp::body::bytes())
+        .and(addresses::client(server.addresses.clone()))
+        .map(move |seat: Seat, body: Bytes, address: Option<IpAddr>| -> Box<dyn Reply> {
+            let msg = match std::str::from_utf8(&body).ok().and_then(parse_message) {
+                Some(msg) => msg,
+                None => return Box::new(warp::reply::with_status("invalid message", StatusCode::BAD_REQUEST))
+            };
+            let mut ctx = match seat_context(&server, &seat) {
+                Some(ctx) => ctx,
+                None => return Box::new(warp::reply::with_status("invalid player secret", StatusCode::FORBIDDEN))
+            };
+            ctx.address = address;
+            handle_message(&server, &mut ctx, msg);
+            Box::new(StatusCode::ACCEPTED)
+        });
+
+    events.or(send).unify()
+}
+
+/// Move a seat to a new transport, returning the context for the new connection if the secret matches.
+fn connect(server: &ServerState, seat: &Seat, sink: Sink) -> Option<ConnectionContext> {
+    let game = get_game(&server.games, &seat.game_id)?;
+    let mut state = game.lock();
+    if state.get_player_secret(&seat.player_id) != Some(seat.player_secret) || !state.reconnect(seat.player_id, sink.clone()) {
+        return None
+    }
+    state.timeout = None;
+    state.broadcast_game_state();
+
+    let mut ctx = ConnectionContext::with_sink(sink);
+    ctx.game = Some(seat.game_id);
+    ctx.player = Some(seat.player_id);
+    let conn = state.conn.get(&seat.player_id)?;
+    ctx.topics = conn.topics.clone();
+    ctx.language = conn.language;
+    ctx.view_version = conn.view_version;
+    ctx.community = state.community.clone();
+    Some(ctx)
+}
+
+/// A context for a single posted message, which answers on whichever transport the seat is using.
+fn seat_context(server: &ServerState, seat: &Seat) -> Option<ConnectionContext> {
+    let game = get_game(&server.games, &seat.game_id)?;
+    let state = game.lock();
+    if state.get_player_secret(&seat.player_id) != Some(seat.player_secret) {
+        return None
+    }
+    let conn = state.conn.get(&seat.player_id)?;
+    let mut ctx = ConnectionContext::with_sink(conn.tx.clone());
+    ctx.game = Some(seat.game_id);
+    ctx.player = Some(seat.player_id);
+    ctx.topics = conn.topics.clone();
+    ctx.language = conn.language;
+    ctx.view_version = conn.view_version;
+    ctx.community = state.community.clone();
+    Some(ctx)
+}
@@ -0,0 +1,276 @@
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::{FairMutex, RwLock};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+use warp::{Filter, Rejection, Reply, http::StatusCode};
+
+use crate::{bridge::{Action, Audience, Button, Markup, Pressed, Table}, limits::ServerLimits, server::{GlobalState, get_game}};
+
+const API_URL: &str = "https://api.telegram.org";
+
+/// The command that opens a game in the group it is used in.
+const COMMAND: &str = "/secrethitler";
+
+/// Most buttons shown side by side in a message.
+const BUTTONS_PER_ROW: usize = 3;
+
+/// Credentials for the bot, from BotFather.
+#[derive(Clone)]
+pub struct TelegramConfig {
+    pub bot_token: String,
+    /// Sent back by Telegram with every update, to check that updates really come from Telegram.
+    pub webhook_secret: String,
+    /// Where Telegram should send updates. The webhook is registered at startup when this is set.
+    pub webhook_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Update {
+    message: Option<Message>,
+    callback_query: Option<CallbackQuery>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    message_id: i64,
+    chat: Chat,
+    from: Option<User>,
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Chat {
+    id: i64,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct User {
+    id: i64,
+    first_name: String,
+    last_name: Option<String>,
+}
+
+impl User {
+    fn name(&self) -> String {
+        match &self.last_name {
+            Some(last_name) => format!("{} {}", self.first_name, last_name),
+            None => self.first_name.clone()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CallbackQuery {
+    id: String,
+    from: User,
+    message: Option<Message>,
+    data: Option<String>,
+}
+
+/// A Bot API call for the delivery task to make.
+struct Call {
+    method: &'static str,
+    body: Value,
+}
+
+/// Telegram's HTML formatting, where a mention is a link to the user.
+struct TelegramMarkup;
+
+impl Markup for TelegramMarkup {
+    fn mention(&self, user_id: &str, name: &str) -> String {
+        format!("<a href=\"tg://user?id={}\">{}</a>", user_id, self.escape(name))
+    }
+
+    fn bold(&self, text: &str) -> String {
+        format!("<b>{}</b>", text)
+    }
+
+    fn escape(&self, text: &str) -> String {
+        text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+    }
+}
+
+/// Bridges Telegram group chats to games, so that a game can be played with a command, inline buttons, and private messages.
+/// The games are kept alongside the server's other games, so they can also be watched from the website.
+#[derive(Clone)]
+pub struct TelegramBridge {
+    config: Arc<TelegramConfig>,
+    games: GlobalState,
+    limits: Arc<ServerLimits>,
+    tables: Arc<RwLock<HashMap<Uuid, Table>>>,
+    outbox: mpsc::UnboundedSender<Call>,
+}
+
+impl TelegramBridge {
+    /// Register the webhook and spawn the task that makes calls to the Bot API.
+    pub fn start(config: TelegramConfig, games: GlobalState, limits: Arc<ServerLimits>) -> TelegramBridge {
+        let config = Arc::new(config);
+        let (outbox, rx) = mpsc::unbounded_channel();
+        tokio::spawn(deliver(config.clone(), rx));
+
+        let bridge = TelegramBridge { config, games, limits, tables: Arc::default(), outbox };
+        if let Some(url) = &bridge.config.webhook_url {
+            bridge.send("setWebhook", json!({ "url": url, "secret_token": bridge.config.webhook_secret, "allowed_updates": ["message", "callback_query"] }));
+        }
+        bridge
+    }
+
+    /// Handle an update sent to the bot's webhook, returning a Bot API call to make in the response.
+    /// Returns none if the request did not carry the webhook's secret token.
+    pub fn handle(&self, secret: &str, body: &[u8]) -> Option<Value> {
+        if secret != self.config.webhook_secret {
+            return None
+        }
+        let update: Update = match serde_json::from_slice(body) {
+            Ok(update) => update,
+            Err(_) => return Some(json!({}))
+        };
+        Some(match (update.message, update.callback_query) {
+            (_, Some(query)) => self.press(query),
+            (Some(message), None) => self.command(message),
+            (None, None) => json!({})
+        })
+    }
+
+    fn command(&self, message: Message) -> Value {
+        // commands in groups may be addressed to a particular bot, as in /secrethitler@somebot
+        let command = message.text.as_deref().and_then(|text| text.split_whitespace().next()).map(|word| word.split('@').next().unwrap_or_default());
+        let from = match message.from {
+            Some(from) => from,
+            None => return json!({})
+        };
+        match (command, message.chat.kind.as_str()) {
+            (Some(COMMAND), "group" | "supergroup") => self.host(message.chat.id, from),
+            (Some(COMMAND), _) => send_message(message.chat.id, "Add me to a group and use /secrethitler there to start a game.".into(), vec![]),
+            (Some("/start"), "private") => send_message(message.chat.id, "You will get your role and policy cards here when you play a game.".into(), vec![]),
+            _ => json!({})
+        }
+    }
+
+    /// Open a lobby in the group the command was used in, with the user who used it as the host.
+    fn host(&self, chat_id: i64, from: User) -> Value {
+        if !self.limits.can_host(self.games.read().len()) {
+            return send_message(chat_id, "The server is too busy to start a new game right now. Try again later.".into(), vec![])
+        }
+
+        let (table, state) = Table::host(chat_id.to_string(), from.id.to_string(), from.name());
+        let lobby = table.lobby(&state, &TelegramMarkup);
+        self.games.write().insert(table.game_id, Arc::new(FairMutex::new(state)));
+        self.tables.write().insert(table.game_id, table);
+        send_message(chat_id, lobby.text, lobby.buttons)
+    }
+
+    /// Apply a button press to the game it belongs to, answering the callback query.
+    fn press(&self, query: CallbackQuery) -> Value {
+        let answer = |text: &str| json!({ "method": "answerCallbackQuery", "callback_query_id": query.id, "text": text });
+        let (game_id, action) = match query.data.as_deref().and_then(Action::decode) {
+            Some(pressed) => pressed,
+            None => return answer("That button does not do anything.")
+        };
+        let game = match get_game(&self.games, &game_id) {
+            Some(game) => game,
+            None => return answer("This game is over.")
+        };
+        let state = &mut game.lock();
+        let mut tables = self.tables.write();
+        let table = match tables.get_mut(&game_id) {
+            Some(table) => table,
+            None => return answer("This game is over.")
+        };
+
+        let (pressed, notices) = table.press(state, &query.from.id.to_string(), query.from.name(), action, &TelegramMarkup);
+        for notice in notices {
+            let chat_id = match notice.to {
+                Audience::Table => table.chat_id.clone(),
+                // a private chat with a user has the same id as the user
+                Audience::User(user_id) => user_id
+            };
+            self.send("sendMessage", message(chat_id, notice.text, notice.buttons));
+        }
+        let response = match pressed {
+            Pressed::Refused(reason) => answer(&reason),
+            Pressed::Joined => {
+                if let Some(lobby_message) = &query.message {
+                    let lobby = table.lobby(state, &TelegramMarkup);
+                    let mut edit = message(lobby_message.chat.id, lobby.text, lobby.buttons);
+                    edit["message_id"] = json!(lobby_message.message_id);
+                    self.send("editMessageText", edit);
+                }
+                answer("You joined the game. Start a private chat with me so I can send you your role.")
+            },
+            Pressed::Applied(Some(confirmation)) => answer(&confirmation),
+            Pressed::Applied(None) => answer("")
+        };
+        if state.winner().is_some() {
+            tables.remove(&game_id);
+        }
+        response
+    }
+
+    fn send(&self, method: &'static str, body: Value) {
+        if self.outbox.send(Call { method, body }).is_err() {
+            eprintln!("telegram: delivery task has stopped");
+        }
+    }
+}
+
+/// Accept updates from Telegram at `/telegram/webhook`, if the bridge is enabled.
+pub fn route(bridge: Option<TelegramBridge>) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone {
+    warp::path!("telegram" / "webhook")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("x-telegram-bot-api-secret-token"))
+        .and(warp::body::content_length_limit(64 * 1024))
+        .and(warp::body::bytes())
+        .and_then(move |secret: Option<String>, body: warp::hyper::body::Bytes| {
+            let bridge = bridge.clone();
+            async move {
+                let bridge = bridge.ok_or_else(warp::reject::not_found)?;
+                Ok::<Box<dyn Reply>, Rejection>(match bridge.handle(&secret.unwrap_or_default(), &body) {
+                    Some(response) => Box::new(warp::reply::json(&response)),
+                    None => Box::new(warp::reply::with_status("invalid secret token", StatusCode::UNAUTHORIZED))
+                })
+            }
+        })
+}
+
+fn message(chat_id: impl ToString, text: String, buttons: Vec<Button>) -> Value {
+    json!({ "chat_id": chat_id.to_string(), "text": text, "parse_mode": "HTML", "reply_markup": keyboard(buttons) })
+}
+
+/// A sendMessage call made in the response to an update, which saves a request to the Bot API.
+fn send_message(chat_id: impl ToString, text: String, buttons: Vec<Button>) -> Value {
+    let mut call = message(chat_id, text, buttons);
+    call["method"] = json!("sendMessage");
+    call
+}
+
+fn keyboard(buttons: Vec<Button>) -> Value {
+    let rows: Vec<Vec<Value>> = buttons.chunks(BUTTONS_PER_ROW).map(|row| {
+        row.iter().map(|button| json!({ "text": button.label, "callback_data": button.data })).collect()
+    }).collect();
+    json!({ "inline_keyboard": rows })
+}
+
+/// Make queued calls in order, so that messages arrive in the order the game produced them.
+async fn deliver(config: Arc<TelegramConfig>, mut rx: mpsc::UnboundedReceiver<Call>) {
+    let client = reqwest::Client::new();
+    while let Some(call) = rx.recv().await {
+        let response = client.post(format!("{}/bot{}/{}", API_URL, config.bot_token, call.method))
+            .header("Content-Type", "application/json")
+            .body(call.body.to_string())
+            .send().await;
+        match response {
+            Ok(response) if response.status() == reqwest::StatusCode::FORBIDDEN => {
+                eprintln!("telegram: {} was refused, the user may not have started a private chat with the bot", call.method);
+            },
+            Ok(response) if !response.status().is_success() => eprintln!("telegram: {} failed with status {}", call.method, response.status()),
+            Ok(_) => {},
+            Err(e) => eprintln!("telegram: {} failed: {}", call.method, e)
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::{convert::TryInto, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const PAYLOAD_LEN: usize = 16 + 16 + 8;
+const MAC_LEN: usize = 32;
+
+/// Issues and verifies signed resume tokens that let a player rejoin a game without their secret.
+/// A token is `game id || player id || expiry || hmac` encoded as url-safe base64.
+/// The player's current secret is mixed into the hmac, so rotating the secret revokes old tokens.
+pub struct ResumeTokens {
+    /// The first key signs new tokens, the rest are only accepted for verification so keys can be rotated.
+    keys: Vec<Vec<u8>>,
+    ttl: Duration,
+}
+
+impl ResumeTokens {
+    /// Create a token issuer from the configured keys, or a random key if none are configured.
+    pub fn new(keys: Vec<String>, ttl: Duration) -> ResumeTokens {
+        let mut keys: Vec<Vec<u8>> = keys.into_iter().map(|k| k.into_bytes()).collect();
+        if keys.is_empty() {
+            let mut key = vec![0; 32];
+            rand::thread_rng().fill_bytes(&mut key);
+            keys.push(key);
+        }
+        ResumeTokens { keys, ttl }
+    }
+
+    fn sign(key: &[u8], payload: &[u8], secret: Uuid) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(key).expect("hmac accepts keys of any length");
+        mac.update(payload);
+        mac.update(secret.as_bytes());
+        mac
+    }
+
+    /// Issue a token for the player, returning the token and its expiry time.
+    pub fn issue(&self, game_id: Uuid, player_id: Uuid, secret: Uuid) -> (String, SystemTime) {
+        let expires = SystemTime::now() + self.ttl;
+        let mut data = Vec::with_capacity(PAYLOAD_LEN + MAC_LEN);
+        data.extend_from_slice(game_id.as_bytes());
+        data.extend_from_slice(player_id.as_bytes());
+        data.extend_from_slice(&expires.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs().to_be_bytes());
+        let mac = ResumeTokens::sign(&self.keys[0], &data, secret).finalize().into_bytes();
+        data.extend_from_slice(&mac);
+        (URL_SAFE_NO_PAD.encode(data), expires)
+    }
+
+    fn decode(token: &str) -> Option<Vec<u8>> {
+        let data = URL_SAFE_NO_PAD.decode(token).ok()?;
+        if data.len() != PAYLOAD_LEN + MAC_LEN {
+            return None;
+        }
+        Some(data)
+    }
+
+    /// Read the game and player a token claims to be for, if it is well formed and has not expired.
+    /// The claims are not trusted until the token is checked with `verify`.
+    pub fn claims(&self, token: &str) -> Option<(Uuid, Uuid)> {
+        let data = ResumeTokens::decode(token)?;
+        let expires = UNIX_EPOCH + Duration::from_secs(u64::from_be_bytes(data[32..40].try_into().ok()?));
+        if expires < SystemTime::now() {
+            return None;
+        }
+        let game_id = Uuid::from_slice(&data[0..16]).ok()?;
+        let player_id = Uuid::from_slice(&data[16..32]).ok()?;
+        Some((game_id, player_id))
+    }
+
+    /// Check that the token was signed by this server for a player whose current secret is `secret`.
+    pub fn verify(&self, token: &str, secret: Uuid) -> bool {
+        match ResumeTokens::decode(token) {
+            Some(data) => {
+                let (payload, mac) = data.split_at(PAYLOAD_LEN);
+                self.claims(token).is_some() && self.keys.iter().any(|key| ResumeTokens::sign(key, payload, secret).verify_slice(mac).is_ok())
+            },
+            None => false
+        }
+    }
+}
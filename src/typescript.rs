@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+
+use schemars::schema::RootSchema;
+use serde_json::Value;
+
+use secrethitler_core::schema;
+
+const HEADER: &str = "// Generated from the Rust protocol types by `secrethitler generate-types`. Do not edit by hand.\n";
+
+/// TypeScript definitions for every protocol message and the player view, worked out from their JSON Schemas.
+pub fn protocol_types() -> String {
+    let roots = [("ClientProtocol", schema::client_schema()), ("ServerProtocol", schema::server_schema()), ("PlayerView", schema::player_view_schema())];
+    let mut types = BTreeMap::new();
+    for (name, root) in roots {
+        let RootSchema { schema, definitions, .. } = root;
+        types.insert(name.to_string(), serde_json::to_value(schema).unwrap());
+        for (name, definition) in definitions {
+            types.insert(name, serde_json::to_value(definition).unwrap());
+        }
+    }
+    let mut out = HEADER.to_string();
+    for (name, schema) in &types {
+        out.push('\n');
+        if let Some(description) = schema["description"].as_str() {
+            out.push_str(&format!("/** {} */\n", description.replace('\n', " ").replace("*/", "* /")));
+        }
+        out.push_str(&format!("export type {} = {};\n", name, render(schema, 0)));
+    }
+    out
+}
+
+/// The TypeScript type for a schema, indented to sit at the given depth.
+fn render(schema: &Value, depth: usize) -> String {
+    if let Some(reference) = schema["$ref"].as_str() {
+        return reference.trim_start_matches("#/definitions/").to_string()
+    }
+    if schema == &Value::Bool(true) || schema.as_object().is_some_and(|object| object.is_empty()) {
+        return "unknown".into()
+    }
+    if let Some(values) = schema["enum"].as_array() {
+        return values.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(" | ")
+    }
+    for key in ["allOf", "anyOf", "oneOf"] {
+        if let Some(options) = schema[key].as_array() {
+            let separator = if key == "allOf" { " & " } else { " | " };
+            let combined = options.iter().map(|option| render(option, depth)).collect::<Vec<_>>().join(separator);
+            // an object can also have fields flattened in from one of several shapes
+            return match schema.get("properties") {
+                Some(_) => format!("{} & ({})", render_object(schema, depth), combined),
+                None if options.len() > 1 => format!("({})", combined),
+                None => combined
+            }
+        }
+    }
+    match &schema["type"] {
+        Value::Array(types) => types.iter().map(|kind| render_type(schema, kind.as_str().unwrap_or_default(), depth)).collect::<Vec<_>>().join(" | "),
+        Value::String(kind) => render_type(schema, kind, depth),
+        _ => "unknown".into()
+    }
+}
+
+fn render_type(schema: &Value, kind: &str, depth: usize) -> String {
+    match kind {
+        "string" => "string".into(),
+        "integer" | "number" => "number".into(),
+        "boolean" => "boolean".into(),
+        "null" => "null".into(),
+        "array" => match &schema["items"] {
+            Value::Array(items) => format!("[{}]", items.iter().map(|item| render(item, depth)).collect::<Vec<_>>().join(", ")),
+            Value::Null => "unknown[]".into(),
+            item => {
+                let item = render(item, depth);
+                if item.contains(' ') { format!("({})[]", item) } else { format!("{}[]", item) }
+            }
+        },
+        "object" => render_object(schema, depth),
+        _ => "unknown".into()
+    }
+}
+
+fn render_object(schema: &Value, depth: usize) -> String {
+    let properties = match schema["properties"].as_object() {
+        Some(properties) => properties,
+        None => return match schema.get("additionalProperties") {
+            Some(values) => format!("{{ [key: string]: {} }}", render(values, depth)),
+            None => "{}".into()
+        }
+    };
+    let required: Vec<&str> = schema["required"].as_array().map(|required| required.iter().filter_map(Value::as_str).collect()).unwrap_or_default();
+    let indent = "  ".repeat(depth + 1);
+    let mut out = "{\n".to_string();
+    for (name, property) in properties {
+        if let Some(description) = property["description"].as_str() {
+            out.push_str(&format!("{}/** {} */\n", indent, description.replace('\n', " ").replace("*/", "* /")));
+        }
+        let optional = if required.contains(&name.as_str()) { "" } else { "?" };
+        out.push_str(&format!("{}{}{}: {};\n", indent, name, optional, render(property, depth + 1)));
+    }
+    out.push_str(&"  ".repeat(depth));
+    out.push('}');
+    out
+}
@@ -0,0 +1,149 @@
+use std::collections::{HashMap, VecDeque};
+
+use parking_lot::RwLock;
+use serde::Deserialize;
+use uuid::Uuid;
+use warp::{Filter, Rejection, Reply, http::{Response, StatusCode}, hyper::body::Bytes};
+
+use crate::server::{ServerState, get_game};
+
+/// Largest image that can be attached to a chat message.
+pub const MAX_UPLOAD_BYTES: usize = 1024 * 1024;
+
+/// Most attachments one game holds at once. The oldest are dropped first.
+const MAX_UPLOADS_PER_GAME: usize = 20;
+
+/// Most bytes held for all games together, since attachments are kept in memory.
+const MAX_TOTAL_BYTES: usize = 256 * 1024 * 1024;
+
+/// An image attached to a chat message.
+#[derive(Clone)]
+pub struct Attachment {
+    pub game_id: Uuid,
+    pub content_type: &'static str,
+    pub data: Bytes,
+}
+
+#[derive(Default)]
+struct Stored {
+    attachments: HashMap<Uuid, Attachment>,
+    /// The attachments of each game, oldest first.
+    games: HashMap<Uuid, VecDeque<Uuid>>,
+    bytes: usize,
+}
+
+/// Images uploaded for the chat of a game, each found by a random token.
+/// Attachments are kept in memory and are removed along with their game.
+#[derive(Default)]
+pub struct Uploads {
+    stored: RwLock<Stored>,
+}
+
+/// The type of an image from its first bytes, if it is a PNG, JPEG, GIF, or WebP.
+/// The type the client claims is not trusted.
+pub fn image_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    }
+    else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    }
+    else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    }
+    else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    }
+    else {
+        None
+    }
+}
+
+impl Uploads {
+    /// Keep an image for a game, returning the token it can be fetched and attached with.
+    pub fn store(&self, game_id: Uuid, data: Bytes) -> Result<Uuid, &'static str> {
+        if data.len() > MAX_UPLOAD_BYTES {
+            return Err("Attachments cannot be larger than 1 MB.")
+        }
+        let content_type = image_type(&data).ok_or("Only PNG, JPEG, GIF, and WebP images can be attached.")?;
+        let mut stored = self.stored.write();
+        if stored.bytes + data.len() > MAX_TOTAL_BYTES {
+            return Err("The server cannot hold any more attachments right now.")
+        }
+        let token = Uuid::new_v4();
+        stored.bytes += data.len();
+        stored.attachments.insert(token, Attachment { game_id, content_type, data });
+        let tokens = stored.games.entry(game_id).or_default();
+        tokens.push_back(token);
+        let dropped = if tokens.len() > MAX_UPLOADS_PER_GAME { tokens.pop_front() } else { None };
+        if let Some(attachment) = dropped.and_then(|dropped| stored.attachments.remove(&dropped)) {
+            stored.bytes -= attachment.data.len();
+        }
+        Ok(token)
+    }
+
+    pub fn get(&self, token: &Uuid) -> Option<Attachment> {
+        self.stored.read().attachments.get(token).cloned()
+    }
+
+    /// Whether the attachment exists and was uploaded for this game.
+    pub fn belongs_to(&self, token: &Uuid, game_id: &Uuid) -> bool {
+        self.stored.read().attachments.get(token).is_some_and(|attachment| attachment.game_id == *game_id)
+    }
+
+    /// Forget the attachments of games that no longer exist.
+    pub fn retain_games(&self, exists: impl Fn(&Uuid) -> bool) {
+        let mut stored = self.stored.write();
+        let stored = &mut *stored;
+        stored.games.retain(|game_id, _| exists(game_id));
+        let games = &stored.games;
+        stored.attachments.retain(|_, attachment| games.contains_key(&attachment.game_id));
+        stored.bytes = stored.attachments.values().map(|attachment| attachment.data.len()).sum();
+    }
+}
+
+/// The seat an upload is for.
+#[derive(Deserialize)]
+struct Seat {
+    game_id: Uuid,
+    player_id: Uuid,
+    player_secret: Uuid,
+}
+
+/// Take images for chat at `POST /upload` from players seated in a game, and serve them at `GET /upload/{token}`.
+pub fn route(server: ServerState) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone {
+    let uploads = server.uploads.clone();
+    let upload = warp::path!("upload")
+        .and(warp::post())
+        .and(warp::query::<Seat>())
+        .and(warp::body::content_length_limit(MAX_UPLOAD_BYTES as u64))
+        .and(warp::body::bytes())
+        .map(move |seat: Seat, data: Bytes| -> Box<dyn Reply> {
+            let seated = get_game(&server.games, &seat.game_id).is_some_and(|game| {
+                let state = game.lock();
+                state.get_player_secret(&seat.player_id) == Some(seat.player_secret) && state.has_player(&seat.player_id)
+            });
+            if !seated {
+                return Box::new(warp::reply::with_status("invalid player secret", StatusCode::FORBIDDEN))
+            }
+            match server.uploads.store(seat.game_id, data) {
+                Ok(token) => Box::new(warp::reply::json(&serde_json::json!({ "token": token }))),
+                Err(message) => Box::new(warp::reply::with_status(message, StatusCode::BAD_REQUEST))
+            }
+        });
+
+    let download = warp::path!("upload" / Uuid)
+        .and(warp::get())
+        .map(move |token: Uuid| -> Box<dyn Reply> {
+            match uploads.get(&token) {
+                Some(attachment) => Box::new(Response::builder()
+                    .header("content-type", attachment.content_type)
+                    .header("x-content-type-options", "nosniff")
+                    .header("cache-control", "private, max-age=3600")
+                    .body(attachment.data)),
+                None => Box::new(warp::reply::with_status("no such attachment", StatusCode::NOT_FOUND))
+            }
+        });
+
+    upload.or(download).unify()
+}
@@ -0,0 +1,92 @@
+use std::{collections::HashMap, str::FromStr, sync::atomic::{AtomicUsize, Ordering}, time::{Duration, SystemTime}};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// What to do with a game that is stuck waiting on players who have left.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StuckPolicy {
+    /// Only report the game.
+    Report,
+    /// Make the moves the game is waiting on for the players who left, as a bot would.
+    Skip,
+    /// End the game and remove it from the server.
+    Abort,
+}
+
+impl FromStr for StuckPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<StuckPolicy, String> {
+        match s {
+            "report" => Ok(StuckPolicy::Report),
+            "skip" => Ok(StuckPolicy::Skip),
+            "abort" => Ok(StuckPolicy::Abort),
+            _ => Err(format!("unknown policy {}, expected report, skip, or abort", s))
+        }
+    }
+}
+
+/// How many stuck games have been found, reported on the health check.
+#[derive(Serialize)]
+pub struct StuckGames {
+    pub found: usize,
+    pub skipped: usize,
+    pub aborted: usize,
+}
+
+/// Finds games that have waited too long on players who have all lost their connection, and recovers them with a policy.
+/// Each stuck phase is only handled once, so a game that cannot be moved on is not reported over and over.
+#[derive(Default)]
+pub struct Watchdog {
+    config: Option<(Duration, StuckPolicy)>,
+    /// The phase of each game that was last found stuck, by when it started.
+    handled: Mutex<HashMap<Uuid, SystemTime>>,
+    found: AtomicUsize,
+    skipped: AtomicUsize,
+    aborted: AtomicUsize,
+}
+
+impl Watchdog {
+    /// Watch for games stuck for longer than `after`, or for none if it is not set.
+    pub fn new(after: Option<Duration>, policy: StuckPolicy) -> Watchdog {
+        Watchdog { config: after.map(|after| (after, policy)), ..Watchdog::default() }
+    }
+
+    /// How long a game has to wait before it counts as stuck, and what is done about it, if the watchdog is enabled.
+    pub fn config(&self) -> Option<(Duration, StuckPolicy)> {
+        self.config
+    }
+
+    /// Note that the phase starting at `phase_started_at` is stuck, returning false if it was already handled.
+    pub fn report(&self, game_id: Uuid, phase_started_at: SystemTime) -> bool {
+        if self.handled.lock().insert(game_id, phase_started_at) == Some(phase_started_at) {
+            return false
+        }
+        self.found.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// Count a recovery made with the policy.
+    pub fn recovered(&self, policy: StuckPolicy) {
+        match policy {
+            StuckPolicy::Report => {},
+            StuckPolicy::Skip => {
+                self.skipped.fetch_add(1, Ordering::Relaxed);
+            },
+            StuckPolicy::Abort => {
+                self.aborted.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> StuckGames {
+        StuckGames { found: self.found.load(Ordering::Relaxed), skipped: self.skipped.load(Ordering::Relaxed), aborted: self.aborted.load(Ordering::Relaxed) }
+    }
+
+    /// Forget games that no longer exist.
+    pub fn retain_games(&self, keep: impl Fn(&Uuid) -> bool) {
+        self.handled.lock().retain(|game_id, _| keep(game_id));
+    }
+}
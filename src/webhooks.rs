@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use secrethitler_core::game_state::GameSummary;
+
+const MAX_ATTEMPTS: u32 = 4;
+
+#[derive(Clone, Copy, Serialize)]
+pub enum WebhookEvent {
+    Created,
+    Started,
+    Ended,
+    /// An asynchronous game is waiting on players who may not be watching it.
+    TurnWaiting,
+    /// A scheduled game opens for joining soon.
+    ScheduleReminder,
+    /// A scheduled game is now open for joining.
+    Opened,
+    /// A game has waited too long on players who have all lost their connection.
+    Stuck,
+}
+
+/// The JSON body posted to each webhook.
+/// Discord reads `content` and Slack reads `text`, the remaining fields are for custom integrations.
+#[derive(Serialize)]
+struct WebhookPayload {
+    content: String,
+    text: String,
+    event: WebhookEvent,
+    game_id: Uuid,
+    url: Option<String>,
+    summary: GameSummary,
+    /// Names of the players the game is waiting on, for turn notifications and stuck games.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    awaiting: Vec<String>,
+}
+
+/// Handle used to queue webhook notifications from connection handlers.
+/// Delivery happens on a separate task so slow endpoints never block the game.
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    tx: Option<mpsc::UnboundedSender<WebhookPayload>>,
+    public_url: Option<String>,
+}
+
+impl WebhookDispatcher {
+    /// Spawn the delivery task. If no urls are configured, notifications are dropped.
+    pub fn start(urls: Vec<String>, public_url: Option<String>) -> WebhookDispatcher {
+        if urls.is_empty() {
+            return WebhookDispatcher { tx: None, public_url };
+        }
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<WebhookPayload>();
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            while let Some(payload) = rx.recv().await {
+                let body = serde_json::to_string(&payload).unwrap();
+                for url in urls.iter() {
+                    tokio::spawn(deliver(client.clone(), url.clone(), body.clone()));
+                }
+            }
+        });
+        WebhookDispatcher { tx: Some(tx), public_url }
+    }
+
+    pub fn notify(&self, event: WebhookEvent, game_id: Uuid, summary: GameSummary) {
+        self.send(event, game_id, summary, vec![]);
+    }
+
+    /// Let the players of an asynchronous game know that it is their turn.
+    pub fn notify_turn(&self, game_id: Uuid, summary: GameSummary, awaiting: Vec<String>) {
+        self.send(WebhookEvent::TurnWaiting, game_id, summary, awaiting);
+    }
+
+    /// Report a game that is stuck waiting on players who have left.
+    pub fn notify_stuck(&self, game_id: Uuid, summary: GameSummary, awaiting: Vec<String>) {
+        self.send(WebhookEvent::Stuck, game_id, summary, awaiting);
+    }
+
+    fn send(&self, event: WebhookEvent, game_id: Uuid, summary: GameSummary, awaiting: Vec<String>) {
+        if let Some(tx) = &self.tx {
+            let url = self.public_url.as_ref().map(|base| format!("{}/game/{}", base, game_id));
+            let message = describe(event, &summary, &awaiting, url.as_deref());
+            let payload = WebhookPayload { content: message.clone(), text: message, event, game_id, url, summary, awaiting };
+            if tx.send(payload).is_err() {
+                eprintln!("webhook dispatcher has stopped");
+            }
+        }
+    }
+}
+
+fn describe(event: WebhookEvent, summary: &GameSummary, awaiting: &[String], url: Option<&str>) -> String {
+    let host = summary.host.clone().unwrap_or_else(|| "Someone".into());
+    match event {
+        WebhookEvent::Created => match url {
+            Some(url) => format!("{} is hosting a new game of Secret Hitler! Join at {}", host, url),
+            None => format!("{} is hosting a new game of Secret Hitler!", host)
+        },
+        WebhookEvent::Started => format!("A game of Secret Hitler has started with {} players: {}.", summary.players.len(), summary.players.join(", ")),
+        WebhookEvent::Ended => match summary.winner {
+            Some(winner) => format!("A game of Secret Hitler has ended. The {}s won with {} liberal and {} facist policies enacted.", winner, summary.liberal_policies, summary.facist_policies),
+            None => "A game of Secret Hitler has ended.".into()
+        },
+        WebhookEvent::ScheduleReminder => match url {
+            Some(url) => format!("{}'s game of Secret Hitler opens in 15 minutes! Join at {}", host, url),
+            None => format!("{}'s game of Secret Hitler opens in 15 minutes!", host)
+        },
+        WebhookEvent::Opened => match url {
+            Some(url) => format!("{}'s game of Secret Hitler is open for joining! Join at {}", host, url),
+            None => format!("{}'s game of Secret Hitler is open for joining!", host)
+        },
+        WebhookEvent::TurnWaiting => match url {
+            Some(url) => format!("It is your turn in Secret Hitler, {}! Play at {}", awaiting.join(", "), url),
+            None => format!("It is your turn in Secret Hitler, {}!", awaiting.join(", "))
+        },
+        WebhookEvent::Stuck => format!("A game of Secret Hitler is stuck waiting on {}, who lost their connection.", awaiting.join(", "))
+    }
+}
+
+/// Post the payload to a single endpoint, retrying with exponential backoff on failure.
+async fn deliver(client: reqwest::Client, url: String, body: String) {
+    for attempt in 0..MAX_ATTEMPTS {
+        if attempt > 0 {
+            tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+        }
+        match client.post(&url).header("Content-Type", "application/json").body(body.clone()).send().await {
+            Ok(resp) if resp.status().is_success() => return,
+            // other client errors will not succeed on retry
+            Ok(resp) if resp.status().is_client_error() && resp.status().as_u16() != 429 => {
+                eprintln!("webhook {} rejected payload: {}", url, resp.status());
+                return
+            },
+            Ok(resp) => eprintln!("webhook {} failed with status {}", url, resp.status()),
+            Err(e) => eprintln!("webhook {} failed: {}", url, e)
+        }
+    }
+    eprintln!("giving up on webhook {} after {} attempts", url, MAX_ATTEMPTS);
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "discord")]
+
+use std::{sync::Arc, time::Duration};
+
+use ring::{rand::SystemRandom, signature::{Ed25519KeyPair, KeyPair}};
+use secrethitler::{discord::{DiscordBridge, DiscordConfig}, limits::ServerLimits, server::GlobalState};
+use secrethitler_core::game_state::TurnPhase;
+use serde_json::{Value, json};
+
+fn bridge() -> (DiscordBridge, GlobalState, Ed25519KeyPair) {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+    let key = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+    let config = DiscordConfig { application_id: "app".into(), bot_token: "token".into(), public_key: key.public_key().as_ref().to_vec() };
+    let games = GlobalState::default();
+    let limits = Arc::new(ServerLimits::new(None, None, Duration::from_secs(10)));
+    (DiscordBridge::start(config, games.clone(), limits), games, key)
+}
+
+fn send(bridge: &DiscordBridge, key: &Ed25519KeyPair, interaction: Value) -> Option<Value> {
+    let body = interaction.to_string();
+    let timestamp = "1700000000";
+    let signature = key.sign(format!("{}{}", timestamp, body).as_bytes());
+    let hex: String = signature.as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+    bridge.handle(&hex, timestamp, body.as_bytes())
+}
+
+fn press(user: usize, custom_id: &str) -> Value {
+    json!({ "type": 3, "channel_id": "channel", "member": { "user": { "id": user.to_string(), "username": format!("user {}", user) } }, "data": { "custom_id": custom_id } })
+}
+
+#[tokio::test]
+async fn test_discord_lobby() {
+    let (bridge, games, key) = bridge();
+
+    assert_eq!(send(&bridge, &key, json!({ "type": 1 })).unwrap()["type"], 1);
+    assert!(bridge.handle("00", "1700000000", b"{\"type\":1}").is_none());
+
+    let lobby = send(&bridge, &key, json!({ "type": 2, "channel_id": "channel", "member": { "user": { "id": "0", "username": "host" } }, "data": {} })).unwrap();
+    let join = lobby["data"]["components"][0]["components"][0]["custom_id"].as_str().unwrap().to_string();
+    let start = lobby["data"]["components"][0]["components"][1]["custom_id"].as_str().unwrap().to_string();
+
+    // joining twice is refused, privately
+    assert_eq!(send(&bridge, &key, press(0, &join)).unwrap()["data"]["flags"], 64);
+    for user in 1..5 {
+        assert_eq!(send(&bridge, &key, press(user, &join)).unwrap()["type"], 7);
+    }
+    // only the host may start
+    assert_eq!(send(&bridge, &key, press(1, &start)).unwrap()["data"]["flags"], 64);
+    assert_eq!(send(&bridge, &key, press(0, &start)).unwrap()["type"], 6);
+
+    let game = games.read().values().next().unwrap().clone();
+    let state = game.lock();
+    assert_eq!(state.living_players().len(), 5);
+    assert!(matches!(state.turn_phase(), TurnPhase::Electing));
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "graphql")]
+
+use std::sync::Arc;
+
+use parking_lot::FairMutex;
+
+use secrethitler::{graphql, server::GlobalState};
+use secrethitler_core::{game_state::GameState, protocol::{NullSink, PlayerConnection}, simulation::Simulation};
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+fn lobby(players: usize) -> GameState {
+    let mut state = GameState::new();
+    for i in 0..players {
+        let mut conn = PlayerConnection::new(Arc::new(NullSink));
+        conn.name = Some(format!("player {}", i));
+        state.add_player(Uuid::new_v4(), conn);
+    }
+    state
+}
+
+async fn query(games: &GlobalState, query: &str) -> Value {
+    let response = graphql::schema(games.clone(), Arc::default()).execute(query).await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    response.data.into_json().unwrap()
+}
+
+#[tokio::test]
+async fn test_graphql_queries() {
+    let games = GlobalState::default();
+    let lobby_id = Uuid::new_v4();
+    let finished_id = Uuid::new_v4();
+    games.write().insert(lobby_id, Arc::new(FairMutex::new(lobby(3))));
+    let finished = Simulation::new(Some(7)).play(5);
+    let winner = finished.winner().unwrap().to_string();
+    games.write().insert(finished_id, Arc::new(FairMutex::new(finished)));
+
+    let lobbies = query(&games, "{ lobbies { id host players phase } }").await;
+    assert_eq!(lobbies, json!({ "lobbies": [{ "id": lobby_id.to_string(), "host": "player 0", "players": ["player 0", "player 1", "player 2"], "phase": "Lobby" }] }));
+
+    let game = query(&games, &format!("{{ game(id: \"{}\") {{ phase winner }} }}", finished_id)).await;
+    assert_eq!(game, json!({ "game": { "phase": "Ended", "winner": winner } }));
+
+    // only finished games can be replayed
+    let replays = query(&games, &format!("{{ finished: replay(id: \"{}\") {{ kind }} lobby: replay(id: \"{}\") {{ kind }} }}", finished_id, lobby_id)).await;
+    assert!(!replays["finished"].as_array().unwrap().is_empty());
+    assert!(replays["lobby"].is_null());
+
+    let stats = query(&games, "{ playerStats { name games wins } }").await;
+    let stats = stats["playerStats"].as_array().unwrap();
+    assert_eq!(stats.len(), 5);
+    assert!(stats.iter().all(|s| s["games"] == 1));
+    let wins: u64 = stats.iter().map(|s| s["wins"].as_u64().unwrap()).sum();
+    assert!(wins == 2 || wins == 3);
+}
@@ -0,0 +1,1609 @@
+use std::{sync::Arc, time::{Duration, Instant, SystemTime}};
+
+use secrethitler::{achievements::Achievements, addresses::{self, AddressPolicy}, archive::{self, ArchivedGame, GameArchive, HistoryQuery, PAGE_SIZE}, audit::{self, AuditReason}, bans::{BanList, Visitor}, blocks::BlockList, captcha::{HostCheck, HostGate, proof_of_work_valid}, communities::{self, Communities}, cors::{self, CorsPolicy}, dashboard, dispatch::{CatchPanics, Dispatcher, RateLimit}, friends::friend_id, calendar, email::{EmailConfig, EmailDispatcher}, limits::{MessageRateLimiter, ServerLimits}, loadtest::{self, LoadTest, percentile}, ratings::{RatedSeat, Ratings}, replays::ReplayCache, server::{ActivePlayers, ConnectionContext, Frame, GlobalState, MAX_MESSAGE_BYTES, ServerState, get_game, handle_connect, handle_disconnect, handle_message, parse_message, read_frame}, snapshots::Snapshots, tokens::ResumeTokens, typescript, uploads::{self, MAX_UPLOAD_BYTES}, watchdog::{StuckPolicy, Watchdog}, webhooks::WebhookDispatcher};
+use secrethitler_core::{achievements::Achievement, chat::LinkPolicy, game_state::{CardColor, GameOptions, PlayerType, TurnPhase, epoch_millis}, protocol::{BanTarget, ClientProtocol, FeedbackReport, ModeratorAction}, simulation::Simulation};
+use tokio::sync::mpsc;
+use tokio_stream::{StreamExt, wrappers::UnboundedReceiverStream};
+use uuid::Uuid;
+use warp::{Filter, ws::Message};
+
+type Receiver = mpsc::UnboundedReceiver<Result<Message, warp::Error>>;
+
+fn test_server(max_games: Option<usize>) -> ServerState {
+    ServerState {
+        games: GlobalState::default(),
+        webhooks: WebhookDispatcher::start(vec![], None),
+        email: EmailDispatcher::start(None, None),
+        tokens: Arc::new(ResumeTokens::new(vec![], Duration::from_secs(60))),
+        limits: Arc::new(ServerLimits::new(max_games, None, Duration::from_secs(10))),
+        audit: Arc::default(),
+        presets: Arc::default(),
+        friends: Arc::default(),
+        replays: Arc::default(),
+        archive: Arc::default(),
+        ratings: Arc::default(),
+        bans: Arc::default(),
+        addresses: Arc::default(),
+        host_gate: Arc::default(),
+        achievements: Arc::default(),
+        watchdog: Arc::default(),
+        active_players: ActivePlayers::default(),
+        motd: Arc::default(),
+        communities: Arc::new(Communities::new(&["chess-club".to_string()]).unwrap()),
+        admin_token: Some("admin".into()),
+        allow_multiple_games: false,
+        require_action_auth: false,
+        chat_links: Arc::default(),
+        uploads: Arc::default(),
+        blocks: Arc::default(),
+    }
+}
+
+fn connect() -> (ConnectionContext, Receiver) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    (ConnectionContext::new(tx), rx)
+}
+
+/// Collect all of the messages sent to the connection so far.
+fn drain(rx: &mut Receiver) -> Vec<serde_json::Value> {
+    let mut messages = vec![];
+    while let Ok(Ok(msg)) = rx.try_recv() {
+        messages.push(serde_json::from_str(msg.to_str().unwrap()).unwrap());
+    }
+    messages
+}
+
+fn find<'a>(messages: &'a [serde_json::Value], kind: &str) -> Option<&'a serde_json::Value> {
+    messages.iter().find(|m| m["type"] == kind)
+}
+
+fn host(nickname: &str) -> ClientProtocol {
+    ClientProtocol::HostGame { nickname: nickname.into(), options: GameOptions::default(), avatar: None, color: None, player_secret: None, preset: None, captcha: None }
+}
+
+fn join(id: Uuid, nickname: &str) -> ClientProtocol {
+    ClientProtocol::JoinGame { id, nickname: nickname.into(), player_id: None, player_secret: None, resume_token: None, avatar: None, color: None }
+}
+
+#[test]
+fn test_host_game() {
+    let server = test_server(None);
+    let (mut ctx, mut rx) = connect();
+
+    handle_message(&server, &mut ctx, host("  "));
+    assert!(find(&drain(&mut rx), "Alert").is_some());
+    assert!(ctx.game.is_none());
+
+    handle_message(&server, &mut ctx, host("alice"));
+    let messages = drain(&mut rx);
+    let identifiers = find(&messages, "SetIdentifiers").expect("host should receive identifiers");
+    assert_eq!(identifiers["game_id"], ctx.game.unwrap().to_string());
+    assert!(find(&messages, "GameState").is_some());
+    assert!(server.games.read().contains_key(&ctx.game.unwrap()));
+}
+
+#[test]
+fn test_suggest_nickname() {
+    let server = test_server(None);
+    let (mut ctx, mut rx) = connect();
+
+    // a blank nickname is turned down with one that could be used instead
+    handle_message(&server, &mut ctx, host(""));
+    let messages = drain(&mut rx);
+    assert!(find(&messages, "Alert").is_some());
+    assert!(!find(&messages, "NicknameSuggestion").unwrap()["nickname"].as_str().unwrap().is_empty());
+
+    handle_message(&server, &mut ctx, host("alice"));
+    let game_id = ctx.game.unwrap();
+    handle_message(&server, &mut ctx, ClientProtocol::SuggestNickname { game_id: Some(game_id) });
+    let suggested = find(&drain(&mut rx), "NicknameSuggestion").unwrap()["nickname"].as_str().unwrap().to_string();
+    assert_ne!(suggested, "alice");
+
+    // joining without a nickname gets one nobody else in the game has
+    let (mut guest, _guest_rx) = connect();
+    handle_message(&server, &mut guest, join(game_id, " "));
+    assert_eq!(guest.game, Some(game_id));
+    let game = server.games.read().get(&game_id).unwrap().clone();
+    let name = game.lock().conn.get(&guest.player.unwrap()).unwrap().name.clone().unwrap();
+    assert!(!name.trim().is_empty());
+    assert_ne!(name, "alice");
+}
+
+#[test]
+fn test_host_while_in_game() {
+    let server = test_server(None);
+    let (mut host_ctx, mut host_rx) = connect();
+    handle_message(&server, &mut host_ctx, host("alice"));
+    let game_id = host_ctx.game.unwrap();
+    for i in 0..4 {
+        let (mut ctx, _) = connect();
+        handle_message(&server, &mut ctx, join(game_id, &format!("player {}", i)));
+        assert_eq!(ctx.game, Some(game_id));
+    }
+    handle_message(&server, &mut host_ctx, ClientProtocol::StartGame { request_id: None });
+    drain(&mut host_rx);
+
+    handle_message(&server, &mut host_ctx, host("alice"));
+    assert!(find(&drain(&mut host_rx), "Alert").is_some());
+    assert_eq!(host_ctx.game, Some(game_id));
+    assert_eq!(server.games.read().len(), 1);
+}
+
+#[test]
+fn test_join_with_invalid_secret() {
+    let server = test_server(None);
+    let (mut host_ctx, _) = connect();
+    handle_message(&server, &mut host_ctx, host("alice"));
+
+    let (mut ctx, mut rx) = connect();
+    handle_message(&server, &mut ctx, ClientProtocol::JoinGame {
+        id: host_ctx.game.unwrap(),
+        nickname: "mallory".into(),
+        player_id: host_ctx.player,
+        player_secret: Some(Uuid::new_v4()),
+        resume_token: None,
+        avatar: None,
+        color: None
+    });
+    let messages = drain(&mut rx);
+    assert!(find(&messages, "Alert").is_some());
+    assert!(find(&messages, "SetIdentifiers").is_none());
+    assert!(ctx.game.is_none());
+
+    handle_message(&server, &mut ctx, join(Uuid::new_v4(), "bob"));
+    assert!(find(&drain(&mut rx), "Alert").is_some());
+    assert!(ctx.game.is_none());
+}
+
+#[test]
+fn test_server_busy() {
+    let server = test_server(Some(1));
+    let (mut first, _) = connect();
+    handle_message(&server, &mut first, host("alice"));
+    assert!(first.game.is_some());
+
+    let (mut second, mut rx) = connect();
+    handle_message(&server, &mut second, host("bob"));
+    let messages = drain(&mut rx);
+    let busy = find(&messages, "ServerBusy").expect("server should be busy");
+    assert_eq!(busy["retry_after"], 10);
+    assert!((10_000..=15_000).contains(&busy["retry_after_ms"].as_u64().unwrap()));
+    assert!(second.game.is_none());
+
+    // leaving the lobby frees the seat but the game stays around until it is cleaned up
+    handle_disconnect(&server, &first);
+    handle_message(&server, &mut second, host("bob"));
+    assert!(find(&drain(&mut rx), "ServerBusy").is_some());
+}
+
+#[test]
+fn test_reconnect_throttle() {
+    let server = test_server(None);
+    let (mut host_ctx, _host_rx) = connect();
+    handle_message(&server, &mut host_ctx, host("alice"));
+    let (mut ctx, mut rx) = connect();
+    handle_message(&server, &mut ctx, join(host_ctx.game.unwrap(), "bob"));
+    let identifiers = find(&drain(&mut rx), "SetIdentifiers").unwrap().clone();
+    let player_id = Uuid::parse_str(identifiers["player_id"].as_str().unwrap()).unwrap();
+    let secret = Uuid::parse_str(identifiers["secret"].as_str().unwrap()).unwrap();
+    let rejoin = || ClientProtocol::JoinGame { id: host_ctx.game.unwrap(), nickname: "bob".into(), player_id: Some(player_id), player_secret: Some(secret), resume_token: None, avatar: None, color: None };
+
+    // player ids are public, so attempts with the wrong secret do not use up the real player's budget
+    for _ in 0..10 {
+        let (mut ctx, mut rx) = connect();
+        handle_message(&server, &mut ctx, ClientProtocol::JoinGame { id: host_ctx.game.unwrap(), nickname: "mallory".into(), player_id: Some(player_id), player_secret: Some(Uuid::new_v4()), resume_token: None, avatar: None, color: None });
+        assert_eq!(find(&drain(&mut rx), "Alert").unwrap()["message"], "Invalid player secret passed to server!");
+    }
+    for _ in 0..5 {
+        let (mut ctx, mut rx) = connect();
+        handle_message(&server, &mut ctx, rejoin());
+        assert_eq!(ctx.player, Some(player_id));
+        assert!(find(&drain(&mut rx), "ReconnectThrottled").is_none());
+    }
+    // a player who keeps coming back is told to wait, with the wait spread out so a whole game does not return at once
+    let (mut ctx, mut rx) = connect();
+    handle_message(&server, &mut ctx, rejoin());
+    assert!(ctx.player.is_none());
+    let messages = drain(&mut rx);
+    let throttled = find(&messages, "ReconnectThrottled").expect("reconnect should be throttled");
+    assert!((1..=15_000).contains(&throttled["retry_after_ms"].as_u64().unwrap()));
+}
+
+#[test]
+fn test_game_error() {
+    let server = test_server(None);
+    let (mut host_ctx, _) = connect();
+    handle_message(&server, &mut host_ctx, host("alice"));
+    let (mut ctx, mut rx) = connect();
+    handle_message(&server, &mut ctx, join(host_ctx.game.unwrap(), "bob"));
+    drain(&mut rx);
+
+    handle_message(&server, &mut ctx, ClientProtocol::StartGame { request_id: None });
+    let messages = drain(&mut rx);
+    let error = find(&messages, "Error").expect("only the host can start the game");
+    assert_eq!(error["code"], "NotHost");
+    assert_eq!(error["message"], "Only the host may start the game!");
+
+    handle_message(&server, &mut ctx, ClientProtocol::ChooseChancellor { player: host_ctx.player.unwrap(), request_id: None });
+    assert_eq!(find(&drain(&mut rx), "Error").unwrap()["code"], "WrongPhase");
+}
+
+#[test]
+fn test_request_id_retry() {
+    let server = test_server(None);
+    let (mut host_ctx, mut rx) = connect();
+    handle_message(&server, &mut host_ctx, host("alice"));
+    let game_id = host_ctx.game.unwrap();
+
+    // starting too early fails, and a retry gets the same failure even once it would succeed
+    let start = || ClientProtocol::StartGame { request_id: Some("start".into()) };
+    handle_message(&server, &mut host_ctx, start());
+    assert_eq!(find(&drain(&mut rx), "Error").unwrap()["request_id"], "start");
+    for i in 0..4 {
+        let (mut ctx, _) = connect();
+        handle_message(&server, &mut ctx, join(game_id, &format!("player {}", i)));
+    }
+    drain(&mut rx);
+    handle_message(&server, &mut host_ctx, start());
+    assert_eq!(find(&drain(&mut rx), "Error").unwrap()["code"], "InvalidPlayerCount");
+
+    handle_message(&server, &mut host_ctx, ClientProtocol::StartGame { request_id: Some("start again".into()) });
+    let messages = drain(&mut rx);
+    assert_eq!(find(&messages, "Ack").unwrap()["request_id"], "start again");
+    assert!(find(&messages, "Error").is_none());
+
+    // applying the start twice would fail because the game is already running
+    handle_message(&server, &mut host_ctx, ClientProtocol::StartGame { request_id: Some("start again".into()) });
+    let messages = drain(&mut rx);
+    assert!(find(&messages, "Ack").is_some());
+    assert!(find(&messages, "Error").is_none());
+}
+
+#[test]
+fn test_one_game_per_player() {
+    let server = test_server(None);
+    let (mut ctx, mut rx) = connect();
+    let secret = Uuid::new_v4();
+    handle_message(&server, &mut ctx, ClientProtocol::HostGame { nickname: "alice".into(), options: GameOptions::default(), avatar: None, color: None, player_secret: Some(secret), preset: None, captcha: None });
+    let game_id = ctx.game.unwrap();
+    drain(&mut rx);
+
+    // a reopened tab can find the game again from the secret alone
+    let (mut tab, mut tab_rx) = connect();
+    handle_message(&server, &mut tab, ClientProtocol::WhereAmI { player_secret: secret });
+    let messages = drain(&mut tab_rx);
+    let current = find(&messages, "CurrentGame").unwrap();
+    assert_eq!(current["game_id"], game_id.to_string());
+    assert_eq!(current["player_id"], ctx.player.unwrap().to_string());
+
+    // but it cannot be used to sit in a second game
+    handle_message(&server, &mut tab, ClientProtocol::HostGame { nickname: "alice".into(), options: GameOptions::default(), avatar: None, color: None, player_secret: Some(secret), preset: None, captcha: None });
+    assert!(find(&drain(&mut tab_rx), "Alert").is_some());
+    assert!(tab.game.is_none());
+    let (mut other_host, _) = connect();
+    handle_message(&server, &mut other_host, host("bob"));
+    handle_message(&server, &mut tab, ClientProtocol::JoinGame { id: other_host.game.unwrap(), nickname: "alice".into(), player_id: None, player_secret: Some(secret), resume_token: None, avatar: None, color: None });
+    assert!(find(&drain(&mut tab_rx), "Alert").is_some());
+    assert!(tab.game.is_none());
+
+    // after leaving, the player is free to join another game
+    handle_message(&server, &mut ctx, ClientProtocol::Leave);
+    handle_message(&server, &mut tab, ClientProtocol::WhereAmI { player_secret: secret });
+    assert!(find(&drain(&mut tab_rx), "CurrentGame").unwrap()["game_id"].is_null());
+    handle_message(&server, &mut tab, ClientProtocol::JoinGame { id: other_host.game.unwrap(), nickname: "alice".into(), player_id: None, player_secret: Some(secret), resume_token: None, avatar: None, color: None });
+    assert_eq!(tab.game, other_host.game);
+}
+
+#[test]
+fn test_move_to_another_connection() {
+    let server = test_server(None);
+    let (mut old_ctx, mut old_rx) = connect();
+    handle_message(&server, &mut old_ctx, host("alice"));
+    let messages = drain(&mut old_rx);
+    let identifiers = find(&messages, "SetIdentifiers").unwrap();
+    let game_id = old_ctx.game.unwrap();
+    let player_id: Uuid = identifiers["player_id"].as_str().unwrap().parse().unwrap();
+    let player_secret: Uuid = identifiers["secret"].as_str().unwrap().parse().unwrap();
+
+    // the player opens the game on another device while the first is still connected
+    let (mut new_ctx, mut new_rx) = connect();
+    handle_message(&server, &mut new_ctx, ClientProtocol::JoinGame { id: game_id, nickname: "alice".into(), player_id: Some(player_id), player_secret: Some(player_secret), resume_token: None, avatar: None, color: None });
+    assert!(find(&drain(&mut new_rx), "GameState").is_some());
+
+    // closing the old connection does not take the seat away from the new one
+    handle_disconnect(&server, &old_ctx);
+    let game = server.games.read().get(&game_id).unwrap().clone();
+    assert!(game.lock().conn.get(&player_id).unwrap().connected);
+    let (mut other_ctx, _) = connect();
+    handle_message(&server, &mut other_ctx, join(game_id, "bob"));
+    assert!(find(&drain(&mut new_rx), "GameState").is_some());
+    assert!(drain(&mut old_rx).is_empty());
+
+    // messages sent while the player has no connection are replayed when they come back
+    handle_disconnect(&server, &new_ctx);
+    let (mut other_ctx, _) = connect();
+    handle_message(&server, &mut other_ctx, join(game_id, "carol"));
+    let (mut last_ctx, mut last_rx) = connect();
+    handle_message(&server, &mut last_ctx, ClientProtocol::JoinGame { id: game_id, nickname: "alice".into(), player_id: Some(player_id), player_secret: Some(player_secret), resume_token: None, avatar: None, color: None });
+    let messages = drain(&mut last_rx);
+    assert!(messages.iter().any(|m| m["type"] == "ReceiveChat" && m["message"].as_str().unwrap().contains("carol")));
+}
+
+#[test]
+fn test_reports_and_shared_addresses() {
+    let server = test_server(None);
+    let address = "203.0.113.7".parse().ok();
+    let (mut host_ctx, mut host_rx) = connect();
+    host_ctx.address = address;
+    handle_message(&server, &mut host_ctx, host("alice"));
+    let game_id = host_ctx.game.unwrap();
+    let (mut other_ctx, _) = connect();
+    handle_message(&server, &mut other_ctx, join(game_id, "bob"));
+    assert!(server.audit.entries().is_empty());
+
+    // a second seat from the same address flags the game
+    let (mut twin_ctx, _) = connect();
+    twin_ctx.address = address;
+    handle_message(&server, &mut twin_ctx, join(game_id, "carol"));
+    let entries = server.audit.entries();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].reason, AuditReason::SharedAddress);
+    assert_eq!(entries[0].players, vec![twin_ctx.player.unwrap(), host_ctx.player.unwrap()]);
+    drain(&mut host_rx);
+
+    let host_id = host_ctx.player.unwrap();
+    handle_message(&server, &mut host_ctx, ClientProtocol::ReportPlayer { player: host_id, reason: "testing".into() });
+    assert_eq!(find(&drain(&mut host_rx), "Alert").unwrap()["message"], "You cannot report yourself.");
+    handle_message(&server, &mut host_ctx, ClientProtocol::ReportPlayer { player: other_ctx.player.unwrap(), reason: "  ".into() });
+    assert_eq!(find(&drain(&mut host_rx), "Alert").unwrap()["message"], "Please say why you are reporting this player.");
+    handle_message(&server, &mut host_ctx, ClientProtocol::ReportPlayer { player: other_ctx.player.unwrap(), reason: "knew my role".into() });
+    assert_eq!(find(&drain(&mut host_rx), "Alert").unwrap()["message"], "Thanks, your report has been passed on to the admins.");
+    handle_message(&server, &mut host_ctx, ClientProtocol::ReportPlayer { player: other_ctx.player.unwrap(), reason: "knew my role".into() });
+    assert_eq!(find(&drain(&mut host_rx), "Alert").unwrap()["message"], "You have already reported this player.");
+    let entries = server.audit.entries();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1].reason, AuditReason::Reported);
+    assert_eq!(entries[1].detail, "knew my role");
+}
+
+#[test]
+fn test_shared_device_warning() {
+    let server = test_server(None);
+    let (mut host_ctx, mut host_rx) = connect();
+    host_ctx.address = "203.0.113.7".parse().ok();
+    host_ctx.user_agent = Some("Firefox".into());
+    handle_message(&server, &mut host_ctx, host("alice"));
+    let game_id = host_ctx.game.unwrap();
+
+    // the same address from another browser is not a warning, since friends often share a network
+    let (mut phone_ctx, _) = connect();
+    phone_ctx.address = host_ctx.address;
+    phone_ctx.user_agent = Some("Safari".into());
+    handle_message(&server, &mut phone_ctx, join(game_id, "bob"));
+    assert!(find(&drain(&mut host_rx), "GameState").unwrap()["state"].get("shared_devices").is_none());
+
+    let (mut twin_ctx, mut twin_rx) = connect();
+    twin_ctx.address = host_ctx.address;
+    twin_ctx.user_agent = host_ctx.user_agent.clone();
+    handle_message(&server, &mut twin_ctx, join(game_id, "carol"));
+    let messages = drain(&mut host_rx);
+    let mut expected = vec![host_ctx.player.unwrap().to_string(), twin_ctx.player.unwrap().to_string()];
+    expected.sort();
+    assert_eq!(find(&messages, "GameState").unwrap()["state"]["shared_devices"], serde_json::json!([expected]));
+
+    // only the host is warned
+    assert!(find(&drain(&mut twin_rx), "GameState").unwrap()["state"].get("shared_devices").is_none());
+}
+
+#[test]
+fn test_presets() {
+    let server = test_server(None);
+    let (mut ctx, mut rx) = connect();
+    let secret = Uuid::new_v4();
+    let options = GameOptions { max_players: Some(6), turn_timer: Some(90), ..GameOptions::default() };
+    handle_message(&server, &mut ctx, ClientProtocol::SavePreset { player_secret: secret, name: " quick ".into(), options });
+    let messages = drain(&mut rx);
+    let presets = &find(&messages, "Presets").unwrap()["presets"];
+    assert_eq!(presets[0]["name"], "quick");
+    assert_eq!(presets[0]["options"]["max_players"], 6);
+
+    // presets belong to the secret that saved them
+    let host_with = |preset: &str, player_secret| ClientProtocol::HostGame { nickname: "alice".into(), options: GameOptions::default(), avatar: None, color: None, player_secret: Some(player_secret), preset: Some(preset.into()), captcha: None };
+    handle_message(&server, &mut ctx, host_with("quick", Uuid::new_v4()));
+    assert_eq!(find(&drain(&mut rx), "Alert").unwrap()["message"], "You have no preset with that name.");
+    assert!(ctx.game.is_none());
+
+    handle_message(&server, &mut ctx, host_with("quick", secret));
+    let game = server.games.read().get(&ctx.game.unwrap()).unwrap().clone();
+    assert_eq!(game.lock().max_players(), 6);
+    assert_eq!(game.lock().options.turn_timer, Some(90));
+    drain(&mut rx);
+
+    handle_message(&server, &mut ctx, ClientProtocol::DeletePreset { player_secret: secret, name: "quick".into() });
+    assert_eq!(find(&drain(&mut rx), "Presets").unwrap()["presets"], serde_json::json!([]));
+    handle_message(&server, &mut ctx, ClientProtocol::DeletePreset { player_secret: secret, name: "quick".into() });
+    assert!(find(&drain(&mut rx), "Alert").is_some());
+}
+
+#[test]
+fn test_scheduled_game() {
+    let server = test_server(None);
+    let (mut host_ctx, mut host_rx) = connect();
+    let opens_at = epoch_millis(SystemTime::now() + Duration::from_secs(24 * 60 * 60));
+    let options = GameOptions { scheduled_at: Some(opens_at), ..GameOptions::default() };
+    handle_message(&server, &mut host_ctx, ClientProtocol::HostGame { nickname: "alice".into(), options: options.clone(), avatar: None, color: None, player_secret: None, preset: None, captcha: None });
+    let messages = drain(&mut host_rx);
+    assert_eq!(find(&messages, "GameState").unwrap()["state"]["opens_at"], opens_at);
+
+    let (mut ctx, mut rx) = connect();
+    handle_message(&server, &mut ctx, join(host_ctx.game.unwrap(), "bob"));
+    assert_eq!(find(&drain(&mut rx), "Alert").unwrap()["message"], "This game has not opened for joining yet.");
+    assert!(ctx.game.is_none());
+
+    let far_off = GameOptions { scheduled_at: Some(opens_at + 60 * 24 * 60 * 60 * 1000), ..GameOptions::default() };
+    handle_message(&server, &mut ctx, ClientProtocol::HostGame { nickname: "bob".into(), options: far_off, avatar: None, color: None, player_secret: None, preset: None, captcha: None });
+    assert_eq!(find(&drain(&mut rx), "Alert").unwrap()["message"], "Games can be scheduled at most 30 days ahead.");
+
+    let game_id = Uuid::new_v4();
+    let ics = calendar::event(game_id, 1709319600000, 1709000000000, Some("alice, bob; carol"), Some("https://example.com/game/1"));
+    assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+    assert!(ics.contains(&format!("UID:{}@secrethitler\r\n", game_id)));
+    assert!(ics.contains("DTSTART:20240301T190000Z\r\n"));
+    assert!(ics.contains("SUMMARY:Secret Hitler hosted by alice\\, bob\\; carol\r\n"));
+    assert!(ics.ends_with("END:VCALENDAR\r\n"));
+}
+
+#[tokio::test]
+async fn test_email_opt_in_and_invitations() {
+    let mut server = test_server(None);
+    let (mut ctx, mut rx) = connect();
+    handle_message(&server, &mut ctx, ClientProtocol::SetEmail { email: Some("alice@example.com".into()) });
+    assert!(find(&drain(&mut rx), "Alert").is_some());
+
+    // email is disabled until an SMTP server is configured
+    handle_message(&server, &mut ctx, host("alice"));
+    drain(&mut rx);
+    handle_message(&server, &mut ctx, ClientProtocol::InviteByEmail { email: "bob@example.com".into() });
+    assert_eq!(find(&drain(&mut rx), "Alert").unwrap()["message"], "Email is not set up on this server.");
+
+    let config = EmailConfig { smtp_url: "smtp://127.0.0.1:1".into(), from: "Secret Hitler <games@example.com>".into(), per_minute: 1, template_dir: None };
+    server.email = EmailDispatcher::start(Some(config), None);
+    handle_message(&server, &mut ctx, ClientProtocol::SetEmail { email: Some("not an address".into()) });
+    assert_eq!(find(&drain(&mut rx), "Alert").unwrap()["message"], "That is not a valid email address.");
+    handle_message(&server, &mut ctx, ClientProtocol::SetEmail { email: Some("alice@example.com".into()) });
+    assert_eq!(find(&drain(&mut rx), "Alert").unwrap()["message"], "You will get email about this game.");
+
+    for _ in 0..10 {
+        handle_message(&server, &mut ctx, ClientProtocol::InviteByEmail { email: "bob@example.com".into() });
+        assert_eq!(find(&drain(&mut rx), "Alert").unwrap()["message"], "An invitation is on its way to bob@example.com.");
+    }
+    handle_message(&server, &mut ctx, ClientProtocol::InviteByEmail { email: "bob@example.com".into() });
+    assert_eq!(find(&drain(&mut rx), "Alert").unwrap()["message"], "This game has already sent as many invitations as it can.");
+}
+
+#[test]
+fn test_friend_invites() {
+    let server = test_server(None);
+    let (mut alice_ctx, mut alice_rx) = connect();
+    let (mut bob_ctx, mut bob_rx) = connect();
+    let (alice_secret, bob_secret) = (Uuid::new_v4(), Uuid::new_v4());
+    handle_message(&server, &mut bob_ctx, ClientProtocol::ListFriends { player_secret: bob_secret });
+    let bob_id: Uuid = serde_json::from_value(find(&drain(&mut bob_rx), "Friends").unwrap()["friend_id"].clone()).unwrap();
+    handle_message(&server, &mut alice_ctx, ClientProtocol::ListFriends { player_secret: alice_secret });
+    let alice_id: Uuid = serde_json::from_value(find(&drain(&mut alice_rx), "Friends").unwrap()["friend_id"].clone()).unwrap();
+
+    handle_message(&server, &mut alice_ctx, ClientProtocol::HostGame { nickname: "alice".into(), options: GameOptions::default(), avatar: None, color: None, player_secret: Some(alice_secret), preset: None, captcha: None });
+    let game_id = alice_ctx.game.unwrap();
+    drain(&mut alice_rx);
+
+    // a friend request has to be accepted before invitations can be sent
+    handle_message(&server, &mut alice_ctx, ClientProtocol::AddFriend { player_secret: alice_secret, friend_id: bob_id });
+    assert_eq!(find(&drain(&mut alice_rx), "Friends").unwrap()["friends"], serde_json::json!([]));
+    handle_message(&server, &mut alice_ctx, ClientProtocol::InviteFriend { friend_id: bob_id, game_id });
+    assert_eq!(find(&drain(&mut alice_rx), "Alert").unwrap()["message"], "You can only invite your friends.");
+
+    handle_message(&server, &mut bob_ctx, ClientProtocol::ListFriends { player_secret: bob_secret });
+    assert_eq!(find(&drain(&mut bob_rx), "Friends").unwrap()["requests"], serde_json::json!([alice_id]));
+    handle_message(&server, &mut bob_ctx, ClientProtocol::AddFriend { player_secret: bob_secret, friend_id: alice_id });
+    assert_eq!(find(&drain(&mut bob_rx), "Friends").unwrap()["friends"], serde_json::json!([alice_id]));
+
+    // the invitation reaches bob's connection right away and waits until he answers it
+    handle_message(&server, &mut alice_ctx, ClientProtocol::InviteFriend { friend_id: bob_id, game_id });
+    assert_eq!(find(&drain(&mut alice_rx), "Alert").unwrap()["message"], "Your friend has been invited.");
+    let invite = find(&drain(&mut bob_rx), "InviteReceived").unwrap()["invite"].clone();
+    assert_eq!(invite["name"], "alice");
+    assert_eq!(invite["game_id"], game_id.to_string());
+    handle_message(&server, &mut bob_ctx, ClientProtocol::ListFriends { player_secret: bob_secret });
+    assert_eq!(find(&drain(&mut bob_rx), "Friends").unwrap()["invites"][0]["id"], invite["id"]);
+
+    let invite_id: Uuid = serde_json::from_value(invite["id"].clone()).unwrap();
+    handle_message(&server, &mut bob_ctx, ClientProtocol::RespondToInvite { player_secret: bob_secret, invite_id, accept: true });
+    assert_eq!(find(&drain(&mut bob_rx), "Friends").unwrap()["invites"], serde_json::json!([]));
+    assert_eq!(find(&drain(&mut alice_rx), "Alert").unwrap()["message"], "A friend accepted your invitation.");
+
+    // invitations are only for games the sender is in
+    handle_message(&server, &mut bob_ctx, ClientProtocol::InviteFriend { friend_id: alice_id, game_id });
+    assert_eq!(find(&drain(&mut bob_rx), "Alert").unwrap()["message"], "You are not in that game.");
+}
+
+#[test]
+fn test_replay_cache() {
+    let mut simulation = Simulation::new(Some(3));
+    let (first, second) = (simulation.play(5), simulation.play(7));
+    let (first_id, second_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+    // with no room in memory and nowhere to spill, only the latest replay is kept
+    let replays = ReplayCache::new(1, None);
+    replays.insert(first_id, first.timeline());
+    replays.insert(second_id, second.timeline());
+    assert!(replays.get(first_id).is_none());
+    assert_eq!(replays.get(second_id).unwrap().len(), second.timeline().len());
+
+    // replays pushed out of memory are read back from disk
+    let dir = std::env::temp_dir().join(format!("replays-{}", Uuid::new_v4()));
+    let replays = ReplayCache::new(1, Some(dir.clone()));
+    replays.insert(first_id, first.timeline());
+    replays.insert(second_id, second.timeline());
+    assert_eq!(replays.counts(), (1, 1));
+    assert!(dir.join(format!("{}.json.zst", first_id)).exists());
+    let timeline = replays.get(first_id).unwrap();
+    assert_eq!(serde_json::to_value(&timeline).unwrap(), serde_json::to_value(first.timeline()).unwrap());
+    assert!(!dir.join(format!("{}.json.zst", first_id)).exists());
+    assert_eq!(replays.counts(), (1, 1));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// Seat five players in a game and start it, returning each seat's connection and secret.
+fn start_game(server: &ServerState) -> Vec<(ConnectionContext, Receiver, Uuid)> {
+    let mut seats: Vec<(ConnectionContext, Receiver, Uuid)> = vec![];
+    for i in 0..5 {
+        let (mut ctx, mut rx) = connect();
+        if i == 0 {
+            handle_message(server, &mut ctx, host("player 0"));
+        }
+        else {
+            handle_message(server, &mut ctx, join(seats[0].0.game.unwrap(), &format!("player {}", i)));
+        }
+        let secret: Uuid = serde_json::from_value(find(&drain(&mut rx), "SetIdentifiers").unwrap()["secret"].clone()).unwrap();
+        seats.push((ctx, rx, secret));
+    }
+    handle_message(server, &mut seats[0].0, ClientProtocol::StartGame { request_id: None });
+    seats
+}
+
+#[test]
+fn test_export_and_import_game() {
+    let server = test_server(None);
+    let mut seats = start_game(&server);
+    let game_id = seats[0].0.game.unwrap();
+    let before = drain(&mut seats[1].1).into_iter().rfind(|m| m["type"] == "GameState").unwrap()["state"].clone();
+
+    let (mut admin_ctx, mut admin_rx) = connect();
+    handle_message(&server, &mut admin_ctx, ClientProtocol::ExportGame { admin_token: "wrong".into(), game_id });
+    assert_eq!(find(&drain(&mut admin_rx), "Alert").unwrap()["message"], "Invalid admin token.");
+    handle_message(&server, &mut admin_ctx, ClientProtocol::ExportGame { admin_token: "admin".into(), game_id });
+    let export = find(&drain(&mut admin_rx), "GameExport").unwrap()["game"].clone();
+
+    // the game carries over to a fresh server, where players take their seats again with their secrets
+    let other = test_server(None);
+    let import = serde_json::json!({ "type": "ImportGame", "admin_token": "admin", "game_id": game_id, "game": export });
+    handle_message(&other, &mut admin_ctx, serde_json::from_value(import.clone()).unwrap());
+    assert_eq!(find(&drain(&mut admin_rx), "Alert").unwrap()["message"], "The game has been imported.");
+    handle_message(&other, &mut admin_ctx, serde_json::from_value(import).unwrap());
+    assert_eq!(find(&drain(&mut admin_rx), "Alert").unwrap()["message"], "A game with that id already exists.");
+
+    // games that could not be played on are turned away before they reach the server
+    let broken = |change: &dyn Fn(&mut serde_json::Value)| {
+        let mut export = export.clone();
+        change(&mut export);
+        let (mut admin_ctx, mut admin_rx) = connect();
+        handle_message(&other, &mut admin_ctx, serde_json::from_value(serde_json::json!({ "type": "ImportGame", "admin_token": "admin", "game_id": Uuid::new_v4(), "game": export })).unwrap());
+        let error = find(&drain(&mut admin_rx), "Error").unwrap().clone();
+        assert_eq!(error["code"], "InvalidExport");
+        error["message"].as_str().unwrap().to_string()
+    };
+    assert_eq!(broken(&|export| { export["cards"].as_array_mut().unwrap().pop(); }), "The game cannot be imported: The deck, discard pile, and board do not add up to a full set of policies.");
+    assert_eq!(broken(&|export| export["president"] = serde_json::to_value(Uuid::new_v4()).unwrap()), "The game cannot be imported: The president and chancellor have to be living players.");
+    assert_eq!(broken(&|export| {
+        let players = export["players"].as_object_mut().unwrap();
+        let first = players.keys().next().unwrap().clone();
+        players.remove(&first);
+    }), "The game cannot be imported: A game in progress needs between 5 and 10 players.");
+    assert_eq!(broken(&|export| export["turn_phase"] = serde_json::json!({ "type": "PresidentSelect" })), "The game cannot be imported: The game is waiting on a government without a chancellor.");
+    assert_eq!(other.games.read().len(), 1);
+
+    let (player_id, secret) = (seats[1].0.player.unwrap(), seats[1].2);
+    assert_eq!(other.current_game(secret), Some((game_id, player_id)));
+    let (mut ctx, mut rx) = connect();
+    handle_message(&other, &mut ctx, ClientProtocol::JoinGame { id: game_id, nickname: "player 1".into(), player_id: Some(player_id), player_secret: Some(secret), resume_token: None, avatar: None, color: None });
+    let after = drain(&mut rx).into_iter().rfind(|m| m["type"] == "GameState").unwrap()["state"].clone();
+    for key in ["turn_phase", "turn_order", "president", "players", "liberal_cards", "facist_cards"] {
+        assert_eq!(after[key], before[key], "{} changed", key);
+    }
+}
+
+#[test]
+fn test_snapshots() {
+    let server = test_server(None);
+    let seats = start_game(&server);
+    let game_id = seats[0].0.game.unwrap();
+    let (mut ctx, _rx) = connect();
+    handle_message(&server, &mut ctx, host("lobby"));
+    let dir = std::env::temp_dir().join(format!("snapshots-{}", Uuid::new_v4()));
+    let stale = dir.join(format!("{}.json", Uuid::new_v4()));
+
+    // only games in progress are saved, and snapshots of games that are gone are removed
+    let snapshots = Snapshots::new(dir.clone());
+    std::fs::write(&stale, "{}").unwrap();
+    assert_eq!(snapshots.save(&server), 1);
+    assert!(dir.join(format!("{}.json", game_id)).exists());
+    assert!(!stale.exists());
+
+    // a snapshot that no longer describes a playable game is skipped
+    let broken_id = Uuid::new_v4();
+    let mut broken: serde_json::Value = serde_json::from_slice(&std::fs::read(dir.join(format!("{}.json", game_id))).unwrap()).unwrap();
+    broken["cards"].as_array_mut().unwrap().pop();
+    std::fs::write(dir.join(format!("{}.json", broken_id)), broken.to_string()).unwrap();
+
+    // a fresh server picks the game up again, where players take their seats with their secrets
+    let other = test_server(None);
+    assert_eq!(Snapshots::new(dir.clone()).resume(&other), 1);
+    assert!(!other.games.read().contains_key(&broken_id));
+    let (player_id, secret) = (seats[1].0.player.unwrap(), seats[1].2);
+    assert_eq!(other.current_game(secret), Some((game_id, player_id)));
+    assert_eq!(snapshots.resume(&other), 0);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_debug_game() {
+    let server = test_server(None);
+    let seats = start_game(&server);
+    let game_id = seats[0].0.game.unwrap();
+    let (mut admin_ctx, mut admin_rx) = connect();
+    handle_message(&server, &mut admin_ctx, ClientProtocol::DebugGame { admin_token: "wrong".into(), game_id, redact_deck: true });
+    assert_eq!(find(&drain(&mut admin_rx), "Alert").unwrap()["message"], "Invalid admin token.");
+
+    // the dump lists every connection and who the game is waiting on, with the deck in order when it is redacted
+    handle_message(&server, &mut admin_ctx, ClientProtocol::DebugGame { admin_token: "admin".into(), game_id, redact_deck: true });
+    let debug = find(&drain(&mut admin_rx), "GameDebug").unwrap()["debug"].clone();
+    assert_eq!(debug["connections"].as_object().unwrap().len(), 5);
+    assert!(debug["connections"].as_object().unwrap().values().all(|conn| conn["connected"] == true && conn["attached"] == true));
+    assert_eq!(debug["awaiting"].as_array().unwrap().len(), 1);
+    let cards: Vec<String> = serde_json::from_value(debug["game"]["cards"].clone()).unwrap();
+    let mut sorted = cards.clone();
+    sorted.sort_by_key(|card| card.to_lowercase());
+    assert_eq!(cards, sorted);
+}
+
+#[test]
+fn test_stuck_game_watchdog() {
+    for policy in [StuckPolicy::Skip, StuckPolicy::Abort] {
+        let server = ServerState { watchdog: Arc::new(Watchdog::new(Some(Duration::ZERO), policy)), ..test_server(None) };
+        let mut seats = start_game(&server);
+        let game_id = seats[0].0.game.unwrap();
+        let game = server.games.read().get(&game_id).unwrap().clone();
+        let president = game.lock().president().unwrap();
+        let seat = seats.iter().position(|(ctx, _, _)| ctx.player == Some(president)).unwrap();
+
+        // nothing happens while the president is still connected
+        server.recover_stuck_games();
+        assert_eq!(server.watchdog.stats().found, 0);
+
+        handle_disconnect(&server, &seats[seat].0);
+        let other = (seat + 1) % seats.len();
+        drain(&mut seats[other].1);
+        server.recover_stuck_games();
+        let messages = drain(&mut seats[other].1);
+        assert_eq!(server.watchdog.stats().found, 1);
+        match policy {
+            StuckPolicy::Skip => {
+                // a bot nominated a chancellor for the absent president, and the game went on
+                assert!(!matches!(game.lock().turn_phase(), TurnPhase::Electing));
+                assert_eq!(server.watchdog.stats().skipped, 1);
+            },
+            _ => {
+                assert!(find(&messages, "Alert").is_some());
+                assert!(server.games.read().get(&game_id).is_none());
+                assert_eq!(server.watchdog.stats().aborted, 1);
+            }
+        }
+
+        // the same phase is not reported twice
+        server.recover_stuck_games();
+        assert_eq!(server.watchdog.stats().found, 1);
+    }
+}
+
+#[test]
+fn test_panic_recovery() {
+    let server = test_server(None);
+    let seats = start_game(&server);
+    let game_id = seats[0].0.game.unwrap();
+    let (mut admin_ctx, mut admin_rx) = connect();
+    handle_message(&server, &mut admin_ctx, ClientProtocol::ExportGame { admin_token: "admin".into(), game_id });
+    let mut export = find(&drain(&mut admin_rx), "GameExport").unwrap()["game"].clone();
+
+    // a chancellor missing from a hand edited export makes the vote that elects them panic
+    let (player_id, secret) = (seats[1].0.player.unwrap(), seats[1].2);
+    export["turn_phase"] = serde_json::json!({ "type": "Voting" });
+    export["chancellor"] = serde_json::json!(Uuid::new_v4());
+    for (id, player) in export["players"].as_object_mut().unwrap() {
+        player["vote"] = if *id == player_id.to_string() { serde_json::Value::Null } else { serde_json::json!(true) };
+    }
+    let other = test_server(None);
+    handle_message(&other, &mut admin_ctx, serde_json::from_value(serde_json::json!({ "type": "ImportGame", "admin_token": "admin", "game_id": game_id, "game": export.clone() })).unwrap());
+    assert_eq!(find(&drain(&mut admin_rx), "Error").unwrap()["message"], "The game cannot be imported: The president and chancellor have to be living players.");
+    // admins cannot import such a game any more, so it is put on the server directly
+    assert!(other.import_game(game_id, serde_json::from_value(export).unwrap()));
+
+    let (mut ctx, mut rx) = connect();
+    handle_message(&other, &mut ctx, ClientProtocol::JoinGame { id: game_id, nickname: "player 1".into(), player_id: Some(player_id), player_secret: Some(secret), resume_token: None, avatar: None, color: None });
+    drain(&mut rx);
+    handle_message(&other, &mut ctx, ClientProtocol::VoteChancellor { vote: true, request_id: None });
+    assert_eq!(find(&drain(&mut rx), "Error").unwrap()["code"], "Internal");
+
+    // the game and the server are still usable afterwards
+    let game = other.games.read().get(&game_id).unwrap().clone();
+    assert!(game.try_lock().is_some());
+    handle_message(&other, &mut ctx, ClientProtocol::GetRules);
+    assert!(find(&drain(&mut rx), "Rules").is_some());
+    let (mut host_ctx, mut host_rx) = connect();
+    handle_message(&other, &mut host_ctx, host("alice"));
+    assert!(find(&drain(&mut host_rx), "SetIdentifiers").is_some());
+
+    // nor does a panic on another thread while every game is locked
+    let games = other.games.clone();
+    assert!(std::thread::spawn(move || {
+        let _games = games.write();
+        panic!("serializer failed");
+    }).join().is_err());
+    handle_message(&other, &mut host_ctx, host("bob"));
+    assert!(find(&drain(&mut host_rx), "SetIdentifiers").is_some());
+}
+
+#[test]
+fn test_busy_game_does_not_block_others() {
+    let server = test_server(None);
+    let (mut host_ctx, _host_rx) = connect();
+    handle_message(&server, &mut host_ctx, host("alice"));
+    let game_id = host_ctx.game.unwrap();
+
+    // while one game is busy, other games can still be hosted and cleaned up
+    let game = server.games.read().get(&game_id).unwrap().clone();
+    let busy = game.lock();
+    let other = server.clone();
+    let hosted = std::thread::spawn(move || {
+        let (mut ctx, mut rx) = connect();
+        handle_message(&other, &mut ctx, host("bob"));
+        other.cleanup();
+        find(&drain(&mut rx), "SetIdentifiers").is_some()
+    });
+    assert!(hosted.join().unwrap());
+    drop(busy);
+
+    // players joining at once from different threads are all seated
+    let joins: Vec<_> = (1..5).map(|i| {
+        let server = server.clone();
+        std::thread::spawn(move || {
+            let (mut ctx, _rx) = connect();
+            handle_message(&server, &mut ctx, join(game_id, &format!("player {}", i)));
+            ctx.player.is_some()
+        })
+    }).collect();
+    assert!(joins.into_iter().all(|join| join.join().unwrap()));
+    assert_eq!(game.lock().conn.len(), 5);
+}
+
+#[test]
+fn test_ranked_ratings() {
+    let ratings = Ratings::default();
+    let secrets: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    let seats = || secrets.iter().enumerate().map(|(i, secret)| RatedSeat { secret: *secret, name: format!("player {}", i), party: if i < 3 { CardColor::Liberal } else { CardColor::Facist } }).collect::<Vec<_>>();
+    let now = SystemTime::now();
+
+    // players only show up once they have played their placement games
+    for _ in 0..4 {
+        ratings.record_game(None, &seats(), CardColor::Liberal, now);
+    }
+    assert!(ratings.leaderboard(None, now).is_empty());
+    ratings.record_game(None, &seats(), CardColor::Liberal, now);
+    let leaderboard = ratings.leaderboard(None, now);
+    assert_eq!(leaderboard.len(), 5);
+    assert_eq!(leaderboard[0].liberal.wins, 5);
+    assert!(leaderboard[0].liberal.rating > 1500.0 && leaderboard[0].facist.rating == 1500.0);
+    assert!(leaderboard[4].facist.rating < 1500.0 && leaderboard[4].facist.games == 5);
+    assert_eq!(leaderboard.iter().filter(|standing| standing.rating > 1500.0).count(), 3);
+
+    // ratings of players who stop playing drift back toward the start
+    let later = now + Duration::from_secs(365 * 24 * 60 * 60);
+    let decayed = ratings.leaderboard(None, later);
+    assert!(decayed[0].rating < leaderboard[0].rating && decayed[0].rating > 1500.0);
+
+    // bots cannot be rated, so games with bots are not ranked
+    assert!(RatedSeat::from_game(&Simulation::new(Some(1)).play(5)).is_none());
+}
+
+#[test]
+fn test_achievements_and_seasons() {
+    let achievements = Achievements::default();
+    let mut game = Simulation::new(Some(3)).play(5);
+    for conn in game.conn.values_mut() {
+        conn.is_bot = false;
+        conn.secret = Some(Uuid::new_v4());
+    }
+    let winner = game.winner().unwrap();
+    let now = SystemTime::now();
+
+    // achievements from the game itself are only earned the first time
+    let results = achievements.record_game(&game, now);
+    assert_eq!(results.len(), 5);
+    for result in &results {
+        let hitler = matches!(game.role(&result.player), Some(PlayerType::Hitler));
+        assert_eq!(result.earned.contains(&Achievement::HitlerWin), hitler && winner == CardColor::Facist);
+        assert_eq!(result.earned, game.game_achievements()[&result.player]);
+    }
+    for _ in 0..3 {
+        assert!(achievements.record_game(&game, now).iter().all(|result| result.earned.is_empty()));
+    }
+
+    // winning five in a row earns a streak
+    let results = achievements.record_game(&game, now);
+    assert!(results.iter().all(|result| result.earned == vec![Achievement::WinStreak] || result.season_wins == 0));
+    assert_eq!(results.iter().filter(|result| result.season_wins == 5).count(), if winner == CardColor::Liberal { 3 } else { 2 });
+
+    // a new season starts with an empty leaderboard, and the last one is kept
+    let season = achievements.season_at(now);
+    let leaderboard = achievements.leaderboard(None, season, now).unwrap();
+    assert_eq!(leaderboard.standings.len(), 5);
+    assert_eq!(leaderboard.standings[0].wins, 5);
+    assert_eq!(leaderboard.standings[4].games, 5);
+    let later = now + Duration::from_secs(90 * 24 * 60 * 60);
+    assert_eq!(achievements.season_at(later), season + 1);
+    assert!(achievements.leaderboard(None, season + 1, later).unwrap().standings.is_empty());
+    assert!(achievements.leaderboard(None, season, later).is_some());
+    assert!(achievements.leaderboard(None, season + 2, later).is_none());
+    assert!(achievements.leaderboard(None, season - 1, later).is_none());
+}
+
+#[test]
+fn test_motd_and_announcements() {
+    let server = test_server(None);
+    let (mut lobby_ctx, mut lobby_rx) = connect();
+    handle_message(&server, &mut lobby_ctx, host("alice"));
+    let mut seats = start_game(&server);
+    let game_id = seats[0].0.game.unwrap();
+    drain(&mut lobby_rx);
+    drain(&mut seats[0].1);
+
+    let (mut admin_ctx, mut admin_rx) = connect();
+    handle_message(&server, &mut admin_ctx, ClientProtocol::SetMotd { admin_token: "wrong".into(), message: Some("Maintenance tonight".into()), community: None });
+    assert_eq!(find(&drain(&mut admin_rx), "Alert").unwrap()["message"], "Invalid admin token.");
+    handle_message(&server, &mut admin_ctx, ClientProtocol::SetMotd { admin_token: "admin".into(), message: Some("Maintenance tonight".into()), community: None });
+    assert_eq!(find(&drain(&mut admin_rx), "Alert").unwrap()["message"], "The message of the day has been updated.");
+
+    // lobbies hear about it right away, and games in progress are not interrupted
+    assert_eq!(find(&drain(&mut lobby_rx), "Motd").unwrap()["message"], "Maintenance tonight");
+    assert!(find(&drain(&mut seats[0].1), "Motd").is_none());
+
+    // new connections are greeted with it until it is cleared
+    let (ctx, mut rx) = connect();
+    handle_connect(&server, &ctx);
+    assert_eq!(find(&drain(&mut rx), "Motd").unwrap()["message"], "Maintenance tonight");
+    handle_message(&server, &mut admin_ctx, ClientProtocol::SetMotd { admin_token: "admin".into(), message: None, community: None });
+    handle_connect(&server, &ctx);
+    assert!(drain(&mut rx).is_empty());
+
+    // announcements only reach the game they are sent to
+    handle_message(&server, &mut admin_ctx, ClientProtocol::Announce { admin_token: "admin".into(), game_id, message: "The server restarts in 10 minutes".into() });
+    assert_eq!(find(&drain(&mut seats[0].1), "Announcement").unwrap()["message"], "The server restarts in 10 minutes");
+    assert!(find(&drain(&mut lobby_rx), "Announcement").is_none());
+    assert_eq!(drain(&mut admin_rx).last().unwrap()["message"], "The announcement has been sent.");
+    handle_message(&server, &mut admin_ctx, ClientProtocol::Announce { admin_token: "admin".into(), game_id, message: " ".into() });
+    assert_eq!(find(&drain(&mut admin_rx), "Alert").unwrap()["message"], "Announcements must be between 1 and 500 characters long.");
+}
+
+#[test]
+fn test_ban_list() {
+    let path = std::env::temp_dir().join(format!("bans-{}.json", Uuid::new_v4()));
+    let server = ServerState { bans: Arc::new(BanList::load(Some(path.clone()))), ..test_server(None) };
+    let (mut host_ctx, _host_rx) = connect();
+    handle_message(&server, &mut host_ctx, host("alice"));
+    let game_id = host_ctx.game.unwrap();
+
+    let (mut admin_ctx, mut admin_rx) = connect();
+    let secret = Uuid::new_v4();
+    let ban = |target: BanTarget, expires_at: Option<u64>| ClientProtocol::Ban { admin_token: "admin".into(), target, reason: "spamming".into(), expires_at, community: None };
+    handle_message(&server, &mut admin_ctx, ClientProtocol::Ban { admin_token: "wrong".into(), target: BanTarget::Account { friend_id: friend_id(secret) }, reason: "spamming".into(), expires_at: None, community: None });
+    assert_eq!(find(&drain(&mut admin_rx), "Alert").unwrap()["message"], "Invalid admin token.");
+    handle_message(&server, &mut admin_ctx, ban(BanTarget::Account { friend_id: friend_id(secret) }, None));
+    let account_ban: Uuid = serde_json::from_value(find(&drain(&mut admin_rx), "Bans").unwrap()["bans"][0]["id"].clone()).unwrap();
+
+    // a banned account cannot join, and is told why
+    let (mut ctx, mut rx) = connect();
+    handle_message(&server, &mut ctx, ClientProtocol::JoinGame { id: game_id, nickname: "bob".into(), player_id: None, player_secret: Some(secret), resume_token: None, avatar: None, color: None });
+    assert_eq!(find(&drain(&mut rx), "Banned").unwrap()["reason"], "spamming");
+    assert!(ctx.game.is_none());
+
+    // nor can they get back into a seat they already held with a resume token
+    let (mut seated_ctx, mut seated_rx) = connect();
+    let seated = Uuid::new_v4();
+    handle_message(&server, &mut seated_ctx, ClientProtocol::JoinGame { id: game_id, nickname: "dave".into(), player_id: None, player_secret: Some(seated), resume_token: None, avatar: None, color: None });
+    let token = find(&drain(&mut seated_rx), "ResumeToken").unwrap()["token"].as_str().unwrap().to_string();
+    handle_message(&server, &mut admin_ctx, ban(BanTarget::Account { friend_id: friend_id(seated) }, None));
+    drain(&mut admin_rx);
+    let (mut ctx, mut rx) = connect();
+    handle_message(&server, &mut ctx, ClientProtocol::JoinGame { id: game_id, nickname: "dave".into(), player_id: None, player_secret: None, resume_token: Some(token), avatar: None, color: None });
+    assert_eq!(find(&drain(&mut rx), "Banned").unwrap()["reason"], "spamming");
+    assert!(ctx.game.is_none());
+
+    // address ranges cover every address in them, and bans can expire
+    handle_message(&server, &mut admin_ctx, ban(BanTarget::Address { range: "not an address".into() }, None));
+    assert_eq!(find(&drain(&mut admin_rx), "Alert").unwrap()["message"], "That is not a valid address range.");
+    let soon = epoch_millis(SystemTime::now() + Duration::from_secs(60));
+    handle_message(&server, &mut admin_ctx, ban(BanTarget::Address { range: "203.0.113.0/24".into() }, Some(soon)));
+    let visitor = |address: &str| Visitor { secret: None, address: Some(address.parse().unwrap()), fingerprint: None };
+    assert!(server.bans.find(&visitor("203.0.113.77"), None, SystemTime::now()).is_some());
+    assert!(server.bans.find(&visitor("203.0.114.1"), None, SystemTime::now()).is_none());
+    assert!(server.bans.find(&visitor("203.0.113.77"), None, SystemTime::now() + Duration::from_secs(120)).is_none());
+    let (mut ctx, mut rx) = connect();
+    ctx.address = Some("203.0.113.5".parse().unwrap());
+    handle_message(&server, &mut ctx, host("carol"));
+    assert!(find(&drain(&mut rx), "Banned").is_some());
+
+    // appeal notes and bans are kept in the file across restarts, until the ban is lifted
+    handle_message(&server, &mut admin_ctx, ClientProtocol::SetBanAppeal { admin_token: "admin".into(), ban_id: account_ban, note: Some("says it was their sibling".into()) });
+    assert_eq!(find(&drain(&mut admin_rx), "Alert").unwrap()["message"], "The appeal has been noted.");
+    let reloaded = test_server(None);
+    let reloaded = ServerState { bans: Arc::new(BanList::load(Some(path.clone()))), ..reloaded };
+    let (mut admin_ctx, mut admin_rx) = connect();
+    handle_message(&reloaded, &mut admin_ctx, ClientProtocol::ListBans { admin_token: "admin".into() });
+    let bans = find(&drain(&mut admin_rx), "Bans").unwrap()["bans"].clone();
+    assert_eq!(bans.as_array().unwrap().len(), 3);
+    assert_eq!(bans[0]["appeal_note"], "says it was their sibling");
+    handle_message(&reloaded, &mut admin_ctx, ClientProtocol::Unban { admin_token: "admin".into(), ban_id: account_ban });
+    assert_eq!(find(&drain(&mut admin_rx), "Alert").unwrap()["message"], "The ban has been lifted.");
+    assert!(BanList::load(Some(path.clone())).find(&Visitor { secret: Some(secret), ..Visitor::default() }, None, SystemTime::now()).is_none());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_host_proof_of_work() {
+    let server = ServerState { host_gate: Arc::new(HostGate::new(Some(HostCheck::ProofOfWork { difficulty: 8 }))), ..test_server(None) };
+    let host_with = |captcha: Option<String>| ClientProtocol::HostGame { nickname: "alice".into(), options: GameOptions::default(), avatar: None, color: None, player_secret: None, preset: None, captcha };
+    let (mut ctx, mut rx) = connect();
+    handle_message(&server, &mut ctx, host_with(None));
+    assert_eq!(find(&drain(&mut rx), "Alert").unwrap()["message"], "Please complete the captcha to host a game.");
+
+    handle_message(&server, &mut ctx, ClientProtocol::GetHostChallenge);
+    let challenge = find(&drain(&mut rx), "HostChallenge").unwrap()["challenge"].clone();
+    assert_eq!(challenge["type"], "ProofOfWork");
+    assert_eq!(challenge["difficulty"], 8);
+    let challenge = challenge["challenge"].as_str().unwrap();
+    let nonce = (0..).map(|nonce: u32| nonce.to_string()).find(|nonce| proof_of_work_valid(challenge, nonce, 8)).unwrap();
+
+    // a wrong answer does not use up the challenge, but a right one does
+    let wrong = (0..).map(|nonce: u32| nonce.to_string()).find(|nonce| !proof_of_work_valid(challenge, nonce, 8)).unwrap();
+    handle_message(&server, &mut ctx, host_with(Some(format!("{}:{}", challenge, wrong))));
+    assert!(ctx.game.is_none());
+    handle_message(&server, &mut ctx, host_with(Some(format!("{}:{}", challenge, nonce))));
+    assert!(ctx.game.is_some());
+    let (mut other, mut other_rx) = connect();
+    handle_message(&server, &mut other, host_with(Some(format!("{}:{}", challenge, nonce))));
+    assert!(other.game.is_none());
+
+    // without a check configured, hosts are not asked for anything
+    let open = test_server(None);
+    handle_message(&open, &mut other, ClientProtocol::GetHostChallenge);
+    assert_eq!(find(&drain(&mut other_rx), "HostChallenge").unwrap()["challenge"]["type"], "None");
+}
+
+#[test]
+fn test_typescript_types_up_to_date() {
+    let checked_in = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/frontend/src/protocol.ts")).unwrap();
+    assert!(checked_in == typescript::protocol_types(), "frontend/src/protocol.ts is out of date, run `cargo run -- generate-types --out frontend/src/protocol.ts`");
+}
+
+#[tokio::test]
+async fn test_cors() {
+    let policy = Arc::new(CorsPolicy::new(vec!["https://alt.example.com/".into()]));
+    let routes = cors::preflight(policy.clone()).or(cors::wrap(policy.clone(), warp::path!("healthz").and(warp::get()).map(|| "ok")));
+
+    // allowed origins can read responses and are answered before sending anything else
+    let response = warp::test::request().path("/healthz").header("origin", "https://alt.example.com").reply(&routes).await;
+    assert_eq!(response.headers()["access-control-allow-origin"], "https://alt.example.com");
+    let response = warp::test::request().method("OPTIONS").path("/healthz").header("origin", "https://alt.example.com").reply(&routes).await;
+    assert_eq!(response.status(), 204);
+    assert!(response.headers().contains_key("access-control-allow-methods"));
+
+    // other origins get no headers, so browsers keep the response from them
+    let response = warp::test::request().path("/healthz").header("origin", "https://evil.example.com").reply(&routes).await;
+    assert!(!response.headers().contains_key("access-control-allow-origin"));
+    let response = warp::test::request().method("OPTIONS").path("/healthz").header("origin", "https://evil.example.com").reply(&routes).await;
+    assert!(!response.headers().contains_key("access-control-allow-methods"));
+
+    // websockets are refused from other sites, but not from the server's own pages or from outside a browser
+    assert!(policy.allows_websocket(Some("https://alt.example.com"), Some("game.example.com")));
+    assert!(policy.allows_websocket(Some("https://game.example.com"), Some("game.example.com")));
+    assert!(policy.allows_websocket(None, Some("game.example.com")));
+    assert!(!policy.allows_websocket(Some("https://evil.example.com"), Some("game.example.com")));
+    assert!(CorsPolicy::default().allows_websocket(Some("https://evil.example.com"), Some("game.example.com")));
+    assert!(CorsPolicy::new(vec!["*".into()]).allows("https://evil.example.com"));
+}
+
+#[test]
+fn test_malformed_messages() {
+    for raw in ["", "{", "null", "[1, 2]", "{\"type\":\"Nope\"}", "{\"type\":\"VoteChancellor\"}", "{\"type\":\"VoteChancellor\",\"vote\":\"yes\",\"request_id\":null}", "{\"type\":\"PickCard\",\"index\":-1,\"request_id\":null}"] {
+        assert!(parse_message(raw).is_none(), "{} should not parse", raw);
+    }
+
+    // deeply nested and oversized messages are turned away before they are parsed, but brackets in strings do not count
+    let nested = |depth| format!("{{\"type\":\"PickCard\",\"index\":0,\"request_id\":null,\"extra\":{}{}}}", "[".repeat(depth), "]".repeat(depth));
+    assert!(parse_message(&nested(8)).is_some());
+    assert!(parse_message(&nested(64)).is_none());
+    let chat = |message: &str| format!("{{\"type\":\"SendChat\",\"message\":\"{}\"}}", message);
+    assert!(parse_message(&chat(&"[{\\\"".repeat(64))).is_some());
+    assert!(parse_message(&chat(&"a".repeat(MAX_MESSAGE_BYTES))).is_none());
+
+    let server = test_server(None);
+    let mut seats = start_game(&server);
+    let game_id = seats[0].0.game.unwrap();
+    let export = || serde_json::to_value(server.games.read().get(&game_id).unwrap().lock().export()).unwrap();
+
+    // a whole game still fits, for admins moving it between servers
+    let import = serde_json::json!({ "type": "ImportGame", "admin_token": "admin", "game_id": game_id, "game": export() });
+    assert!(parse_message(&import.to_string()).is_some());
+    let stranger = Uuid::new_v4();
+    let near_valid = [
+        format!("{{\"type\":\"ChooseChancellor\",\"player\":\"{}\",\"request_id\":null}}", stranger),
+        "{\"type\":\"VoteChancellor\",\"vote\":true,\"request_id\":\"1\"}".to_string(),
+        "{\"type\":\"PickCard\",\"index\":7,\"request_id\":null,\"extra\":[]}".to_string(),
+        "{\"type\":\"PresidentialPower\",\"player\":null,\"request_id\":null}".to_string(),
+        "{\"type\":\"Claim\",\"cards\":[],\"request_id\":null}".to_string(),
+        "{\"type\":\"RespondVeto\",\"accept\":true,\"request_id\":null}".to_string(),
+    ];
+
+    let mut rejected = 0;
+    for raw in &near_valid {
+        for (ctx, rx, _) in seats.iter_mut() {
+            drain(rx);
+            let before = export();
+            handle_message(&server, ctx, parse_message(raw).unwrap());
+            let messages = drain(rx);
+            assert!(!messages.iter().any(|m| m["type"] == "Error" && m["code"] == "Internal"), "{} panicked", raw);
+            if find(&messages, "Error").is_some() {
+                rejected += 1;
+                assert_eq!(before, export(), "{} was rejected but changed the game", raw);
+            }
+        }
+    }
+    assert!(rejected > 0);
+}
+
+#[tokio::test]
+async fn test_loadtest() {
+    assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+    let samples: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+    assert_eq!(percentile(&samples, 50.0), Duration::from_millis(51));
+    assert_eq!(percentile(&samples, 99.0), Duration::from_millis(99));
+
+    // the same loop as the server's websocket handler
+    let server = test_server(None);
+    let route = warp::path("ws").and(warp::ws()).map(move |ws: warp::ws::Ws| {
+        let server = server.clone();
+        ws.on_upgrade(move |socket| async move {
+            let (tx, mut rx) = futures::StreamExt::split(socket);
+            let (ptx, prx) = mpsc::unbounded_channel();
+            tokio::spawn(futures::StreamExt::forward(UnboundedReceiverStream::new(prx), tx));
+            let mut ctx = ConnectionContext::new(ptx);
+            while let Some(Ok(message)) = rx.next().await {
+                if let Some(message) = message.to_str().ok().and_then(parse_message) {
+                    handle_message(&server, &mut ctx, message);
+                }
+            }
+            handle_disconnect(&server, &ctx);
+        })
+    });
+    let (address, serve) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(serve);
+
+    let report = loadtest::run(&LoadTest { url: format!("ws://{}/ws", address), games: 3, players: 5, ramp_up: Duration::ZERO, timeout: Duration::from_secs(30) }).await;
+    assert_eq!(report.games_failed, Vec::<String>::new());
+    assert_eq!(report.games_finished, 3);
+    assert_eq!(report.latencies["StartGame"].len(), 3);
+    assert!(report.latencies["VoteChancellor"].len() >= 15);
+    assert!(report.latencies["PickCard"].len() >= 6);
+}
+
+#[test]
+fn test_message_rate_limit() {
+    let start = Instant::now();
+    let mut limiter = MessageRateLimiter::new(2.0, 2);
+    assert!(limiter.check(start).is_ok());
+    assert!(limiter.check(start).is_ok());
+    assert_eq!(limiter.check(start), Err(Duration::from_millis(500)));
+    assert!(limiter.check(start + Duration::from_millis(500)).is_ok());
+
+    let server = test_server(None);
+    let (mut ctx, mut rx) = connect();
+    ctx.rate_limiter = ServerLimits::new(None, None, Duration::from_secs(10)).with_message_rate(Some(1.0), 2).message_limiter();
+    for _ in 0..3 {
+        handle_message(&server, &mut ctx, ClientProtocol::WhereAmI { player_secret: Uuid::new_v4() });
+    }
+    let messages = drain(&mut rx);
+    assert_eq!(messages.iter().filter(|m| m["type"] == "CurrentGame").count(), 2);
+    assert!(find(&messages, "MessagesThrottled").unwrap()["retry_after_ms"].as_u64().unwrap() > 0);
+
+    // middleware wraps any dispatcher, so a panic in one is still reported to the player
+    let panicking = |_: &ServerState, _: &mut ConnectionContext, _: ClientProtocol| panic!("oops");
+    let (mut ctx, mut rx) = connect();
+    CatchPanics(RateLimit(panicking)).dispatch(&server, &mut ctx, ClientProtocol::GetRules);
+    assert_eq!(find(&drain(&mut rx), "Error").unwrap()["code"], "Internal");
+}
+
+#[test]
+fn test_set_view_version() {
+    let server = test_server(None);
+    let mut seats = start_game(&server);
+    let (ctx, rx, _) = &mut seats[1];
+    drain(rx);
+
+    handle_message(&server, ctx, ClientProtocol::SetViewVersion { version: 7 });
+    let messages = drain(rx);
+    let version = find(&messages, "ViewVersion").unwrap();
+    assert_eq!((version["version"].as_u64(), version["latest"].as_u64()), (Some(2), Some(2)));
+    assert!(find(&messages, "GameState").unwrap()["state"]["options"].is_object());
+
+    handle_message(&server, ctx, ClientProtocol::SetViewVersion { version: 1 });
+    let messages = drain(rx);
+    assert_eq!(find(&messages, "ViewVersion").unwrap()["version"], 1);
+    assert!(find(&messages, "GameState").unwrap()["state"].get("options").is_none());
+}
+
+#[tokio::test]
+async fn test_game_history() {
+    let path = std::env::temp_dir().join(format!("history-{}.jsonl", Uuid::new_v4()));
+    let archive = Arc::new(GameArchive::load(Some(path.clone())));
+    let mut simulation = Simulation::new(Some(5));
+    let games: Vec<ArchivedGame> = (0..PAGE_SIZE + 5).map(|_| ArchivedGame::from_game(Uuid::new_v4(), &simulation.play(5)).unwrap()).collect();
+    for game in &games {
+        archive.record(game.clone());
+    }
+    // recording the same round again does not list it twice
+    archive.record(games[0].clone());
+
+    let first = archive.query(&HistoryQuery::default()).unwrap();
+    assert_eq!((first.total, first.pages, first.games.len()), (PAGE_SIZE + 5, 2, PAGE_SIZE));
+    assert_eq!(first.games[0].game_id, games[0].game_id);
+    let second = archive.query(&HistoryQuery { page: Some(2), ..HistoryQuery::default() }).unwrap();
+    assert_eq!(second.games.len(), 5);
+
+    let liberal = archive.query(&HistoryQuery { result: Some("liberal".into()), ..HistoryQuery::default() }).unwrap();
+    assert_eq!(liberal.total, games.iter().filter(|game| game.winner == CardColor::Liberal).count());
+    let name = games[1].players[0].to_uppercase();
+    let by_player = archive.query(&HistoryQuery { player: Some(name.clone()), ..HistoryQuery::default() }).unwrap();
+    assert!(by_player.games.iter().all(|game| game.players.iter().any(|player| player.to_uppercase() == name)));
+    assert!(by_player.total > 0);
+    let ended = games[3].ended_at;
+    let in_range = archive.query(&HistoryQuery { from: Some(ended), to: Some(ended), ..HistoryQuery::default() }).unwrap();
+    assert_eq!(in_range.total, games.iter().filter(|game| game.ended_at == ended).count());
+    assert!(archive.query(&HistoryQuery { result: Some("hitler".into()), ..HistoryQuery::default() }).is_err());
+    assert!(archive.query(&HistoryQuery { page: Some(0), ..HistoryQuery::default() }).is_err());
+
+    // the history is read back from its file after a restart
+    assert_eq!(GameArchive::load(Some(path.clone())).query(&HistoryQuery::default()).unwrap().total, PAGE_SIZE + 5);
+    let _ = std::fs::remove_file(path);
+
+    let route = secrethitler::archive::route(archive);
+    let response = warp::test::request().path("/games?result=facist&page=1").reply(&route).await;
+    assert_eq!(response.status(), 200);
+    let page: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(page["total"], games.iter().filter(|game| game.winner == CardColor::Facist).count());
+    assert_eq!(warp::test::request().path("/games?result=nobody").reply(&route).await.status(), 400);
+}
+
+#[tokio::test]
+async fn test_my_games() {
+    let server = test_server(None);
+    let seats = start_game(&server);
+    let game_id = seats[0].0.game.unwrap();
+    let secret = seats[1].2;
+
+    let games = dashboard::my_games(&server, secret, Some("https://example.com"));
+    assert!(games.recent.is_empty());
+    assert_eq!(games.active.len(), 1);
+    let game = &games.active[0];
+    assert_eq!((game.game_id, game.player_id, game.name.as_deref()), (game_id, seats[1].0.player.unwrap(), Some("player 1")));
+    assert_eq!(game.resume_url, format!("https://example.com/game/{}?resume={}", game_id, game.resume_token));
+    assert!(dashboard::my_games(&server, Uuid::new_v4(), None).active.is_empty());
+
+    // the token takes the seat back from a new connection without the secret
+    let (mut ctx, mut rx) = connect();
+    handle_message(&server, &mut ctx, ClientProtocol::JoinGame { id: game_id, nickname: "player 1".into(), player_id: None, player_secret: None, resume_token: Some(game.resume_token.clone()), avatar: None, color: None });
+    assert_eq!(ctx.player, seats[1].0.player);
+    assert!(find(&drain(&mut rx), "ResumeToken").is_some());
+
+    let route = dashboard::route(server.clone(), None);
+    assert_eq!(warp::test::request().path("/me/games").reply(&route).await.status(), 401);
+    assert_eq!(warp::test::request().path("/me/games").header("authorization", "Bearer nonsense").reply(&route).await.status(), 401);
+    let response = warp::test::request().path("/me/games").header("authorization", format!("Bearer {}", secret)).reply(&route).await;
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["active"][0]["game_id"], game_id.to_string());
+    assert_eq!(body["active"][0]["turn_phase"]["type"], "Electing");
+}
+
+#[test]
+fn test_chat_links() {
+    let mut server = test_server(None);
+    server.chat_links = Arc::new(LinkPolicy::only(vec!["example.com".into()]));
+    let mut seats = start_game(&server);
+    drain(&mut seats[1].1);
+    handle_message(&server, &mut seats[0].0, ClientProtocol::SendChat { message: "<i>rules</i> at https://example.com/rules and https://elsewhere.net".into(), attachment: None });
+    let chat = find(&drain(&mut seats[1].1), "ReceiveChat").unwrap().clone();
+    assert_eq!(chat["message"], "rules at https://example.com/rules and https://elsewhere.net");
+    assert_eq!(chat["segments"], serde_json::json!([
+        { "type": "Text", "text": "rules at " },
+        { "type": "Link", "text": "https://example.com/rules", "url": "https://example.com/rules" },
+        { "type": "Text", "text": " and https://elsewhere.net" },
+    ]));
+
+    // a message that was only markup is not sent at all
+    handle_message(&server, &mut seats[0].0, ClientProtocol::SendChat { message: "<script>alert(1)</script>".into(), attachment: None });
+    assert!(find(&drain(&mut seats[1].1), "ReceiveChat").is_none());
+}
+
+#[tokio::test]
+async fn test_chat_attachments() {
+    let server = test_server(None);
+    let mut seats = start_game(&server);
+    let game_id = seats[0].0.game.unwrap();
+    let player_id = seats[0].0.player.unwrap();
+    let gif = b"GIF89a\x01\x00\x01\x00\x00\x00\x00;".to_vec();
+    let route = uploads::route(server.clone());
+
+    let upload = |secret: Uuid, body: Vec<u8>| warp::test::request().method("POST").path(&format!("/upload?game_id={}&player_id={}&player_secret={}", game_id, player_id, secret)).body(body).reply(&route);
+    assert_eq!(upload(Uuid::new_v4(), gif.clone()).await.status(), 403);
+    assert_eq!(upload(seats[0].2, b"<svg onload=alert(1)>".to_vec()).await.status(), 400);
+    assert_eq!(upload(seats[0].2, vec![0; MAX_UPLOAD_BYTES + 1]).await.status(), 413);
+    let response = upload(seats[0].2, gif.clone()).await;
+    assert_eq!(response.status(), 200);
+    let token: Uuid = serde_json::from_value(serde_json::from_slice::<serde_json::Value>(response.body()).unwrap()["token"].clone()).unwrap();
+
+    let response = warp::test::request().path(&format!("/upload/{}", token)).reply(&route).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "image/gif");
+    assert_eq!(response.body().to_vec(), gif);
+
+    drain(&mut seats[1].1);
+    handle_message(&server, &mut seats[0].0, ClientProtocol::SendChat { message: "".into(), attachment: Some(token) });
+    assert_eq!(find(&drain(&mut seats[1].1), "ReceiveChat").unwrap()["attachment"], token.to_string());
+    handle_message(&server, &mut seats[0].0, ClientProtocol::SendChat { message: "hi".into(), attachment: Some(Uuid::new_v4()) });
+    assert!(find(&drain(&mut seats[0].1), "Alert").is_some());
+    assert!(find(&drain(&mut seats[1].1), "ReceiveChat").is_none());
+
+    // attachments go away with their game
+    server.games.write().clear();
+    server.cleanup();
+    assert_eq!(warp::test::request().path(&format!("/upload/{}", token)).reply(&route).await.status(), 404);
+}
+
+#[test]
+fn test_block_players() {
+    let server = test_server(None);
+    let mut seats = start_game(&server);
+    let blocked = seats[0].0.player.unwrap();
+    let blocked_friend = friend_id(seats[0].2);
+    let secret = seats[1].2;
+    handle_message(&server, &mut seats[1].0, ClientProtocol::BlockPlayer { player_secret: secret, friend_id: None, player: Some(blocked) });
+    assert_eq!(find(&drain(&mut seats[1].1), "Blocked").unwrap()["blocked"], serde_json::json!([blocked_friend]));
+
+    // the player who blocked no longer gets the blocked player's chat, while everyone else does
+    drain(&mut seats[2].1);
+    handle_message(&server, &mut seats[0].0, ClientProtocol::SendChat { message: "hello".into(), attachment: None });
+    assert!(find(&drain(&mut seats[1].1), "ReceiveChat").is_none());
+    assert!(find(&drain(&mut seats[2].1), "ReceiveChat").is_some());
+    handle_message(&server, &mut seats[1].0, ClientProtocol::GetChatLog);
+    let log = find(&drain(&mut seats[1].1), "ChatLog").unwrap()["log"].clone();
+    assert!(log.as_array().unwrap().iter().all(|line| line["id"] != blocked.to_string()));
+
+    handle_message(&server, &mut seats[1].0, ClientProtocol::UnblockPlayer { player_secret: secret, friend_id: blocked_friend });
+    assert_eq!(find(&drain(&mut seats[1].1), "Blocked").unwrap()["blocked"], serde_json::json!([]));
+    handle_message(&server, &mut seats[0].0, ClientProtocol::SendChat { message: "hello again".into(), attachment: None });
+    assert!(find(&drain(&mut seats[1].1), "ReceiveChat").is_some());
+
+    // players who have blocked each other are not seated together
+    let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+    let (mut host_ctx, _host_rx) = connect();
+    handle_message(&server, &mut host_ctx, ClientProtocol::HostGame { nickname: "alice".into(), options: GameOptions::default(), avatar: None, color: None, player_secret: Some(alice), preset: None, captcha: None });
+    let game_id = host_ctx.game.unwrap();
+    server.blocks.block(alice, friend_id(bob)).unwrap();
+    let bob_joins = || ClientProtocol::JoinGame { id: game_id, nickname: "bob".into(), player_id: None, player_secret: Some(bob), resume_token: None, avatar: None, color: None };
+    server.blocks.block(bob, friend_id(alice)).unwrap();
+    let (mut bob_ctx, mut bob_rx) = connect();
+    handle_message(&server, &mut bob_ctx, bob_joins());
+    assert_eq!(find(&drain(&mut bob_rx), "Alert").unwrap()["message"], "You and a player in this game have blocked each other.");
+    server.blocks.unblock(bob, friend_id(alice));
+    handle_message(&server, &mut bob_ctx, bob_joins());
+    assert_eq!(bob_ctx.game, Some(game_id));
+    assert!(server.blocks.block(bob, friend_id(bob)).is_err());
+
+    // block lists are read back from their file after a restart
+    let path = std::env::temp_dir().join(format!("blocks-{}.json", Uuid::new_v4()));
+    BlockList::load(Some(path.clone())).block(alice, friend_id(bob)).unwrap();
+    assert_eq!(BlockList::load(Some(path.clone())).list(alice), vec![friend_id(bob)]);
+    let _ = std::fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn test_game_feedback() {
+    let server = test_server(None);
+    let mut seats = start_game(&server);
+    let game_id = seats[0].0.game.unwrap();
+    let players: Vec<Uuid> = seats.iter().map(|(ctx, _, _)| ctx.player.unwrap()).collect();
+    let feedback = |fun, reports| ClientProtocol::SubmitFeedback { fun, rules_unclear: true, comment: Some(" who is hitler? ".into()), reports };
+    handle_message(&server, &mut seats[0].0, feedback(Some(4), vec![]));
+    assert_eq!(find(&drain(&mut seats[0].1), "Alert").unwrap()["message"], "You can give feedback once the game has ended.");
+
+    // play the game out as though everyone had walked away, then record it as the server does when a game ends
+    {
+        let game = secrethitler::server::get_game(&server.games, &game_id).unwrap();
+        let mut state = game.lock();
+        state.conn.values_mut().for_each(|conn| conn.connected = false);
+        while state.winner().is_none() {
+            assert!(state.stand_in_for_absent());
+        }
+        state.conn.values_mut().for_each(|conn| conn.connected = true);
+        server.archive.record(ArchivedGame::from_game(game_id, &state).unwrap());
+    }
+    drain(&mut seats[0].1);
+
+    handle_message(&server, &mut seats[0].0, feedback(Some(6), vec![]));
+    assert_eq!(find(&drain(&mut seats[0].1), "Alert").unwrap()["message"], "Please rate the game from 1 to 5.");
+    handle_message(&server, &mut seats[0].0, feedback(Some(1), vec![FeedbackReport { player: players[0], reason: "me".into() }]));
+    assert_eq!(find(&drain(&mut seats[0].1), "Alert").unwrap()["message"], "You cannot report yourself.");
+    handle_message(&server, &mut seats[0].0, feedback(Some(1), vec![FeedbackReport { player: players[1], reason: "threw the game".into() }]));
+    assert_eq!(find(&drain(&mut seats[0].1), "Alert").unwrap()["message"], "Thanks for your feedback!");
+    handle_message(&server, &mut seats[0].0, feedback(Some(1), vec![]));
+    assert_eq!(find(&drain(&mut seats[0].1), "Alert").unwrap()["message"], "You have already given feedback on this game.");
+    handle_message(&server, &mut seats[1].0, ClientProtocol::SubmitFeedback { fun: Some(4), rules_unclear: false, comment: None, reports: vec![] });
+    assert!(server.audit.entries().iter().any(|entry| entry.game_id == game_id && entry.players == vec![players[0], players[1]]));
+
+    // feedback is kept out of the public history
+    let page = server.archive.query(&HistoryQuery::default()).unwrap();
+    assert!(page.games[0].feedback.is_empty());
+
+    let route = archive::feedback_route(server.archive.clone(), Some("admin".into()));
+    assert_eq!(warp::test::request().path("/admin/feedback").reply(&route).await.status(), 401);
+    let response = warp::test::request().path("/admin/feedback").header("authorization", "Bearer admin").reply(&route).await;
+    let stats: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!((stats["games"].as_u64(), stats["responses"].as_u64(), stats["average_fun"].as_f64(), stats["reports"].as_u64()), (Some(1), Some(2), Some(2.5), Some(1)));
+    assert_eq!(stats["troubled_games"][0]["game_id"], game_id.to_string());
+    assert_eq!(stats["troubled_games"][0]["comments"], serde_json::json!(["who is hitler?"]));
+}
+
+#[test]
+fn test_authenticated_actions() {
+    let mut server = test_server(None);
+    server.require_action_auth = true;
+    let mut seats = start_game(&server);
+    let game_id = seats[0].0.game.unwrap();
+    let (host_secret, other_secret) = (seats[0].2, seats[1].2);
+
+    // actions sent on their own are refused
+    assert_eq!(find(&drain(&mut seats[0].1), "Error").unwrap()["code"], "Unauthenticated");
+    assert!(!get_game(&server.games, &game_id).unwrap().lock().is_in_game());
+
+    // as are actions carrying the secret of another seat
+    let start = |player_secret, resume_token| ClientProtocol::Authenticated { player_secret, resume_token, message: Box::new(ClientProtocol::StartGame { request_id: None }) };
+    handle_message(&server, &mut seats[0].0, start(Some(other_secret), None));
+    assert_eq!(find(&drain(&mut seats[0].1), "Error").unwrap()["code"], "Unauthenticated");
+    let token = dashboard::my_games(&server, other_secret, None).active[0].resume_token.clone();
+    handle_message(&server, &mut seats[0].0, start(None, Some(token.clone())));
+    assert_eq!(find(&drain(&mut seats[0].1), "Error").unwrap()["code"], "Unauthenticated");
+
+    handle_message(&server, &mut seats[0].0, start(Some(host_secret), None));
+    assert!(find(&drain(&mut seats[0].1), "Error").is_none());
+    assert!(get_game(&server.games, &game_id).unwrap().lock().is_in_game());
+
+    // a resume token for the seat works as well as its secret
+    let chat = ClientProtocol::Authenticated { player_secret: None, resume_token: Some(token), message: Box::new(ClientProtocol::SendChat { message: "hello".into(), attachment: None }) };
+    handle_message(&server, &mut seats[1].0, chat);
+    assert!(find(&drain(&mut seats[2].1), "ReceiveChat").is_some());
+
+    // messages that do not act for the seat need neither
+    handle_message(&server, &mut seats[1].0, ClientProtocol::GetRules);
+    assert!(find(&drain(&mut seats[1].1), "Rules").is_some());
+}
+
+#[tokio::test]
+async fn test_address_policy() {
+    let local = Some("127.0.0.1".parse().unwrap());
+    let policy = AddressPolicy::default();
+    // only a proxy on this machine is believed, and only about the address it was reached from
+    assert_eq!(policy.resolve(Some("198.51.100.7".parse().unwrap()), Some("203.0.113.5"), None), Some("198.51.100.7".parse().unwrap()));
+    assert_eq!(policy.resolve(local, Some("203.0.113.5, 198.51.100.7"), None), Some("198.51.100.7".parse().unwrap()));
+    assert_eq!(policy.resolve(local, None, Some("for=192.0.2.60:8080;proto=https, for=\"[2001:db8::17]:4711\"")), Some("2001:db8::17".parse().unwrap()));
+    assert_eq!(policy.resolve(local, None, None), local);
+    assert_eq!(policy.resolve(None, Some("203.0.113.5"), None), Some("203.0.113.5".parse().unwrap()));
+
+    // a chain of trusted proxies is walked back to the client
+    let proxies = AddressPolicy::new(Some(&["10.0.0.0/8".into()]), &[], &["203.0.113.0/24".into()]).unwrap();
+    assert_eq!(proxies.resolve(Some("10.0.0.2".parse().unwrap()), Some("203.0.113.5, 10.0.0.1"), None), Some("203.0.113.5".parse().unwrap()));
+    assert_eq!(proxies.resolve(local, Some("203.0.113.5"), None), local);
+    assert!(!proxies.admits(Some("203.0.113.5".parse().unwrap())));
+    assert!(proxies.admits(Some("198.51.100.7".parse().unwrap())));
+    assert!(proxies.admits(None));
+
+    let allowed = AddressPolicy::new(None, &["198.51.100.0/24".into()], &[]).unwrap();
+    assert!(allowed.admits(Some("198.51.100.7".parse().unwrap())));
+    assert!(!allowed.admits(Some("192.0.2.1".parse().unwrap())));
+    assert!(!allowed.admits(None));
+    assert!(AddressPolicy::new(Some(&["10.0.0.0/33".into()]), &[], &[]).is_err());
+
+    let routes = addresses::guard(Arc::new(proxies)).or(warp::any().map(|| "ok"));
+    let blocked = warp::test::request().remote_addr("10.0.0.2:4000".parse().unwrap()).header("x-forwarded-for", "203.0.113.5").reply(&routes).await;
+    assert_eq!(blocked.status(), 403);
+    let admitted = warp::test::request().remote_addr("10.0.0.2:4000".parse().unwrap()).header("x-forwarded-for", "198.51.100.7").reply(&routes).await;
+    assert_eq!(admitted.body(), "ok");
+}
+
+#[test]
+fn test_websocket_frames() {
+    let leave = "{\"type\":\"Leave\"}";
+    assert!(matches!(read_frame(&Message::text(leave)), Frame::Message(msg) if matches!(*msg, ClientProtocol::Leave)));
+    assert!(matches!(read_frame(&Message::binary(leave)), Frame::Message(msg) if matches!(*msg, ClientProtocol::Leave)));
+    assert!(matches!(read_frame(&Message::binary(vec![0xff, 0xfe])), Frame::Ignore));
+    assert!(matches!(read_frame(&Message::ping(b"hi".to_vec())), Frame::Ping(payload) if payload == b"hi"));
+    assert!(matches!(read_frame(&Message::pong(b"hi".to_vec())), Frame::Ignore));
+
+    // only a normal closure gives up the seat
+    assert!(matches!(read_frame(&Message::close_with(1000u16, "bye")), Frame::Close { leave: true }));
+    assert!(matches!(read_frame(&Message::close_with(1001u16, "")), Frame::Close { leave: false }));
+    assert!(matches!(read_frame(&Message::close()), Frame::Close { leave: false }));
+}
+
+#[tokio::test]
+async fn test_communities() {
+    let server = test_server(None);
+    let in_club = || {
+        let (mut ctx, rx) = connect();
+        ctx.community = Some("chess-club".into());
+        (ctx, rx)
+    };
+
+    // games hosted in a community can only be found from inside it
+    let (mut host_ctx, mut host_rx) = in_club();
+    handle_message(&server, &mut host_ctx, host("alice"));
+    let game_id = host_ctx.game.unwrap();
+    assert_eq!(server.games.read().get(&game_id).unwrap().lock().community.as_deref(), Some("chess-club"));
+    let (mut outsider, mut outsider_rx) = connect();
+    handle_message(&server, &mut outsider, join(game_id, "bob"));
+    assert!(outsider.game.is_none());
+    assert_eq!(find(&drain(&mut outsider_rx), "Alert").unwrap()["message"], "The game that you are looking for does not exist!");
+    let (mut member, _member_rx) = in_club();
+    handle_message(&server, &mut member, join(game_id, "bob"));
+    assert_eq!(member.game, Some(game_id));
+
+    // a community's bans keep players out of its games alone
+    let carol = Uuid::new_v4();
+    let (mut admin_ctx, mut admin_rx) = connect();
+    let ban = |community: Option<&str>| ClientProtocol::Ban { admin_token: "admin".into(), target: BanTarget::Account { friend_id: friend_id(carol) }, reason: "spamming".into(), expires_at: None, community: community.map(str::to_string) };
+    handle_message(&server, &mut admin_ctx, ban(Some("checkers")));
+    assert_eq!(find(&drain(&mut admin_rx), "Alert").unwrap()["message"], "There is no community with that name.");
+    handle_message(&server, &mut admin_ctx, ban(Some("chess-club")));
+    assert!(find(&drain(&mut admin_rx), "Bans").is_some());
+    let carol_visits = Visitor { secret: Some(carol), ..Visitor::default() };
+    assert!(server.bans.find(&carol_visits, Some("chess-club"), SystemTime::now()).is_some());
+    assert!(server.bans.find(&carol_visits, None, SystemTime::now()).is_none());
+
+    // a community's message of the day goes to its own lobbies and connections, on top of the server's
+    let (mut lobby_ctx, mut lobby_rx) = connect();
+    handle_message(&server, &mut lobby_ctx, host("dave"));
+    drain(&mut host_rx);
+    handle_message(&server, &mut admin_ctx, ClientProtocol::SetMotd { admin_token: "admin".into(), message: Some("Club night".into()), community: Some("chess-club".into()) });
+    assert_eq!(find(&drain(&mut host_rx), "Motd").unwrap()["message"], "Club night");
+    assert!(find(&drain(&mut lobby_rx), "Motd").is_none());
+    handle_message(&server, &mut admin_ctx, ClientProtocol::SetMotd { admin_token: "admin".into(), message: Some("Maintenance tonight".into()), community: None });
+    let (ctx, mut rx) = in_club();
+    handle_connect(&server, &ctx);
+    let greetings: Vec<serde_json::Value> = drain(&mut rx).into_iter().map(|message| message["message"].clone()).collect();
+    assert_eq!(greetings, vec!["Maintenance tonight", "Club night"]);
+    let (ctx, mut rx) = connect();
+    handle_connect(&server, &ctx);
+    assert_eq!(drain(&mut rx).len(), 1);
+
+    // leaderboards are kept for each community
+    let ratings = Ratings::default();
+    let seats: Vec<RatedSeat> = (0..5).map(|seat| RatedSeat { secret: Uuid::new_v4(), name: format!("player {}", seat), party: if seat < 3 { CardColor::Liberal } else { CardColor::Facist } }).collect();
+    for _ in 0..5 {
+        ratings.record_game(Some("chess-club"), &seats, CardColor::Liberal, SystemTime::now());
+    }
+    assert_eq!(ratings.leaderboard(Some("chess-club"), SystemTime::now()).len(), 5);
+    assert!(ratings.leaderboard(None, SystemTime::now()).is_empty());
+
+    // communities the server does not have are turned away
+    let routes = communities::guard(server.communities.clone()).or(warp::any().map(|| "ok"));
+    assert_eq!(warp::test::request().path("/leaderboard?community=checkers").reply(&routes).await.status(), 404);
+    assert_eq!(warp::test::request().path("/leaderboard?community=chess-club").reply(&routes).await.body(), "ok");
+    assert_eq!(warp::test::request().path("/leaderboard").reply(&routes).await.body(), "ok");
+    assert!(Communities::new(&["Chess Club".to_string()]).is_err());
+}
+
+#[tokio::test]
+async fn test_community_moderators() {
+    let mut server = test_server(None);
+    let (owner, moderator) = (Uuid::new_v4(), Uuid::new_v4());
+    server.communities = Arc::new(Communities::new(&["chess-club".to_string()]).unwrap().with_owners(&[format!("chess-club:{}", friend_id(owner))]).unwrap());
+    let in_club = || {
+        let (mut ctx, rx) = connect();
+        ctx.community = Some("chess-club".into());
+        (ctx, rx)
+    };
+    let (mut host_ctx, mut host_rx) = in_club();
+    handle_message(&server, &mut host_ctx, host("alice"));
+    let game_id = host_ctx.game.unwrap();
+    let (mut bob_ctx, mut bob_rx) = in_club();
+    handle_message(&server, &mut bob_ctx, join(game_id, "bob"));
+    let (mut outside_ctx, _outside_rx) = connect();
+    handle_message(&server, &mut outside_ctx, host("dave"));
+    let outside_game = outside_ctx.game.unwrap();
+
+    let (mut ctx, mut rx) = connect();
+    let mut moderate = |secret: Uuid, community: &str, action: ModeratorAction| {
+        handle_message(&server, &mut ctx, ClientProtocol::Moderate { player_secret: secret, community: community.into(), action });
+        drain(&mut rx)
+    };
+    let alert = |messages: Vec<serde_json::Value>| find(&messages, "Alert").unwrap()["message"].clone();
+
+    // only owners can choose moderators
+    assert_eq!(alert(moderate(moderator, "chess-club", ModeratorAction::ListBans)), "You are not a moderator of this community.");
+    assert_eq!(alert(moderate(owner, "checkers", ModeratorAction::ListBans)), "There is no community with that name.");
+    assert_eq!(alert(moderate(owner, "chess-club", ModeratorAction::AddModerator { friend_id: friend_id(moderator) })), "The player is now a moderator.");
+    assert_eq!(alert(moderate(moderator, "chess-club", ModeratorAction::AddModerator { friend_id: Uuid::new_v4() })), "Only the owners of this community can do that.");
+    let listed = moderate(moderator, "chess-club", ModeratorAction::ListModerators);
+    let listed = find(&listed, "Moderators").unwrap();
+    assert_eq!(listed["owners"], serde_json::json!([friend_id(owner)]));
+    assert_eq!(listed["moderators"], serde_json::json!([friend_id(moderator)]));
+
+    // moderators can remove players from the community's games, but not from anyone else's
+    let bob = bob_ctx.player.unwrap();
+    assert_eq!(alert(moderate(moderator, "chess-club", ModeratorAction::Kick { game_id: outside_game, player: outside_ctx.player.unwrap() })), "That game is not part of this community.");
+    assert_eq!(alert(moderate(moderator, "chess-club", ModeratorAction::Kick { game_id, player: bob })), "The player has been removed from the game.");
+    assert_eq!(find(&drain(&mut bob_rx), "Alert").unwrap()["message"], "You have been removed from the game by a moderator.");
+    assert!(!server.games.read().get(&game_id).unwrap().lock().conn.contains_key(&bob));
+    assert_eq!(alert(moderate(moderator, "chess-club", ModeratorAction::Kick { game_id, player: bob })), "That player is not in this game.");
+
+    // bans made by moderators only cover their community, and they cannot lift anyone else's
+    let (mut admin_ctx, mut admin_rx) = connect();
+    handle_message(&server, &mut admin_ctx, ClientProtocol::Ban { admin_token: "admin".into(), target: BanTarget::Address { range: "203.0.113.0/24".into() }, reason: "spam".into(), expires_at: None, community: None });
+    let server_ban = find(&drain(&mut admin_rx), "Bans").unwrap()["bans"][0]["id"].as_str().unwrap().parse().unwrap();
+    let banned = moderate(moderator, "chess-club", ModeratorAction::Ban { target: BanTarget::Account { friend_id: Uuid::new_v4() }, reason: "rude in chat".into(), expires_at: None });
+    assert_eq!(find(&banned, "Bans").unwrap()["bans"][0]["community"], "chess-club");
+    let bans = moderate(moderator, "chess-club", ModeratorAction::ListBans);
+    assert_eq!(find(&bans, "Bans").unwrap()["bans"].as_array().unwrap().len(), 1);
+    assert_eq!(alert(moderate(moderator, "chess-club", ModeratorAction::Unban { ban_id: server_ban })), "There is no ban with that id.");
+
+    // and can talk to the community's players
+    assert_eq!(alert(moderate(moderator, "chess-club", ModeratorAction::Announce { game_id: outside_game, message: "hello".into() })), "That game is not part of this community.");
+    drain(&mut host_rx);
+    assert_eq!(alert(moderate(moderator, "chess-club", ModeratorAction::Announce { game_id, message: "Tournament at eight".into() })), "The announcement has been sent.");
+    assert_eq!(find(&drain(&mut host_rx), "Announcement").unwrap()["message"], "Tournament at eight");
+    assert_eq!(alert(moderate(moderator, "chess-club", ModeratorAction::SetMotd { message: Some("Club night".into()) })), "The message of the day has been updated.");
+    assert_eq!(server.motd.read().get(&Some("chess-club".to_string())).unwrap(), "Club night");
+
+    // the audit log shows moderators their own community's games
+    let (mut carol_ctx, _) = in_club();
+    handle_message(&server, &mut carol_ctx, join(game_id, "carol"));
+    handle_message(&server, &mut host_ctx, ClientProtocol::ReportPlayer { player: carol_ctx.player.unwrap(), reason: "knew my role".into() });
+    let routes = audit::route(server.audit.clone(), server.communities.clone(), Some("admin".into()));
+    let request = |path: &str, secret: Uuid| warp::test::request().path(path).header("authorization", format!("Bearer {}", secret));
+    let response = request("/admin/audit?community=chess-club", moderator).reply(&routes).await;
+    let entries: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(entries.as_array().unwrap().len(), 1);
+    assert_eq!(entries[0]["community"], "chess-club");
+    assert_eq!(request("/admin/audit", moderator).reply(&routes).await.status(), 401);
+    assert_eq!(alert(moderate(owner, "chess-club", ModeratorAction::RemoveModerator { friend_id: friend_id(moderator) })), "The player is no longer a moderator.");
+    assert_eq!(request("/admin/audit?community=chess-club", moderator).reply(&routes).await.status(), 401);
+    assert!(Communities::new(&["chess-club".to_string()]).unwrap().with_owners(&[format!("checkers:{}", friend_id(owner))]).is_err());
+}
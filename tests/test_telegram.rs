@@ -0,0 +1,58 @@
+#![cfg(feature = "telegram")]
+
+use std::{sync::Arc, time::Duration};
+
+use secrethitler::{limits::ServerLimits, server::GlobalState, telegram::{TelegramBridge, TelegramConfig}};
+use secrethitler_core::game_state::TurnPhase;
+use serde_json::{Value, json};
+
+const SECRET: &str = "secret";
+
+fn bridge() -> (TelegramBridge, GlobalState) {
+    let config = TelegramConfig { bot_token: "token".into(), webhook_secret: SECRET.into(), webhook_url: None };
+    let games = GlobalState::default();
+    let limits = Arc::new(ServerLimits::new(None, None, Duration::from_secs(10)));
+    (TelegramBridge::start(config, games.clone(), limits), games)
+}
+
+fn send(bridge: &TelegramBridge, update: Value) -> Value {
+    bridge.handle(SECRET, update.to_string().as_bytes()).unwrap()
+}
+
+fn press(user: usize, data: &str) -> Value {
+    json!({ "update_id": 2, "callback_query": {
+        "id": user.to_string(), "from": { "id": user, "first_name": format!("user {}", user) }, "data": data,
+        "message": { "message_id": 1, "chat": { "id": -100, "type": "group" } }
+    } })
+}
+
+#[tokio::test]
+async fn test_telegram_lobby() {
+    let (bridge, games) = bridge();
+
+    assert!(bridge.handle("wrong", b"{}").is_none());
+
+    // games can only be opened in a group
+    let private = send(&bridge, json!({ "update_id": 1, "message": { "message_id": 1, "chat": { "id": 5, "type": "private" }, "from": { "id": 5, "first_name": "host" }, "text": "/secrethitler" } }));
+    assert!(games.read().is_empty());
+    assert_eq!(private["chat_id"], "5");
+
+    let lobby = send(&bridge, json!({ "update_id": 1, "message": { "message_id": 1, "chat": { "id": -100, "type": "group" }, "from": { "id": 0, "first_name": "host" }, "text": "/secrethitler@bot" } }));
+    assert_eq!(lobby["method"], "sendMessage");
+    let join = lobby["reply_markup"]["inline_keyboard"][0][0]["callback_data"].as_str().unwrap().to_string();
+    let start = lobby["reply_markup"]["inline_keyboard"][0][1]["callback_data"].as_str().unwrap().to_string();
+    assert!(join.len() <= 64);
+
+    assert_eq!(send(&bridge, press(0, &join))["text"], "You already have a seat in this game.");
+    for user in 1..5 {
+        assert_eq!(send(&bridge, press(user, &join))["method"], "answerCallbackQuery");
+    }
+    // only the host may start
+    assert_ne!(send(&bridge, press(1, &start))["text"], "");
+    assert_eq!(send(&bridge, press(0, &start))["text"], "");
+
+    let game = games.read().values().next().unwrap().clone();
+    let state = game.lock();
+    assert_eq!(state.living_players().len(), 5);
+    assert!(matches!(state.turn_phase(), TurnPhase::Electing));
+}